@@ -0,0 +1,65 @@
+//! Compares the decoding of the registers with the previous implementation,
+//! which collected the bytes of every value into a `Vec<u8>`.
+//!
+//! Run with `cargo bench --no-default-features --bench codec`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use sdm72_lib::protocol::{BaudRate, L1Voltage, Word};
+
+/// The words of the first batch of input registers, 30 `f32` values.
+fn batch() -> Vec<Word> {
+    (0..30u16)
+        .flat_map(|i| {
+            let bits = (230.0 + f32::from(i)).to_bits();
+            [(bits >> 16) as Word, bits as Word]
+        })
+        .collect()
+}
+
+/// The previous decoding of `words_to_protocol_value!`.
+fn decode_allocating(words: &[Word]) -> Option<f32> {
+    let bytes = words
+        .iter()
+        .copied()
+        .flat_map(u16::to_be_bytes)
+        .collect::<Vec<u8>>();
+    Some(f32::from_be_bytes(bytes.try_into().ok()?))
+}
+
+fn decode(c: &mut Criterion) {
+    let words = batch();
+    let mut group = c.benchmark_group("decode 30 values");
+    group.bench_function("allocating (previous)", |b| {
+        b.iter(|| {
+            for value in black_box(&words).chunks_exact(2) {
+                black_box(decode_allocating(value));
+            }
+        })
+    });
+    group.bench_function("words_to_protocol_value", |b| {
+        b.iter(|| {
+            for value in black_box(&words).chunks_exact(2) {
+                black_box(L1Voltage::decode_from_input_register(value).ok());
+            }
+        })
+    });
+    group.finish();
+}
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    group.bench_function("encode_for_write_registers", |b| {
+        b.iter(|| black_box(black_box(BaudRate::B9600).encode_for_write_registers()))
+    });
+    group.bench_function("encode_into", |b| {
+        let mut words = [0; 2];
+        b.iter(|| {
+            black_box(BaudRate::B9600).encode_into(&mut words).unwrap();
+            black_box(words)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decode, encode);
+criterion_main!(benches);
@@ -0,0 +1,469 @@
+//! Threshold rules which drive outputs, e.g. to switch a contactor when the
+//! exported power exceeds a limit.
+//!
+//! Every rule watches a single measurement with the [`Threshold`] watches of
+//! the client, so the rules are checked after every successful read of the
+//! daemon, whatever its output mode is. Surplus rules switch a load on and off
+//! by the surplus of exported power with a [`SurplusSwitch`]. Alarms are
+//! raised and cleared by an [`Alarm`], every state transition is logged and
+//! published retained to `<topic>/alarms/<name>` of the `mqtt` section.
+
+use crate::{
+    config_file,
+    events::{self, Event},
+    mqtt,
+};
+use anyhow::{bail, Context, Result};
+use sdm72_lib::{
+    alarms::{Alarm, AlarmEvent, AlarmRule, AlarmState},
+    protocol as proto,
+    surplus::{SurplusConfig, SurplusSwitch},
+    tokio_sync_safe_client::SafeClient,
+    watch::{Threshold, WatchEvent},
+};
+use serde::Deserialize;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The timeout of webhook requests.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An output driven by a rule.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Publish a message to the broker of the `mqtt` section.
+    Mqtt {
+        topic: String,
+        payload: String,
+        #[serde(default)]
+        retain: bool,
+    },
+    /// POST the event as JSON to the URL.
+    Webhook { url: String },
+    /// Set a GPIO line of a local GPIO chip, the line stays requested as an
+    /// output until the daemon exits.
+    #[cfg(feature = "gpio")]
+    Gpio {
+        /// The GPIO chip, e.g. `/dev/gpiochip0`
+        chip: String,
+        line: u32,
+        /// `true` sets the line active
+        value: bool,
+    },
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Rule {
+    pub name: String,
+    pub measurement: proto::Measurement,
+    pub threshold: Threshold,
+    pub action: Action,
+}
+
+/// Switches a load by the surplus of exported power, see [`SurplusSwitch`].
+#[derive(Debug, Deserialize, Clone)]
+pub struct SurplusRule {
+    pub name: String,
+    /// The surplus in W at which the `on` action runs.
+    pub on_surplus: f32,
+    /// The surplus in W below which the `off` action runs.
+    pub off_surplus: f32,
+    #[serde(default, with = "humantime_serde")]
+    pub min_on_time: Duration,
+    #[serde(default, with = "humantime_serde")]
+    pub min_off_time: Duration,
+    pub on: Action,
+    pub off: Action,
+}
+
+impl SurplusRule {
+    fn switch_config(&self) -> SurplusConfig {
+        SurplusConfig {
+            on_surplus: self.on_surplus,
+            off_surplus: self.off_surplus,
+            min_on_time: self.min_on_time,
+            min_off_time: self.min_off_time,
+        }
+    }
+}
+
+/// An alarm with a rule like `l1_voltage < 207 V for 30s`, see [`AlarmRule`].
+#[derive(Debug, Deserialize, Clone)]
+pub struct AlarmConfig {
+    pub name: String,
+    /// The expression of the rule.
+    pub when: String,
+    /// The hysteresis in the unit of the measurement.
+    #[serde(default)]
+    pub hysteresis: f32,
+    /// POST the state transitions as JSON to the URL.
+    pub webhook: Option<String>,
+}
+
+impl AlarmConfig {
+    fn rule(&self) -> Result<AlarmRule> {
+        Ok(AlarmRule::parse(&self.name, &self.when)?.with_hysteresis(self.hysteresis))
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ActionsConfig {
+    /// The broker for the MQTT actions.
+    mqtt: Option<mqtt::MqttConfig>,
+    #[serde(default)]
+    rules: Vec<Rule>,
+    #[serde(default)]
+    surplus: Vec<SurplusRule>,
+    #[serde(default)]
+    alarms: Vec<AlarmConfig>,
+}
+
+impl ActionsConfig {
+    pub fn load(config_file_path: &str) -> Result<Self> {
+        log::debug!("Loading actions file from {config_file_path:?}");
+        config_file::load(config_file_path).with_context(|| "Cannot read actions")
+    }
+
+    /// Checks the rules which can be verified without running them.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(mqtt) = &self.mqtt {
+            mqtt.validate()?;
+        }
+        for rule in &self.rules {
+            match rule.threshold {
+                Threshold::Delta(delta) if delta.is_nan() || delta <= 0.0 => {
+                    bail!("Invalid delta {delta} of rule {:?}", rule.name)
+                }
+                Threshold::Above(limit) | Threshold::Below(limit) if limit.is_nan() => {
+                    bail!("Invalid limit of rule {:?}", rule.name)
+                }
+                _ => {}
+            }
+            self.validate_action(&rule.name, &rule.action)?;
+        }
+        for rule in &self.surplus {
+            if rule.on_surplus.is_nan()
+                || rule.off_surplus.is_nan()
+                || rule.off_surplus >= rule.on_surplus
+            {
+                bail!(
+                    "The off surplus of rule {:?} must be less than the on surplus",
+                    rule.name
+                );
+            }
+            self.validate_action(&rule.name, &rule.on)?;
+            self.validate_action(&rule.name, &rule.off)?;
+        }
+        for alarm in &self.alarms {
+            if alarm.name.is_empty() || alarm.name.contains(['+', '#', '/']) {
+                bail!(
+                    "Invalid alarm name {:?}, it is a level of the MQTT topic",
+                    alarm.name
+                );
+            }
+            if alarm.hysteresis.is_nan() || alarm.hysteresis < 0.0 {
+                bail!("Invalid hysteresis of alarm {:?}", alarm.name);
+            }
+            alarm.rule()?;
+        }
+        Ok(())
+    }
+
+    fn validate_action(&self, name: &str, action: &Action) -> Result<()> {
+        if matches!(action, Action::Mqtt { .. }) && self.mqtt.is_none() {
+            bail!("The rule {name:?} publishes to MQTT, but the mqtt section is missing");
+        }
+        Ok(())
+    }
+}
+
+/// The outputs shared by all rules.
+struct Outputs {
+    mqtt: Option<mqtt::Connection>,
+    /// The requested lines by chip and offset.
+    #[cfg(feature = "gpio")]
+    gpio: std::sync::Mutex<std::collections::HashMap<(String, u32), gpiocdev::Request>>,
+}
+
+impl Outputs {
+    fn run(&self, name: &str, action: &Action, event: &WatchEvent) -> Result<()> {
+        match action {
+            Action::Mqtt {
+                topic,
+                payload,
+                retain,
+            } => match &self.mqtt {
+                Some(cli) => cli.publish(topic.clone(), payload.as_str(), *retain),
+                None => bail!("No MQTT broker configured"),
+            },
+            Action::Webhook { url } => post_json(
+                url,
+                &serde_json::json!({
+                    "rule": name,
+                    "measurement": event.measurement.name(),
+                    "value": event.value,
+                    "previous": event.previous,
+                    "timestamp": humantime::format_rfc3339_millis(event.timestamp).to_string(),
+                }),
+            ),
+            #[cfg(feature = "gpio")]
+            Action::Gpio { chip, line, value } => {
+                use gpiocdev::line::Value;
+
+                let value = if *value {
+                    Value::Active
+                } else {
+                    Value::Inactive
+                };
+                let mut lines = self.gpio.lock().unwrap();
+                match lines.entry((chip.clone(), *line)) {
+                    std::collections::hash_map::Entry::Occupied(entry) => entry
+                        .get()
+                        .set_value(*line, value)
+                        .with_context(|| format!("Cannot set GPIO line {line} of {chip}"))?,
+                    std::collections::hash_map::Entry::Vacant(entry) => {
+                        let request = gpiocdev::Request::builder()
+                            .on_chip(chip)
+                            .with_consumer("sdm72")
+                            .with_line(*line)
+                            .as_output(value)
+                            .request()
+                            .with_context(|| {
+                                format!("Cannot request GPIO line {line} of {chip}")
+                            })?;
+                        entry.insert(request);
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn post_json(url: &str, body: &serde_json::Value) -> Result<()> {
+    ureq::post(url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .set("Content-Type", "application/json")
+        .send_string(&body.to_string())
+        .with_context(|| format!("Cannot call webhook {url}"))?;
+    Ok(())
+}
+
+/// Logs and publishes a state transition of an alarm.
+fn report_alarm(
+    outputs: &Outputs,
+    topic: Option<&str>,
+    webhook: Option<&str>,
+    event: &AlarmEvent,
+) -> Result<()> {
+    match event.state {
+        AlarmState::Active => log::warn!(
+            "Alarm {:?} raised by {} = {}",
+            event.rule,
+            event.measurement,
+            event.value
+        ),
+        AlarmState::Normal => log::info!(
+            "Alarm {:?} cleared by {} = {}",
+            event.rule,
+            event.measurement,
+            event.value
+        ),
+    }
+    events::emit(Event::AlarmChanged {
+        alarm: &event.rule,
+        measurement: event.measurement.name(),
+        state: event.state,
+        value: event.value,
+    });
+    let body = serde_json::json!({
+        "alarm": event.rule,
+        "measurement": event.measurement.name(),
+        "state": event.state,
+        "value": event.value,
+        "timestamp": humantime::format_rfc3339_millis(event.timestamp).to_string(),
+    });
+    if let (Some(cli), Some(topic)) = (&outputs.mqtt, topic) {
+        cli.publish(topic.to_string(), body.to_string(), true)?;
+    }
+    if let Some(url) = webhook {
+        post_json(url, &body)?;
+    }
+    Ok(())
+}
+
+/// Registers the rules with the client and runs their actions in a thread per
+/// rule. The threads exit with the client.
+pub fn start(client: &SafeClient, config: &ActionsConfig) -> Result<()> {
+    config.validate()?;
+    let mqtt_config = config
+        .mqtt
+        .clone()
+        .map(|mqtt| mqtt.resolve(&mut client.clone()))
+        .transpose()?;
+    let outputs = Arc::new(Outputs {
+        mqtt: mqtt_config
+            .as_ref()
+            .map(mqtt::MqttConfig::create_client)
+            .transpose()?,
+        #[cfg(feature = "gpio")]
+        gpio: Default::default(),
+    });
+    for rule in &config.rules {
+        let watch_events = client.watch(rule.measurement, rule.threshold);
+        let outputs = outputs.clone();
+        let meter = events::meter();
+        std::thread::Builder::new()
+            .name(format!("action {}", rule.name))
+            .spawn({
+                let rule = rule.clone();
+                move || {
+                    events::set_meter(meter.as_deref());
+                    for event in watch_events {
+                        log::info!(
+                            "Rule {:?} triggered by {} = {}",
+                            rule.name,
+                            event.measurement,
+                            event.value
+                        );
+                        events::emit(Event::AlertFired {
+                            rule: &rule.name,
+                            measurement: event.measurement.name(),
+                            value: event.value,
+                        });
+                        if let Err(err) = outputs.run(&rule.name, &rule.action, &event) {
+                            log::warn!("Action of rule {:?} failed: {err:#}", rule.name);
+                        }
+                    }
+                }
+            })
+            .with_context(|| format!("Cannot start the action of rule {:?}", rule.name))?;
+    }
+    for rule in &config.surplus {
+        // A delta of 0 passes every value to the switch
+        let watch_events = client.watch(proto::Measurement::TotalPower, Threshold::Delta(0.0));
+        let outputs = outputs.clone();
+        let meter = events::meter();
+        std::thread::Builder::new()
+            .name(format!("surplus {}", rule.name))
+            .spawn({
+                let rule = rule.clone();
+                move || {
+                    events::set_meter(meter.as_deref());
+                    let mut switch = SurplusSwitch::new(rule.switch_config());
+                    for event in watch_events {
+                        let Some(on) = switch.update(-event.value, Instant::now()) else {
+                            continue;
+                        };
+                        log::info!(
+                            "Surplus rule {:?} switches {} at a total power of {} W",
+                            rule.name,
+                            if on { "on" } else { "off" },
+                            event.value
+                        );
+                        events::emit(Event::AlertFired {
+                            rule: &rule.name,
+                            measurement: event.measurement.name(),
+                            value: event.value,
+                        });
+                        let action = if on { &rule.on } else { &rule.off };
+                        if let Err(err) = outputs.run(&rule.name, action, &event) {
+                            log::warn!("Action of surplus rule {:?} failed: {err:#}", rule.name);
+                        }
+                    }
+                }
+            })
+            .with_context(|| format!("Cannot start the surplus rule {:?}", rule.name))?;
+    }
+    for alarm in &config.alarms {
+        let mut state = Alarm::new(alarm.rule()?);
+        // A delta of 0 passes every value to the alarm
+        let watch_events = client.watch(state.rule().measurement, Threshold::Delta(0.0));
+        let outputs = outputs.clone();
+        let meter = events::meter();
+        let topic = mqtt_config
+            .as_ref()
+            .map(|mqtt| mqtt.alarm_topic(&alarm.name));
+        let webhook = alarm.webhook.clone();
+        std::thread::Builder::new()
+            .name(format!("alarm {}", alarm.name))
+            .spawn(move || {
+                events::set_meter(meter.as_deref());
+                for event in watch_events {
+                    let Some(event) = state.check(event.value, Instant::now(), event.timestamp)
+                    else {
+                        continue;
+                    };
+                    if let Err(err) =
+                        report_alarm(&outputs, topic.as_deref(), webhook.as_deref(), &event)
+                    {
+                        log::warn!("Cannot report alarm {:?}: {err:#}", event.rule);
+                    }
+                }
+            })
+            .with_context(|| format!("Cannot start the alarm {:?}", alarm.name))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rules() {
+        let config: ActionsConfig = config_file::parse(
+            config_file::Format::Yaml,
+            r#"
+rules:
+  - name: heat pump on
+    measurement: TotalPower
+    threshold: !Below -1500
+    action: !webhook
+      url: http://localhost/on
+  - name: heat pump off
+    measurement: TotalPower
+    threshold: !Above -200
+    action: !mqtt
+      topic: home/heatpump
+      payload: "OFF"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.rules.len(), 2);
+        assert_eq!(config.rules[0].threshold, Threshold::Below(-1500.0));
+        // The MQTT action requires a broker
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn parse_alarms() {
+        let config: ActionsConfig = config_file::parse(
+            config_file::Format::Yaml,
+            r#"
+alarms:
+  - name: undervoltage L1
+    when: l1_voltage < 207 V for 30s
+    hysteresis: 3
+  - name: overload
+    when: total_power > 20 kW
+    webhook: http://localhost/alarm
+"#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let rule = config.alarms[0].rule().unwrap();
+        assert_eq!(rule.delay, Duration::from_secs(30));
+        assert_eq!(rule.hysteresis, 3.0);
+
+        let config: ActionsConfig = config_file::parse(
+            config_file::Format::Yaml,
+            "alarms:\n  - name: overload\n    when: total_power > 20 kVA\n",
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,394 @@
+//! This module raises and clears alarms by rules on the measurement values,
+//! e.g. for an undervoltage which lasts at least 30 seconds.
+//!
+//! An [`AlarmRule`] is parsed from an expression like
+//! `l1_voltage < 207 V for 30s` or `total_power > 20 kW`: the name of the
+//! measurement as in the serialized `AllValues`, `<` or `>`, the limit with
+//! an optional unit, which may have a `k` or `M` prefix, and an optional
+//! duration the condition must hold before the alarm is raised. The
+//! hysteresis of a rule keeps an active alarm from flapping: it is cleared
+//! only once the value is back by the hysteresis on the other side of the
+//! limit.
+//!
+//! An [`Alarm`] tracks the state of a single rule, the [`AlarmEngine`] the
+//! states of several rules, which are evaluated with every new `AllValues`.
+//! Both return an [`AlarmEvent`] for every state transition.
+//!
+//! # Example
+//!
+//! ```
+//! use sdm72_lib::alarms::{Alarm, AlarmRule, AlarmState};
+//! use std::time::{Duration, Instant, SystemTime};
+//!
+//! let rule = AlarmRule::parse("undervoltage", "l1_voltage < 207 V for 30s")
+//!     .unwrap()
+//!     .with_hysteresis(3.0);
+//! let mut alarm = Alarm::new(rule);
+//! let start = Instant::now();
+//! let at = |secs| start + Duration::from_secs(secs);
+//! assert!(alarm.check(205.0, at(0), SystemTime::now()).is_none());
+//! let event = alarm.check(204.0, at(30), SystemTime::now()).unwrap();
+//! assert_eq!(event.state, AlarmState::Active);
+//! // Still active within the hysteresis
+//! assert!(alarm.check(208.0, at(40), SystemTime::now()).is_none());
+//! let event = alarm.check(210.0, at(50), SystemTime::now()).unwrap();
+//! assert_eq!(event.state, AlarmState::Normal);
+//! ```
+
+use crate::{protocol as proto, tokio_common::AllValues};
+use std::time::{Duration, Instant, SystemTime};
+
+/// An invalid expression of an [`AlarmRule`].
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("Invalid alarm rule {expression:?}: {reason}")]
+pub struct ParseRuleError {
+    pub expression: String,
+    pub reason: String,
+}
+
+/// The condition of an [`AlarmRule`] which raises the alarm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Condition {
+    /// The value is above the limit.
+    Above(f32),
+    /// The value is below the limit.
+    Below(f32),
+}
+
+impl Condition {
+    fn violated(&self, value: f32) -> bool {
+        match *self {
+            Self::Above(limit) => value > limit,
+            Self::Below(limit) => value < limit,
+        }
+    }
+
+    fn cleared(&self, value: f32, hysteresis: f32) -> bool {
+        match *self {
+            Self::Above(limit) => value <= limit - hysteresis,
+            Self::Below(limit) => value >= limit + hysteresis,
+        }
+    }
+}
+
+/// A rule which raises an alarm, see the [module](self) documentation.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AlarmRule {
+    pub name: String,
+    pub measurement: proto::Measurement,
+    /// The condition with the limit in the unit of the measurement.
+    pub condition: Condition,
+    /// The margin by which the value must be back on the other side of the
+    /// limit to clear the alarm.
+    pub hysteresis: f32,
+    /// How long the condition must hold before the alarm is raised.
+    pub delay: Duration,
+}
+
+/// The factor of a unit prefix.
+fn prefix_factor(prefix: &str) -> Option<f32> {
+    match prefix {
+        "" => Some(1.0),
+        "k" => Some(1e3),
+        "M" => Some(1e6),
+        _ => None,
+    }
+}
+
+/// The factor which converts a value in `unit` to the unit of the
+/// measurement, e.g. 1000 from `kW` to `W` or 0.001 from `Wh` to `kWh`.
+fn unit_factor(unit: &str, measurement_unit: &str) -> Option<f32> {
+    // The energies are in kWh and kvarh
+    let (base, base_factor) = match measurement_unit.strip_prefix('k') {
+        Some(base) if !base.is_empty() => (base, 1e3),
+        _ => (measurement_unit, 1.0),
+    };
+    let prefix = unit.strip_suffix(base).filter(|_| !base.is_empty())?;
+    Some(prefix_factor(prefix)? / base_factor)
+}
+
+/// Parses a duration like `30s`, `500ms`, `5min` or `1h`.
+fn parse_duration(duration: &str) -> Option<Duration> {
+    let split = duration
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(duration.len());
+    let (number, unit) = duration.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let secs = match unit {
+        "ms" => number / 1000.0,
+        "s" => number,
+        "m" | "min" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+impl AlarmRule {
+    /// Parses a rule from an expression like `l1_voltage < 207 V for 30s`,
+    /// without hysteresis.
+    pub fn parse(name: impl Into<String>, expression: &str) -> Result<Self, ParseRuleError> {
+        let error = |reason: String| ParseRuleError {
+            expression: expression.to_string(),
+            reason,
+        };
+        let mut tokens = expression.split_whitespace().peekable();
+        let measurement = tokens
+            .next()
+            .ok_or_else(|| error("the measurement is missing".to_string()))?;
+        let measurement = proto::Measurement::ALL
+            .iter()
+            .copied()
+            .find(|m| m.name() == measurement)
+            .ok_or_else(|| error(format!("unknown measurement {measurement:?}")))?;
+        let above = match tokens.next() {
+            Some(">") => true,
+            Some("<") => false,
+            _ => return Err(error("expected `<` or `>`".to_string())),
+        };
+        let limit: f32 = tokens
+            .next()
+            .and_then(|limit| limit.parse().ok())
+            .filter(|limit: &f32| limit.is_finite())
+            .ok_or_else(|| error("the limit is not a number".to_string()))?;
+        let mut factor = 1.0;
+        if let Some(unit) = tokens.next_if(|token| *token != "for") {
+            factor = unit_factor(unit, measurement.unit()).ok_or_else(|| {
+                error(format!(
+                    "the unit {unit:?} doesn't match the unit {:?} of {}",
+                    measurement.unit(),
+                    measurement.name()
+                ))
+            })?;
+        }
+        let mut delay = Duration::ZERO;
+        if tokens.next_if_eq(&"for").is_some() {
+            delay = tokens
+                .next()
+                .and_then(parse_duration)
+                .ok_or_else(|| error("expected a duration like `30s` after `for`".to_string()))?;
+        }
+        if let Some(token) = tokens.next() {
+            return Err(error(format!("unexpected {token:?}")));
+        }
+        let limit = limit * factor;
+        Ok(Self {
+            name: name.into(),
+            measurement,
+            condition: if above {
+                Condition::Above(limit)
+            } else {
+                Condition::Below(limit)
+            },
+            hysteresis: 0.0,
+            delay,
+        })
+    }
+
+    /// Sets the hysteresis in the unit of the measurement.
+    pub fn with_hysteresis(mut self, hysteresis: f32) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+}
+
+/// The state of an alarm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum AlarmState {
+    #[default]
+    Normal,
+    Active,
+}
+
+/// A state transition of an alarm.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AlarmEvent {
+    /// The name of the rule.
+    pub rule: String,
+    pub measurement: proto::Measurement,
+    /// The new state.
+    pub state: AlarmState,
+    /// The value which caused the transition.
+    pub value: f32,
+    /// The time the value was read.
+    pub timestamp: SystemTime,
+}
+
+/// The state of a single [`AlarmRule`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alarm {
+    rule: AlarmRule,
+    state: AlarmState,
+    /// The time since which the condition holds while the alarm is normal.
+    violated_since: Option<Instant>,
+}
+
+impl Alarm {
+    /// Creates an alarm in the normal state.
+    pub fn new(rule: AlarmRule) -> Self {
+        Self {
+            rule,
+            state: AlarmState::Normal,
+            violated_since: None,
+        }
+    }
+
+    /// The rule of the alarm.
+    pub fn rule(&self) -> &AlarmRule {
+        &self.rule
+    }
+
+    /// The current state of the alarm.
+    pub fn state(&self) -> AlarmState {
+        self.state
+    }
+
+    /// Checks the next value of the measurement and returns the event if the
+    /// state changed, `NaN` values are ignored.
+    pub fn check(&mut self, value: f32, now: Instant, timestamp: SystemTime) -> Option<AlarmEvent> {
+        if value.is_nan() {
+            return None;
+        }
+        let condition = self.rule.condition;
+        let changed = match self.state {
+            AlarmState::Normal if condition.violated(value) => {
+                let since = *self.violated_since.get_or_insert(now);
+                now.saturating_duration_since(since) >= self.rule.delay
+            }
+            AlarmState::Normal => {
+                self.violated_since = None;
+                false
+            }
+            AlarmState::Active => condition.cleared(value, self.rule.hysteresis),
+        };
+        if !changed {
+            return None;
+        }
+        self.violated_since = None;
+        self.state = match self.state {
+            AlarmState::Normal => AlarmState::Active,
+            AlarmState::Active => AlarmState::Normal,
+        };
+        Some(AlarmEvent {
+            rule: self.rule.name.clone(),
+            measurement: self.rule.measurement,
+            state: self.state,
+            value,
+            timestamp,
+        })
+    }
+}
+
+/// Evaluates several [`AlarmRule`]s on all values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AlarmEngine {
+    alarms: Vec<Alarm>,
+}
+
+impl AlarmEngine {
+    pub fn new(rules: impl IntoIterator<Item = AlarmRule>) -> Self {
+        Self {
+            alarms: rules.into_iter().map(Alarm::new).collect(),
+        }
+    }
+
+    /// The alarms in the order of the rules.
+    pub fn alarms(&self) -> &[Alarm] {
+        &self.alarms
+    }
+
+    /// The rules of the active alarms.
+    pub fn active(&self) -> impl Iterator<Item = &AlarmRule> {
+        self.alarms
+            .iter()
+            .filter(|alarm| alarm.state == AlarmState::Active)
+            .map(Alarm::rule)
+    }
+
+    /// Checks all rules with the values and returns the state transitions.
+    pub fn evaluate(
+        &mut self,
+        values: &AllValues,
+        now: Instant,
+        timestamp: SystemTime,
+    ) -> Vec<AlarmEvent> {
+        self.alarms
+            .iter_mut()
+            .filter_map(|alarm| {
+                let value = values.value(alarm.rule.measurement);
+                alarm.check(value, now, timestamp)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_evaluate() {
+        let rule = AlarmRule::parse("overload", "total_power > 20 kW").unwrap();
+        assert_eq!(rule.measurement, proto::Measurement::TotalPower);
+        assert_eq!(rule.condition, Condition::Above(20_000.0));
+        assert_eq!(rule.delay, Duration::ZERO);
+        let rule = AlarmRule::parse("import", "import_energy_active > 500 Wh for 1.5min").unwrap();
+        assert_eq!(rule.condition, Condition::Above(0.5));
+        assert_eq!(rule.delay, Duration::from_secs(90));
+        assert!(AlarmRule::parse("a", "l1_voltage < 207 A").is_err());
+        assert!(AlarmRule::parse("a", "l1_voltage = 207").is_err());
+        assert!(AlarmRule::parse("a", "l1_voltage < 207 V for").is_err());
+        assert!(AlarmRule::parse("a", "voltage < 207").is_err());
+
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut alarm = Alarm::new(
+            AlarmRule::parse("overload", "total_power > 20 kW")
+                .unwrap()
+                .with_hysteresis(1000.0),
+        );
+        let event = alarm
+            .check(25_000.0, at(0), SystemTime::UNIX_EPOCH)
+            .unwrap();
+        assert_eq!(event.rule, "overload");
+        assert_eq!(event.state, AlarmState::Active);
+        assert!(alarm
+            .check(19_500.0, at(1), SystemTime::UNIX_EPOCH)
+            .is_none());
+        assert!(alarm
+            .check(f32::NAN, at(2), SystemTime::UNIX_EPOCH)
+            .is_none());
+        let event = alarm
+            .check(19_000.0, at(3), SystemTime::UNIX_EPOCH)
+            .unwrap();
+        assert_eq!(event.state, AlarmState::Normal);
+
+        // All values are 230
+        let rsp = crate::tokio_common::VALUE_BATCHES
+            .map(|(_, quantity)| [0x4366, 0x0000].repeat(quantity as usize / 2));
+        let values = crate::tokio_common::decode_values(&rsp).unwrap();
+        let mut engine = AlarmEngine::new([
+            AlarmRule::parse("overload", "total_power > 200 W").unwrap(),
+            AlarmRule::parse("overvoltage", "l1_voltage > 220 for 10s").unwrap(),
+            AlarmRule::parse("undervoltage", "l1_voltage < 207").unwrap(),
+        ]);
+        let events = engine.evaluate(&values, at(0), SystemTime::UNIX_EPOCH);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].rule, "overload");
+        let events = engine.evaluate(&values, at(10), SystemTime::UNIX_EPOCH);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].rule, "overvoltage");
+        assert_eq!(
+            engine
+                .active()
+                .map(|rule| &rule.name[..])
+                .collect::<Vec<_>>(),
+            ["overload", "overvoltage"]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! This module converts measurement values into Arrow record batches, e.g. to
+//! hand them to DataFusion or Polars or to write them as Parquet.
+//!
+//! The [`schema`] is stable: it has a `timestamp` column followed by one
+//! nullable `Float32` column per [`Measurement`](proto::Measurement), in
+//! register address order and named like the fields of the serialized
+//! `AllValues`. Measurements missing in a row, e.g. because only a selection
+//! was read, are null.
+//!
+//! # Example
+//!
+//! ```
+//! use sdm72_lib::{
+//!     arrow,
+//!     protocol::{Measurement, MeasurementValue},
+//!     tokio_common::TimestampedValues,
+//! };
+//! use std::time::SystemTime;
+//!
+//! let rows = vec![TimestampedValues {
+//!     timestamp: SystemTime::now(),
+//!     values: vec![MeasurementValue {
+//!         measurement: Measurement::Frequency,
+//!         value: 50.0,
+//!     }],
+//! }];
+//! let batch = arrow::to_record_batch(&rows).unwrap();
+//! assert_eq!(batch.num_rows(), 1);
+//! assert_eq!(batch.num_columns(), 1 + Measurement::ALL.len());
+//! assert_eq!(batch.column_by_name("frequency").unwrap().null_count(), 0);
+//! assert_eq!(batch.column_by_name("total_power").unwrap().null_count(), 1);
+//! ```
+
+use crate::{protocol as proto, tokio_common::TimestampedValues};
+use arrow_array::{ArrayRef, Float32Array, RecordBatch, TimestampMillisecondArray};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef, TimeUnit};
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock},
+    time::SystemTime,
+};
+
+/// The name of the timestamp column, in milliseconds since the Unix epoch (UTC).
+pub const TIMESTAMP_COLUMN: &str = "timestamp";
+
+static SCHEMA: LazyLock<SchemaRef> = LazyLock::new(|| {
+    let mut fields = vec![Field::new(
+        TIMESTAMP_COLUMN,
+        DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+        false,
+    )];
+    fields.extend(proto::Measurement::ALL.iter().map(|measurement| {
+        Field::new(measurement.name(), DataType::Float32, true).with_metadata(HashMap::from([(
+            "unit".to_string(),
+            measurement.unit().to_string(),
+        )]))
+    }));
+    Arc::new(Schema::new(fields))
+});
+
+/// Returns the schema of the record batches, the unit of each measurement is
+/// stored in the `unit` metadata of its field.
+pub fn schema() -> SchemaRef {
+    SCHEMA.clone()
+}
+
+fn unix_millis(time: SystemTime) -> i64 {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(since) => since.as_millis() as i64,
+        Err(err) => -(err.duration().as_millis() as i64),
+    }
+}
+
+/// Converts the rows of values into a record batch with the [`schema`].
+pub fn to_record_batch(rows: &[TimestampedValues]) -> Result<RecordBatch, ArrowError> {
+    let timestamps = TimestampMillisecondArray::from_iter_values(
+        rows.iter().map(|row| unix_millis(row.timestamp)),
+    )
+    .with_timezone("UTC");
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(timestamps)];
+    columns.extend(proto::Measurement::ALL.iter().map(|measurement| {
+        let values: Float32Array = rows
+            .iter()
+            .map(|row| {
+                row.values
+                    .iter()
+                    .find(|value| value.measurement == *measurement)
+                    .map(|value| value.value)
+            })
+            .collect();
+        Arc::new(values) as ArrayRef
+    }));
+    RecordBatch::try_new(schema(), columns)
+}
@@ -0,0 +1,209 @@
+use crate::{energy::EnergyTracker, events, health::HealthMonitor, systemd};
+use anyhow::Result;
+use sdm72_lib::{
+    stats::{Demand, SmoothedValues},
+    tokio_common::AllValues,
+    tokio_sync_safe_client::SafeClient,
+};
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Values as served by the daemons, flagged as stale if the meter is
+/// unreachable and the last known values are served instead.
+#[derive(Debug, serde::Serialize)]
+pub struct CachedValues {
+    #[serde(flatten)]
+    pub values: AllValues,
+    /// `true` if the values are the last known ones.
+    pub stale: bool,
+    /// The age of the values in seconds.
+    pub age: f64,
+    /// The smoothed values by measurement name, see `--smooth`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub smoothed: BTreeMap<&'static str, f32>,
+    /// The lifetime totals of the energy counters by measurement name, see
+    /// `--energy-state`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub lifetime_energy: BTreeMap<&'static str, f64>,
+    /// The energy counters which decreased since the previous read.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rollovers: Vec<&'static str>,
+}
+
+/// Remembers the last values read from the meter, so the daemons can keep
+/// serving them while the bus is down.
+///
+/// Reads within the minimum poll interval after the last poll are served
+/// from the cache, which protects slow buses from frequent requests.
+#[derive(Debug, Default)]
+pub struct ValueCache {
+    last: Option<(AllValues, Instant)>,
+    /// The time of the last poll, successful or not.
+    last_poll: Option<Instant>,
+    min_poll_interval: Duration,
+    health: HealthMonitor,
+    demand: Demand,
+    smoothed: SmoothedValues,
+    energy: Option<EnergyTracker>,
+}
+
+impl ValueCache {
+    pub fn new(health: HealthMonitor, min_poll_interval: Duration) -> Self {
+        Self {
+            last: None,
+            last_poll: None,
+            min_poll_interval,
+            health,
+            demand: Demand::default(),
+            smoothed: SmoothedValues::default(),
+            energy: None,
+        }
+    }
+
+    /// Smooths the values read, see [`CachedValues::smoothed`].
+    pub fn with_smoothing(mut self, smoothed: SmoothedValues) -> Self {
+        self.smoothed = smoothed;
+        self
+    }
+
+    /// Tracks the energy counters of the values read, see
+    /// [`CachedValues::lifetime_energy`].
+    pub fn with_energy_tracker(mut self, energy: Option<EnergyTracker>) -> Self {
+        self.energy = energy;
+        self
+    }
+
+    fn lifetime_energy(&self) -> BTreeMap<&'static str, f64> {
+        self.energy
+            .as_ref()
+            .map(EnergyTracker::lifetime)
+            .unwrap_or_default()
+    }
+
+    /// Updates the energy tracker, returns the names of the counters which
+    /// rolled over.
+    fn track_energy(&mut self, values: &AllValues) -> Vec<&'static str> {
+        let Some(energy) = &mut self.energy else {
+            return Vec::new();
+        };
+        let rollovers = energy.record(values).unwrap_or_else(|err| {
+            log::warn!("{err:#}");
+            Vec::new()
+        });
+        rollovers
+            .into_iter()
+            .map(|rollover| {
+                log::warn!(
+                    "The energy counter {} decreased from {} to {}, counted as a rollover",
+                    rollover.measurement.name(),
+                    rollover.previous,
+                    rollover.value
+                );
+                events::emit(events::Event::EnergyRollover {
+                    measurement: rollover.measurement.name(),
+                    previous: rollover.previous,
+                    value: rollover.value,
+                });
+                rollover.measurement.name()
+            })
+            .collect()
+    }
+
+    fn smoothed(&self) -> BTreeMap<&'static str, f32> {
+        self.smoothed
+            .values()
+            .iter()
+            .map(|value| (value.measurement.name(), value.value))
+            .collect()
+    }
+
+    /// The health of the reads.
+    pub fn health(&self) -> &HealthMonitor {
+        &self.health
+    }
+
+    /// The sliding-window statistics of the values read, as a JSON object by
+    /// measurement and window, e.g. `{"total_power": {"15m": {...}}}`.
+    pub fn demand(&self) -> serde_json::Value {
+        let mut demand = serde_json::Map::new();
+        for value in self.demand.values() {
+            let windows = demand
+                .entry(value.measurement.name())
+                .or_insert_with(|| serde_json::json!({}));
+            windows[humantime::format_duration(value.window).to_string()] =
+                serde_json::json!(value.stats);
+        }
+        serde_json::Value::Object(demand)
+    }
+
+    /// The last known values, if the meter was polled within the minimum poll
+    /// interval.
+    fn coalesced(&self, now: Instant) -> Option<CachedValues> {
+        let polled_at = self.last_poll?;
+        if now.saturating_duration_since(polled_at) >= self.min_poll_interval {
+            return None;
+        }
+        let (values, read_at) = self.last.as_ref()?;
+        Some(CachedValues {
+            values: *values,
+            // The last poll failed
+            stale: *read_at < polled_at,
+            age: now.saturating_duration_since(*read_at).as_secs_f64(),
+            smoothed: self.smoothed(),
+            lifetime_energy: self.lifetime_energy(),
+            rollovers: Vec::new(),
+        })
+    }
+
+    /// Reads all values from the meter, falls back to the last known values if
+    /// the meter is unreachable.
+    ///
+    /// Fails only if no values were read yet.
+    pub fn read(&mut self, client: &mut SafeClient) -> Result<CachedValues> {
+        if let Some(cached) = self.coalesced(Instant::now()) {
+            log::trace!("Serving the values of the last poll {:.3}s ago", cached.age);
+            return Ok(cached);
+        }
+        self.last_poll = Some(Instant::now());
+        match client.read_all() {
+            Ok(values) => {
+                // Taken after the start of the poll, so the values aren't stale
+                let now = Instant::now();
+                self.health.record_success(SystemTime::now());
+                systemd::poll_succeeded(&values);
+                self.demand.update(&values, now);
+                self.smoothed.update(&values);
+                let rollovers = self.track_energy(&values);
+                self.last = Some((values, now));
+                Ok(CachedValues {
+                    values,
+                    stale: false,
+                    age: 0.0,
+                    smoothed: self.smoothed(),
+                    lifetime_energy: self.lifetime_energy(),
+                    rollovers,
+                })
+            }
+            Err(err) => {
+                self.health.record_failure(client, &err);
+                systemd::poll_failed(&err);
+                match &self.last {
+                    Some((values, read_at)) => {
+                        log::warn!("Cannot read all values, serving the last known values: {err}");
+                        Ok(CachedValues {
+                            values: *values,
+                            stale: true,
+                            age: read_at.elapsed().as_secs_f64(),
+                            smoothed: self.smoothed(),
+                            lifetime_energy: self.lifetime_energy(),
+                            rollovers: Vec::new(),
+                        })
+                    }
+                    None => Err(anyhow::Error::new(err).context("Cannot read all values")),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! The `capture` command, which polls the values for a duration and writes
+//! them into a Parquet file, e.g. for the offline analysis of a day-long
+//! measurement campaign.
+//!
+//! The file has the Arrow schema of the library, a timestamp column and one
+//! column per measurement. The rows are written as a row group every
+//! [`ROWS_PER_GROUP`] polls into `<output>.partial`, which is renamed to the
+//! output once the capture is complete.
+
+use crate::run_limit::RunLimit;
+use anyhow::{Context, Result};
+use parquet::{arrow::ArrowWriter, file::properties::WriterProperties};
+use sdm72_lib::{
+    arrow,
+    schedule::Ticker,
+    tokio_common::{SystemClock, TimestampedValues},
+    tokio_sync_safe_client::SafeClient,
+};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+/// The number of rows of a row group, an hour of polls every second.
+const ROWS_PER_GROUP: usize = 3600;
+
+/// Writes the rows of the capture as Parquet.
+struct ParquetSink {
+    writer: ArrowWriter<File>,
+    rows: Vec<TimestampedValues>,
+}
+
+impl ParquetSink {
+    fn create(path: &Path) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("Cannot create {}", path.display()))?;
+        let props = WriterProperties::builder()
+            .set_max_row_group_size(ROWS_PER_GROUP)
+            .build();
+        let writer = ArrowWriter::try_new(file, arrow::schema(), Some(props))?;
+        Ok(Self {
+            writer,
+            rows: Vec::with_capacity(ROWS_PER_GROUP),
+        })
+    }
+
+    fn push(&mut self, row: TimestampedValues) -> Result<()> {
+        self.rows.push(row);
+        if self.rows.len() >= ROWS_PER_GROUP {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    fn write_row_group(&mut self) -> Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        self.writer.write(&arrow::to_record_batch(&self.rows)?)?;
+        self.writer.flush()?;
+        self.rows.clear();
+        Ok(())
+    }
+
+    /// Writes the remaining rows and the footer of the file.
+    fn close(mut self) -> Result<()> {
+        self.write_row_group()?;
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+fn partial_path(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".partial");
+    PathBuf::from(path)
+}
+
+/// Polls the values every `interval` until the limit is reached. A failed
+/// poll is logged and skipped, so a day-long capture doesn't stop at the
+/// first timeout.
+pub fn run(
+    client: &mut SafeClient,
+    output: &Path,
+    interval: Duration,
+    limit: &mut RunLimit,
+) -> Result<()> {
+    let partial = partial_path(output);
+    let mut sink = ParquetSink::create(&partial)?;
+    let mut ticker = Ticker::new(interval, Arc::new(SystemClock));
+    loop {
+        let succeeded = match client.read_all() {
+            Ok(values) => {
+                sink.push(TimestampedValues {
+                    timestamp: SystemTime::now(),
+                    values: values.select(&[]),
+                })?;
+                true
+            }
+            Err(err) => {
+                log::warn!("Cannot read all values: {err}");
+                false
+            }
+        };
+        if !limit.next_poll(succeeded, &mut ticker) {
+            break;
+        }
+    }
+    sink.close()?;
+    std::fs::rename(&partial, output)
+        .with_context(|| format!("Cannot rename {} to the output", partial.display()))?;
+    log::info!("{}, written to {}", limit.summary(), output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdm72_lib::protocol as proto;
+
+    #[test]
+    fn write_parquet() {
+        let path =
+            std::env::temp_dir().join(format!("sdm72-capture-{}.parquet", std::process::id()));
+        let mut sink = ParquetSink::create(&path).unwrap();
+        for index in 0..3 {
+            sink.push(TimestampedValues {
+                timestamp: SystemTime::UNIX_EPOCH + Duration::from_secs(index),
+                values: vec![proto::MeasurementValue {
+                    measurement: proto::Measurement::Frequency,
+                    value: 50.0,
+                }],
+            })
+            .unwrap();
+        }
+        sink.close().unwrap();
+
+        let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(
+            File::open(&path).unwrap(),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+        let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(rows, 3);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            partial_path(Path::new("data.parquet")),
+            PathBuf::from("data.parquet.partial")
+        );
+    }
+}
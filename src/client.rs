@@ -0,0 +1,246 @@
+//! This module provides the [`Sdm72SyncClient`] and [`Sdm72Client`] traits,
+//! which cover the read and write operations of the safe clients.
+//!
+//! The traits are implemented by the synchronous and the asynchronous
+//! `SafeClient` and by the [`MockClient`](crate::testing::MockClient), so
+//! applications can be written against the trait and tested with the mock
+//! or adapted to another transport. The asynchronous trait uses
+//! [`async_trait`](https://docs.rs/async-trait) and requires the
+//! `safe-client-async` feature.
+//!
+//! # Example
+//!
+//! ```
+//! use sdm72_lib::{client::Sdm72SyncClient, tokio_common::Error};
+//!
+//! fn total_power(client: &mut impl Sdm72SyncClient) -> Result<f32, Error> {
+//!     Ok(*client.read_all()?.total_power)
+//! }
+//! ```
+
+use crate::{
+    protocol as proto,
+    settings_diff::Setting,
+    tokio_common::{AllSettings, AllValues, MeterIdentity, Result, TimestampedAllValues},
+};
+
+/// The operations of a synchronous SDM72 client.
+pub trait Sdm72SyncClient {
+    fn system_type(&mut self) -> Result<proto::SystemType>;
+    fn set_system_type(&mut self, value: proto::SystemType) -> Result<()>;
+    fn pulse_width(&mut self) -> Result<proto::PulseWidth>;
+    fn set_pulse_width(&mut self, value: proto::PulseWidth) -> Result<()>;
+    fn kppa(&mut self) -> Result<proto::KPPA>;
+    /// Sets the Key Parameter Programming Authorization with the password.
+    fn set_kppa(&mut self, password: proto::Password) -> Result<()>;
+    fn parity_and_stop_bit(&mut self) -> Result<proto::ParityAndStopBit>;
+    fn set_parity_and_stop_bit(&mut self, value: proto::ParityAndStopBit) -> Result<()>;
+    fn address(&mut self) -> Result<proto::Address>;
+    fn set_address(&mut self, value: proto::Address) -> Result<()>;
+    fn pulse_constant(&mut self) -> Result<proto::PulseConstant>;
+    fn set_pulse_constant(&mut self, value: proto::PulseConstant) -> Result<()>;
+    fn password(&mut self) -> Result<proto::Password>;
+    fn set_password(&mut self, value: proto::Password) -> Result<()>;
+    fn baud_rate(&mut self) -> Result<proto::BaudRate>;
+    fn set_baud_rate(&mut self, value: proto::BaudRate) -> Result<()>;
+    fn auto_scroll_time(&mut self) -> Result<proto::AutoScrollTime>;
+    fn set_auto_scroll_time(&mut self, value: proto::AutoScrollTime) -> Result<()>;
+    fn backlight_time(&mut self) -> Result<proto::BacklightTime>;
+    fn set_backlight_time(&mut self, value: proto::BacklightTime) -> Result<()>;
+    fn pulse_energy_type(&mut self) -> Result<proto::PulseEnergyType>;
+    fn set_pulse_energy_type(&mut self, value: proto::PulseEnergyType) -> Result<()>;
+    fn serial_number(&mut self) -> Result<proto::SerialNumber>;
+    fn meter_code(&mut self) -> Result<proto::MeterCode>;
+    fn software_version(&mut self) -> Result<proto::SoftwareVersion>;
+    fn reset_historical_data(&mut self) -> Result<()>;
+    fn reset(&mut self, target: proto::ResetTarget) -> Result<()>;
+    fn write_setting(&mut self, setting: Setting) -> Result<()>;
+    fn read_raw_input(&mut self, address: u16, quantity: u16) -> Result<Vec<proto::Word>>;
+    fn read_raw_holding(&mut self, address: u16, quantity: u16) -> Result<Vec<proto::Word>>;
+    fn write_raw_holding(&mut self, address: u16, words: &[proto::Word]) -> Result<()>;
+    fn identify(&mut self) -> Result<MeterIdentity>;
+    fn read_all_settings(&mut self) -> Result<AllSettings>;
+    fn read_all(&mut self) -> Result<AllValues>;
+    fn read_all_timestamped(&mut self) -> Result<TimestampedAllValues>;
+}
+
+/// The operations of an asynchronous SDM72 client, the twin of
+/// [`Sdm72SyncClient`].
+#[cfg_attr(docsrs, doc(cfg(feature = "safe-client-async")))]
+#[cfg(feature = "safe-client-async")]
+#[async_trait::async_trait]
+pub trait Sdm72Client: Send {
+    async fn system_type(&mut self) -> Result<proto::SystemType>;
+    async fn set_system_type(&mut self, value: proto::SystemType) -> Result<()>;
+    async fn pulse_width(&mut self) -> Result<proto::PulseWidth>;
+    async fn set_pulse_width(&mut self, value: proto::PulseWidth) -> Result<()>;
+    async fn kppa(&mut self) -> Result<proto::KPPA>;
+    /// Sets the Key Parameter Programming Authorization with the password.
+    async fn set_kppa(&mut self, password: proto::Password) -> Result<()>;
+    async fn parity_and_stop_bit(&mut self) -> Result<proto::ParityAndStopBit>;
+    async fn set_parity_and_stop_bit(&mut self, value: proto::ParityAndStopBit) -> Result<()>;
+    async fn address(&mut self) -> Result<proto::Address>;
+    async fn set_address(&mut self, value: proto::Address) -> Result<()>;
+    async fn pulse_constant(&mut self) -> Result<proto::PulseConstant>;
+    async fn set_pulse_constant(&mut self, value: proto::PulseConstant) -> Result<()>;
+    async fn password(&mut self) -> Result<proto::Password>;
+    async fn set_password(&mut self, value: proto::Password) -> Result<()>;
+    async fn baud_rate(&mut self) -> Result<proto::BaudRate>;
+    async fn set_baud_rate(&mut self, value: proto::BaudRate) -> Result<()>;
+    async fn auto_scroll_time(&mut self) -> Result<proto::AutoScrollTime>;
+    async fn set_auto_scroll_time(&mut self, value: proto::AutoScrollTime) -> Result<()>;
+    async fn backlight_time(&mut self) -> Result<proto::BacklightTime>;
+    async fn set_backlight_time(&mut self, value: proto::BacklightTime) -> Result<()>;
+    async fn pulse_energy_type(&mut self) -> Result<proto::PulseEnergyType>;
+    async fn set_pulse_energy_type(&mut self, value: proto::PulseEnergyType) -> Result<()>;
+    async fn serial_number(&mut self) -> Result<proto::SerialNumber>;
+    async fn meter_code(&mut self) -> Result<proto::MeterCode>;
+    async fn software_version(&mut self) -> Result<proto::SoftwareVersion>;
+    async fn reset_historical_data(&mut self) -> Result<()>;
+    async fn reset(&mut self, target: proto::ResetTarget) -> Result<()>;
+    async fn write_setting(&mut self, setting: Setting) -> Result<()>;
+    async fn read_raw_input(&mut self, address: u16, quantity: u16) -> Result<Vec<proto::Word>>;
+    async fn read_raw_holding(&mut self, address: u16, quantity: u16) -> Result<Vec<proto::Word>>;
+    async fn write_raw_holding(&mut self, address: u16, words: &[proto::Word]) -> Result<()>;
+    async fn identify(&mut self) -> Result<MeterIdentity>;
+    async fn read_all_settings(&mut self) -> Result<AllSettings>;
+    async fn read_all(&mut self) -> Result<AllValues>;
+    async fn read_all_timestamped(&mut self) -> Result<TimestampedAllValues>;
+}
+
+/// Calls `$callback` with the methods of the traits, so the implementations
+/// delegating to the inherent methods are generated from one list.
+#[allow(unused_macros)]
+macro_rules! client_methods {
+    ($callback:ident!($($args:tt)*)) => {
+        $callback!($($args)*;
+            system_type() -> proto::SystemType;
+            set_system_type(value: proto::SystemType) -> ();
+            pulse_width() -> proto::PulseWidth;
+            set_pulse_width(value: proto::PulseWidth) -> ();
+            kppa() -> proto::KPPA;
+            set_kppa(password: proto::Password) -> ();
+            parity_and_stop_bit() -> proto::ParityAndStopBit;
+            set_parity_and_stop_bit(value: proto::ParityAndStopBit) -> ();
+            address() -> proto::Address;
+            set_address(value: proto::Address) -> ();
+            pulse_constant() -> proto::PulseConstant;
+            set_pulse_constant(value: proto::PulseConstant) -> ();
+            password() -> proto::Password;
+            set_password(value: proto::Password) -> ();
+            baud_rate() -> proto::BaudRate;
+            set_baud_rate(value: proto::BaudRate) -> ();
+            auto_scroll_time() -> proto::AutoScrollTime;
+            set_auto_scroll_time(value: proto::AutoScrollTime) -> ();
+            backlight_time() -> proto::BacklightTime;
+            set_backlight_time(value: proto::BacklightTime) -> ();
+            pulse_energy_type() -> proto::PulseEnergyType;
+            set_pulse_energy_type(value: proto::PulseEnergyType) -> ();
+            serial_number() -> proto::SerialNumber;
+            meter_code() -> proto::MeterCode;
+            software_version() -> proto::SoftwareVersion;
+            reset_historical_data() -> ();
+            reset(target: proto::ResetTarget) -> ();
+            write_setting(setting: Setting) -> ();
+            read_raw_input(address: u16, quantity: u16) -> Vec<proto::Word>;
+            read_raw_holding(address: u16, quantity: u16) -> Vec<proto::Word>;
+            write_raw_holding(address: u16, words: &[proto::Word]) -> ();
+            identify() -> MeterIdentity;
+            read_all_settings() -> AllSettings;
+            read_all() -> AllValues;
+            read_all_timestamped() -> TimestampedAllValues;
+        );
+    };
+}
+
+/// Implements [`Sdm72SyncClient`] with the inherent methods of `$ty`.
+#[allow(unused_macros)]
+macro_rules! impl_sync_client {
+    ($ty:ty; $($method:ident($($arg:ident: $arg_ty:ty),*) -> $ret:ty;)*) => {
+        impl Sdm72SyncClient for $ty {
+            $(
+                fn $method(&mut self, $($arg: $arg_ty),*) -> Result<$ret> {
+                    <$ty>::$method(self, $($arg),*)
+                }
+            )*
+        }
+    };
+}
+
+/// Implements [`Sdm72Client`] with the inherent methods of `$ty`, which are
+/// awaited if they are `async`.
+#[allow(unused_macros)]
+macro_rules! impl_async_client {
+    ($ty:ty, async; $($method:ident($($arg:ident: $arg_ty:ty),*) -> $ret:ty;)*) => {
+        #[async_trait::async_trait]
+        impl Sdm72Client for $ty {
+            $(
+                async fn $method(&mut self, $($arg: $arg_ty),*) -> Result<$ret> {
+                    <$ty>::$method(self, $($arg),*).await
+                }
+            )*
+        }
+    };
+    ($ty:ty, sync; $($method:ident($($arg:ident: $arg_ty:ty),*) -> $ret:ty;)*) => {
+        #[async_trait::async_trait]
+        impl Sdm72Client for $ty {
+            $(
+                async fn $method(&mut self, $($arg: $arg_ty),*) -> Result<$ret> {
+                    <$ty>::$method(self, $($arg),*)
+                }
+            )*
+        }
+    };
+}
+
+#[cfg(all(
+    feature = "safe-client-sync",
+    any(feature = "tokio-rtu-sync", feature = "tokio-tcp-sync")
+))]
+client_methods!(impl_sync_client!(crate::tokio_sync_safe_client::SafeClient));
+
+#[cfg(all(
+    feature = "safe-client-async",
+    any(feature = "tokio-rtu", feature = "tokio-tcp")
+))]
+client_methods!(impl_async_client!(
+    crate::tokio_async_safe_client::SafeClient,
+    async
+));
+
+#[cfg(feature = "testing")]
+client_methods!(impl_sync_client!(crate::testing::MockClient));
+
+#[cfg(all(feature = "testing", feature = "safe-client-async"))]
+client_methods!(impl_async_client!(crate::testing::MockClient, sync));
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::MockClient;
+
+    fn configure(client: &mut impl Sdm72SyncClient) -> Result<AllValues> {
+        client.set_system_type(proto::SystemType::Type1P2W)?;
+        client.read_all()
+    }
+
+    #[test]
+    fn mock_implements_the_client() {
+        let mut client = MockClient::new().with_measurement(proto::Measurement::Frequency, 50.0);
+        assert_eq!(*configure(&mut client).unwrap().frequency, 50.0);
+        assert_eq!(
+            Sdm72SyncClient::system_type(&mut client).unwrap(),
+            proto::SystemType::Type1P2W
+        );
+    }
+
+    #[cfg(feature = "safe-client-async")]
+    #[tokio::test]
+    async fn mock_implements_the_async_client() {
+        async fn frequency(client: &mut dyn Sdm72Client) -> Result<f32> {
+            Ok(*client.read_all().await?.frequency)
+        }
+        let mut client = MockClient::new().with_measurement(proto::Measurement::Frequency, 50.0);
+        assert_eq!(frequency(&mut client).await.unwrap(), 50.0);
+    }
+}
@@ -1,8 +1,16 @@
-use crate::mqtt::MqttConfig;
+use crate::{config_file, mqtt::MqttConfig};
 use clap::{Parser, Subcommand, ValueEnum};
 use clap_verbosity_flag::{InfoLevel, Verbosity};
-use sdm72_lib::protocol as proto;
-use std::{fmt, ops::Deref, time::Duration};
+use sdm72_lib::{
+    alarms::AlarmRule,
+    labels::Labels,
+    protocol as proto,
+    settings_diff::Setting,
+    stats::Smoothing,
+    tokio_common::{self, Calibration},
+};
+use serde::Deserialize;
+use std::{fmt, ops::Deref, path::PathBuf, time::Duration};
 
 pub fn parse_address(s: &str) -> Result<proto::Address, String> {
     proto::Address::try_from(clap_num::maybe_hex::<u8>(s)?).map_err(|e| format!("{e}"))
@@ -18,6 +26,11 @@ pub fn parse_baud_rate(s: &str) -> Result<proto::BaudRate, String> {
         .map_err(|e| format!("{e}"))
 }
 
+pub fn parse_pulse_width(s: &str) -> Result<proto::PulseWidth, String> {
+    proto::PulseWidth::try_from(s.parse::<u16>().map_err(|e| format!("{e}"))?)
+        .map_err(|e| format!("{e}"))
+}
+
 pub fn parse_auto_scroll_time(s: &str) -> Result<proto::AutoScrollTime, String> {
     proto::AutoScrollTime::try_from(s.parse::<u8>().map_err(|e| format!("{e}"))?)
         .map_err(|e| format!("{e}"))
@@ -28,6 +41,107 @@ pub fn parse_backlight_time(s: &str) -> Result<proto::BacklightTime, String> {
         .map_err(|e| format!("{e}"))
 }
 
+/// Parses the smoothing of a measurement, e.g. `l1_current=ema:0.2`.
+pub fn parse_smoothing(s: &str) -> Result<(proto::Measurement, Smoothing), String> {
+    let (name, smoothing) = s
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid smoothing {s:?}, expected <MEASUREMENT>=<SMOOTHING>"))?;
+    Ok((parse_measurement(name)?, smoothing.parse()?))
+}
+
+/// Parses the decimals of a measurement class, e.g. `energy=3`.
+pub fn parse_precision(s: &str) -> Result<(proto::MeasurementClass, u8), String> {
+    let (name, decimals) = s
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid precision {s:?}, expected <CLASS>=<DECIMALS>"))?;
+    let class = proto::MeasurementClass::ALL
+        .iter()
+        .find(|class| class.name() == name)
+        .copied()
+        .ok_or_else(|| format!("Unknown measurement class {name:?}"))?;
+    let decimals = decimals.parse::<u8>().map_err(|e| format!("{e}"))?;
+    proto::Precision::default()
+        .set(class, decimals)
+        .map_err(|e| format!("{e}"))?;
+    Ok((class, decimals))
+}
+
+/// Parses the name of a measurement as in the serialized values, e.g. `l1_current`.
+pub fn parse_measurement(name: &str) -> Result<proto::Measurement, String> {
+    proto::Measurement::ALL
+        .iter()
+        .find(|measurement| measurement.name() == name)
+        .copied()
+        .ok_or_else(|| format!("Unknown measurement {name:?}"))
+}
+
+/// Parses the calibration of a measurement, e.g. `l1_current=1.02` or
+/// `l1_voltage=1:-0.5` with an offset.
+pub fn parse_calibration(s: &str) -> Result<(proto::Measurement, Calibration), String> {
+    let (name, calibration) = s.split_once('=').ok_or_else(|| {
+        format!("Invalid calibration {s:?}, expected <MEASUREMENT>=<MULTIPLIER>[:<OFFSET>]")
+    })?;
+    let (multiplier, offset) = calibration.split_once(':').unwrap_or((calibration, "0"));
+    let calibration = Calibration {
+        multiplier: multiplier.parse().map_err(|e| format!("{e}"))?,
+        offset: offset.parse().map_err(|e| format!("{e}"))?,
+    };
+    check_calibration(&calibration)?;
+    Ok((parse_measurement(name)?, calibration))
+}
+
+pub fn check_calibration(calibration: &Calibration) -> Result<(), String> {
+    if calibration.multiplier.is_finite() && calibration.offset.is_finite() {
+        Ok(())
+    } else {
+        Err(format!("The calibration {calibration} must be finite"))
+    }
+}
+
+/// Parses an HTTP header, e.g. `Authorization: Bearer TOKEN`.
+pub fn parse_header(s: &str) -> Result<(String, String), String> {
+    match s.split_once(':') {
+        Some((name, value))
+            if !name.trim().is_empty() && name.trim().bytes().all(|b| b.is_ascii_graphic()) =>
+        {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!("Invalid header {s:?}, expected <NAME>: <VALUE>")),
+    }
+}
+
+fn check_header(s: &str) -> Result<String, String> {
+    parse_header(s).map(|_| s.to_string())
+}
+
+/// Checks an alarm rule like `l1_voltage < 207 V for 30s`, see [`AlarmRule`].
+pub fn check_alarm(s: &str) -> Result<String, String> {
+    AlarmRule::parse(s, s)
+        .map(|_| s.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Parses the labels of the text output and the MQTT topics, `english`,
+/// `keys` or a file with labels by key.
+pub fn parse_labels(s: &str) -> Result<Labels, String> {
+    match s {
+        "english" => Ok(Labels::English),
+        "keys" => Ok(Labels::Keys),
+        path => config_file::load(path)
+            .map(Labels::Custom)
+            .map_err(|e| format!("{e:#}")),
+    }
+}
+
+pub fn parse_ratio(s: &str) -> Result<f32, String> {
+    let ratio = s.parse::<f32>().map_err(|e| format!("{e}"))?;
+    if ratio.is_finite() && ratio > 0.0 {
+        Ok(ratio)
+    } else {
+        Err(format!("The ratio {ratio} must be a positive number"))
+    }
+}
+
 fn default_device_name() -> String {
     if cfg!(target_os = "windows") {
         String::from("COM1")
@@ -40,7 +154,7 @@ fn default_device_name() -> String {
 pub enum Connection {
     /// Use Modbus/TCP connection
     Tcp {
-        // TCP address (e.g. 192.168.0.222:502)
+        /// TCP address, host name or IPv6 address with optional port (e.g. 192.168.0.222:502, meter1.local or [fd00::12]:502)
         address: String,
 
         #[command(subcommand)]
@@ -61,26 +175,265 @@ pub enum Connection {
         address: proto::Address,
 
         /// Parity and stop bits of the Modbus RTU protocol for the RS485 serial port.
-        #[arg(long, default_value_t = ParityAndStopBit(proto::ParityAndStopBit::default()))]
+        #[arg(long, default_value_t = ParityAndStopBit::default())]
         parity_and_stop_bit: ParityAndStopBit,
 
         #[command(subcommand)]
         command: Commands,
     },
+    /// Show a live view of the meters of the daemon configuration file, refreshed in place
+    Top {
+        /// Interval for refreshing the view
+        #[arg(value_parser = humantime::parse_duration, long, default_value = "1s")]
+        refresh: Duration,
+    },
+    /// Print the register map of the meter, e.g. to generate the mapping of a SCADA or PLC tool
+    Registers {
+        #[arg(long, value_enum, default_value_t = RegisterFormat::Json)]
+        format: RegisterFormat,
+    },
+    /// Summarize the imported and exported energy per day or month from the database of the
+    /// sqlite daemon or a Parquet file of the capture command
+    Report {
+        /// The SQLite database, or a Parquet file with the extension .parquet
+        history: PathBuf,
+
+        /// The period of the summaries
+        #[arg(long, value_enum, default_value_t = ReportPeriod::Day)]
+        by: ReportPeriod,
+
+        /// The output format of the summaries
+        #[arg(long, value_enum, default_value_t = ReportFormat::Json)]
+        format: ReportFormat,
+
+        /// Add the cost of a time-of-use tariff (YAML, TOML or JSON)
+        #[arg(long)]
+        tariff: Option<String>,
+    },
 }
 
-#[derive(Subcommand, Debug, Clone, PartialEq)]
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum ReportPeriod {
+    Day,
+    Month,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Json,
+    /// Comma-separated values with a header line
+    Csv,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum RegisterFormat {
+    Json,
+    /// Comma-separated values with a header line
+    Csv,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum ValuesFormat {
+    /// JSON, or plain text with --no-json
+    Json,
+    /// OpenMetrics text exposition with the serial number as label, e.g. for the textfile
+    /// collector of the node_exporter
+    Openmetrics,
+}
+
+/// The output of the daemon, also used in the `output` sections of the
+/// daemon configuration file.
+#[derive(Subcommand, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub enum DaemonOutput {
     /// Continuously read and print values to the standard output (console).
-    Console,
+    Console {
+        /// The JSON output format, ignored if `--no-json` is set
+        #[arg(long, value_enum, default_value_t = JsonFormat::Pretty)]
+        #[serde(default)]
+        format: JsonFormat,
+    },
     /// Continuously read and publish values to an MQTT Broker
     Mqtt {
-        /// The configuration file for the MQTT broker
+        /// The configuration file for the MQTT broker (YAML, TOML or JSON)
         #[arg(long, default_value_t = MqttConfig::DEFAULT_CONFIG_FILE.to_string())]
+        #[serde(default = "default_mqtt_config_file")]
         config_file: String,
+
+        /// Count the energy and cost per band of a time-of-use tariff (YAML, TOML or JSON)
+        #[arg(long)]
+        #[serde(default)]
+        tariff: Option<String>,
+    },
+    /// Serve the values and settings over a REST HTTP API (the poll interval is ignored)
+    Http {
+        /// The socket address to listen on. The API has no authentication of its own, only
+        /// listen on addresses reachable by trusted clients
+        #[arg(long, default_value = DEFAULT_LISTEN)]
+        #[serde(default = "default_listen")]
+        listen: String,
+
+        /// Serve `POST /settings/{name}`, which lets every client reaching the API write
+        /// settings once it has the password of the meter
+        #[arg(long)]
+        #[serde(default)]
+        allow_writes: bool,
+
+        /// Probe the connection if no request was made within this interval
+        #[arg(value_parser = humantime::parse_duration, long, default_value = "30s")]
+        #[serde(default = "default_probe_interval", with = "humantime_serde")]
+        probe_interval: Duration,
+
+        /// Cache the settings in this directory, keyed by the serial number of the meter
+        #[arg(long)]
+        #[serde(default)]
+        settings_cache: Option<PathBuf>,
+
+        /// Re-read the cached settings from the meter after this age
+        #[arg(value_parser = humantime::parse_duration, long, default_value = "1day")]
+        #[serde(default = "default_settings_cache_ttl", with = "humantime_serde")]
+        settings_cache_ttl: Duration,
+    },
+    /// Continuously read and POST the values as JSON to an HTTP webhook, e.g. of Node-RED
+    Webhook {
+        /// The URL the values are posted to
+        #[arg(long)]
+        url: String,
+
+        /// An additional HTTP header "NAME: VALUE", e.g. for authorization
+        #[arg(long = "header", value_parser = check_header)]
+        #[serde(default)]
+        headers: Vec<String>,
+
+        /// When the values are posted
+        #[arg(long, value_enum, default_value_t = WebhookTrigger::Always)]
+        #[serde(default)]
+        on: WebhookTrigger,
+
+        /// The minimum change of a value which is posted with `--on change`
+        #[arg(long, default_value_t = 0.0)]
+        #[serde(default)]
+        deadband: f32,
+
+        /// An alarm rule like "l1_voltage < 207 V for 30s", its transitions are posted with the
+        /// values
+        #[arg(long = "alarm", value_parser = check_alarm)]
+        #[serde(default)]
+        alarms: Vec<String>,
+
+        /// Retry a failed request up to this number of times
+        #[arg(long, default_value_t = 3)]
+        #[serde(default = "default_webhook_retries")]
+        retries: u8,
+
+        /// The delay before the first retry, doubled for every further retry
+        #[arg(value_parser = humantime::parse_duration, long, default_value = "1s")]
+        #[serde(default = "default_retry_delay", with = "humantime_serde")]
+        retry_delay: Duration,
+    },
+    /// Continuously read and store values in a local SQLite database
+    Sqlite {
+        /// The SQLite database file, created if it does not exist
+        #[arg(long, default_value = DEFAULT_DB)]
+        #[serde(default = "default_db")]
+        db: String,
+
+        /// Delete the oldest rows when the table grows beyond this number of rows
+        #[arg(long)]
+        #[serde(default)]
+        max_rows: Option<u64>,
+
+        /// Delete rows older than this age (e.g. "30days")
+        #[arg(long, value_parser = humantime::parse_duration)]
+        #[serde(default, with = "humantime_serde")]
+        max_age: Option<Duration>,
     },
 }
 
+impl DaemonOutput {
+    /// The name of the output, as in the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Console { .. } => "console",
+            Self::Mqtt { .. } => "mqtt",
+            Self::Http { .. } => "http",
+            Self::Webhook { .. } => "webhook",
+            Self::Sqlite { .. } => "sqlite",
+        }
+    }
+}
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+const DEFAULT_DB: &str = "meter.db";
+
+fn default_mqtt_config_file() -> String {
+    MqttConfig::DEFAULT_CONFIG_FILE.to_string()
+}
+
+fn default_listen() -> String {
+    DEFAULT_LISTEN.to_string()
+}
+
+fn default_probe_interval() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_settings_cache_ttl() -> Duration {
+    Duration::from_secs(24 * 60 * 60)
+}
+
+fn default_db() -> String {
+    DEFAULT_DB.to_string()
+}
+
+fn default_webhook_retries() -> u8 {
+    3
+}
+
+fn default_retry_delay() -> Duration {
+    Duration::from_secs(1)
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonFormat {
+    /// Multi-line pretty printed JSON
+    #[default]
+    Pretty,
+    /// One compact JSON object per line with an ISO-8601 timestamp (JSON Lines)
+    Jsonl,
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookTrigger {
+    /// Post the values of every poll
+    #[default]
+    Always,
+    /// Post the values if one of them changed by more than the deadband
+    Change,
+    /// Post the values only if an alarm was raised or cleared
+    Alarm,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum PanicBehavior {
+    /// Abort the whole process, e.g. to let systemd restart it
+    Abort,
+    /// Restart the failed daemon worker
+    Restart,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    /// The error and its causes as text
+    Text,
+    /// One JSON object with the kind, exit code and causes of the error
+    Json,
+}
+
+/// The system types which can be written, 3P3W is only decoded as the SDM72
+/// doesn't support it.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct WiringType(proto::SystemType);
 impl clap::ValueEnum for WiringType {
@@ -99,6 +452,7 @@ impl clap::ValueEnum for WiringType {
             proto::SystemType::Type3P4W => {
                 Some(clap::builder::PossibleValue::new("3p4w").help("3 phase with 4 wire"))
             }
+            _ => None,
         }
     }
 }
@@ -109,7 +463,7 @@ impl Deref for WiringType {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub struct ParityAndStopBit(proto::ParityAndStopBit);
 impl clap::ValueEnum for ParityAndStopBit {
     fn value_variants<'a>() -> &'a [Self] {
@@ -135,6 +489,7 @@ impl clap::ValueEnum for ParityAndStopBit {
             proto::ParityAndStopBit::NoParityTwoStopBits => {
                 Some(clap::builder::PossibleValue::new("np2b").help("no parity, two stop bits"))
             }
+            _ => None,
         }
     }
 }
@@ -182,6 +537,7 @@ impl clap::ValueEnum for PulseConstant {
             proto::PulseConstant::PC1 => {
                 Some(clap::builder::PossibleValue::new("1").help("1 imp/kWh"))
             }
+            _ => None,
         }
     }
 }
@@ -214,6 +570,7 @@ impl clap::ValueEnum for PulseEnergyType {
             proto::PulseEnergyType::ExportActiveEnergy => {
                 Some(clap::builder::PossibleValue::new("export"))
             }
+            _ => None,
         }
     }
 }
@@ -224,6 +581,164 @@ impl Deref for PulseEnergyType {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValueValidation(tokio_common::ValueValidation);
+impl clap::ValueEnum for ValueValidation {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            ValueValidation(tokio_common::ValueValidation::Off),
+            ValueValidation(tokio_common::ValueValidation::Finite),
+            ValueValidation(tokio_common::ValueValidation::Plausible),
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        match self.0 {
+            tokio_common::ValueValidation::Off => Some(
+                clap::builder::PossibleValue::new("off").help("the values are used as decoded"),
+            ),
+            tokio_common::ValueValidation::Finite => Some(
+                clap::builder::PossibleValue::new("finite").help("reject NaN and infinite values"),
+            ),
+            tokio_common::ValueValidation::Plausible => {
+                Some(clap::builder::PossibleValue::new("plausible").help(
+                    "reject non-finite values and values outside the range of their register type",
+                ))
+            }
+        }
+    }
+}
+impl Deref for ValueValidation {
+    type Target = tokio_common::ValueValidation;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+impl fmt::Display for ValueValidation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.to_possible_value()
+                .map(|val| val.get_name().to_string())
+                .unwrap_or_default()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WordOrder(proto::WordOrder);
+impl clap::ValueEnum for WordOrder {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            WordOrder(proto::WordOrder::BigEndian),
+            WordOrder(proto::WordOrder::LittleEndianWords),
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        match self.0 {
+            proto::WordOrder::BigEndian => Some(
+                clap::builder::PossibleValue::new("big-endian")
+                    .help("most significant word first, as sent by the device"),
+            ),
+            proto::WordOrder::LittleEndianWords => Some(
+                clap::builder::PossibleValue::new("little-endian-words")
+                    .help("least significant word first, used by some TCP gateways"),
+            ),
+        }
+    }
+}
+impl Deref for WordOrder {
+    type Target = proto::WordOrder;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+impl fmt::Display for WordOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.to_possible_value()
+                .map(|val| val.get_name().to_string())
+                .unwrap_or_default()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResetTarget(proto::ResetTarget);
+impl clap::ValueEnum for ResetTarget {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            ResetTarget(proto::ResetTarget::ResettableEnergy),
+            ResetTarget(proto::ResetTarget::MaxDemand),
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        match self.0 {
+            proto::ResetTarget::ResettableEnergy => Some(
+                clap::builder::PossibleValue::new("resettable-energy")
+                    .alias("historical-data")
+                    .help("the resettable energies, also known as the historical data"),
+            ),
+            proto::ResetTarget::MaxDemand => Some(
+                clap::builder::PossibleValue::new("max-demand")
+                    .help("the maximum demand values, if the firmware has them"),
+            ),
+        }
+    }
+}
+impl Deref for ResetTarget {
+    type Target = proto::ResetTarget;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+impl fmt::Display for ResetTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.to_possible_value()
+                .map(|val| val.get_name().to_string())
+                .unwrap_or_default()
+        )
+    }
+}
+
+/// A setting which can be written to the broadcast address.
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum BroadcastSetting {
+    /// Set the parity and stop bit of all meters
+    ParityAndStopBit {
+        parity_and_stop_bit: ParityAndStopBit,
+    },
+
+    /// Set the baud rate of all meters
+    BaudRate {
+        /// The new baud rate any value of 1200, 2400, 4800, 9600, 19200
+        #[arg(value_parser = parse_baud_rate)]
+        baud_rate: proto::BaudRate,
+    },
+}
+
+impl BroadcastSetting {
+    pub fn setting(&self) -> Setting {
+        match self {
+            Self::ParityAndStopBit {
+                parity_and_stop_bit,
+            } => Setting::ParityAndStopBit(**parity_and_stop_bit),
+            Self::BaudRate { baud_rate } => Setting::BaudRate(*baud_rate),
+        }
+    }
+}
+
+// The command is parsed once per run, boxing the daemon arguments would only
+// complicate the matches
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug, Clone, PartialEq)]
 pub enum Commands {
     /// Daemon mode to read all values of the measured and calculated electrical quantities
@@ -232,16 +747,74 @@ pub enum Commands {
         #[arg(value_parser = humantime::parse_duration, short, long, default_value = "2sec")]
         poll_iterval: Duration,
 
+        /// Threshold rules which drive outputs like MQTT messages, webhooks or GPIO lines (YAML, TOML or JSON)
+        #[arg(long)]
+        actions: Option<String>,
+
+        /// Restart the Modbus connection after this number of consecutive failed polls
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+        reconnect_after: Option<u32>,
+
+        /// Serve the values of the last poll to the MQTT and REST daemons within this interval
+        /// after it, instead of polling the meter again (e.g. to protect a slow bus)
+        #[arg(value_parser = humantime::parse_duration, long, default_value = "0s")]
+        min_poll_interval: Duration,
+
+        /// Smooth a measurement of the MQTT and REST daemons with a moving average or a median
+        /// (e.g. l1_current=ema:0.2 or l1_current=median:5), the raw value is kept
+        #[arg(long, value_name = "MEASUREMENT=SMOOTHING", value_parser = parse_smoothing)]
+        smooth: Vec<(proto::Measurement, Smoothing)>,
+
+        /// Track the energy counters of the MQTT and REST daemons across their resets and store
+        /// the lifetime totals in this state file
+        #[arg(long, value_name = "FILE")]
+        energy_state: Option<PathBuf>,
+
+        /// Stop after the first poll, like --count 1
+        #[arg(long, conflicts_with = "count")]
+        once: bool,
+
+        /// Stop after this number of polls and print a summary
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        count: Option<u64>,
+
+        /// Stop after this time and print a summary, e.g. 15min
+        #[arg(value_parser = humantime::parse_duration, long)]
+        duration: Option<Duration>,
+
         #[command(subcommand)]
         mode: DaemonOutput,
     },
 
     /// Read all values of the measured and calculated electrical quantities
-    ReadAll,
+    ReadAll {
+        #[arg(long, value_enum, default_value_t = ValuesFormat::Json)]
+        format: ValuesFormat,
+    },
+
+    /// Poll all values and write them into a Parquet file, e.g. for the offline analysis of a
+    /// measurement campaign
+    #[cfg(feature = "parquet")]
+    Capture {
+        /// The Parquet file, written as <OUTPUT>.partial until the capture is complete
+        #[arg(long)]
+        output: std::path::PathBuf,
+
+        /// Interval of the polls
+        #[arg(value_parser = humantime::parse_duration, long, default_value = "1s")]
+        interval: Duration,
+
+        /// Duration of the capture, e.g. 24h
+        #[arg(value_parser = humantime::parse_duration, long)]
+        duration: Duration,
+    },
 
     /// Read all settings
     ReadAllSettings,
 
+    /// Read the serial number, meter code, software version and model of the meter
+    Identify,
+
     /// Password to obtain authorization to change the settings
     Password {
         #[arg(value_parser = parse_password)]
@@ -276,6 +849,13 @@ pub enum Commands {
         pulse_constant_in_kwh: PulseConstant,
     },
 
+    /// Pulse width for the pulse output, fixed to 35 ms at a pulse constant of 1000 imp/kWh
+    SetPulseWidth {
+        /// The width is specified in milliseconds, any value of 60, 100, 200
+        #[arg(value_parser = parse_pulse_width)]
+        pulse_width_in_ms: proto::PulseWidth,
+    },
+
     /// Set password to change the settings
     SetPassword {
         /// The password must be in the range from 0 to 9999
@@ -303,36 +883,214 @@ pub enum Commands {
         pulse_energy_type: PulseEnergyType,
     },
 
+    /// Apply the desired settings from a YAML file, only changed settings are written
+    ApplySettings {
+        /// The YAML, TOML or JSON file with the desired settings
+        file: String,
+    },
+
+    /// Write a setting to the broadcast address, all meters on the bus execute it without a
+    /// response, e.g. to change the baud rate of many meters during commissioning
+    Broadcast {
+        /// Confirm that the setting of all meters on the bus is changed although no meter
+        /// acknowledges the write
+        #[arg(long, required = true)]
+        all_meters: bool,
+
+        #[command(subcommand)]
+        setting: BroadcastSetting,
+    },
+
     /// Reset the historical saved data
     ResetHistoricalData,
+
+    /// Reset the data of a target, e.g. the resettable energies
+    Reset {
+        #[arg(long, default_value_t = ResetTarget(proto::ResetTarget::ResettableEnergy))]
+        target: ResetTarget,
+    },
+
+    /// Check that the meter responds by reading one register, exits with 0 if
+    /// it does and with the exit code of the error otherwise (e.g. for a
+    /// container HEALTHCHECK)
+    Healthcheck,
+}
+
+/// The write of a `set-*` or `reset-*` command, see `--dry-run`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommandWrite {
+    Setting(Setting),
+    ResetHistoricalData,
+    Reset(proto::ResetTarget),
+}
+
+impl Commands {
+    /// The write of the command, `None` if it is not a `set-*` or `reset-*`
+    /// command.
+    pub fn write(&self) -> Option<CommandWrite> {
+        let setting = match self {
+            Self::SetParityAndStopBit {
+                parity_and_stop_bit,
+            } => Setting::ParityAndStopBit(**parity_and_stop_bit),
+            Self::SetBaudRate { baud_rate } => Setting::BaudRate(*baud_rate),
+            Self::SetAddress { address } => Setting::Address(*address),
+            Self::SetWiringType { wiring_type } => Setting::SystemType(**wiring_type),
+            Self::SetPulseConstant {
+                pulse_constant_in_kwh,
+            } => Setting::PulseConstant(**pulse_constant_in_kwh),
+            Self::SetPulseWidth { pulse_width_in_ms } => Setting::PulseWidth(*pulse_width_in_ms),
+            Self::SetPassword { password } => Setting::Password(*password),
+            Self::SetAutoScrollTime {
+                auto_scroll_time_in_seconds,
+            } => Setting::AutoScrollTime(*auto_scroll_time_in_seconds),
+            Self::SetBacklightTime {
+                backlight_time_in_minutes,
+            } => Setting::BacklightTime(*backlight_time_in_minutes),
+            Self::SetPulseEnergyType { pulse_energy_type } => {
+                Setting::PulseEnergyType(**pulse_energy_type)
+            }
+            Self::ResetHistoricalData => return Some(CommandWrite::ResetHistoricalData),
+            Self::Reset { target } => return Some(CommandWrite::Reset(**target)),
+            _ => return None,
+        };
+        Some(CommandWrite::Setting(setting))
+    }
 }
 
 const fn about_text() -> &'static str {
     "SDM72 powermeter for the command line tool"
 }
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(version, about=about_text(), long_about = None)]
 pub struct Args {
     #[command(flatten)]
     pub verbose: Verbosity<InfoLevel>,
 
+    /// What to do if a daemon worker panics, by default the process exits
+    #[arg(long)]
+    pub on_panic: Option<PanicBehavior>,
+
     /// Output to stdout not in JSON format
     #[arg(long, default_value = "false")]
     pub no_json: bool,
 
-    // Connection type
+    /// Labels of the output with --no-json and of the MQTT topics: english, keys (e.g. l1_voltage)
+    /// or a YAML, TOML or JSON file with labels by key (e.g. "l1_voltage: Spannung L1")
+    #[arg(long, value_name = "english|keys|FILE", default_value = "english", value_parser = parse_labels)]
+    pub labels: Labels,
+
+    /// Format of the error printed to stderr if the command fails
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Text)]
+    pub error_format: ErrorFormat,
+
+    // Connection type, optional with a daemon configuration file
     #[command(subcommand)]
-    pub connection: Connection,
+    pub connection: Option<Connection>,
 
-    /// Modbus Input/Output operations timeout
-    #[arg(value_parser = humantime::parse_duration, long, default_value = "200ms")]
+    /// Run the daemons of the meters in this configuration file (YAML, TOML or JSON) instead of a
+    /// connection command, the flags override the values of the file
+    #[arg(long)]
+    pub config: Option<String>,
+
+    /// Timeout for resolving the host name of a TCP address
+    #[arg(value_parser = humantime::parse_duration, long, default_value = "5s")]
+    pub resolve_timeout: Duration,
+
+    /// Timeout of every Modbus request
+    #[arg(
+        value_parser = humantime::parse_duration,
+        long = "request-timeout",
+        visible_alias = "timeout",
+        value_name = "REQUEST_TIMEOUT",
+        default_value = "200ms"
+    )]
     pub timeout: Duration,
 
+    /// Timeout for opening the connection, e.g. the TCP connect to a slow gateway [default: the
+    /// request timeout]
+    #[arg(value_parser = humantime::parse_duration, long)]
+    pub connect_timeout: Option<Duration>,
+
     // According to Modbus specification:
     // Wait at least 3.5 char between frames
     // However, some USB - RS485 dongles requires at least 10ms to switch between TX and RX, so use a save delay between frames
     /// Delay between multiple modbus commands
     #[arg(value_parser = humantime::parse_duration, long, default_value = "50ms")]
     pub delay: Duration,
+
+    /// Ratio of external current transformers, applied to currents, powers and energies
+    #[arg(long, value_parser = parse_ratio)]
+    pub ct_ratio: Option<f32>,
+
+    /// Ratio of external voltage transformers, applied to voltages, powers and energies
+    #[arg(long, value_parser = parse_ratio)]
+    pub vt_ratio: Option<f32>,
+
+    /// Calibrate a measurement after the CT and VT ratios, as value * MULTIPLIER + OFFSET
+    /// (e.g. l2_current=1.03 or l1_voltage=1:-0.8)
+    #[arg(long, value_name = "MEASUREMENT=MULTIPLIER[:OFFSET]", value_parser = parse_calibration)]
+    pub calibrate: Vec<(proto::Measurement, Calibration)>,
+
+    /// Decimals of the displayed and serialized values of a measurement class, 2 by default
+    /// (e.g. energy=3 or voltage=1), the classes are voltage, current, power, power_factor,
+    /// frequency and energy
+    #[arg(long, value_name = "CLASS=DECIMALS", value_parser = parse_precision)]
+    pub precision: Vec<(proto::MeasurementClass, u8)>,
+
+    /// Password to obtain the authorization for changing settings, instead of the prompt
+    #[arg(long, env = "SDM72_PASSWORD", hide_env_values = true, value_parser = parse_password)]
+    pub password: Option<proto::Password>,
+
+    /// File with the password in its first line, e.g. a Docker or systemd secret
+    #[arg(long, env = "SDM72_PASSWORD_FILE")]
+    pub password_file: Option<String>,
+
+    /// Command printing the password, e.g. "secret-tool lookup service sdm72" for the keyring
+    #[arg(long)]
+    pub password_command: Option<String>,
+
+    /// Never prompt, fail if an authorization is required but no password is given
+    #[arg(long, default_value = "false")]
+    pub non_interactive: bool,
+
+    /// Read back every written setting and fail if the meter did not accept it
+    #[arg(long, default_value = "false")]
+    pub verify: bool,
+
+    /// Print the register address, the encoded words and the current and target value of a set-*
+    /// or reset-* command without writing, e.g. for an audit before changing a production meter
+    #[arg(long, default_value = "false")]
+    pub dry_run: bool,
+
+    /// Retry a batch of values alone this many times after a timeout or another link failure, e.g.
+    /// for gateways which time out on a specific block of registers
+    #[arg(long, default_value = "0")]
+    pub batch_retries: u32,
+
+    /// Read a batch of values which failed all retries with two smaller requests
+    #[arg(long, default_value = "false")]
+    pub split_failed_batches: bool,
+
+    /// Reject implausible values, e.g. a NaN returned while the meter updates its registers, so
+    /// they don't end up in a database; a read with a rejected value fails
+    #[arg(long, default_value_t = ValueValidation(tokio_common::ValueValidation::default()))]
+    pub value_validation: ValueValidation,
+
+    /// Order of the two words of 32-bit values
+    #[arg(long, default_value_t = WordOrder(proto::WordOrder::default()))]
+    pub word_order: WordOrder,
+
+    /// Read the values with function code 03 (read holding registers), for RS485 to TCP bridges
+    /// which only forward function code 03
+    #[arg(long, default_value = "false")]
+    pub input_via_holding: bool,
+
+    /// Append the daemon events as JSON Lines to this file, or to a Unix socket with unix:PATH
+    #[arg(long)]
+    pub events: Option<String>,
+
+    /// Validate the arguments and configuration files, then exit without opening the connection
+    #[arg(long, default_value = "false")]
+    pub check_config: bool,
 }
@@ -0,0 +1,120 @@
+//! Configuration files can be written in YAML, TOML or JSON, the format is
+//! selected by the file extension.
+
+use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// Detects the format by the file extension, YAML is the default.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => Self::Toml,
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Yaml,
+        }
+    }
+}
+
+/// Returns the 1-based line and column of a byte offset.
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+    (line, column)
+}
+
+/// Parses the content, errors are reported as `line:column: message`.
+pub fn parse<T: DeserializeOwned>(format: Format, content: &str) -> Result<T> {
+    // serde_yaml and serde_json append the position to their messages
+    fn strip_position(message: String, line: usize, column: usize) -> String {
+        let suffix = format!(" at line {line} column {column}");
+        message
+            .strip_suffix(&suffix)
+            .map(str::to_string)
+            .unwrap_or(message)
+    }
+
+    let (position, message) = match format {
+        Format::Yaml => match serde_yaml::from_str(content) {
+            Ok(value) => return Ok(value),
+            Err(err) => match err.location() {
+                Some(loc) => (
+                    Some((loc.line(), loc.column())),
+                    strip_position(err.to_string(), loc.line(), loc.column()),
+                ),
+                None => (None, err.to_string()),
+            },
+        },
+        Format::Toml => match toml::from_str(content) {
+            Ok(value) => return Ok(value),
+            Err(err) => (
+                err.span().map(|span| line_column(content, span.start)),
+                err.message().to_string(),
+            ),
+        },
+        Format::Json => match serde_json::from_str(content) {
+            Ok(value) => return Ok(value),
+            Err(err) => (
+                Some((err.line(), err.column())),
+                strip_position(err.to_string(), err.line(), err.column()),
+            ),
+        },
+    };
+    Err(match position {
+        Some((line, column)) => anyhow!("{line}:{column}: {message}"),
+        None => anyhow!("{message}"),
+    })
+}
+
+/// Reads and parses a configuration file, see [`Format::from_path`].
+pub fn load<T: DeserializeOwned>(path: &str) -> Result<T> {
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("Cannot open file {path:?}"))?;
+    parse(Format::from_path(Path::new(path)), &content)
+        .with_context(|| format!("Cannot parse file {path:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    type Map = BTreeMap<String, u16>;
+
+    #[test]
+    fn detect_format() {
+        assert_eq!(Format::from_path(Path::new("mqtt.toml")), Format::Toml);
+        assert_eq!(Format::from_path(Path::new("mqtt.JSON")), Format::Json);
+        assert_eq!(Format::from_path(Path::new("mqtt.yml")), Format::Yaml);
+        assert_eq!(Format::from_path(Path::new("mqtt")), Format::Yaml);
+    }
+
+    #[test]
+    fn same_content_in_all_formats() {
+        let expected = Map::from([("qos".to_string(), 1)]);
+        assert_eq!(parse::<Map>(Format::Yaml, "qos: 1").unwrap(), expected);
+        assert_eq!(parse::<Map>(Format::Toml, "qos = 1").unwrap(), expected);
+        assert_eq!(
+            parse::<Map>(Format::Json, r#"{"qos": 1}"#).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn errors_with_position() {
+        let err = parse::<Map>(Format::Yaml, "qos: 1\nport: x").unwrap_err();
+        assert!(err.to_string().starts_with("2:7: "), "{err}");
+        let err = parse::<Map>(Format::Toml, "qos = 1\nport = \"x\"").unwrap_err();
+        assert!(err.to_string().starts_with("2:8: "), "{err}");
+        let err = parse::<Map>(Format::Json, "{\"qos\": 1,\n\"port\": \"x\"}").unwrap_err();
+        assert!(err.to_string().starts_with("2:11: "), "{err}");
+    }
+}
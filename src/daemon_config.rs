@@ -0,0 +1,332 @@
+//! The daemon configuration file of `--config`, which runs a daemon per meter
+//! instead of the connection and daemon commands of the command line.
+//!
+//! Syntax errors are reported with their line and column, the errors of
+//! [`DaemonConfig::validate`] name the offending key, e.g.
+//! `meters[1].connection.baud_rate`.
+
+use crate::{commandline, config_file};
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use sdm72_lib::{protocol as proto, tokio_common::Calibration};
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+/// The connection to a meter.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum MeterConnection {
+    Tcp {
+        /// Host name or IP address with optional port
+        address: String,
+    },
+    Rtu {
+        device: String,
+        #[serde(default = "MeterConnection::default_baud_rate")]
+        baud_rate: u16,
+        #[serde(default = "MeterConnection::default_address")]
+        address: u8,
+        /// One of `np1b`, `ep1b`, `op1b` or `np2b`
+        parity_and_stop_bit: Option<String>,
+    },
+}
+
+impl MeterConnection {
+    fn default_baud_rate() -> u16 {
+        u16::from(&proto::BaudRate::default())
+    }
+
+    fn default_address() -> u8 {
+        *proto::Address::default()
+    }
+
+    /// Converts the connection into the connection of the command line, the
+    /// daemon command is added by the caller.
+    pub fn to_connection(&self, command: commandline::Commands) -> Result<commandline::Connection> {
+        Ok(match self {
+            Self::Tcp { address } => commandline::Connection::Tcp {
+                address: address.clone(),
+                command,
+            },
+            Self::Rtu {
+                device,
+                baud_rate,
+                address,
+                parity_and_stop_bit,
+            } => commandline::Connection::Rtu {
+                device: device.clone(),
+                baud_rate: proto::BaudRate::try_from(*baud_rate).with_context(|| "baud_rate")?,
+                address: proto::Address::try_from(*address).with_context(|| "address")?,
+                parity_and_stop_bit: match parity_and_stop_bit {
+                    Some(value) => commandline::ParityAndStopBit::from_str(value, true)
+                        .map_err(anyhow::Error::msg)
+                        .with_context(|| "parity_and_stop_bit")?,
+                    None => commandline::ParityAndStopBit::default(),
+                },
+                command,
+            },
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MeterConfig {
+    /// The name of the meter, appended to the MQTT topic and client id if the
+    /// file has more than one meter.
+    pub name: String,
+    pub connection: MeterConnection,
+    /// The output of this meter, instead of the top-level output.
+    pub output: Option<commandline::DaemonOutput>,
+    /// The actions file of this meter, instead of the top-level actions file.
+    pub actions: Option<String>,
+    pub ct_ratio: Option<f32>,
+    pub vt_ratio: Option<f32>,
+    /// The calibrations by measurement name, like `--calibrate`, e.g.
+    /// `l2_current: { multiplier: 1.03 }`.
+    #[serde(default)]
+    pub scaling: BTreeMap<String, Calibration>,
+    /// The state file of the lifetime energy of this meter, like
+    /// `--energy-state`.
+    pub energy_state: Option<PathBuf>,
+}
+
+impl MeterConfig {
+    fn calibration(
+        name: &str,
+        calibration: &Calibration,
+    ) -> std::result::Result<(proto::Measurement, Calibration), String> {
+        commandline::check_calibration(calibration)?;
+        Ok((commandline::parse_measurement(name)?, *calibration))
+    }
+
+    /// The calibrations of the meter, checked by the validation.
+    pub fn scaling(&self) -> Vec<(proto::Measurement, Calibration)> {
+        self.scaling
+            .iter()
+            .filter_map(|(name, calibration)| Self::calibration(name, calibration).ok())
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DaemonConfig {
+    #[serde(
+        default = "DaemonConfig::default_poll_interval",
+        with = "humantime_serde"
+    )]
+    pub poll_interval: Duration,
+    /// The delay between Modbus requests, `--delay` takes precedence.
+    #[serde(default, with = "humantime_serde")]
+    pub delay: Option<Duration>,
+    /// The timeout of Modbus requests, `--request-timeout` takes precedence.
+    #[serde(default, with = "humantime_serde")]
+    pub timeout: Option<Duration>,
+    /// The timeout for opening the connection, `--connect-timeout` takes
+    /// precedence.
+    #[serde(default, with = "humantime_serde")]
+    pub connect_timeout: Option<Duration>,
+    pub reconnect_after: Option<u32>,
+    /// Serve the values of the last poll within this interval after it.
+    #[serde(default, with = "humantime_serde")]
+    pub min_poll_interval: Duration,
+    /// The smoothed measurements like `--smooth`, e.g. `l1_current=ema:0.2`.
+    #[serde(default)]
+    pub smooth: Vec<String>,
+    /// The actions file of the meters without their own.
+    pub actions: Option<String>,
+    /// The output of the meters without their own.
+    pub output: Option<commandline::DaemonOutput>,
+    pub meters: Vec<MeterConfig>,
+}
+
+impl DaemonConfig {
+    fn default_poll_interval() -> Duration {
+        Duration::from_secs(2)
+    }
+
+    pub fn load(config_file_path: &str) -> Result<Self> {
+        log::debug!("Loading daemon config file from {config_file_path:?}");
+        config_file::load(config_file_path).with_context(|| "Cannot read daemon config")
+    }
+
+    /// The output of a meter.
+    pub fn output<'a>(&'a self, meter: &'a MeterConfig) -> Option<&'a commandline::DaemonOutput> {
+        meter.output.as_ref().or(self.output.as_ref())
+    }
+
+    /// The actions file of a meter.
+    pub fn actions<'a>(&'a self, meter: &'a MeterConfig) -> Option<&'a str> {
+        meter.actions.as_deref().or(self.actions.as_deref())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.poll_interval.is_zero() {
+            bail!("poll_interval: The poll interval must not be zero");
+        }
+        if self.reconnect_after == Some(0) {
+            bail!("reconnect_after: The number of failed polls must be at least 1");
+        }
+        for (index, smoothing) in self.smooth.iter().enumerate() {
+            commandline::parse_smoothing(smoothing)
+                .map_err(anyhow::Error::msg)
+                .with_context(|| format!("smooth[{index}]"))?;
+        }
+        if self.meters.is_empty() {
+            bail!("meters: At least one meter is required");
+        }
+        let mut listen_addresses = Vec::new();
+        for (index, meter) in self.meters.iter().enumerate() {
+            let key = format!("meters[{index}]");
+            if meter.name.is_empty() || meter.name.contains(['/', '+', '#']) {
+                bail!(
+                    "{key}.name: Invalid name {:?}, it must not be empty or contain '/', '+' or '#'",
+                    meter.name
+                );
+            }
+            if self.meters[..index]
+                .iter()
+                .any(|other| other.name == meter.name)
+            {
+                bail!("{key}.name: The meter {:?} is defined twice", meter.name);
+            }
+            meter
+                .connection
+                .to_connection(commandline::Commands::ReadAll {
+                    format: commandline::ValuesFormat::Json,
+                })
+                .with_context(|| format!("{key}.connection"))?;
+            for (ratio_key, ratio) in [("ct_ratio", meter.ct_ratio), ("vt_ratio", meter.vt_ratio)] {
+                if let Some(ratio) = ratio {
+                    commandline::parse_ratio(&ratio.to_string())
+                        .map_err(anyhow::Error::msg)
+                        .with_context(|| format!("{key}.{ratio_key}"))?;
+                }
+            }
+            for (name, calibration) in &meter.scaling {
+                MeterConfig::calibration(name, calibration)
+                    .map_err(anyhow::Error::msg)
+                    .with_context(|| format!("{key}.scaling.{name}"))?;
+            }
+            match self.output(meter) {
+                None => {
+                    bail!("{key}.output: The meter has no output and there is no top-level output")
+                }
+                Some(commandline::DaemonOutput::Http { listen, .. }) => {
+                    if listen_addresses.contains(&listen) {
+                        bail!("{key}.output.listen: The address {listen} is used by another meter");
+                    }
+                    listen_addresses.push(listen);
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Result<DaemonConfig> {
+        config_file::parse(config_file::Format::Yaml, content)
+    }
+
+    #[test]
+    fn parse_meters() {
+        let config = parse(
+            r#"
+poll_interval: 5s
+output: !mqtt
+  config_file: mqtt.yaml
+meters:
+  - name: house
+    connection: !tcp
+      address: 192.168.0.222:502
+  - name: garage
+    connection: !rtu
+      device: /dev/ttyUSB0
+      baud_rate: 9600
+      address: 2
+    output: !http
+      listen: 0.0.0.0:8080
+    scaling:
+      l2_current: { multiplier: 1.03 }
+      l1_voltage: { offset: -0.8 }
+"#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.poll_interval, Duration::from_secs(5));
+        assert_eq!(
+            config.output(&config.meters[0]),
+            Some(&commandline::DaemonOutput::Mqtt {
+                config_file: "mqtt.yaml".to_string(),
+                tariff: None,
+            })
+        );
+        assert!(matches!(
+            config.output(&config.meters[1]),
+            Some(commandline::DaemonOutput::Http { probe_interval, .. })
+                if *probe_interval == Duration::from_secs(30)
+        ));
+        assert_eq!(
+            config.meters[1].scaling(),
+            vec![
+                (
+                    proto::Measurement::L1Voltage,
+                    Calibration {
+                        multiplier: 1.0,
+                        offset: -0.8
+                    }
+                ),
+                (
+                    proto::Measurement::L2Current,
+                    Calibration {
+                        multiplier: 1.03,
+                        offset: 0.0
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_name_the_key() {
+        let config = parse(
+            r#"
+output: !console
+meters:
+  - name: house
+    connection: !rtu
+      device: /dev/ttyUSB0
+  - name: garage
+    connection: !rtu
+      device: /dev/ttyUSB0
+      baud_rate: 1234
+"#,
+        )
+        .unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(
+            format!("{err:#}").starts_with("meters[1].connection: baud_rate: "),
+            "{err:#}"
+        );
+
+        let mut config = config;
+        config.meters.truncate(1);
+        config.meters[0]
+            .scaling
+            .insert("l4_current".to_string(), Calibration::default());
+        let err = config.validate().unwrap_err();
+        assert!(
+            format!("{err:#}").starts_with("meters[0].scaling.l4_current: "),
+            "{err:#}"
+        );
+
+        let err = parse("meters: []\npoll_intervall: 1s").unwrap_err();
+        assert!(err.to_string().starts_with("2:1: "), "{err}");
+    }
+}
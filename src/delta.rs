@@ -0,0 +1,170 @@
+//! This module compares two reads of the measurement values, e.g. to publish
+//! only the values which changed significantly.
+//!
+//! A [`Deadband`] is the band around the previous value in which changes are
+//! ignored, given as an absolute amount and as a share of the previous value.
+//! [`DeadbandConfig`] holds a default deadband and overrides for single
+//! measurements, [`ValuesDelta::compare`] returns the measurements which
+//! changed beyond their deadband.
+//!
+//! # Example
+//!
+//! ```
+//! use sdm72_lib::{
+//!     delta::{Deadband, DeadbandConfig},
+//!     protocol::Measurement,
+//! };
+//!
+//! let config = DeadbandConfig::new(Deadband::absolute(0.05))
+//!     .with(Measurement::TotalPower, Deadband::relative(0.01));
+//! assert!(!config.deadband(Measurement::TotalPower).exceeded(1000.0, 1005.0));
+//! assert!(config.deadband(Measurement::Frequency).exceeded(50.0, 50.1));
+//! ```
+
+use crate::{protocol as proto, tokio_common::AllValues};
+use std::collections::HashMap;
+
+/// The band around the previous value in which changes are ignored.
+///
+/// A value changed if it differs from the previous value by more than the
+/// `absolute` amount and by more than the `relative` share of the previous
+/// value. The default deadband reports every change.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct Deadband {
+    pub absolute: f32,
+    /// The share of the previous value, e.g. `0.01` for 1 %.
+    pub relative: f32,
+}
+
+impl Deadband {
+    pub fn absolute(absolute: f32) -> Self {
+        Self {
+            absolute,
+            relative: 0.0,
+        }
+    }
+
+    pub fn relative(relative: f32) -> Self {
+        Self {
+            absolute: 0.0,
+            relative,
+        }
+    }
+
+    /// Returns `true` if `value` is outside of the deadband around `previous`.
+    ///
+    /// A change from or to `NaN` is always outside.
+    pub fn exceeded(&self, previous: f32, value: f32) -> bool {
+        if previous.is_nan() || value.is_nan() {
+            return previous.is_nan() != value.is_nan();
+        }
+        let band = self.absolute.max(self.relative * previous.abs());
+        (value - previous).abs() > band
+    }
+}
+
+/// The deadbands of the measurements, see [`ValuesDelta::compare`].
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct DeadbandConfig {
+    /// The deadband of the measurements without an override.
+    pub default: Deadband,
+    pub overrides: HashMap<proto::Measurement, Deadband>,
+}
+
+impl DeadbandConfig {
+    pub fn new(default: Deadband) -> Self {
+        Self {
+            default,
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Sets the deadband of a single measurement.
+    pub fn with(mut self, measurement: proto::Measurement, deadband: Deadband) -> Self {
+        self.overrides.insert(measurement, deadband);
+        self
+    }
+
+    /// The deadband of the measurement.
+    pub fn deadband(&self, measurement: proto::Measurement) -> Deadband {
+        self.overrides
+            .get(&measurement)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// A measurement which changed beyond its deadband.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Change {
+    pub measurement: proto::Measurement,
+    pub previous: f32,
+    pub value: f32,
+}
+
+/// The measurements which changed between two reads.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ValuesDelta {
+    /// The changes in the order of [`Measurement::ALL`](proto::Measurement::ALL).
+    pub changes: Vec<Change>,
+}
+
+impl ValuesDelta {
+    /// Compares the values of two reads with the deadbands of the measurements.
+    pub fn compare(previous: &AllValues, next: &AllValues, config: &DeadbandConfig) -> Self {
+        let changes = proto::Measurement::ALL
+            .iter()
+            .map(|&measurement| Change {
+                measurement,
+                previous: previous.value(measurement),
+                value: next.value(measurement),
+            })
+            .filter(|change| {
+                config
+                    .deadband(change.measurement)
+                    .exceeded(change.previous, change.value)
+            })
+            .collect();
+        Self { changes }
+    }
+
+    /// Returns `true` if no measurement changed.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Returns `true` if the measurement changed.
+    pub fn contains(&self, measurement: proto::Measurement) -> bool {
+        self.changes
+            .iter()
+            .any(|change| change.measurement == measurement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadbands() {
+        let deadband = Deadband {
+            absolute: 1.0,
+            relative: 0.1,
+        };
+        assert!(!deadband.exceeded(5.0, 6.0));
+        assert!(deadband.exceeded(5.0, 6.5));
+        assert!(!deadband.exceeded(100.0, 109.0));
+        assert!(deadband.exceeded(-100.0, -111.0));
+        assert!(deadband.exceeded(f32::NAN, 0.0));
+        assert!(!deadband.exceeded(f32::NAN, f32::NAN));
+
+        assert!(Deadband::default().exceeded(1.0, 1.001));
+        assert!(!Deadband::default().exceeded(1.0, 1.0));
+    }
+}
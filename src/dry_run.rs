@@ -0,0 +1,143 @@
+//! This module records the writes of settings and resets instead of sending
+//! them, e.g. to audit a change before touching a production meter.
+//!
+//! The [`Recorder`] takes the place of the Modbus transport: it has the same
+//! write methods as the safe clients, encodes the values like they do,
+//! including the [`WordOrder`](proto::WordOrder), and keeps the requests as
+//! [`PlannedWrite`]s.
+//!
+//! # Example
+//!
+//! ```
+//! use sdm72_lib::{
+//!     dry_run::Recorder,
+//!     protocol::{Address, WordOrder},
+//!     settings_diff::Setting,
+//! };
+//!
+//! let mut recorder = Recorder::new(WordOrder::default());
+//! let write = recorder.write_setting(Setting::Address(Address::try_from(2).unwrap()));
+//! assert_eq!(write.address, 0x0014);
+//! assert_eq!(write.words, vec![0x4000, 0x0000]);
+//! assert_eq!(write.to_string(), "Address: write 0x4000 0x0000 to holding register 0x0014");
+//! ```
+
+use crate::{
+    protocol::{self as proto, ModbusParam},
+    settings_diff::Setting,
+};
+use std::fmt;
+
+/// A write request which was recorded instead of sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedWrite {
+    /// What is written, e.g. the name of the setting.
+    pub name: &'static str,
+    /// The first holding register.
+    pub address: u16,
+    /// The encoded words in the order they are sent.
+    pub words: Vec<proto::Word>,
+}
+impl fmt::Display for PlannedWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: write", self.name)?;
+        for word in &self.words {
+            write!(f, " {word:#06X}")?;
+        }
+        write!(f, " to holding register {:#06X}", self.address)
+    }
+}
+
+/// Records the writes instead of sending them, see the [module](self)
+/// documentation.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    word_order: proto::WordOrder,
+    writes: Vec<PlannedWrite>,
+}
+impl Recorder {
+    /// Creates a recorder which encodes the words like a client with
+    /// `word_order`.
+    pub fn new(word_order: proto::WordOrder) -> Self {
+        Self {
+            word_order,
+            writes: Vec::new(),
+        }
+    }
+
+    fn record(
+        &mut self,
+        name: &'static str,
+        address: u16,
+        words: Vec<proto::Word>,
+    ) -> &PlannedWrite {
+        self.writes.push(PlannedWrite {
+            name,
+            address,
+            words,
+        });
+        self.writes.last().unwrap()
+    }
+
+    /// Records the write of the Key Parameter Programming Authorization.
+    pub fn set_kppa(&mut self, password: proto::Password) -> &PlannedWrite {
+        let words = self
+            .word_order
+            .apply(proto::KPPA::encode_for_write_registers(password));
+        self.record("KPPA", proto::KPPA::ADDRESS, words)
+    }
+
+    /// Records the write of a setting.
+    pub fn write_setting(&mut self, setting: Setting) -> &PlannedWrite {
+        let (address, words) = setting.encode_for_write_registers();
+        let words = self.word_order.apply(words);
+        self.record(setting.name(), address, words)
+    }
+
+    /// Records the reset of the historical data.
+    pub fn reset_historical_data(&mut self) -> &PlannedWrite {
+        self.record(
+            "Reset historical data",
+            proto::ResetHistoricalData::ADDRESS,
+            proto::ResetHistoricalData::encode_for_write_registers(),
+        )
+    }
+
+    /// Records the reset of the data of `target`.
+    pub fn reset(&mut self, target: proto::ResetTarget) -> &PlannedWrite {
+        self.record(
+            "Reset",
+            proto::ResetHistoricalData::ADDRESS,
+            target.encode_for_write_registers(),
+        )
+    }
+
+    /// The recorded writes in the order they were made.
+    pub fn writes(&self) -> &[PlannedWrite] {
+        &self.writes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_encoded_writes() {
+        let mut recorder = Recorder::new(proto::WordOrder::LittleEndianWords);
+        recorder.set_kppa(proto::Password::try_from(1000).unwrap());
+        recorder.write_setting(Setting::BaudRate(proto::BaudRate::B9600));
+        recorder.reset(proto::ResetTarget::ResettableEnergy);
+        let writes = recorder.writes();
+        assert_eq!(writes.len(), 3);
+        assert_eq!(writes[0].address, proto::KPPA::ADDRESS);
+        assert_eq!(writes[1].name, "Baud rate");
+        assert_eq!(writes[1].address, proto::BaudRate::ADDRESS);
+        let (_, words) = Setting::BaudRate(proto::BaudRate::B9600).encode_for_write_registers();
+        assert_eq!(writes[1].words, vec![words[1], words[0]]);
+        assert_eq!(
+            writes[2].to_string(),
+            "Reset: write 0x0003 to holding register 0xF010"
+        );
+    }
+}
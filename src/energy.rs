@@ -0,0 +1,274 @@
+//! Tracks the energy counters of the meter across their resets, so the
+//! daemons can serve a lifetime total which never decreases.
+//!
+//! The counters of the meter are 32-bit floats, which are reset by
+//! `reset-energy`, by a replaced meter or wrap when they lose the precision to
+//! count. A counter which drops near zero and stays there for a few reads is
+//! taken as a rollover: its last value is added to the offset of the counter,
+//! so the lifetime total is the offset plus the current value. Smaller
+//! decreases are glitches of a read and don't lower the total. The state file
+//! is written when the offsets change and otherwise at most every
+//! [`SAVE_INTERVAL`], so a restarted daemon continues counting.
+
+use anyhow::{anyhow, Context, Result};
+use sdm72_lib::{protocol as proto, tokio_common::AllValues};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// The energy counters which are tracked, the resettable counters are
+/// expected to be reset.
+const COUNTERS: [proto::Measurement; 4] = [
+    proto::Measurement::ImportEnergyActive,
+    proto::Measurement::ExportEnergyActive,
+    proto::Measurement::TotalEnergyActive,
+    proto::Measurement::TotalEnergyReactive,
+];
+
+/// A counter which drops below this fraction of its last value is a rollover
+/// candidate.
+const ROLLOVER_FRACTION: f64 = 0.1;
+
+/// The number of consecutive reads below the [`ROLLOVER_FRACTION`] which
+/// confirm a rollover.
+const ROLLOVER_READS: u32 = 3;
+
+/// The longest time the last values of the counters are not stored.
+const SAVE_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// The tracked state of a counter.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+struct Counter {
+    /// The value of the counter at the last update.
+    last: Option<f64>,
+    /// The sum of the values of the counter before its rollovers.
+    offset: f64,
+    rollovers: u32,
+    /// The number of consecutive reads below the [`ROLLOVER_FRACTION`] of
+    /// the last value.
+    #[serde(skip)]
+    candidates: u32,
+}
+
+impl Counter {
+    fn lifetime(&self) -> f64 {
+        self.offset + self.last.unwrap_or_default()
+    }
+
+    /// Updates the counter with the value of a read, returns the previous
+    /// value if a rollover is confirmed.
+    fn update(&mut self, value: f64) -> Option<f64> {
+        let Some(previous) = self.last else {
+            self.last = Some(value);
+            return None;
+        };
+        if value >= previous * ROLLOVER_FRACTION {
+            self.candidates = 0;
+            self.last = Some(value.max(previous));
+            return None;
+        }
+        self.candidates += 1;
+        if self.candidates < ROLLOVER_READS {
+            return None;
+        }
+        self.candidates = 0;
+        self.offset += previous;
+        self.rollovers += 1;
+        self.last = Some(value);
+        Some(previous)
+    }
+}
+
+/// The persisted state of an [`EnergyTracker`].
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct State {
+    /// The counters by measurement name.
+    counters: BTreeMap<String, Counter>,
+}
+
+/// A confirmed reset of an energy counter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rollover {
+    pub measurement: proto::Measurement,
+    pub previous: f64,
+    pub value: f64,
+}
+
+/// Accumulates the lifetime totals of the energy counters.
+#[derive(Debug)]
+pub struct EnergyTracker {
+    state_file: PathBuf,
+    state: State,
+    /// When the state file was written last.
+    saved: Option<Instant>,
+}
+
+impl EnergyTracker {
+    /// Continues with the offsets of the state file, if it exists.
+    pub fn open(state_file: PathBuf) -> Result<Self> {
+        let state = match std::fs::read(&state_file) {
+            Ok(content) => serde_json::from_slice(&content)
+                .with_context(|| format!("Invalid energy state file {state_file:?}"))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(err) => {
+                return Err(
+                    anyhow!(err).context(format!("Cannot read energy state file {state_file:?}"))
+                )
+            }
+        };
+        Ok(Self {
+            state_file,
+            state,
+            saved: None,
+        })
+    }
+
+    /// The lifetime totals by measurement name.
+    pub fn lifetime(&self) -> BTreeMap<&'static str, f64> {
+        COUNTERS
+            .iter()
+            .filter_map(|measurement| {
+                let counter = self.state.counters.get(measurement.name())?;
+                Some((measurement.name(), counter.lifetime()))
+            })
+            .collect()
+    }
+
+    /// Updates the counters with the values of a read, `NaN` values are
+    /// skipped.
+    fn update(&mut self, values: &AllValues) -> Vec<Rollover> {
+        let mut rollovers = Vec::new();
+        for measurement in COUNTERS {
+            let value = f64::from(values.value(measurement));
+            if value.is_nan() {
+                continue;
+            }
+            let counter = self
+                .state
+                .counters
+                .entry(measurement.name().to_string())
+                .or_default();
+            if let Some(previous) = counter.update(value) {
+                rollovers.push(Rollover {
+                    measurement,
+                    previous,
+                    value,
+                });
+            }
+        }
+        rollovers
+    }
+
+    /// Updates the counters with the values read now, returns the rollovers
+    /// confirmed by this read.
+    ///
+    /// The state is stored if an offset changed or a counter is new, and
+    /// otherwise if it was not stored for the [`SAVE_INTERVAL`].
+    pub fn record(&mut self, values: &AllValues) -> Result<Vec<Rollover>> {
+        let counters = self.state.counters.len();
+        let rollovers = self.update(values);
+        if !rollovers.is_empty()
+            || self.state.counters.len() != counters
+            || self
+                .saved
+                .is_none_or(|saved| saved.elapsed() >= SAVE_INTERVAL)
+        {
+            self.save()?;
+        }
+        Ok(rollovers)
+    }
+
+    /// Writes the state file.
+    fn save(&mut self) -> Result<()> {
+        let path = &self.state_file;
+        let content = serde_json::to_vec_pretty(&self.state)?;
+        // Replace the file atomically, so a crash doesn't lose the offsets
+        let tmp = path.with_extension("tmp");
+        std::fs::File::create(&tmp)
+            .and_then(|mut file| {
+                file.write_all(&content)?;
+                // Otherwise the rename may reach the disk before the content
+                file.sync_all()
+            })
+            .and_then(|()| std::fs::rename(&tmp, path))
+            .with_context(|| format!("Cannot write energy state file {path:?}"))?;
+        self.saved = Some(Instant::now());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulate_across_rollovers() {
+        let mut tracker = EnergyTracker {
+            state_file: PathBuf::from("energy.json"),
+            state: State::default(),
+            saved: None,
+        };
+        let mut counter = Counter::default();
+        assert_eq!(counter.update(100.0), None);
+        assert_eq!(counter.update(110.0), None);
+        assert_eq!(counter.lifetime(), 110.0);
+        // A glitch doesn't lower the total
+        assert_eq!(counter.update(109.0), None);
+        assert_eq!(counter.update(5.0), None);
+        assert_eq!(counter.update(110.0), None);
+        assert_eq!(counter.lifetime(), 110.0);
+        // A reset of the meter, confirmed by the third read
+        assert_eq!(counter.update(5.0), None);
+        assert_eq!(counter.update(5.0), None);
+        assert_eq!(counter.lifetime(), 110.0);
+        assert_eq!(counter.update(5.0), Some(110.0));
+        assert_eq!(counter.update(6.0), None);
+        assert_eq!(counter.lifetime(), 116.0);
+        assert_eq!(counter.rollovers, 1);
+
+        tracker.state.counters.insert(
+            proto::Measurement::TotalEnergyActive.name().to_string(),
+            counter,
+        );
+        assert_eq!(
+            tracker.lifetime(),
+            BTreeMap::from([(proto::Measurement::TotalEnergyActive.name(), 116.0)])
+        );
+    }
+
+    fn values(energy: f32) -> AllValues {
+        let values = proto::Measurement::ALL
+            .iter()
+            .map(|measurement| (measurement.name().to_string(), energy.into()))
+            .collect::<serde_json::Map<_, _>>();
+        serde_json::from_value(values.into()).unwrap()
+    }
+
+    #[test]
+    fn persist_offsets() {
+        let state_file =
+            std::env::temp_dir().join(format!("sdm72-energy-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&state_file);
+
+        let mut tracker = EnergyTracker::open(state_file.clone()).unwrap();
+        assert!(tracker.record(&values(100.0)).unwrap().is_empty());
+        assert!(state_file.exists());
+        for _ in 1..ROLLOVER_READS {
+            assert!(tracker.record(&values(2.0)).unwrap().is_empty());
+        }
+        let rollovers = tracker.record(&values(2.0)).unwrap();
+        assert_eq!(rollovers.len(), COUNTERS.len());
+        assert_eq!(rollovers[0].previous, 100.0);
+
+        let tracker = EnergyTracker::open(state_file.clone()).unwrap();
+        assert_eq!(
+            tracker.lifetime()[proto::Measurement::TotalEnergyActive.name()],
+            102.0
+        );
+        std::fs::remove_file(&state_file).unwrap();
+    }
+}
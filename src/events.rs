@@ -0,0 +1,210 @@
+//! A stream of the daemon events in JSON Lines format, so SIEM or automation
+//! systems can consume them without parsing the log lines.
+//!
+//! The target of `--events` is a file the events are appended to, or
+//! `unix:PATH` for a Unix stream socket. Every line has the `timestamp`, the
+//! `meter` of the daemon configuration file if there is one, and the `event`
+//! with its fields, e.g.
+//!
+//! ```json
+//! {"timestamp":"2024-05-01T12:00:00.000Z","meter":"house","event":"poll_failed","error":"...","consecutive_failures":3}
+//! ```
+//!
+//! The events are in addition to the log and best effort, if the target
+//! cannot be written the error is logged once until a write succeeds again,
+//! and the daemon carries on.
+
+use anyhow::{Context, Result};
+use sdm72_lib::{alarms::AlarmState, tokio_common::AllValues, tokio_sync_safe_client::SafeClient};
+use std::{
+    cell::RefCell,
+    io::Write,
+    sync::{Mutex, OnceLock},
+    time::SystemTime,
+};
+
+/// An event of a daemon.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// The daemon opened the connection and starts polling.
+    Started {
+        output: &'a str,
+    },
+    PollOk,
+    PollFailed {
+        error: String,
+        consecutive_failures: u32,
+    },
+    /// The connection was restarted after consecutive failed polls.
+    Reconnect {
+        error: Option<String>,
+    },
+    /// A rule of the actions file was triggered.
+    AlertFired {
+        rule: &'a str,
+        measurement: &'static str,
+        value: f32,
+    },
+    /// An alarm of the actions file was raised or cleared.
+    AlarmChanged {
+        alarm: &'a str,
+        measurement: &'static str,
+        state: AlarmState,
+        value: f32,
+    },
+    /// An energy counter decreased, e.g. after a reset of the meter, see
+    /// `--energy-state`.
+    EnergyRollover {
+        measurement: &'static str,
+        previous: f64,
+        value: f64,
+    },
+    /// A setting was written, the value of passwords is omitted.
+    SettingChanged {
+        setting: &'a str,
+        value: Option<&'a str>,
+    },
+    /// The daemon stopped with an error.
+    Stopped {
+        error: String,
+    },
+}
+
+/// The line of an event.
+#[derive(serde::Serialize)]
+struct Line<'a> {
+    timestamp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meter: Option<&'a str>,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+struct Sink {
+    writer: Box<dyn Write + Send>,
+    /// Set after the first failed write, which is logged.
+    failed: bool,
+}
+
+static SINK: OnceLock<Mutex<Sink>> = OnceLock::new();
+
+thread_local! {
+    static METER: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Opens the target of the events, see the module documentation.
+pub fn open(target: &str) -> Result<()> {
+    let writer: Box<dyn Write + Send> = match target.strip_prefix("unix:") {
+        #[cfg(unix)]
+        Some(path) => Box::new(
+            std::os::unix::net::UnixStream::connect(path)
+                .with_context(|| format!("Cannot connect to the events socket {path}"))?,
+        ),
+        #[cfg(not(unix))]
+        Some(_) => anyhow::bail!("Unix sockets are not supported on this platform"),
+        None => Box::new(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(target)
+                .with_context(|| format!("Cannot open the events file {target}"))?,
+        ),
+    };
+    let _ = SINK.set(Mutex::new(Sink {
+        writer,
+        failed: false,
+    }));
+    Ok(())
+}
+
+/// Sets the meter of the events emitted by the current thread.
+pub fn set_meter(meter: Option<&str>) {
+    METER.with(|current| *current.borrow_mut() = meter.map(str::to_string));
+}
+
+/// The meter of the events emitted by the current thread, to pass it on to
+/// the threads it spawns.
+pub fn meter() -> Option<String> {
+    METER.with(|current| current.borrow().clone())
+}
+
+fn format(event: &Event, meter: Option<&str>, now: SystemTime) -> String {
+    let line = Line {
+        timestamp: humantime::format_rfc3339_millis(now).to_string(),
+        meter,
+        event,
+    };
+    serde_json::to_string(&line).expect("events are serializable")
+}
+
+/// Reads all values for the daemons without a [`HealthMonitor`], which stop
+/// at the first failed poll.
+///
+/// [`HealthMonitor`]: crate::health::HealthMonitor
+pub fn read_all(client: &mut SafeClient) -> Result<AllValues> {
+    match client.read_all() {
+        Ok(values) => {
+            emit(Event::PollOk);
+            crate::systemd::poll_succeeded(&values);
+            Ok(values)
+        }
+        Err(err) => {
+            crate::systemd::poll_failed(&err);
+            emit(Event::PollFailed {
+                error: err.to_string(),
+                consecutive_failures: 1,
+            });
+            Err(anyhow::Error::new(err).context("Cannot read all values"))
+        }
+    }
+}
+
+/// Writes an event to the target, if `--events` was given.
+pub fn emit(event: Event) {
+    let Some(sink) = SINK.get() else {
+        return;
+    };
+    let line = METER.with(|meter| format(&event, meter.borrow().as_deref(), SystemTime::now()));
+    let mut sink = sink.lock().unwrap();
+    let result = writeln!(sink.writer, "{line}").and_then(|()| sink.writer.flush());
+    match result {
+        Ok(()) => sink.failed = false,
+        Err(err) if !sink.failed => {
+            sink.failed = true;
+            log::warn!("Cannot write the event: {err}");
+        }
+        Err(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_events() {
+        assert_eq!(
+            format(
+                &Event::PollFailed {
+                    error: "Timeout".to_string(),
+                    consecutive_failures: 2,
+                },
+                Some("house"),
+                SystemTime::UNIX_EPOCH
+            ),
+            r#"{"timestamp":"1970-01-01T00:00:00.000Z","meter":"house","event":"poll_failed","error":"Timeout","consecutive_failures":2}"#
+        );
+        assert_eq!(
+            format(
+                &Event::SettingChanged {
+                    setting: "password",
+                    value: None,
+                },
+                None,
+                SystemTime::UNIX_EPOCH
+            ),
+            r#"{"timestamp":"1970-01-01T00:00:00.000Z","event":"setting_changed","setting":"password","value":null}"#
+        );
+    }
+}
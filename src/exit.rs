@@ -0,0 +1,234 @@
+//! The exit codes of the command line and the error output of
+//! `--error-format json`, so scripts can tell the causes of a failure apart.
+//!
+//! | Code | Kind            | Cause                                                         |
+//! |------|-----------------|---------------------------------------------------------------|
+//! | 0    |                 | Success                                                       |
+//! | 1    | `other`         | Any other error                                               |
+//! | 2    | `usage`         | Invalid arguments, reported by the argument parser            |
+//! | 3    | `connect`       | The connection cannot be opened, e.g. an unknown host         |
+//! | 4    | `timeout`       | The meter didn't answer in time                               |
+//! | 5    | `auth`          | No password was given or the authorization (KPPA) failed      |
+//! | 6    | `invalid_value` | The meter rejected a value or sent an invalid one             |
+//! | 7    | `exception`     | Any other exception response of the meter                     |
+
+use crate::commandline::ErrorFormat;
+use sdm72_lib::{
+    modbus::ExceptionCode,
+    protocol as proto,
+    tokio_common::{Error, LinkError, Operation},
+};
+use std::process::ExitCode;
+
+/// The kind of a failure of the command line, see the module documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Other,
+    Connect,
+    Timeout,
+    Auth,
+    InvalidValue,
+    Exception,
+}
+
+impl ErrorKind {
+    /// The exit code of the process.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::Other => 1,
+            Self::Connect => 3,
+            Self::Timeout => 4,
+            Self::Auth => 5,
+            Self::InvalidValue => 6,
+            Self::Exception => 7,
+        }
+    }
+
+    /// Classifies an error by the first of its causes with a known kind.
+    pub fn classify(err: &anyhow::Error) -> Self {
+        if let Some(classified) = err.downcast_ref::<Classified>() {
+            return classified.kind;
+        }
+        err.chain()
+            .find_map(|cause| {
+                if let Some(err) = cause.downcast_ref::<Error>() {
+                    Some(Self::of_error(err))
+                } else if cause.is::<proto::Error>() {
+                    Some(Self::InvalidValue)
+                } else {
+                    cause
+                        .downcast_ref::<std::io::Error>()
+                        .filter(|err| err.kind() == std::io::ErrorKind::TimedOut)
+                        .map(|_| Self::Timeout)
+                }
+            })
+            .unwrap_or(Self::Other)
+    }
+
+    fn of_error(err: &Error) -> Self {
+        match err {
+            Error::Register { context, .. }
+                if context.operation == Operation::Write && context.register == "KPPA" =>
+            {
+                Self::Auth
+            }
+            Error::Register { source, .. } => Self::of_error(source),
+            Error::Unreachable { .. } => Self::Connect,
+            Error::Password(_) | Error::Unauthorized => Self::Auth,
+            Error::Protocol(_)
+            | Error::VerificationFailed { .. }
+            | Error::NotBroadcastable(_)
+            | Error::ModbusException(ExceptionCode::IllegalDataValue) => Self::InvalidValue,
+            Error::ModbusException(_) => Self::Exception,
+            _ => match err.link_error() {
+                Some(LinkError::Timeout) => Self::Timeout,
+                Some(LinkError::Transport) => Self::Connect,
+                _ => Self::Other,
+            },
+        }
+    }
+}
+
+/// An error message with the kind of the failure, e.g. as the context of the
+/// errors opening a connection.
+#[derive(Debug)]
+pub struct Classified {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl Classified {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Classified {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// The error object of `--error-format json`.
+#[derive(Debug, serde::Serialize)]
+struct JsonError {
+    kind: ErrorKind,
+    exit_code: u8,
+    message: String,
+    /// The causes of the error, the innermost last.
+    causes: Vec<String>,
+    /// What to do about an exception response of the meter, see
+    /// [`Error::hint`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+}
+
+/// The hint of the first cause which is an exception response.
+fn hint(err: &anyhow::Error) -> Option<String> {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<Error>())
+        .find_map(Error::hint)
+}
+
+fn json(err: &anyhow::Error) -> String {
+    let kind = ErrorKind::classify(err);
+    let error = JsonError {
+        kind,
+        exit_code: kind.exit_code(),
+        message: err.to_string(),
+        causes: err.chain().skip(1).map(ToString::to_string).collect(),
+        hint: hint(err),
+    };
+    serde_json::to_string(&error).expect("errors are serializable")
+}
+
+/// Prints the error to stderr and returns the exit code of its kind.
+pub fn report(err: &anyhow::Error, format: ErrorFormat) -> ExitCode {
+    match format {
+        ErrorFormat::Text => {
+            eprintln!("Error: {err:?}");
+            if let Some(hint) = hint(err) {
+                eprintln!("\nHint: {hint}");
+            }
+        }
+        ErrorFormat::Json => eprintln!("{}", json(err)),
+    }
+    ExitCode::from(ErrorKind::classify(err).exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use sdm72_lib::tokio_common::RegisterContext;
+
+    fn register_error(register: &'static str, source: Error) -> Error {
+        Error::Register {
+            context: RegisterContext {
+                operation: Operation::Write,
+                kind: proto::RegisterKind::Holding,
+                register,
+                address: None,
+                slave: None,
+            },
+            source: Box::new(source),
+        }
+    }
+
+    #[test]
+    fn classify_errors() {
+        let timeout: anyhow::Error =
+            Error::Modbus(std::io::Error::new(std::io::ErrorKind::TimedOut, "Timeout").into())
+                .into();
+        assert_eq!(
+            ErrorKind::classify(&timeout.context("Cannot read all values")),
+            ErrorKind::Timeout
+        );
+
+        let connect = anyhow::anyhow!("Connection refused")
+            .context(Classified::new(
+                ErrorKind::Connect,
+                "Cannot open 127.0.0.1:502",
+            ))
+            .context("Cannot start the daemon");
+        assert_eq!(ErrorKind::classify(&connect), ErrorKind::Connect);
+
+        let auth = register_error(
+            "KPPA",
+            Error::ModbusException(ExceptionCode::IllegalDataValue),
+        );
+        assert_eq!(ErrorKind::classify(&auth.into()), ErrorKind::Auth);
+
+        let invalid = register_error(
+            "SystemType",
+            Error::ModbusException(ExceptionCode::IllegalDataValue),
+        );
+        assert_eq!(
+            ErrorKind::classify(&invalid.into()),
+            ErrorKind::InvalidValue
+        );
+
+        let other = Err::<(), _>(std::io::Error::other("Disk full"))
+            .context("Cannot write")
+            .unwrap_err();
+        assert_eq!(ErrorKind::classify(&other), ErrorKind::Other);
+
+        assert_eq!(
+            json(
+                &anyhow::anyhow!(Classified::new(ErrorKind::Auth, "No password"))
+                    .context("Cannot set")
+            ),
+            r#"{"kind":"auth","exit_code":5,"message":"Cannot set","causes":["No password"]}"#
+        );
+        let invalid = register_error(
+            "SystemType",
+            Error::ModbusException(ExceptionCode::IllegalDataValue),
+        );
+        assert!(json(&invalid.into())
+            .contains(r#""hint":"The meter rejected the value of SystemType: it is outside"#));
+    }
+}
@@ -0,0 +1,233 @@
+//! Tracks the health of the daemon's polling of the meter, e.g. for a
+//! watchdog or a monitoring system.
+
+use crate::events::{self, Event};
+use sdm72_lib::{stats::LinkStats, tokio_sync_safe_client::SafeClient};
+use std::{fmt::Write, time::SystemTime};
+
+/// The counters of the polls, see [`HealthMonitor::health`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Health {
+    /// `true` if the last poll succeeded.
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+    /// The time of the last successful read in RFC 3339 format.
+    pub last_success: Option<String>,
+    pub polls: u64,
+    pub failures: u64,
+    /// The share of failed polls since the daemon started.
+    pub error_rate: f64,
+    /// The number of connections restarted by the monitor.
+    pub reconnects: u64,
+    pub last_error: Option<String>,
+    /// The requests on the link to the meter, by outcome.
+    pub link: LinkStats,
+}
+
+/// Counts successful and failed polls and restarts the connection after too
+/// many consecutive failures.
+#[derive(Debug, Default)]
+pub struct HealthMonitor {
+    /// Restart the connection after this number of consecutive failures.
+    reconnect_after: Option<u32>,
+    polls: u64,
+    failures: u64,
+    consecutive_failures: u32,
+    last_success: Option<SystemTime>,
+    reconnects: u64,
+    last_error: Option<String>,
+}
+
+impl HealthMonitor {
+    pub fn new(reconnect_after: Option<u32>) -> Self {
+        Self {
+            reconnect_after,
+            ..Self::default()
+        }
+    }
+
+    pub fn record_success(&mut self, now: SystemTime) {
+        self.polls += 1;
+        self.consecutive_failures = 0;
+        self.last_success = Some(now);
+        events::emit(Event::PollOk);
+    }
+
+    /// Records a failed poll and restarts the connection of the client if the
+    /// limit of consecutive failures is reached.
+    pub fn record_failure(&mut self, client: &mut SafeClient, err: &impl std::fmt::Display) {
+        self.polls += 1;
+        self.failures += 1;
+        self.consecutive_failures += 1;
+        self.last_error = Some(err.to_string());
+        events::emit(Event::PollFailed {
+            error: err.to_string(),
+            consecutive_failures: self.consecutive_failures,
+        });
+        if self.reconnect_due() {
+            log::warn!(
+                "{} consecutive polls failed, restarting the connection",
+                self.consecutive_failures
+            );
+            match client.reconnect() {
+                Ok(true) => {
+                    self.reconnects += 1;
+                    events::emit(Event::Reconnect { error: None });
+                }
+                Ok(false) => log::warn!("The connection cannot be restarted"),
+                Err(err) => {
+                    log::warn!("Cannot restart the connection: {err}");
+                    events::emit(Event::Reconnect {
+                        error: Some(err.to_string()),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Returns `true` every `reconnect_after` consecutive failures.
+    fn reconnect_due(&self) -> bool {
+        self.reconnect_after
+            .is_some_and(|limit| self.consecutive_failures.is_multiple_of(limit))
+    }
+
+    pub fn health(&self, link: LinkStats) -> Health {
+        Health {
+            healthy: self.polls > 0 && self.consecutive_failures == 0,
+            consecutive_failures: self.consecutive_failures,
+            last_success: self
+                .last_success
+                .map(|time| humantime::format_rfc3339_millis(time).to_string()),
+            polls: self.polls,
+            failures: self.failures,
+            error_rate: if self.polls == 0 {
+                0.0
+            } else {
+                self.failures as f64 / self.polls as f64
+            },
+            reconnects: self.reconnects,
+            last_error: self.last_error.clone(),
+            link,
+        }
+    }
+
+    /// Formats the counters in the Prometheus text exposition format.
+    pub fn prometheus(&self, link: LinkStats) -> String {
+        let health = self.health(link);
+        let last_success = self
+            .last_success
+            .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map_or(0.0, |since| since.as_secs_f64());
+        let metrics: [(&str, &str, &str, f64); 12] = [
+            (
+                "sdm72_up",
+                "gauge",
+                "Whether the last poll of the meter succeeded",
+                f64::from(u8::from(health.healthy)),
+            ),
+            (
+                "sdm72_polls_total",
+                "counter",
+                "The number of polls of the meter",
+                self.polls as f64,
+            ),
+            (
+                "sdm72_poll_failures_total",
+                "counter",
+                "The number of failed polls of the meter",
+                self.failures as f64,
+            ),
+            (
+                "sdm72_consecutive_poll_failures",
+                "gauge",
+                "The number of failed polls since the last successful one",
+                f64::from(self.consecutive_failures),
+            ),
+            (
+                "sdm72_last_success_timestamp_seconds",
+                "gauge",
+                "The time of the last successful poll, 0 if there was none",
+                last_success,
+            ),
+            (
+                "sdm72_reconnects_total",
+                "counter",
+                "The number of connections restarted after consecutive failures",
+                self.reconnects as f64,
+            ),
+            (
+                "sdm72_link_requests_total",
+                "counter",
+                "The number of requests on the link to the meter",
+                link.requests as f64,
+            ),
+            (
+                "sdm72_link_timeouts_total",
+                "counter",
+                "The number of requests the meter didn't answer in time",
+                link.timeouts as f64,
+            ),
+            (
+                "sdm72_link_crc_errors_total",
+                "counter",
+                "The number of received frames with an invalid CRC",
+                link.crc_errors as f64,
+            ),
+            (
+                "sdm72_link_malformed_frames_total",
+                "counter",
+                "The number of received frames which are truncated or don't match the request",
+                link.malformed_frames as f64,
+            ),
+            (
+                "sdm72_link_exceptions_total",
+                "counter",
+                "The number of exception responses of the meter",
+                link.exceptions as f64,
+            ),
+            (
+                "sdm72_link_transport_errors_total",
+                "counter",
+                "The number of other transport errors",
+                link.transport_errors as f64,
+            ),
+        ];
+        let mut text = String::new();
+        for (name, kind, help, value) in metrics {
+            let _ = writeln!(
+                text,
+                "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}"
+            );
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters() {
+        let mut monitor = HealthMonitor::new(Some(2));
+        assert!(!monitor.health(LinkStats::default()).healthy);
+        monitor.record_success(SystemTime::UNIX_EPOCH);
+        monitor.polls += 1;
+        monitor.failures += 1;
+        monitor.consecutive_failures = 1;
+        assert!(!monitor.reconnect_due());
+        monitor.consecutive_failures = 2;
+        assert!(monitor.reconnect_due());
+
+        let health = monitor.health(LinkStats::default());
+        assert!(!health.healthy);
+        assert_eq!(health.error_rate, 0.5);
+        assert_eq!(
+            health.last_success.as_deref(),
+            Some("1970-01-01T00:00:00.000Z")
+        );
+        assert!(monitor
+            .prometheus(LinkStats::default())
+            .contains("# TYPE sdm72_polls_total counter\nsdm72_polls_total 2\n"));
+    }
+}
@@ -0,0 +1,351 @@
+use crate::{
+    cache::ValueCache,
+    commandline,
+    events::{self, Event},
+    run_limit::RunLimit,
+    settings_cache::SettingsCache,
+    systemd,
+};
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use sdm72_lib::{
+    instrumentation::{RegisterTiming, RegisterTimings},
+    protocol as proto,
+    tokio_common::{AllSettings, Metric, Operation},
+    tokio_sync_safe_client::SafeClient,
+};
+use std::{fmt::Write, sync::Arc, time::Duration};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+type HttpResponse = Response<std::io::Cursor<Vec<u8>>>;
+
+/// The settings which can be written with `POST /settings/{name}`.
+const WRITABLE_SETTINGS: [&str; 11] = [
+    "kppa",
+    "system_type",
+    "pulse_width",
+    "parity_and_stop_bit",
+    "address",
+    "pulse_constant",
+    "password",
+    "baud_rate",
+    "auto_scroll_time",
+    "backlight_time",
+    "pulse_energy_type",
+];
+
+fn json_header() -> Header {
+    Header::from_bytes("Content-Type", "application/json").expect("valid header")
+}
+
+fn json_response<T: serde::Serialize>(value: &T) -> HttpResponse {
+    match serde_json::to_string_pretty(value) {
+        Ok(body) => Response::from_string(body).with_header(json_header()),
+        Err(err) => error_response(500, err),
+    }
+}
+
+/// Serializes the settings with the password as `null`, so reading the settings
+/// doesn't reveal the password to obtain the KPPA with.
+fn redacted_settings(settings: &AllSettings) -> HttpResponse {
+    match serde_json::to_value(settings) {
+        Ok(mut value) => {
+            value["password"] = serde_json::Value::Null;
+            json_response(&value)
+        }
+        Err(err) => error_response(500, err),
+    }
+}
+
+fn error_response(status: u16, message: impl std::fmt::Display) -> HttpResponse {
+    let body = serde_json::json!({ "error": message.to_string() });
+    Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(json_header())
+}
+
+/// Writes a setting, which requires the authorization (KPPA) of the meter.
+///
+/// The KPPA is checked even if the client was given a password: it would
+/// authorize itself, so anybody reaching the daemon could write settings.
+fn write_setting(client: &mut SafeClient, name: &str, value: &str) -> HttpResponse {
+    if !WRITABLE_SETTINGS.contains(&name) {
+        return error_response(404, format!("Unknown setting {name:?}"));
+    }
+    if name != "kppa" {
+        match client.kppa() {
+            Ok(proto::KPPA::Authorized) => {}
+            Ok(_) => {
+                return error_response(
+                    403,
+                    "Authorization required, write the password to /settings/kppa first",
+                )
+            }
+            Err(err) => return error_response(500, err),
+        }
+    }
+
+    macro_rules! parse {
+        ($parsed:expr) => {
+            match $parsed {
+                Ok(val) => val,
+                Err(err) => return error_response(400, err),
+            }
+        };
+    }
+
+    let result = match name {
+        "kppa" => client.set_kppa(parse!(commandline::parse_password(value))),
+        "system_type" => {
+            client.set_system_type(*parse!(commandline::WiringType::from_str(value, true)))
+        }
+        "pulse_width" => {
+            let pulse_width = parse!(commandline::parse_pulse_width(value));
+            match client.pulse_constant() {
+                Ok(pulse_constant) => {
+                    parse!(pulse_width.check_pulse_constant(pulse_constant));
+                    client.set_pulse_width(pulse_width)
+                }
+                Err(err) => Err(err),
+            }
+        }
+        "parity_and_stop_bit" => client.set_parity_and_stop_bit(*parse!(
+            commandline::ParityAndStopBit::from_str(value, true)
+        )),
+        "address" => client.set_address(parse!(commandline::parse_address(value))),
+        "pulse_constant" => {
+            client.set_pulse_constant(*parse!(commandline::PulseConstant::from_str(value, true)))
+        }
+        "password" => client.set_password(parse!(commandline::parse_password(value))),
+        "baud_rate" => client.set_baud_rate(parse!(commandline::parse_baud_rate(value))),
+        "auto_scroll_time" => {
+            client.set_auto_scroll_time(parse!(commandline::parse_auto_scroll_time(value)))
+        }
+        "backlight_time" => {
+            client.set_backlight_time(parse!(commandline::parse_backlight_time(value)))
+        }
+        "pulse_energy_type" => client
+            .set_pulse_energy_type(*parse!(commandline::PulseEnergyType::from_str(value, true))),
+        _ => unreachable!("checked against WRITABLE_SETTINGS"),
+    };
+    match result {
+        Ok(()) => {
+            // The authorization is no setting
+            if name != "kppa" {
+                events::emit(Event::SettingChanged {
+                    setting: name,
+                    value: (name != "password").then_some(value),
+                });
+            }
+            Response::from_data(Vec::new()).with_status_code(204)
+        }
+        Err(err) => error_response(500, err),
+    }
+}
+
+/// Formats the metrics of the values in the Prometheus text exposition format.
+fn prometheus(metrics: &[Metric]) -> String {
+    let mut text = String::new();
+    let mut previous = None;
+    for metric in metrics {
+        let descriptor = &metric.descriptor;
+        if previous != Some(descriptor.name) {
+            let _ = writeln!(
+                text,
+                "# HELP {name} {}\n# TYPE {name} {}",
+                descriptor.help,
+                descriptor.kind.as_str(),
+                name = descriptor.name,
+            );
+            previous = Some(descriptor.name);
+        }
+        let _ = writeln!(text, "{metric}");
+    }
+    text
+}
+
+/// A value of the requests of a register.
+type RegisterValue = fn(&RegisterTiming) -> f64;
+
+/// Formats the requests by register in the Prometheus text exposition format.
+fn register_prometheus(timings: &[RegisterTiming]) -> String {
+    let mut text = String::new();
+    let labels = |timing: &RegisterTiming| {
+        let operation = match timing.operation {
+            Operation::Read => "read",
+            Operation::Write => "write",
+        };
+        let address = timing
+            .address
+            .map(|address| format!("{address:#06x}"))
+            .unwrap_or_default();
+        format!(
+            "register=\"{}\",address=\"{address}\",operation=\"{operation}\"",
+            timing.register
+        )
+    };
+    let metrics: [(&str, &str, &str, RegisterValue); 5] = [
+        (
+            "sdm72_register_requests_total",
+            "counter",
+            "The number of requests by register",
+            |timing| timing.requests as f64,
+        ),
+        (
+            "sdm72_register_request_duration_seconds_total",
+            "counter",
+            "The total duration of the requests by register",
+            |timing| timing.total.as_secs_f64(),
+        ),
+        (
+            "sdm72_register_request_duration_seconds_max",
+            "gauge",
+            "The longest request by register",
+            |timing| timing.max.as_secs_f64(),
+        ),
+        (
+            "sdm72_register_retries_total",
+            "counter",
+            "The number of requests replayed after a reconnection by register",
+            |timing| timing.retries as f64,
+        ),
+        (
+            "sdm72_register_failures_total",
+            "counter",
+            "The number of failed requests by register",
+            |timing| timing.failures as f64,
+        ),
+    ];
+    for (name, kind, help, value) in metrics {
+        let _ = writeln!(text, "# HELP {name} {help}\n# TYPE {name} {kind}");
+        for timing in timings {
+            let _ = writeln!(text, "{name}{{{}}} {}", labels(timing), value(timing));
+        }
+    }
+    let name = "sdm72_register_exceptions_total";
+    let _ = writeln!(
+        text,
+        "# HELP {name} The number of exception responses by register and code\n# TYPE {name} counter"
+    );
+    for timing in timings {
+        for (code, count) in &timing.exceptions {
+            let _ = writeln!(text, "{name}{{{},code=\"{code}\"}} {count}", labels(timing));
+        }
+    }
+    text
+}
+
+fn handle_request(
+    client: &mut SafeClient,
+    timings: &RegisterTimings,
+    cache: &mut ValueCache,
+    settings_cache: &mut Option<SettingsCache>,
+    allow_writes: bool,
+    request: &mut Request,
+) -> HttpResponse {
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    match (request.method(), path.as_str()) {
+        (Method::Get, "/values") => match cache.read(client) {
+            Ok(values) => json_response(&values),
+            Err(err) => error_response(503, format!("{err:#}")),
+        },
+        (Method::Get, "/demand") => json_response(&cache.demand()),
+        (Method::Get, "/health") => {
+            let health = cache.health().health(client.link_stats());
+            let status = if health.healthy { 200 } else { 503 };
+            json_response(&health).with_status_code(status)
+        }
+        (Method::Get, "/metrics") => {
+            let mut text = String::new();
+            // The health metrics are served while the meter is unreachable
+            if let Ok(cached) = cache.read(client) {
+                text = prometheus(&cached.values.to_metrics());
+            }
+            text.push_str(&cache.health().prometheus(client.link_stats()));
+            text.push_str(&register_prometheus(&timings.snapshot()));
+            Response::from_string(text).with_header(
+                Header::from_bytes("Content-Type", "text/plain; version=0.0.4")
+                    .expect("valid header"),
+            )
+        }
+        (Method::Get, "/settings") => {
+            let result = match settings_cache {
+                Some(settings_cache) => settings_cache.read(client),
+                None => client.read_all_settings().map_err(Into::into),
+            };
+            match result {
+                Ok(settings) => redacted_settings(&settings),
+                Err(err) => error_response(500, format!("{err:#}")),
+            }
+        }
+        (Method::Post, path) if path.starts_with("/settings/") && !allow_writes => error_response(
+            403,
+            "Writing settings is disabled, start the daemon with --allow-writes",
+        ),
+        (Method::Post, path) if path.starts_with("/settings/") => {
+            let name = &path["/settings/".len()..];
+            let mut body = String::new();
+            if let Err(err) = request.as_reader().read_to_string(&mut body) {
+                return error_response(400, err);
+            }
+            let response = write_setting(client, name, body.trim());
+            if let Some(settings_cache) = settings_cache {
+                if let Err(err) = settings_cache.invalidate(client) {
+                    log::warn!("{err:#}");
+                }
+            }
+            response
+        }
+        (_, "/values" | "/settings" | "/demand" | "/health" | "/metrics") => {
+            error_response(405, "Method not allowed")
+        }
+        _ => error_response(404, format!("Not found {path:?}")),
+    }
+}
+
+pub fn run_http_daemon(
+    client: &mut SafeClient,
+    listen: &str,
+    allow_writes: bool,
+    probe_interval: &Duration,
+    mut settings_cache: Option<SettingsCache>,
+    mut cache: ValueCache,
+    limit: &RunLimit,
+) -> Result<()> {
+    let server = Server::http(listen).map_err(|err| anyhow!("Cannot listen on {listen}: {err}"))?;
+    log::info!("Serving HTTP on {listen}");
+    let timings = Arc::new(RegisterTimings::default());
+    client.set_instrumentation(Some(timings.clone()));
+
+    while !limit.expired() {
+        systemd::watchdog();
+        let timeout = limit
+            .remaining()
+            .map_or(*probe_interval, |remaining| remaining.min(*probe_interval));
+        let Some(mut request) = server.recv_timeout(timeout)? else {
+            match client.probe_if_idle(*probe_interval) {
+                Ok(()) => systemd::ready(),
+                Err(err) => log::warn!("Connection probe failed: {err}"),
+            }
+            continue;
+        };
+        log::debug!("{} {}", request.method(), request.url());
+        let response = handle_request(
+            client,
+            &timings,
+            &mut cache,
+            &mut settings_cache,
+            allow_writes,
+            &mut request,
+        );
+        if let Err(err) = request.respond(response) {
+            log::warn!("Cannot send HTTP response: {err}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,269 @@
+//! This module reports every request of the safe clients to an
+//! [`Instrumentation`], e.g. to find the batch of registers which is
+//! consistently slow on a gateway.
+//!
+//! A [`RequestRecord`] has the register, the duration and the retries of a
+//! request and its [`RequestOutcome`]. [`RegisterTimings`] is an
+//! instrumentation which sums up the requests by register.
+//!
+//! With the `tracing` feature the safe clients also create a [`request_span`]
+//! per request, which ends with a `debug` event once the request completed.
+//!
+//! # Example
+//!
+//! ```
+//! use sdm72_lib::{
+//!     instrumentation::{Instrumentation, RegisterTimings, RequestOutcome, RequestRecord},
+//!     tokio_common::Operation,
+//! };
+//! use std::time::Duration;
+//!
+//! let timings = RegisterTimings::default();
+//! // Called by the safe clients, see `SafeClient::set_instrumentation`
+//! timings.record(&RequestRecord {
+//!     operation: Operation::Read,
+//!     register: "Voltages",
+//!     address: Some(0x0000),
+//!     duration: Duration::from_millis(120),
+//!     retries: 0,
+//!     outcome: RequestOutcome::Ok,
+//! });
+//! let timing = &timings.snapshot()[0];
+//! assert_eq!(timing.register, "Voltages");
+//! assert_eq!(timing.average(), Duration::from_millis(120));
+//! ```
+
+use crate::{
+    modbus::ExceptionCode,
+    tokio_common::{Error, LinkError, Operation},
+};
+use std::{collections::BTreeMap, sync::Mutex, time::Duration};
+
+/// The outcome of a request, see [`RequestRecord`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestOutcome {
+    Ok,
+    /// The meter answered with an exception response.
+    Exception(ExceptionCode),
+    /// The request failed on the link to the meter.
+    Failed(LinkError),
+    /// The response cannot be decoded.
+    Invalid,
+}
+
+impl RequestOutcome {
+    /// The outcome of the result of a request.
+    pub fn of<T>(result: &Result<T, Error>) -> Self {
+        match result {
+            Ok(_) => Self::Ok,
+            Err(Error::ModbusException(code)) => Self::Exception(*code),
+            Err(err) => err.link_error().map_or(Self::Invalid, Self::Failed),
+        }
+    }
+}
+
+/// A request of a safe client, after the last attempt.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RequestRecord {
+    pub operation: Operation,
+    /// The name of the register or of the block of registers.
+    pub register: &'static str,
+    pub address: Option<u16>,
+    /// The duration of all attempts, including the reconnections in between.
+    pub duration: Duration,
+    /// The number of attempts replayed after a reconnection.
+    pub retries: u32,
+    pub outcome: RequestOutcome,
+}
+
+impl RequestRecord {
+    /// Records the duration, the retries and the outcome in the span of the
+    /// request and emits its completion event.
+    #[cfg(feature = "tracing")]
+    pub fn trace(&self, span: &tracing::Span) {
+        let duration_us = u64::try_from(self.duration.as_micros()).unwrap_or(u64::MAX);
+        span.record("duration_us", duration_us);
+        span.record("retries", self.retries);
+        span.record("outcome", tracing::field::debug(&self.outcome));
+        tracing::debug!(
+            parent: span,
+            duration_us,
+            retries = self.retries,
+            outcome = ?self.outcome,
+            "Modbus request completed"
+        );
+    }
+}
+
+/// The span of a request of the safe clients, its duration, retries and
+/// outcome are recorded by [`RequestRecord::trace`].
+#[cfg(feature = "tracing")]
+pub fn request_span(
+    operation: Operation,
+    register: &'static str,
+    address: Option<u16>,
+    slave: Option<crate::modbus::SlaveId>,
+) -> tracing::Span {
+    tracing::debug_span!(
+        "modbus_request",
+        operation = ?operation,
+        register,
+        address,
+        slave,
+        duration_us = tracing::field::Empty,
+        retries = tracing::field::Empty,
+        outcome = tracing::field::Empty,
+    )
+}
+
+/// Receives the requests of the safe clients, see
+/// `SafeClient::set_instrumentation`.
+///
+/// The requests are recorded while the client holds the connection, so the
+/// implementation should return quickly.
+pub trait Instrumentation: Send + Sync {
+    fn record(&self, request: &RequestRecord);
+}
+
+/// The requests of a register, see [`RegisterTimings`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisterTiming {
+    pub operation: Operation,
+    pub register: &'static str,
+    pub address: Option<u16>,
+    pub requests: u64,
+    pub retries: u64,
+    /// The requests which failed on the link or with an invalid response.
+    pub failures: u64,
+    /// The exception responses by exception code.
+    pub exceptions: BTreeMap<u8, u64>,
+    /// The sum of the durations of all requests.
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl RegisterTiming {
+    fn new(request: &RequestRecord) -> Self {
+        Self {
+            operation: request.operation,
+            register: request.register,
+            address: request.address,
+            requests: 0,
+            retries: 0,
+            failures: 0,
+            exceptions: BTreeMap::new(),
+            total: Duration::ZERO,
+            min: request.duration,
+            max: request.duration,
+        }
+    }
+
+    /// The average duration of the requests.
+    pub fn average(&self) -> Duration {
+        self.total / u32::try_from(self.requests).unwrap_or(u32::MAX)
+    }
+
+    fn record(&mut self, request: &RequestRecord) {
+        self.min = self.min.min(request.duration);
+        self.max = self.max.max(request.duration);
+        self.total += request.duration;
+        self.requests += 1;
+        self.retries += u64::from(request.retries);
+        match request.outcome {
+            RequestOutcome::Ok => {}
+            RequestOutcome::Exception(code) => {
+                *self.exceptions.entry(code.code()).or_default() += 1
+            }
+            RequestOutcome::Failed(_) | RequestOutcome::Invalid => self.failures += 1,
+        }
+    }
+}
+
+/// The key of a [`RegisterTiming`].
+type RegisterKey = (&'static str, Option<u16>, Operation);
+
+/// An [`Instrumentation`] which sums up the requests by register and
+/// operation.
+#[derive(Debug, Default)]
+pub struct RegisterTimings {
+    registers: Mutex<BTreeMap<RegisterKey, RegisterTiming>>,
+}
+
+impl RegisterTimings {
+    /// The requests by register, ordered by register name, address and
+    /// operation.
+    pub fn snapshot(&self) -> Vec<RegisterTiming> {
+        self.registers.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Forgets all requests.
+    pub fn reset(&self) {
+        self.registers.lock().unwrap().clear();
+    }
+}
+
+impl Instrumentation for RegisterTimings {
+    fn record(&self, request: &RequestRecord) {
+        self.registers
+            .lock()
+            .unwrap()
+            .entry((request.register, request.address, request.operation))
+            .or_insert_with(|| RegisterTiming::new(request))
+            .record(request);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_timings() {
+        let timings = RegisterTimings::default();
+        let request = |duration, outcome| RequestRecord {
+            operation: Operation::Read,
+            register: "Energies",
+            address: Some(0x0156),
+            duration: Duration::from_millis(duration),
+            retries: 1,
+            outcome,
+        };
+        timings.record(&request(100, RequestOutcome::Ok));
+        timings.record(&request(
+            300,
+            RequestOutcome::Exception(ExceptionCode::IllegalDataAddress),
+        ));
+        timings.record(&request(200, RequestOutcome::Failed(LinkError::Timeout)));
+
+        let [timing] = timings.snapshot().try_into().unwrap();
+        assert_eq!(timing.requests, 3);
+        assert_eq!(timing.retries, 3);
+        assert_eq!(timing.failures, 1);
+        assert_eq!(timing.exceptions, BTreeMap::from([(2, 1)]));
+        assert_eq!(timing.min, Duration::from_millis(100));
+        assert_eq!(timing.max, Duration::from_millis(300));
+        assert_eq!(timing.average(), Duration::from_millis(200));
+
+        timings.record(&RequestRecord {
+            operation: Operation::Write,
+            ..request(50, RequestOutcome::Ok)
+        });
+        assert_eq!(timings.snapshot().len(), 2);
+        timings.reset();
+        assert!(timings.snapshot().is_empty());
+    }
+
+    #[test]
+    fn request_outcomes() {
+        assert_eq!(RequestOutcome::of(&Ok::<_, Error>(())), RequestOutcome::Ok);
+        assert_eq!(
+            RequestOutcome::of::<()>(&Err(Error::ModbusException(ExceptionCode::IllegalFunction))),
+            RequestOutcome::Exception(ExceptionCode::IllegalFunction)
+        );
+        assert_eq!(
+            RequestOutcome::of::<()>(&Err(Error::Unauthorized)),
+            RequestOutcome::Invalid
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! This module names the measurements and settings in text output and MQTT
+//! topics, e.g. for a non-English HMI built on the output of the CLI.
+//!
+//! [`Labels`] are either the English labels of the `Display` of
+//! [`AllValues`] and [`AllSettings`], the machine keys of their serialized
+//! fields, or a user-provided map from the keys to labels, which falls back to
+//! the English labels for missing keys.
+//!
+//! # Example
+//!
+//! ```
+//! use sdm72_lib::{labels::Labels, protocol::Measurement};
+//! use std::collections::HashMap;
+//!
+//! let labels = Labels::Custom(HashMap::from([(
+//!     "l1_voltage".to_string(),
+//!     "Spannung L1".to_string(),
+//! )]));
+//! assert_eq!(labels.measurement(Measurement::L1Voltage), "Spannung L1");
+//! assert_eq!(labels.measurement(Measurement::L2Voltage), "L2 Voltage");
+//! assert_eq!(labels.topic(Measurement::L1Voltage), "Spannung_L1");
+//! assert_eq!(Labels::Keys.measurement(Measurement::L1Voltage), "l1_voltage");
+//! ```
+
+use crate::{
+    protocol as proto,
+    tokio_common::{AllSettings, AllValues},
+};
+use std::{collections::HashMap, fmt};
+
+/// The labels of the measurements and settings, see the [module](self)
+/// documentation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Labels {
+    /// The English labels, e.g. `L1 Voltage` and the MQTT topic `L1_Voltage`.
+    #[default]
+    English,
+    /// The keys of the serialized values, e.g. `l1_voltage`.
+    Keys,
+    /// Labels by key, the English labels are used for missing keys.
+    Custom(HashMap<String, String>),
+}
+
+impl Labels {
+    /// The label of a key, `english` is the English label.
+    pub fn label<'a>(&'a self, key: &'a str, english: &'a str) -> &'a str {
+        match self {
+            Self::English => english,
+            Self::Keys => key,
+            Self::Custom(labels) => labels.get(key).map_or(english, String::as_str),
+        }
+    }
+
+    /// The label of a measurement.
+    pub fn measurement(&self, measurement: proto::Measurement) -> &str {
+        self.label(measurement.name(), english(measurement))
+    }
+
+    /// The MQTT topic level of a measurement, the label with underscores
+    /// instead of spaces, without parentheses and MQTT special characters.
+    pub fn topic(&self, measurement: proto::Measurement) -> String {
+        self.measurement(measurement)
+            .chars()
+            .filter(|c| !matches!(c, '(' | ')'))
+            .map(|c| match c {
+                ' ' | '/' | '+' | '#' => '_',
+                c => c,
+            })
+            .collect()
+    }
+}
+
+/// The English label of a measurement.
+pub fn english(measurement: proto::Measurement) -> &'static str {
+    use proto::Measurement as M;
+    match measurement {
+        M::L1Voltage => "L1 Voltage",
+        M::L2Voltage => "L2 Voltage",
+        M::L3Voltage => "L3 Voltage",
+        M::L1Current => "L1 Current",
+        M::L2Current => "L2 Current",
+        M::L3Current => "L3 Current",
+        M::L1PowerActive => "L1 Power Active",
+        M::L2PowerActive => "L2 Power Active",
+        M::L3PowerActive => "L3 Power Active",
+        M::L1PowerApparent => "L1 Power Apparent",
+        M::L2PowerApparent => "L2 Power Apparent",
+        M::L3PowerApparent => "L3 Power Apparent",
+        M::L1PowerReactive => "L1 Power Reactive",
+        M::L2PowerReactive => "L2 Power Reactive",
+        M::L3PowerReactive => "L3 Power Reactive",
+        M::L1PowerFactor => "L1 Power Factor",
+        M::L2PowerFactor => "L2 Power Factor",
+        M::L3PowerFactor => "L3 Power Factor",
+        M::LtoNAverageVoltage => "L-N average Voltage",
+        M::LtoNAverageCurrent => "L-N average Current",
+        M::TotalLineCurrent => "Total Line Current",
+        M::TotalPower => "Total Power",
+        M::TotalPowerApparent => "Total Power Apparent",
+        M::TotalPowerReactive => "Total Power Reactive",
+        M::TotalPowerFactor => "Total Power Factor",
+        M::Frequency => "Frequency",
+        M::ImportEnergyActive => "Import Energy Active",
+        M::ExportEnergyActive => "Export Energy Active",
+        M::L1ToL2Voltage => "L1-L2 Voltage",
+        M::L2ToL3Voltage => "L2-L3 Voltage",
+        M::L3ToL1Voltage => "L3-L1 Voltage",
+        M::LtoLAverageVoltage => "L-L average Voltage",
+        M::NeutralCurrent => "Neutral Current",
+        M::TotalEnergyActive => "Total Energy Active",
+        M::TotalEnergyReactive => "Total Energy Reactive",
+        M::ResettableTotalEnergyActive => "Resettable Total Energy Active",
+        M::ResettableTotalEnergyReactive => "Resettable Total Energy Reactive",
+        M::ResettableImportEnergyActive => "Resettable Import Energy Active",
+        M::ResettableExportEnergyActive => "Resettable Export Energy Active",
+        M::NetKwh => "Net kWh (Import - Export)",
+        M::ImportTotalPowerActive => "Import Total Energy Active",
+        M::ExportTotalPowerActive => "Export Total Energy Active",
+    }
+}
+
+/// Displays a value with labels, see [`AllValues::labeled`] and
+/// [`AllSettings::labeled`].
+pub struct Labeled<'a, T> {
+    value: &'a T,
+    labels: &'a Labels,
+}
+
+impl<'a, T> Labeled<'a, T> {
+    pub fn new(value: &'a T, labels: &'a Labels) -> Self {
+        Self { value, labels }
+    }
+}
+
+impl fmt::Display for Labeled<'_, AllValues> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for (index, measurement) in proto::Measurement::ALL.iter().enumerate() {
+            if index > 0 {
+                writeln!(fmt)?;
+            }
+            let value = proto::MeasurementValue {
+                measurement: *measurement,
+                value: self.value.value(*measurement),
+            };
+            write!(
+                fmt,
+                "{}: {}",
+                self.labels.measurement(*measurement),
+                value.rounded(self.value.precision)
+            )?;
+        }
+        if let Some(scaling) = &self.value.scaling {
+            write!(
+                fmt,
+                "\n{}: {scaling}",
+                self.labels.label("scaling", "Scaling")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Labeled<'_, AllSettings> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let settings = self.value;
+        let rows: [(&str, &str, &dyn fmt::Display); 14] = [
+            ("system_type", "System type", &settings.system_type),
+            ("pulse_width", "Pulse width", &settings.pulse_width),
+            ("kppa", "KPPA", &settings.kppa),
+            (
+                "parity_and_stop_bit",
+                "Parity and stop bit",
+                &settings.parity_and_stop_bit,
+            ),
+            ("address", "Address", &settings.address),
+            ("pulse_constant", "Pulse constant", &settings.pulse_constant),
+            ("password", "Password", &settings.password),
+            ("baud_rate", "Baud rate", &settings.baud_rate),
+            (
+                "auto_scroll_time",
+                "Auto scroll time",
+                &settings.auto_scroll_time,
+            ),
+            ("backlight_time", "Backlight time", &settings.backlight_time),
+            (
+                "pulse_energy_type",
+                "Pulse energy type",
+                &settings.pulse_energy_type,
+            ),
+            ("serial_number", "Serial number", &settings.serial_number),
+            ("meter_code", "Meter code", &settings.meter_code),
+            (
+                "software_version",
+                "Software version",
+                &settings.software_version,
+            ),
+        ];
+        for (index, (key, english, value)) in rows.iter().enumerate() {
+            if index > 0 {
+                writeln!(fmt)?;
+            }
+            write!(fmt, "{}: {value}", self.labels.label(key, english))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn english_topics() {
+        assert_eq!(
+            Labels::English.topic(proto::Measurement::L1Voltage),
+            "L1_Voltage"
+        );
+        assert_eq!(
+            Labels::English.topic(proto::Measurement::LtoNAverageVoltage),
+            "L-N_average_Voltage"
+        );
+        assert_eq!(
+            Labels::English.topic(proto::Measurement::NetKwh),
+            "Net_kWh_Import_-_Export"
+        );
+        assert_eq!(
+            Labels::Keys.topic(proto::Measurement::NetKwh),
+            "net_kwh_import_-_export"
+        );
+        let labels = Labels::Custom(HashMap::from([(
+            "frequency".to_string(),
+            "Hz/Netz".to_string(),
+        )]));
+        assert_eq!(labels.topic(proto::Measurement::Frequency), "Hz_Netz");
+        assert_eq!(labels.label("scaling", "Scaling"), "Scaling");
+    }
+}
@@ -1,4 +1,5 @@
 #![cfg_attr(docsrs, feature(doc_cfg))]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 //! A library for controlling the SDM72 series energy meters via Modbus.
 //!
 //! This crate provides two main ways to interact with the SDM72 energy meters:
@@ -42,15 +43,23 @@
 //! - `tokio-tcp-sync`: Enables the synchronous (`blocking`) TCP backend.
 //! - `tokio-rtu`: Enables the asynchronous (`async`) RTU backend.
 //! - `tokio-tcp`: Enables the asynchronous (`async`) TCP backend.
+//! - `serialport-sync`: Enables the blocking RTU client [`serialport_sync::SDM72`]
+//!   on top of the `serialport` crate, without `tokio`.
 //! - `safe-client-sync`: Enables the high-level, thread-safe, synchronous [`tokio_sync_safe_client::SafeClient`].
 //!   Requires either `tokio-rtu-sync` or `tokio-tcp-sync`.
 //! - `safe-client-async`: Enables the high-level, thread-safe, asynchronous [`tokio_async_safe_client::SafeClient`].
 //!   Requires either `tokio-rtu` or `tokio-tcp`.
+//! - `std`: Enables the standard library, all other features except `serde`
+//!   require it. Without it the crate is `no_std` and only provides the
+//!   [`protocol`] module, which needs `alloc` for the encoded words.
 //! - `serde`: Enables `serde` support for the `protocol` types.
+//! - `tracing`: The safe clients create a `tracing` span per Modbus request
+//!   with the register, the address, the duration and the outcome, see
+//!   [`instrumentation::request_span`].
 //! - `bin-dependencies`: Enables all dependencies required for the `sdm72`
 //!   binary. This is not intended for library users.
 //!
-//! The `default` feature enables `bin-dependencies`.
+//! The `default` feature enables `std` and `bin-dependencies`.
 //!
 //! ## Quick Start
 //!
@@ -63,7 +72,6 @@
 //! };
 //! use tokio_modbus::client::sync::tcp;
 //! use tokio_modbus::Slave;
-//! use std::time::Duration;
 //!
 //! fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!     // Connect to the device and create a stateful, safe client
@@ -72,7 +80,7 @@
 //!     let mut client = SafeClient::new(ctx);
 //!
 //!     // Use the client to interact with the device
-//!     let values = client.read_all(&Duration::from_millis(100))?;
+//!     let values = client.read_all()?;
 //!
 //!     println!("Successfully read values: {:#?}", values);
 //!
@@ -82,24 +90,51 @@
 //!
 //! For more details, see the documentation for the specific client you wish to use.
 
+extern crate alloc;
+
 pub mod protocol;
 
-#[cfg_attr(
-    docsrs,
-    doc(cfg(any(
-        feature = "tokio-rtu-sync",
-        feature = "tokio-tcp-sync",
-        feature = "tokio-rtu",
-        feature = "tokio-tcp"
-    )))
-)]
-#[cfg(any(
-    feature = "tokio-rtu-sync",
-    feature = "tokio-tcp-sync",
-    feature = "tokio-rtu",
-    feature = "tokio-tcp"
-))]
-pub mod tokio_common;
+/// Declares the modules which are available with any of the `tokio-modbus`
+/// backends.
+macro_rules! tokio_modules {
+    ($($name:ident),* $(,)?) => {
+        $(
+            #[cfg_attr(
+                docsrs,
+                doc(cfg(any(
+                    feature = "tokio-rtu-sync",
+                    feature = "tokio-tcp-sync",
+                    feature = "tokio-rtu",
+                    feature = "tokio-tcp"
+                )))
+            )]
+            #[cfg(any(
+                feature = "tokio-rtu-sync",
+                feature = "tokio-tcp-sync",
+                feature = "tokio-rtu",
+                feature = "tokio-tcp"
+            ))]
+            pub mod $name;
+        )*
+    };
+}
+
+tokio_modules! {
+    modbus,
+    tokio_common,
+    labels,
+    settings_diff,
+    dry_run,
+    watch,
+    surplus,
+    alarms,
+    stats,
+    schedule,
+    password,
+    delta,
+    instrumentation,
+    client,
+}
 
 #[cfg_attr(
     docsrs,
@@ -108,10 +143,41 @@ pub mod tokio_common;
 #[cfg(any(feature = "tokio-rtu-sync", feature = "tokio-tcp-sync"))]
 pub mod tokio_sync;
 
+#[cfg_attr(docsrs, doc(cfg(any(feature = "tokio-rtu", feature = "tokio-tcp"))))]
+#[cfg(any(feature = "tokio-rtu", feature = "tokio-tcp"))]
+pub mod recording;
+
 #[cfg_attr(docsrs, doc(cfg(any(feature = "tokio-rtu", feature = "tokio-tcp"))))]
 #[cfg(any(feature = "tokio-rtu", feature = "tokio-tcp"))]
 pub mod tokio_async;
 
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(
+        feature = "testing",
+        any(
+            feature = "tokio-rtu-sync",
+            feature = "tokio-tcp-sync",
+            feature = "tokio-rtu",
+            feature = "tokio-tcp"
+        )
+    )))
+)]
+#[cfg(all(
+    feature = "testing",
+    any(
+        feature = "tokio-rtu-sync",
+        feature = "tokio-tcp-sync",
+        feature = "tokio-rtu",
+        feature = "tokio-tcp"
+    )
+))]
+pub mod testing;
+
+#[cfg_attr(docsrs, doc(cfg(feature = "serialport-sync")))]
+#[cfg(feature = "serialport-sync")]
+pub mod serialport_sync;
+
 #[cfg_attr(
     docsrs,
     doc(cfg(all(
@@ -137,3 +203,26 @@ pub mod tokio_sync_safe_client;
     any(feature = "tokio-rtu", feature = "tokio-tcp")
 ))]
 pub mod tokio_async_safe_client;
+
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(
+        feature = "arrow",
+        any(
+            feature = "tokio-rtu-sync",
+            feature = "tokio-tcp-sync",
+            feature = "tokio-rtu",
+            feature = "tokio-tcp"
+        )
+    )))
+)]
+#[cfg(all(
+    feature = "arrow",
+    any(
+        feature = "tokio-rtu-sync",
+        feature = "tokio-tcp-sync",
+        feature = "tokio-rtu",
+        feature = "tokio-tcp"
+    )
+))]
+pub mod arrow;
@@ -1,64 +1,65 @@
-use anyhow::{Context, Result};
-use clap::Parser;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{parser::ValueSource, CommandFactory, FromArgMatches};
 use flexi_logger::{Logger, LoggerHandle};
 use log::*;
-use sdm72_lib::{protocol as proto, tokio_sync_safe_client::SafeClient};
-use std::{ops::Deref, panic, time::Duration};
+use sdm72_lib::{
+    alarms::AlarmRule,
+    dry_run::Recorder,
+    modbus::Slave,
+    password::{CommandPassword, FilePassword, PasswordProvider},
+    protocol as proto,
+    schedule::Ticker,
+    settings_diff::{self, DesiredSettings},
+    stats::SmoothedValues,
+    tokio_common::{
+        resolve_tcp_address, AllSettings, AllValues, BatchRetryPolicy, ConnectionParams,
+        ReconnectPolicy, ScalingProfile, SystemClock, TcpOptions, BROADCAST_TURNAROUND,
+    },
+    tokio_sync::SDM72,
+    tokio_sync_safe_client::SafeClient,
+};
+use std::{process::ExitCode, sync::Arc, time::Duration};
+
+mod actions;
+mod cache;
+#[cfg(feature = "parquet")]
+mod capture;
+/// The delay before a panicked daemon worker is restarted.
+const RESTART_DELAY: Duration = Duration::from_secs(1);
 
 mod commandline;
+mod config_file;
+mod daemon_config;
+mod energy;
+mod events;
+mod exit;
+mod health;
+mod http;
 mod mqtt;
+mod openmetrics;
+mod registers;
+mod report;
+mod run_limit;
+mod settings_cache;
+mod sqlite;
+mod supervisor;
+mod systemd;
+mod tariff;
+mod top;
+mod webhook;
 
-fn logging_init(loglevel: LevelFilter) -> LoggerHandle {
+fn logging_init(loglevel: LevelFilter, abort_on_panic: bool) -> LoggerHandle {
     let log_handle = Logger::try_with_env_or_str(loglevel.as_str())
         .expect("Cannot init logging")
         .start()
         .expect("Cannot start logging");
 
-    panic::set_hook(Box::new(|panic_info| {
-        let (filename, line, column) = panic_info
-            .location()
-            .map(|loc| (loc.file(), loc.line(), loc.column()))
-            .unwrap_or(("<unknown>", 0, 0));
-        let cause = panic_info
-            .payload()
-            .downcast_ref::<String>()
-            .map(String::deref);
-        let cause = cause.unwrap_or_else(|| {
-            panic_info
-                .payload()
-                .downcast_ref::<&str>()
-                .copied()
-                .unwrap_or("<cause unknown>")
-        });
-
-        error!(
-            "Thread '{}' panicked at {}:{}:{}: {}",
-            std::thread::current().name().unwrap_or("<unknown>"),
-            filename,
-            line,
-            column,
-            cause
-        );
-    }));
+    supervisor::install_panic_hook(abort_on_panic);
     log_handle
 }
 
-fn minimum_rtu_delay(baud_rate: &proto::BaudRate) -> Duration {
-    // https://minimalmodbus.readthedocs.io/en/stable/serialcommunication.html#timing-of-the-serial-communications
-    let rate = u16::from(baud_rate) as f64;
-    let bit_time = Duration::from_secs_f64(1.0 / rate);
-    let char_time = bit_time * 11;
-    let result = Duration::from_millis((char_time.as_secs_f64() * 3.5 * 1_000.0) as u64);
-    let min_duration = Duration::from_micros(1_750);
-    if result < min_duration {
-        min_duration
-    } else {
-        result
-    }
-}
-
 fn check_rtu_delay(delay: Duration, baud_rate: &proto::BaudRate) -> Duration {
-    let min_rtu_delay = minimum_rtu_delay(baud_rate);
+    let min_rtu_delay = baud_rate.silent_interval();
     if delay < min_rtu_delay {
         warn!(
             "Your RTU delay of {delay:?} is below the minimum delay of {min_rtu_delay:?}, fallback to minimum"
@@ -68,8 +69,134 @@ fn check_rtu_delay(delay: Duration, baud_rate: &proto::BaudRate) -> Duration {
     delay
 }
 
-fn ensure_authorization(client: &mut SafeClient) -> Result<()> {
-    if proto::KPPA::Authorized != client.kppa().with_context(|| "Cannot get authorization")? {
+fn warn_unknown_settings(settings: &AllSettings) {
+    let unknown = [
+        (
+            "system type",
+            matches!(settings.system_type, proto::SystemType::Unknown(_)),
+            settings.system_type.to_string(),
+        ),
+        (
+            "KPPA",
+            matches!(settings.kppa, proto::KPPA::Unknown(_)),
+            settings.kppa.to_string(),
+        ),
+        (
+            "parity and stop bit",
+            matches!(
+                settings.parity_and_stop_bit,
+                proto::ParityAndStopBit::Unknown(_)
+            ),
+            settings.parity_and_stop_bit.to_string(),
+        ),
+        (
+            "pulse constant",
+            matches!(settings.pulse_constant, proto::PulseConstant::Unknown(_)),
+            settings.pulse_constant.to_string(),
+        ),
+        (
+            "baud rate",
+            matches!(settings.baud_rate, proto::BaudRate::Unknown(_)),
+            settings.baud_rate.to_string(),
+        ),
+        (
+            "pulse energy type",
+            matches!(
+                settings.pulse_energy_type,
+                proto::PulseEnergyType::Unknown(_)
+            ),
+            settings.pulse_energy_type.to_string(),
+        ),
+    ];
+    for (name, _, value) in unknown.iter().filter(|(_, is_unknown, _)| *is_unknown) {
+        warn!("The device returned an unexpected {name}: {value}");
+    }
+}
+
+fn resolve_serial_device(device: &str) -> Result<String> {
+    if cfg!(target_os = "windows") {
+        let ports = tokio_serial::available_ports().with_context(|| "Cannot list serial ports")?;
+        if ports
+            .iter()
+            .any(|port| port.port_name.eq_ignore_ascii_case(device))
+        {
+            Ok(device.to_string())
+        } else {
+            bail!(exit::Classified::new(
+                exit::ErrorKind::Connect,
+                format!("Serial device {device} not found")
+            ))
+        }
+    } else {
+        let path = std::fs::canonicalize(device).with_context(|| {
+            exit::Classified::new(
+                exit::ErrorKind::Connect,
+                format!("Cannot resolve serial device {device}"),
+            )
+        })?;
+        Ok(path.display().to_string())
+    }
+}
+
+/// Validates the configuration without touching the Modbus bus.
+fn check_config(candidates: &[ConnectionParams], command: &commandline::Commands) -> Result<()> {
+    for params in candidates {
+        if let ConnectionParams::Rtu { device, .. } = params {
+            let resolved = resolve_serial_device(device)?;
+            info!("Serial device {device} resolved to {resolved}");
+        }
+    }
+    if let commandline::Commands::Daemon {
+        mode:
+            commandline::DaemonOutput::Mqtt {
+                config_file,
+                tariff,
+            },
+        ..
+    } = command
+    {
+        mqtt::MqttConfig::load(config_file)?
+            .validate()
+            .with_context(|| format!("Invalid MQTT config file {config_file:?}"))?;
+        info!("MQTT config file {config_file:?} is valid");
+        if let Some(tariff_file) = tariff {
+            tariff::TariffConfig::load(tariff_file)?
+                .validate()
+                .with_context(|| format!("Invalid tariff file {tariff_file:?}"))?;
+            info!("Tariff file {tariff_file:?} is valid");
+        }
+    }
+    if let commandline::Commands::Daemon {
+        actions: Some(actions_file),
+        ..
+    } = command
+    {
+        actions::ActionsConfig::load(actions_file)?
+            .validate()
+            .with_context(|| format!("Invalid actions file {actions_file:?}"))?;
+        info!("Actions file {actions_file:?} is valid");
+    }
+    Ok(())
+}
+
+/// The measurement values with the time they were read, used for JSON Lines output.
+#[derive(serde::Serialize)]
+struct TimestampedValues<'a> {
+    timestamp: String,
+    /// The name of the meter, if the daemon configuration file has several.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meter: Option<&'a str>,
+    #[serde(flatten)]
+    values: &'a AllValues,
+}
+
+/// Asks for the password on the terminal.
+struct PromptPassword;
+
+impl PasswordProvider for PromptPassword {
+    fn password(
+        &self,
+    ) -> std::result::Result<Option<proto::Password>, sdm72_lib::tokio_common::Error> {
         let passwd = dialoguer::Input::new()
             .with_prompt("Authorization is required, please enter password")
             .validate_with(|input: &String| -> Result<(), String> {
@@ -78,110 +205,659 @@ fn ensure_authorization(client: &mut SafeClient) -> Result<()> {
             })
             .default(proto::Password::default().to_string())
             .interact_text()
-            .unwrap();
-        client
-            .set_kppa(commandline::parse_password(&passwd).unwrap())
-            .with_context(|| "Authorization failed")?;
+            .map_err(|err| sdm72_lib::tokio_common::Error::Password(format!("prompt: {err}")))?;
+        Ok(Some(commandline::parse_password(&passwd).unwrap()))
+    }
+}
+
+/// Returns the password providers of the command line in the order of
+/// precedence, the prompt last unless `--non-interactive` is given.
+fn password_providers(args: &commandline::Args, prompt: bool) -> Vec<Box<dyn PasswordProvider>> {
+    let mut providers: Vec<Box<dyn PasswordProvider>> = Vec::new();
+    if let Some(password) = args.password {
+        providers.push(Box::new(password));
+    }
+    if let Some(path) = &args.password_file {
+        providers.push(Box::new(FilePassword::new(path)));
+    }
+    if let Some(command) = &args.password_command {
+        providers.push(Box::new(CommandPassword::new(command)));
+    }
+    if prompt && !args.non_interactive {
+        providers.push(Box::new(PromptPassword));
+    }
+    providers
+}
+
+fn ensure_authorization(client: &mut SafeClient, args: &commandline::Args) -> Result<()> {
+    if !password_providers(args, false).is_empty() {
+        // The client acquires the authorization itself before each write
+        return Ok(());
+    }
+    if proto::KPPA::Authorized != client.kppa().with_context(|| "Cannot get authorization")? {
+        let Some(password) = password_providers(args, true).password()? else {
+            bail!(exit::Classified::new(
+                exit::ErrorKind::Auth,
+                "Authorization is required, provide the password with --password, --password-file, --password-command or SDM72_PASSWORD"
+            ));
+        };
+        client.set_kppa(password).with_context(|| {
+            exit::Classified::new(exit::ErrorKind::Auth, "Authorization failed")
+        })?;
     }
     Ok(())
 }
 
-fn with_authorization<F>(client: &mut SafeClient, f: F) -> Result<()>
+fn with_authorization<F>(client: &mut SafeClient, args: &commandline::Args, f: F) -> Result<()>
 where
     F: FnOnce(&mut SafeClient) -> Result<String>,
 {
-    ensure_authorization(client)?;
+    ensure_authorization(client, args)?;
     let msg = f(client)?;
     println!("{msg}");
     Ok(())
 }
 
-fn main() -> Result<()> {
-    let args = commandline::Args::parse();
+/// Prints the write of a `set-*` or `reset-*` command instead of sending it.
+fn dry_run(
+    client: &mut SafeClient,
+    args: &commandline::Args,
+    write: commandline::CommandWrite,
+) -> Result<()> {
+    let mut recorder = Recorder::new(*args.word_order);
+    match write {
+        commandline::CommandWrite::Setting(setting) => {
+            let current = client
+                .read_all_settings()
+                .with_context(|| "Cannot read all settings")?;
+            if let settings_diff::Setting::PulseWidth(pulse_width) = setting {
+                pulse_width.check_pulse_constant(current.pulse_constant)?;
+            }
+            println!("{}", recorder.write_setting(setting));
+            println!(
+                "{}: {} -> {setting}",
+                setting.name(),
+                setting.current(&current)
+            );
+        }
+        commandline::CommandWrite::ResetHistoricalData => {
+            println!("{}", recorder.reset_historical_data());
+        }
+        commandline::CommandWrite::Reset(target) => {
+            println!("{}", recorder.reset(target));
+            println!("Target: {target}");
+        }
+    }
+    println!("Dry run, nothing was written");
+    Ok(())
+}
 
-    let mut delay = args.delay;
+/// Warns that the serial parameters of a gateway don't follow the meter.
+fn warn_gateway_serial_change(args: &commandline::Args) {
+    if !matches!(args.connection, Some(commandline::Connection::Rtu { .. })) {
+        warn!("The serial port of the gateway must be changed to the new setting as well to reach the meter");
+    }
+}
 
-    let _log_handle = logging_init(args.verbose.log_level_filter());
+/// Connects to the first reachable of the candidates, e.g. the resolved
+/// addresses of a host name.
+fn connect(
+    candidates: &[ConnectionParams],
+    connect_timeout: Duration,
+    request_timeout: Duration,
+    policy: ReconnectPolicy,
+) -> Result<SafeClient> {
+    let mut last_error = None;
+    for params in candidates {
+        match SafeClient::connect_with_timeouts(
+            params.clone(),
+            Some(connect_timeout),
+            Some(request_timeout),
+            policy,
+        ) {
+            Ok(client) => return Ok(client),
+            Err(err) => {
+                debug!("Cannot open {params}: {err}");
+                last_error = Some(anyhow::Error::new(err).context(exit::Classified::new(
+                    exit::ErrorKind::Connect,
+                    format!("Cannot open {params}"),
+                )));
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No connection to open")))
+}
 
-    let (mut ctx, command) = match &args.connection {
-        commandline::Connection::Tcp { address, command } => {
-            let socket_addr = address
-                .parse()
-                .with_context(|| format!("Cannot parse address {address}"))?;
-            trace!("Open TCP address {socket_addr}");
-            let ctx = tokio_modbus::client::sync::tcp::connect(socket_addr)
-                .with_context(|| format!("Cannot open {socket_addr:?}"))?;
-            (ctx, command)
+/// Returns the arguments of every meter of the daemon configuration file,
+/// the options of the command line take precedence over the values of the
+/// file.
+fn meter_args(
+    args: &commandline::Args,
+    matches: &clap::ArgMatches,
+    config: &daemon_config::DaemonConfig,
+) -> Result<Vec<(String, commandline::Args)>> {
+    let from_command_line = |id| {
+        matches!(
+            matches.value_source(id),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        )
+    };
+    let mut meters = Vec::with_capacity(config.meters.len());
+    for meter in &config.meters {
+        let mut meter_args = args.clone();
+        meter_args.config = None;
+        meter_args.connection = Some(
+            meter
+                .connection
+                .to_connection(commandline::Commands::Daemon {
+                    poll_iterval: config.poll_interval,
+                    actions: config.actions(meter).map(str::to_string),
+                    reconnect_after: config.reconnect_after,
+                    min_poll_interval: config.min_poll_interval,
+                    smooth: config
+                        .smooth
+                        .iter()
+                        .map(|smoothing| commandline::parse_smoothing(smoothing))
+                        .collect::<std::result::Result<_, _>>()
+                        .map_err(anyhow::Error::msg)?,
+                    energy_state: meter.energy_state.clone(),
+                    once: false,
+                    count: None,
+                    duration: None,
+                    // Checked by the validation
+                    mode: config.output(meter).cloned().unwrap(),
+                })?,
+        );
+        if let Some(timeout) = config.timeout.filter(|_| !from_command_line("timeout")) {
+            meter_args.timeout = timeout;
+        }
+        if let Some(timeout) = config
+            .connect_timeout
+            .filter(|_| !from_command_line("connect_timeout"))
+        {
+            meter_args.connect_timeout = Some(timeout);
+        }
+        if let Some(delay) = config.delay.filter(|_| !from_command_line("delay")) {
+            meter_args.delay = delay;
         }
-        commandline::Connection::Rtu {
+        meter_args.ct_ratio = args.ct_ratio.or(meter.ct_ratio);
+        meter_args.vt_ratio = args.vt_ratio.or(meter.vt_ratio);
+        if !from_command_line("calibrate") {
+            meter_args.calibrate = meter.scaling();
+        }
+        meters.push((meter.name.clone(), meter_args));
+    }
+    Ok(meters)
+}
+
+/// Runs a daemon per meter of the daemon configuration file, or the live view
+/// of the meters with `top`.
+fn run_config_file(
+    args: &commandline::Args,
+    matches: &clap::ArgMatches,
+    config_file: &str,
+) -> Result<()> {
+    let config = daemon_config::DaemonConfig::load(config_file)?;
+    config
+        .validate()
+        .with_context(|| format!("Invalid daemon config file {config_file:?}"))?;
+    let meters = meter_args(args, matches, &config)?;
+    if let Some(commandline::Connection::Top { refresh }) = &args.connection {
+        // With --check-config the meters are checked like their daemons
+        if !args.check_config {
+            return top::run(meters, config.poll_interval, *refresh);
+        }
+    }
+    if let [(_, meter_args)] = meters.as_slice() {
+        return run(meter_args, None);
+    }
+    if args.check_config {
+        for (name, meter_args) in &meters {
+            run(meter_args, Some(name)).with_context(|| format!("Invalid meter {name:?}"))?;
+        }
+        return Ok(());
+    }
+
+    // The process exits with the first daemon which does
+    let (sender, receiver) = std::sync::mpsc::channel();
+    for (name, meter_args) in meters {
+        let sender = sender.clone();
+        std::thread::Builder::new()
+            .name(format!("meter {name}"))
+            .spawn(move || {
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    run(&meter_args, Some(&name))
+                }))
+                .unwrap_or_else(|_| Err(anyhow!("The daemon panicked")));
+                let _ = sender.send((name, result));
+            })
+            .with_context(|| "Cannot start the daemon thread")?;
+    }
+    let (name, result) = receiver.recv()?;
+    result.with_context(|| format!("The daemon of meter {name:?} stopped"))
+}
+
+fn main() -> ExitCode {
+    let matches = commandline::Args::command().get_matches();
+    let args = commandline::Args::from_arg_matches(&matches)
+        .unwrap_or_else(|err| err.format(&mut commandline::Args::command()).exit());
+
+    let _log_handle = logging_init(
+        args.verbose.log_level_filter(),
+        args.on_panic == Some(commandline::PanicBehavior::Abort),
+    );
+
+    match run_args(&args, &matches) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => exit::report(&err, args.error_format),
+    }
+}
+
+fn run_args(args: &commandline::Args, matches: &clap::ArgMatches) -> Result<()> {
+    if let (Some(target), false) = (&args.events, args.check_config) {
+        events::open(target)?;
+    }
+
+    match (&args.config, &args.connection) {
+        (_, Some(commandline::Connection::Registers { format })) => registers::print(*format),
+        (
+            _,
+            Some(commandline::Connection::Report {
+                history,
+                by,
+                format,
+                tariff,
+            }),
+        ) => report::print(history, *by, *format, tariff.as_deref()),
+        (Some(config_file), None | Some(commandline::Connection::Top { .. })) => {
+            run_config_file(args, matches, config_file)
+        }
+        (None, Some(commandline::Connection::Top { .. })) => commandline::Args::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "top shows the meters of a daemon configuration file, which requires --config",
+            )
+            .exit(),
+        (None, Some(_)) => run(args, None),
+        (Some(_), Some(_)) => commandline::Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--config cannot be used with a connection command",
+            )
+            .exit(),
+        (None, None) => commandline::Args::command()
+            .error(
+                clap::error::ErrorKind::MissingSubcommand,
+                "A connection command or --config is required",
+            )
+            .exit(),
+    }
+}
+
+/// Resolves the connection of the arguments into the candidates to connect
+/// to, the delay between requests and the command to run.
+fn resolve(
+    args: &commandline::Args,
+) -> Result<(Vec<ConnectionParams>, Duration, &commandline::Commands)> {
+    let mut delay = args.delay;
+    let (candidates, command) = match &args.connection {
+        Some(commandline::Connection::Tcp { address, command }) => {
+            let socket_addrs =
+                resolve_tcp_address(address, args.resolve_timeout).with_context(|| {
+                    exit::Classified::new(
+                        exit::ErrorKind::Connect,
+                        format!("Cannot resolve address {address}"),
+                    )
+                })?;
+            trace!("Open TCP address {address} resolved to {socket_addrs:?}");
+            let candidates = socket_addrs
+                .into_iter()
+                .map(|socket_addr| ConnectionParams::Tcp {
+                    socket_addr,
+                    slave: Slave::tcp_device(),
+                    options: TcpOptions::default(),
+                })
+                .collect();
+            (candidates, command)
+        }
+        Some(commandline::Connection::Rtu {
             device,
             baud_rate,
             address,
             parity_and_stop_bit: parity_and_stop_bits,
             command,
-        } => {
+        }) => {
             trace!(
                 "Open RTU {device} address {address} baud rate {baud_rate} parity and stop bits {parity_and_stop_bits}"
             );
             delay = check_rtu_delay(delay, baud_rate);
-            let ctx = tokio_modbus::client::sync::rtu::connect_slave(
-                &sdm72_lib::tokio_common::serial_port_builder(
-                    device,
-                    baud_rate,
-                    parity_and_stop_bits,
-                ),
-                tokio_modbus::Slave(**address),
-            )
-            .with_context(|| format!("Cannot open device {device} baud rate {baud_rate}"))?;
-            (ctx, command)
+            let params = ConnectionParams::Rtu {
+                device: device.clone(),
+                baud_rate: *baud_rate,
+                parity_and_stop_bit: **parity_and_stop_bits,
+                slave: Slave(**address),
+            };
+            (vec![params], command)
         }
+        Some(
+            commandline::Connection::Top { .. }
+            | commandline::Connection::Registers { .. }
+            | commandline::Connection::Report { .. },
+        )
+        | None => bail!("No connection to open"),
     };
-    ctx.set_timeout(args.timeout);
-    let mut client = SafeClient::new(ctx);
+    Ok((candidates, delay, command))
+}
+
+/// Connects to the first reachable of the candidates and applies the client
+/// options of the arguments.
+fn open_client(
+    args: &commandline::Args,
+    candidates: &[ConnectionParams],
+    delay: Duration,
+    policy: ReconnectPolicy,
+) -> Result<SafeClient> {
+    let mut client = connect(
+        candidates,
+        args.connect_timeout.unwrap_or(args.timeout),
+        args.timeout,
+        policy,
+    )?;
+    client.set_word_order(*args.word_order);
+    client.set_input_via_holding(args.input_via_holding);
+    client.set_write_verify(args.verify);
+    client.set_reopen_after_comm_change(true);
+    client.set_batch_retry_policy(BatchRetryPolicy {
+        retries: args.batch_retries,
+        split: args.split_failed_batches,
+    });
+    client.set_min_request_interval(delay);
+    client.set_value_validation(*args.value_validation);
+    // Show the settings of newer firmwares, `warn_unknown_settings` names them
+    client.set_decode_mode(proto::DecodeMode::Lenient);
+    let providers = password_providers(args, false);
+    if !providers.is_empty() {
+        client = client.with_password_provider(Arc::new(providers));
+    }
+    if args.ct_ratio.is_some() || args.vt_ratio.is_some() {
+        client.set_scaling_profile(Some(ScalingProfile {
+            ct_ratio: args.ct_ratio.unwrap_or(1.0),
+            vt_ratio: args.vt_ratio.unwrap_or(1.0),
+        }));
+    }
+    if !args.calibrate.is_empty() {
+        client.set_scaling_config(Some(args.calibrate.iter().copied().collect()));
+    }
+    let mut precision = proto::Precision::default();
+    for (class, decimals) in &args.precision {
+        precision.set(*class, *decimals)?;
+    }
+    client.set_precision(precision);
+    Ok(client)
+}
+
+/// Connects to the meter of the connection and runs its command, `meter` is
+/// the name of the meter if the daemon configuration file has several.
+fn run(args: &commandline::Args, meter: Option<&str>) -> Result<()> {
+    let (candidates, delay, command) = resolve(args)?;
+    if args.check_config {
+        check_config(&candidates, command)?;
+        info!("Configuration is valid");
+        return Ok(());
+    }
+
+    let write = if args.dry_run {
+        Some(command.write().ok_or_else(|| {
+            anyhow!("--dry-run is only supported by the set-* and reset-* commands")
+        })?)
+    } else {
+        None
+    };
+
+    let policy = match command {
+        // A health check must answer quickly, without reconnection attempts
+        commandline::Commands::Healthcheck => ReconnectPolicy {
+            max_attempts: 0,
+            ..ReconnectPolicy::default()
+        },
+        _ => ReconnectPolicy::default(),
+    };
+    let mut client = open_client(args, &candidates, delay, policy)?;
+    if let Some(write) = write {
+        return dry_run(&mut client, args, write);
+    }
 
     match command {
-        commandline::Commands::Daemon { poll_iterval, mode } => match mode {
-            commandline::DaemonOutput::Console => loop {
-                let values = client
-                    .read_all(&delay)
-                    .with_context(|| "Cannot read all values")?;
-                if args.no_json {
-                    println!("{values}");
-                } else {
-                    println!("{}", serde_json::to_string_pretty(&values)?);
+        commandline::Commands::Daemon {
+            poll_iterval,
+            actions,
+            reconnect_after,
+            min_poll_interval,
+            smooth,
+            energy_state,
+            once,
+            count,
+            duration,
+            mode,
+        } => {
+            let mut limit = run_limit::RunLimit::new(
+                if *once { Some(1) } else { *count },
+                *duration,
+                Arc::new(SystemClock),
+            );
+            if limit.counts_polls() && matches!(mode, commandline::DaemonOutput::Http { .. }) {
+                bail!("The REST API daemon doesn't poll by itself, use --duration instead of --once or --count");
+            }
+            events::set_meter(meter);
+            if let Some(actions_file) = actions {
+                actions::start(&client, &actions::ActionsConfig::load(actions_file)?)?;
+            }
+            events::emit(events::Event::Started {
+                output: mode.name(),
+            });
+            supervisor::supervise(args.on_panic, RESTART_DELAY, || match mode {
+                commandline::DaemonOutput::Console { format } => {
+                    let mut ticker = Ticker::new(delay.max(*poll_iterval), Arc::new(SystemClock));
+                    loop {
+                        let values = events::read_all(&mut client)?;
+                        // Tells the output of several meters apart
+                        let header = meter.map(|name| format!("[{name}]\n")).unwrap_or_default();
+                        if args.no_json {
+                            println!("{header}{}", values.labeled(&args.labels));
+                        } else {
+                            match format {
+                                commandline::JsonFormat::Pretty => {
+                                    println!("{header}{}", serde_json::to_string_pretty(&values)?);
+                                }
+                                commandline::JsonFormat::Jsonl => {
+                                    let timestamped = TimestampedValues {
+                                        timestamp: humantime::format_rfc3339_millis(
+                                            std::time::SystemTime::now(),
+                                        )
+                                        .to_string(),
+                                        meter,
+                                        values: &values,
+                                    };
+                                    println!("{}", serde_json::to_string(&timestamped)?);
+                                }
+                            }
+                        }
+                        if !limit.next_poll(true, &mut ticker) {
+                            return Ok(());
+                        }
+                    }
                 }
-                std::thread::sleep(delay.max(*poll_iterval));
-            },
-            commandline::DaemonOutput::Mqtt { config_file } => {
-                mqtt::run_mqtt_daemon(
+                commandline::DaemonOutput::Mqtt {
+                    config_file,
+                    tariff,
+                } => mqtt::run_mqtt_daemon(
+                    &mut client,
+                    delay.max(*poll_iterval),
+                    {
+                        let config =
+                            mqtt::MqttConfig::load(config_file)?.with_labels(args.labels.clone());
+                        match meter {
+                            Some(meter) => config.for_meter(meter),
+                            None => config,
+                        }
+                    },
+                    args.no_json,
+                    cache::ValueCache::new(
+                        health::HealthMonitor::new(*reconnect_after),
+                        *min_poll_interval,
+                    )
+                    .with_smoothing(SmoothedValues::new(smooth))
+                    .with_energy_tracker(
+                        energy_state
+                            .clone()
+                            .map(energy::EnergyTracker::open)
+                            .transpose()?,
+                    ),
+                    tariff
+                        .as_deref()
+                        .map(|tariff_file| {
+                            tariff::Tariff::open(tariff::TariffConfig::load(tariff_file)?)
+                        })
+                        .transpose()?,
+                    &mut limit,
+                ),
+                commandline::DaemonOutput::Http {
+                    listen,
+                    allow_writes,
+                    probe_interval,
+                    settings_cache,
+                    settings_cache_ttl,
+                } => http::run_http_daemon(
+                    &mut client,
+                    listen,
+                    *allow_writes,
+                    probe_interval,
+                    settings_cache.as_ref().map(|dir| {
+                        settings_cache::SettingsCache::new(dir.clone(), *settings_cache_ttl)
+                    }),
+                    cache::ValueCache::new(
+                        health::HealthMonitor::new(*reconnect_after),
+                        *min_poll_interval,
+                    )
+                    .with_smoothing(SmoothedValues::new(smooth))
+                    .with_energy_tracker(
+                        energy_state
+                            .clone()
+                            .map(energy::EnergyTracker::open)
+                            .transpose()?,
+                    ),
+                    &limit,
+                ),
+                commandline::DaemonOutput::Webhook {
+                    url,
+                    headers,
+                    on,
+                    deadband,
+                    alarms,
+                    retries,
+                    retry_delay,
+                } => {
+                    if *on == commandline::WebhookTrigger::Alarm && alarms.is_empty() {
+                        bail!("The webhook daemon posts on alarms, but there is no --alarm");
+                    }
+                    webhook::run_webhook_daemon(
+                        &mut client,
+                        delay.max(*poll_iterval),
+                        &webhook::WebhookConfig {
+                            url: url.clone(),
+                            headers: headers
+                                .iter()
+                                .map(|header| commandline::parse_header(header))
+                                .collect::<Result<_, _>>()
+                                .map_err(anyhow::Error::msg)?,
+                            on: *on,
+                            deadband: *deadband,
+                            alarms: alarms
+                                .iter()
+                                .map(|alarm| AlarmRule::parse(alarm.as_str(), alarm))
+                                .collect::<Result<_, _>>()?,
+                            retries: *retries,
+                            retry_delay: *retry_delay,
+                        },
+                        &mut limit,
+                    )
+                }
+                commandline::DaemonOutput::Sqlite {
+                    db,
+                    max_rows,
+                    max_age,
+                } => sqlite::run_sqlite_daemon(
                     &mut client,
                     &delay,
                     poll_iterval,
-                    config_file,
-                    args.no_json,
-                )?;
+                    db,
+                    &sqlite::Retention {
+                        max_rows: *max_rows,
+                        max_age: *max_age,
+                    },
+                    &mut limit,
+                ),
+            })
+            .inspect_err(|err| {
+                events::emit(events::Event::Stopped {
+                    error: format!("{err:#}"),
+                })
+            })?;
+            if limit.is_bounded() {
+                info!("{}", limit.summary());
             }
-        },
-        commandline::Commands::ReadAll => {
+        }
+        commandline::Commands::ReadAll { format } => {
             let values = client
-                .read_all(&delay)
+                .read_all()
                 .with_context(|| "Cannot read all values")?;
-            if args.no_json {
-                println!("{values}");
+            if *format == commandline::ValuesFormat::Openmetrics {
+                let identity = client
+                    .identify()
+                    .with_context(|| "Cannot read the serial number")?;
+                print!(
+                    "{}",
+                    openmetrics::format(
+                        &values.to_metrics(),
+                        &[("serial_number", identity.serial_number.to_string())]
+                    )
+                );
+            } else if args.no_json {
+                println!("{}", values.labeled(&args.labels));
             } else {
                 println!("{}", serde_json::to_string_pretty(&values)?);
             }
         }
+        #[cfg(feature = "parquet")]
+        commandline::Commands::Capture {
+            output,
+            interval,
+            duration,
+        } => {
+            let mut limit = run_limit::RunLimit::new(None, Some(*duration), Arc::new(SystemClock));
+            capture::run(&mut client, output, delay.max(*interval), &mut limit)?;
+        }
         commandline::Commands::ReadAllSettings => {
             let settings = client
-                .read_all_settings(&delay)
+                .read_all_settings()
                 .with_context(|| "Cannot read all settings")?;
+            warn_unknown_settings(&settings);
             if args.no_json {
-                println!("{settings}");
+                println!("{}", settings.labeled(&args.labels));
             } else {
                 println!("{}", serde_json::to_string_pretty(&settings)?);
             }
         }
+        commandline::Commands::Identify => {
+            let identity = client
+                .identify()
+                .with_context(|| "Cannot read the meter identity")?;
+            if args.no_json {
+                println!("{identity}");
+            } else {
+                println!("{}", serde_json::to_string_pretty(&identity)?);
+            }
+        }
 
         commandline::Commands::Password { password } => {
             client
@@ -189,7 +865,7 @@ fn main() -> Result<()> {
                 .with_context(|| "Cannot set authorization")?;
         }
         commandline::Commands::SetWiringType { wiring_type } => {
-            with_authorization(&mut client, |client| {
+            with_authorization(&mut client, args, |client| {
                 client
                     .set_system_type(**wiring_type)
                     .with_context(|| "Cannot set wiring type")?;
@@ -202,7 +878,8 @@ fn main() -> Result<()> {
         commandline::Commands::SetParityAndStopBit {
             parity_and_stop_bit,
         } => {
-            with_authorization(&mut client, |client| {
+            warn_gateway_serial_change(args);
+            with_authorization(&mut client, args, |client| {
                 client
                     .set_parity_and_stop_bit(**parity_and_stop_bit)
                     .with_context(|| "Cannot set parity and stop bit")?;
@@ -213,7 +890,8 @@ fn main() -> Result<()> {
             })?;
         }
         commandline::Commands::SetBaudRate { baud_rate } => {
-            with_authorization(&mut client, |client| {
+            warn_gateway_serial_change(args);
+            with_authorization(&mut client, args, |client| {
                 client
                     .set_baud_rate(*baud_rate)
                     .with_context(|| "Cannot set baud rate")?;
@@ -221,7 +899,7 @@ fn main() -> Result<()> {
             })?;
         }
         commandline::Commands::SetAddress { address } => {
-            with_authorization(&mut client, |client| {
+            with_authorization(&mut client, args, |client| {
                 client
                     .set_address(*address)
                     .with_context(|| "Cannot set RS485 address")?;
@@ -231,7 +909,7 @@ fn main() -> Result<()> {
         commandline::Commands::SetPulseConstant {
             pulse_constant_in_kwh,
         } => {
-            with_authorization(&mut client, |client| {
+            with_authorization(&mut client, args, |client| {
                 client
                     .set_pulse_constant(**pulse_constant_in_kwh)
                     .with_context(|| "Cannot set pulse constant")?;
@@ -242,17 +920,31 @@ fn main() -> Result<()> {
             })?;
         }
         commandline::Commands::SetPassword { password } => {
-            with_authorization(&mut client, |client| {
+            with_authorization(&mut client, args, |client| {
                 client
                     .set_password(*password)
                     .with_context(|| "Cannot set password")?;
                 Ok(format!("Password successfully changed to: {password}"))
             })?;
         }
+        commandline::Commands::SetPulseWidth { pulse_width_in_ms } => {
+            with_authorization(&mut client, args, |client| {
+                let pulse_constant = client
+                    .pulse_constant()
+                    .with_context(|| "Cannot get pulse constant")?;
+                pulse_width_in_ms.check_pulse_constant(pulse_constant)?;
+                client
+                    .set_pulse_width(*pulse_width_in_ms)
+                    .with_context(|| "Cannot set pulse width")?;
+                Ok(format!(
+                    "Pulse width successfully changed to: {pulse_width_in_ms} ms"
+                ))
+            })?;
+        }
         commandline::Commands::SetAutoScrollTime {
             auto_scroll_time_in_seconds,
         } => {
-            with_authorization(&mut client, |client| {
+            with_authorization(&mut client, args, |client| {
                 client
                     .set_auto_scroll_time(*auto_scroll_time_in_seconds)
                     .with_context(|| "Cannot set auto scroll time")?;
@@ -264,7 +956,7 @@ fn main() -> Result<()> {
         commandline::Commands::SetBacklightTime {
             backlight_time_in_minutes,
         } => {
-            with_authorization(&mut client, |client| {
+            with_authorization(&mut client, args, |client| {
                 client
                     .set_backlight_time(*backlight_time_in_minutes)
                     .with_context(|| "Cannot set backlinght time")?;
@@ -274,7 +966,7 @@ fn main() -> Result<()> {
             })?;
         }
         commandline::Commands::SetPulseEnergyType { pulse_energy_type } => {
-            with_authorization(&mut client, |client| {
+            with_authorization(&mut client, args, |client| {
                 client
                     .set_pulse_energy_type(**pulse_energy_type)
                     .with_context(|| "Cannot set pulse energy type")?;
@@ -284,29 +976,86 @@ fn main() -> Result<()> {
                 ))
             })?;
         }
+        commandline::Commands::ApplySettings { file } => {
+            let desired: DesiredSettings =
+                config_file::load(file).with_context(|| "Cannot read desired settings")?;
+            desired
+                .validate()
+                .with_context(|| format!("Invalid settings in file {file:?}"))?;
+            let current = client
+                .read_all_settings()
+                .with_context(|| "Cannot read all settings")?;
+            desired
+                .validate_for(&current)
+                .with_context(|| format!("Invalid settings in file {file:?}"))?;
+            let changes = settings_diff::diff(&current, &desired);
+            if changes.is_empty() {
+                println!("All settings are up to date");
+            } else {
+                with_authorization(&mut client, args, |client| {
+                    let mut report = Vec::with_capacity(changes.len());
+                    for change in &changes {
+                        client
+                            .write_setting(change.to)
+                            .with_context(|| format!("Cannot set {}", change.to.name()))?;
+                        report.push(change.to_string());
+                    }
+                    Ok(report.join("\n"))
+                })?;
+            }
+        }
+        commandline::Commands::Broadcast { setting, .. } => {
+            let setting = setting.setting();
+            let Some(password) = password_providers(args, true).password()? else {
+                bail!(exit::Classified::new(
+                    exit::ErrorKind::Auth,
+                    "A broadcast requires the password of the meters, provide it with --password, --password-file, --password-command or SDM72_PASSWORD"
+                ));
+            };
+            warn!(
+                "Broadcasting the {} to all meters on the bus, no meter confirms the write",
+                setting.name().to_lowercase()
+            );
+            warn_gateway_serial_change(args);
+            let ctx = client.clone_shared();
+            let mut ctx = ctx.lock().unwrap();
+            SDM72::broadcast_setting_with_word_order(
+                &mut ctx,
+                password,
+                setting,
+                *args.word_order,
+                BROADCAST_TURNAROUND,
+                candidates[0].slave(),
+            )
+            .with_context(|| format!("Cannot broadcast the {}", setting.name().to_lowercase()))?;
+            println!(
+                "{} broadcast to: {setting}, read it back from every meter to confirm the change",
+                setting.name()
+            );
+        }
         commandline::Commands::ResetHistoricalData => {
-            with_authorization(&mut client, |client| {
+            with_authorization(&mut client, args, |client| {
                 client
                     .reset_historical_data()
                     .with_context(|| "Cannot reset historical data")?;
                 Ok("Historical data successfully reset".to_string())
             })?;
         }
+        commandline::Commands::Reset { target } => {
+            with_authorization(&mut client, args, |client| {
+                client
+                    .reset(**target)
+                    .with_context(|| format!("Cannot reset the {}", **target))?;
+                Ok(format!("The {} was successfully reset", **target))
+            })?;
+        }
+        commandline::Commands::Healthcheck => {
+            client
+                .probe()
+                .with_context(|| "The meter does not respond")?;
+            println!("OK");
+        }
     }
 
     Ok(())
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn rtu_delay() {
-        assert_eq!(minimum_rtu_delay(&proto::BaudRate::B1200).as_millis(), 32);
-        assert_eq!(minimum_rtu_delay(&proto::BaudRate::B2400).as_millis(), 16);
-        assert_eq!(minimum_rtu_delay(&proto::BaudRate::B4800).as_millis(), 8);
-        assert_eq!(minimum_rtu_delay(&proto::BaudRate::B9600).as_millis(), 4);
-        assert_eq!(minimum_rtu_delay(&proto::BaudRate::B19200).as_millis(), 2);
-    }
-}
@@ -0,0 +1,156 @@
+//! This module holds the crate-owned versions of the `tokio-modbus` types of
+//! the public API, so an upgrade of `tokio-modbus` to a new major version
+//! doesn't break code which only uses this crate.
+//!
+//! [`Slave`] and [`ExceptionCode`] convert from and into their `tokio-modbus`
+//! counterparts. The Modbus contexts can't be wrapped without losing the
+//! `tokio-modbus` clients, they are re-exported as [`SyncContext`] and
+//! `AsyncContext` instead, together with the [`tokio_modbus`] crate itself to
+//! build contexts of the matching version.
+//!
+//! # Example
+//!
+//! ```
+//! use sdm72_lib::{
+//!     modbus::{tokio_modbus, Slave},
+//!     protocol::Address,
+//! };
+//!
+//! let slave = Slave::from(Address::default());
+//! assert_eq!(slave, Slave(1));
+//! assert_eq!(tokio_modbus::Slave::from(slave), tokio_modbus::Slave(1));
+//! ```
+
+pub use tokio_modbus;
+
+#[cfg(any(feature = "tokio-rtu-sync", feature = "tokio-tcp-sync"))]
+pub use tokio_modbus::client::sync::Context as SyncContext;
+
+#[cfg(any(feature = "tokio-rtu", feature = "tokio-tcp"))]
+pub use tokio_modbus::client::Context as AsyncContext;
+
+use crate::protocol as proto;
+
+/// The id of a Modbus slave, the RS485 address for Modbus/RTU or the unit
+/// identifier for Modbus/TCP.
+pub type SlaveId = u8;
+
+/// A Modbus slave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Slave(pub SlaveId);
+
+impl Slave {
+    /// The broadcast address, which all slaves execute without a response.
+    pub const fn broadcast() -> Self {
+        Self(0)
+    }
+
+    /// The unit identifier of a Modbus/TCP device which isn't a gateway.
+    pub const fn tcp_device() -> Self {
+        Self(255)
+    }
+}
+
+impl std::fmt::Display for Slave {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<proto::Address> for Slave {
+    fn from(address: proto::Address) -> Self {
+        Self(*address)
+    }
+}
+
+impl From<tokio_modbus::Slave> for Slave {
+    fn from(slave: tokio_modbus::Slave) -> Self {
+        Self(slave.0)
+    }
+}
+
+impl From<Slave> for tokio_modbus::Slave {
+    fn from(slave: Slave) -> Self {
+        tokio_modbus::Slave(slave.0)
+    }
+}
+
+/// An exception response of the meter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ExceptionCode {
+    #[error("Illegal function")]
+    IllegalFunction,
+    #[error("Illegal data address")]
+    IllegalDataAddress,
+    #[error("Illegal data value")]
+    IllegalDataValue,
+    #[error("Server device failure")]
+    ServerDeviceFailure,
+    #[error("Acknowledge")]
+    Acknowledge,
+    #[error("Server device busy")]
+    ServerDeviceBusy,
+    #[error("Memory parity error")]
+    MemoryParityError,
+    #[error("Gateway path unavailable")]
+    GatewayPathUnavailable,
+    #[error("Gateway target device failed to respond")]
+    GatewayTargetDevice,
+    /// An exception code not defined by the Modbus specification.
+    #[error("Exception code {0:#04x}")]
+    Custom(u8),
+}
+
+impl ExceptionCode {
+    /// Returns the exception code of the Modbus specification.
+    pub fn code(self) -> u8 {
+        match self {
+            Self::IllegalFunction => 0x01,
+            Self::IllegalDataAddress => 0x02,
+            Self::IllegalDataValue => 0x03,
+            Self::ServerDeviceFailure => 0x04,
+            Self::Acknowledge => 0x05,
+            Self::ServerDeviceBusy => 0x06,
+            Self::MemoryParityError => 0x08,
+            Self::GatewayPathUnavailable => 0x0A,
+            Self::GatewayTargetDevice => 0x0B,
+            Self::Custom(code) => code,
+        }
+    }
+}
+
+impl From<tokio_modbus::ExceptionCode> for ExceptionCode {
+    fn from(code: tokio_modbus::ExceptionCode) -> Self {
+        use tokio_modbus::ExceptionCode as Code;
+        match code {
+            Code::IllegalFunction => Self::IllegalFunction,
+            Code::IllegalDataAddress => Self::IllegalDataAddress,
+            Code::IllegalDataValue => Self::IllegalDataValue,
+            Code::ServerDeviceFailure => Self::ServerDeviceFailure,
+            Code::Acknowledge => Self::Acknowledge,
+            Code::ServerDeviceBusy => Self::ServerDeviceBusy,
+            Code::MemoryParityError => Self::MemoryParityError,
+            Code::GatewayPathUnavailable => Self::GatewayPathUnavailable,
+            Code::GatewayTargetDevice => Self::GatewayTargetDevice,
+            Code::Custom(code) => Self::Custom(code),
+        }
+    }
+}
+
+impl From<ExceptionCode> for tokio_modbus::ExceptionCode {
+    fn from(code: ExceptionCode) -> Self {
+        use tokio_modbus::ExceptionCode as Code;
+        match code {
+            ExceptionCode::IllegalFunction => Code::IllegalFunction,
+            ExceptionCode::IllegalDataAddress => Code::IllegalDataAddress,
+            ExceptionCode::IllegalDataValue => Code::IllegalDataValue,
+            ExceptionCode::ServerDeviceFailure => Code::ServerDeviceFailure,
+            ExceptionCode::Acknowledge => Code::Acknowledge,
+            ExceptionCode::ServerDeviceBusy => Code::ServerDeviceBusy,
+            ExceptionCode::MemoryParityError => Code::MemoryParityError,
+            ExceptionCode::GatewayPathUnavailable => Code::GatewayPathUnavailable,
+            ExceptionCode::GatewayTargetDevice => Code::GatewayTargetDevice,
+            ExceptionCode::Custom(code) => Code::Custom(code),
+        }
+    }
+}
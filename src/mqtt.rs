@@ -1,15 +1,64 @@
-use anyhow::{Context, Result};
-use paho_mqtt::{Client, ConnectOptionsBuilder, CreateOptionsBuilder};
+use crate::{cache::ValueCache, config_file, run_limit::RunLimit, tariff::Tariff};
+use anyhow::{bail, Context, Result};
+use sdm72_lib::{
+    delta::Deadband, labels::Labels, protocol as proto, schedule::Ticker,
+    tokio_common::SystemClock, tokio_sync_safe_client::SafeClient,
+};
 use serde::Deserialize;
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+// `bin-dependencies` always enables rumqttc, so Paho takes precedence when it is enabled
+#[cfg(feature = "mqtt-paho")]
+mod paho;
+#[cfg(feature = "mqtt-paho")]
+use paho as backend;
+#[cfg(not(feature = "mqtt-paho"))]
+mod rumqtt;
+#[cfg(not(feature = "mqtt-paho"))]
+use rumqtt as backend;
+
+mod payload;
+mod sparkplug;
+
+pub use backend::Connection;
+pub use payload::PayloadFormat;
+use sparkplug::EdgeNode;
+
+/// The placeholder of a topic template for the key of a value.
+const MEASUREMENT: &str = "{measurement}";
+
+/// The placeholders of topic templates.
+const PLACEHOLDERS: [&str; 4] = ["{serial}", "{slave}", "{group}", MEASUREMENT];
+
+/// Controls which values are published in each poll.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PublishMode {
+    /// Publish all values in every poll.
+    #[default]
+    Always,
+    /// Publish a value only if it changed by more than the deadband.
+    OnChange,
+    /// Like `on_change`, but publish unchanged values after the heartbeat interval.
+    OnChangeWithHeartbeat,
+}
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct MqttConfig {
     uri: String,
     username: Option<String>,
     password: Option<String>,
+    /// The topic or a template of the topics of the values, see
+    /// [`resolve`](Self::resolve).
     #[serde(default = "MqttConfig::default_topic")]
     topic: String,
+    /// The group of the meter for the `{group}` placeholder of the topic,
+    /// e.g. a building or a floor.
+    group: Option<String>,
     #[serde(default = "MqttConfig::default_qos")]
     qos: i32,
     #[serde(default = "MqttConfig::default_client_id")]
@@ -34,6 +83,41 @@ pub struct MqttConfig {
         with = "humantime_serde"
     )]
     auto_reconnect_interval_max: Duration,
+    /// Buffer up to this number of messages while the broker is unreachable,
+    /// the oldest messages are dropped when the buffer is full.
+    max_buffered_messages: Option<i32>,
+    /// Publish the values as retained messages.
+    #[serde(default)]
+    retain: bool,
+    #[serde(default)]
+    publish_mode: PublishMode,
+    /// The minimum absolute change of a value to be published with the
+    /// `on_change` modes.
+    #[serde(default)]
+    deadband: f32,
+    /// The minimum change of a value as a share of the last published value,
+    /// e.g. `0.01` for 1 %.
+    #[serde(default)]
+    relative_deadband: f32,
+    #[serde(
+        default = "MqttConfig::default_heartbeat_interval",
+        with = "humantime_serde"
+    )]
+    heartbeat_interval: Duration,
+    /// The format of the published values, e.g. SenML for platforms which
+    /// require it.
+    #[serde(default)]
+    payload_format: PayloadFormat,
+    /// The Sparkplug B group of the edge node with the `sparkplug` payload
+    /// format.
+    #[serde(default = "MqttConfig::default_sparkplug_group_id")]
+    sparkplug_group_id: String,
+    /// The topic and payload of the last will, if it is not the availability.
+    #[serde(skip)]
+    last_will: Option<(String, Vec<u8>)>,
+    /// The labels of the value topics, see `--labels`.
+    #[serde(skip)]
+    labels: Labels,
 }
 
 impl MqttConfig {
@@ -41,6 +125,10 @@ impl MqttConfig {
         "sdm72".into()
     }
 
+    fn default_sparkplug_group_id() -> String {
+        "sdm72".into()
+    }
+
     fn default_qos() -> i32 {
         0
     }
@@ -71,157 +159,462 @@ impl MqttConfig {
         Duration::from_secs(30)
     }
 
+    fn default_heartbeat_interval() -> Duration {
+        Duration::from_secs(300)
+    }
+
     pub const DEFAULT_CONFIG_FILE: &str = "mqtt.yaml";
 
-    pub fn load(config_file_path: &str) -> Result<Self> {
-        log::debug!("Loading config file from {config_file_path:?}");
-        let config_file = std::fs::File::open(config_file_path)
-            .with_context(|| format!("Cannot open MQTT config file {config_file_path:?}"))?;
-        let config: Self = serde_yaml::from_reader(&config_file)
-            .with_context(|| format!("Cannot read MQTT config from file: {config_file_path:?}"))?;
-        Ok(config)
+    /// Publishes below a topic level of the meter with a client id of the
+    /// meter, so daemons of several meters can share the configuration.
+    pub fn for_meter(mut self, meter: &str) -> Self {
+        self.topic = match self.topic.split_once(MEASUREMENT) {
+            Some((head, tail)) => format!("{head}{meter}/{MEASUREMENT}{tail}"),
+            None => format!("{}/{meter}", self.topic),
+        };
+        self.client_id = format!("{}-{meter}", self.client_id);
+        self
     }
 
-    pub fn create_client(&self) -> Result<Client> {
-        let create_opts = CreateOptionsBuilder::new()
-            .server_uri(&self.uri)
-            .client_id(&self.client_id)
-            .persistence(None) // In-memory persistence
-            .finalize();
+    /// Publishes the values below topics named with the labels.
+    pub fn with_labels(mut self, labels: Labels) -> Self {
+        self.labels = labels;
+        self
+    }
 
-        let mut client = Client::new(create_opts)
-            .with_context(|| format!("Error creating MQTT client for server: {}", self.uri))?;
+    /// Resolves the placeholders `{serial}`, `{slave}` and `{group}` of the
+    /// topic template, e.g. `site/{serial}/{measurement}`, with the serial
+    /// number and the slave address of the meter of `client` and the group.
+    ///
+    /// The values are published to the topic with `{measurement}` replaced
+    /// by their key, or below the topic if it has no `{measurement}` level.
+    /// The other topics, like the availability, are below the topic without
+    /// the `{measurement}` level.
+    pub fn resolve(mut self, client: &mut SafeClient) -> Result<Self> {
+        self.validate()?;
+        let serial_number = if self.topic.contains("{serial}") {
+            Some(client.identify()?.serial_number)
+        } else {
+            None
+        };
+        let slave = client
+            .slave()
+            .map_or(*proto::Address::default(), |slave| slave.0);
+        self.resolve_placeholders(serial_number, slave);
+        Ok(self)
+    }
 
-        client.set_timeout(self.oparation_timeout);
+    fn resolve_placeholders(&mut self, serial_number: Option<proto::SerialNumber>, slave: u8) {
+        if let Some(serial_number) = serial_number {
+            self.topic = self.topic.replace("{serial}", &serial_number.to_string());
+        }
+        self.topic = self.topic.replace("{slave}", &slave.to_string());
+        if let Some(group) = &self.group {
+            self.topic = self.topic.replace("{group}", group);
+        }
+    }
 
-        let mut conn_builder = ConnectOptionsBuilder::new();
-        conn_builder
-            .keep_alive_interval(self.keep_alive_interval)
-            .clean_session(true) // Typically true for telemetry publishers
-            .automatic_reconnect(
-                self.auto_reconnect_interval_min,
-                self.auto_reconnect_interval_max,
-            ); // Enable auto-reconnect
+    /// The topic without the `{measurement}` level.
+    fn base_topic(&self) -> String {
+        self.topic
+            .split('/')
+            .filter(|level| *level != MEASUREMENT)
+            .collect::<Vec<_>>()
+            .join("/")
+    }
 
-        if let Some(user_name_str) = &self.username {
-            conn_builder.user_name(user_name_str.as_str());
+    /// The topic of the value with the key, e.g. `L1_Voltage`.
+    fn value_topic(&self, key: &str) -> String {
+        if self.topic.contains(MEASUREMENT) {
+            self.topic.replace(MEASUREMENT, key)
+        } else {
+            format!("{}/{key}", self.topic)
         }
-        if let Some(password_str) = &self.password {
-            conn_builder.password(password_str.as_str());
+    }
+
+    /// Registers another last will than the retained `offline` availability,
+    /// which is not retained.
+    fn with_last_will(mut self, (topic, payload): (String, Vec<u8>)) -> Self {
+        self.last_will = Some((topic, payload));
+        self
+    }
+
+    /// The topic, payload and retain flag of the last will.
+    fn last_will(&self) -> (String, Vec<u8>, bool) {
+        match &self.last_will {
+            Some((topic, payload)) => (topic.clone(), payload.clone(), false),
+            None => (self.availability_topic(), OFFLINE.into(), true),
         }
-        let conn_opts = conn_builder.finalize();
+    }
+
+    /// The retained topic with the state of an alarm of the actions file.
+    pub fn alarm_topic(&self, alarm: &str) -> String {
+        format!("{}/alarms/{alarm}", self.base_topic())
+    }
+
+    /// The retained topic with `online` or `offline`, depending on whether
+    /// live values of the meter are published.
+    fn availability_topic(&self) -> String {
+        format!("{}/availability", self.base_topic())
+    }
+
+    pub fn load(config_file_path: &str) -> Result<Self> {
+        log::debug!("Loading config file from {config_file_path:?}");
+        config_file::load(config_file_path).with_context(|| "Cannot read MQTT config")
+    }
 
+    /// Checks the settings which can be verified without connecting to the broker.
+    pub fn validate(&self) -> Result<()> {
+        const SCHEMES: [&str; 6] = ["tcp://", "ssl://", "mqtt://", "mqtts://", "ws://", "wss://"];
+        if !SCHEMES.iter().any(|scheme| self.uri.starts_with(scheme)) {
+            bail!(
+                "Invalid MQTT URI {:?}, expected one of the schemes {}",
+                self.uri,
+                SCHEMES.join(", ")
+            );
+        }
+        if !(0..=2).contains(&self.qos) {
+            bail!("Invalid MQTT QoS {}, expected 0, 1 or 2", self.qos);
+        }
+        if self.topic.is_empty() || self.topic.contains(['+', '#']) {
+            bail!(
+                "Invalid MQTT topic {:?}, it must not be empty or contain wildcards",
+                self.topic
+            );
+        }
+        let mut rest = self.topic.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .map_or(rest.len(), |end| start + end + 1);
+            let placeholder = &rest[start..end];
+            if !PLACEHOLDERS.contains(&placeholder) {
+                bail!(
+                    "Invalid placeholder {placeholder:?} in MQTT topic {:?}, expected one of {}",
+                    self.topic,
+                    PLACEHOLDERS.join(", ")
+                );
+            }
+            rest = &rest[end..];
+        }
+        if self.topic.matches(MEASUREMENT).count() > 1
+            || self.topic.contains(MEASUREMENT)
+                && !self.topic.split('/').any(|level| level == MEASUREMENT)
+        {
+            bail!(
+                "Invalid MQTT topic {:?}, {MEASUREMENT} must be a whole topic level and occur once",
+                self.topic
+            );
+        }
+        if self.topic.contains("{group}") && self.group.is_none() {
+            bail!(
+                "The MQTT topic {:?} requires a group for the {{group}} placeholder",
+                self.topic
+            );
+        }
+        if self.sparkplug_group_id.is_empty() || self.sparkplug_group_id.contains(['/', '+', '#']) {
+            bail!(
+                "Invalid Sparkplug B group id {:?}, it must not be empty or contain '/', '+' or '#'",
+                self.sparkplug_group_id
+            );
+        }
+        if self.password.is_some() && self.username.is_none() {
+            bail!("An MQTT password requires a username");
+        }
+        if self.max_buffered_messages.is_some_and(|max| max < 1) {
+            bail!("The maximum number of buffered MQTT messages must be at least 1");
+        }
+        if self.deadband.is_nan() || self.deadband < 0.0 {
+            bail!(
+                "Invalid MQTT deadband {}, it must not be negative",
+                self.deadband
+            );
+        }
+        if self.relative_deadband.is_nan() || self.relative_deadband < 0.0 {
+            bail!(
+                "Invalid MQTT relative deadband {}, it must not be negative",
+                self.relative_deadband
+            );
+        }
+        if self.auto_reconnect_interval_min > self.auto_reconnect_interval_max {
+            bail!("The minimum MQTT reconnect interval is greater than the maximum");
+        }
+        Ok(())
+    }
+
+    pub fn create_client(&self) -> Result<Connection> {
         log::info!(
             "Attempting to connect to MQTT broker: {} with client_id: {}",
             self.uri,
             self.client_id
         );
-
-        client
-            .connect(conn_opts)
-            .with_context(|| "Failed to connect to MQTT broker")?;
+        let connection = backend::connect(self)?;
         log::info!("Connected to MQTT broker.");
-        Ok(client)
+        Ok(connection)
+    }
+}
+
+/// Decides which values are published, according to the [`PublishMode`].
+#[derive(Debug)]
+struct ChangeFilter {
+    mode: PublishMode,
+    deadband: Deadband,
+    heartbeat_interval: Duration,
+    /// The last published value and time of each topic.
+    published: HashMap<String, (f32, Instant)>,
+}
+
+impl ChangeFilter {
+    fn new(config: &MqttConfig) -> Self {
+        Self {
+            mode: config.publish_mode,
+            deadband: Deadband {
+                absolute: config.deadband,
+                relative: config.relative_deadband,
+            },
+            heartbeat_interval: config.heartbeat_interval,
+            published: HashMap::new(),
+        }
+    }
+
+    /// Returns `true` if the value must be published and remembers it.
+    fn check(&mut self, label: &str, value: f32, now: Instant) -> bool {
+        let publish = match (self.mode, self.published.get(label)) {
+            (PublishMode::Always, _) | (_, None) => true,
+            (PublishMode::OnChange, Some((last, _))) => self.deadband.exceeded(*last, value),
+            (PublishMode::OnChangeWithHeartbeat, Some((last, published_at))) => {
+                self.deadband.exceeded(*last, value)
+                    || now.duration_since(*published_at) >= self.heartbeat_interval
+            }
+        };
+        if publish {
+            self.published.insert(label.to_string(), (value, now));
+        }
+        publish
     }
 }
 
+const ONLINE: &str = "online";
+const OFFLINE: &str = "offline";
+
 pub fn run_mqtt_daemon(
-    client: &mut sdm72_lib::tokio_sync_safe_client::SafeClient,
-    delay: &Duration,
-    poll_interval: &Duration,
-    config_file: &str,
+    client: &mut SafeClient,
+    poll_interval: Duration,
+    mut config: MqttConfig,
     no_json: bool,
+    mut cache: ValueCache,
+    mut tariff: Option<Tariff>,
+    limit: &mut RunLimit,
 ) -> Result<()> {
-    let config = MqttConfig::load(config_file)?;
+    config = config.resolve(client)?;
+    let topic = config.base_topic();
+    let mut edge_node = None;
+    if config.payload_format == PayloadFormat::Sparkplug {
+        let identity = client.identify()?;
+        let slave = client
+            .slave()
+            .map_or(*proto::Address::default(), |slave| slave.0);
+        let node = EdgeNode::new(
+            &config.sparkplug_group_id,
+            identity.serial_number,
+            slave,
+            rand::random(),
+        );
+        config = config.with_last_will(node.death());
+        edge_node = Some(node);
+    }
     let cli = config.create_client()?;
+    let mut filter = ChangeFilter::new(&config);
+
+    // Published with every poll, so the retained "offline" of the last will is
+    // replaced after a reconnect to the broker.
+    let publish_availability = |available: bool| {
+        cli.publish(
+            config.availability_topic(),
+            if available { ONLINE } else { OFFLINE },
+            true,
+        )
+    };
 
+    let mut ticker = Ticker::new(poll_interval, Arc::new(SystemClock));
     loop {
-        let values = client
-            .read_all(delay)
-            .with_context(|| "Cannot read all values")?;
+        let cached = cache.read(client);
+        cli.publish(
+            format!("{topic}/health"),
+            serde_json::to_string(&cache.health().health(client.link_stats()))?,
+            true,
+        )?;
+        let cached = match cached {
+            Ok(cached) => cached,
+            Err(err) => {
+                log::warn!("{err:#}");
+                publish_availability(false)?;
+                if !limit.next_poll(false, &mut ticker) {
+                    break;
+                }
+                continue;
+            }
+        };
+        publish_availability(!cached.stale)?;
+        let values = &cached.values;
+        let now = Instant::now();
+        let mut changed = false;
+        let mut published = Vec::new();
 
         macro_rules! pub_msg {
             ($label:expr, $val:expr) => {
-                cli.publish(paho_mqtt::Message::new(
-                    format!("{}/{}", config.topic, $label),
-                    $val.to_string(),
-                    config.qos as i32,
-                ))
-                .with_context(|| "Cannot publish MQTT message")?;
+                pub_msg!($label, $val, *$val)
             };
+            ($label:expr, $val:expr, $num:expr) => {{
+                let publish = filter.check($label, $num, now);
+                if publish {
+                    changed = true;
+                    if config.payload_format == PayloadFormat::Plain {
+                        cli.publish(config.value_topic($label), $val.to_string(), config.retain)?;
+                    }
+                }
+                publish
+            }};
         }
 
-        pub_msg!("L1_Voltage", values.l1_voltage);
-        pub_msg!("L2_Voltage", values.l2_voltage);
-        pub_msg!("L3_Voltage", values.l3_voltage);
-        pub_msg!("L1_Current", values.l1_current);
-        pub_msg!("L2_Current", values.l2_current);
-        pub_msg!("L3_Current", values.l3_current);
-        pub_msg!("L1_Power_Active", values.l1_power_active);
-        pub_msg!("L2_Power_Active", values.l2_power_active);
-        pub_msg!("L3_Power_Active", values.l3_power_active);
-        pub_msg!("L1_Power_Apparent", values.l1_power_apparent);
-        pub_msg!("L2_Power_Apparent", values.l2_power_apparent);
-        pub_msg!("L3_Power_Apparent", values.l3_power_apparent);
-        pub_msg!("L1_Power_Reactive", values.l1_power_reactive);
-        pub_msg!("L2_Power_Reactive", values.l2_power_reactive);
-        pub_msg!("L3_Power_Reactive", values.l3_power_reactive);
-        pub_msg!("L1_Power_Factor", values.l1_power_factor);
-        pub_msg!("L2_Power_Factor", values.l2_power_factor);
-        pub_msg!("L3_Power_Factor", values.l3_power_factor);
-        pub_msg!("L-N_average_Voltage", values.ln_average_voltage);
-        pub_msg!("L-N_average_Current", values.ln_average_current);
-        pub_msg!("Total_Line_Current", values.total_line_current);
-        pub_msg!("Total_Power", values.total_power);
-        pub_msg!("Total_Power_Apparent", values.total_power_apparent);
-        pub_msg!("Total_Power_Reactive", values.total_power_reactive);
-        pub_msg!("Total_Power_Factor", values.total_power_factor);
-        pub_msg!("Frequency", values.frequency);
-        pub_msg!("Import_Energy_Active", values.import_energy_active);
-        pub_msg!("Export_Energy_Active", values.export_energy_active);
-
-        pub_msg!("L1-L2_Voltage", values.l1l2_voltage);
-        pub_msg!("L2-L3_Voltage", values.l2l3_voltage);
-        pub_msg!("L3-L1_Voltage", values.l3l1_voltage);
-        pub_msg!("L-L_average_Voltage", values.ll_average_voltage);
-        pub_msg!("Neutral_Current", values.neutral_current);
-
-        pub_msg!("Total_Energy_Active", values.total_energy_active);
-        pub_msg!("Total_Energy_Reactive", values.total_energy_reactive);
-        pub_msg!(
-            "Resettable_Total_Energy_Active",
-            values.resettable_total_energy_active
-        );
-        pub_msg!(
-            "Resettable_Total_Energy_Reactive",
-            values.resettable_total_energy_reactive
-        );
-        pub_msg!(
-            "Resettable_Import_Energy_Active",
-            values.resettable_import_energy_active
-        );
-        pub_msg!(
-            "Resettable_Export_Energy_Active",
-            values.resettable_export_energy_active
-        );
-        pub_msg!("Net_kWh_Import_-_Export", values.net_kwh);
+        for measurement in proto::Measurement::ALL {
+            let value = proto::MeasurementValue {
+                measurement: *measurement,
+                value: values.value(*measurement),
+            };
+            if pub_msg!(
+                &config.labels.topic(*measurement),
+                value.rounded(values.precision),
+                value.value
+            ) {
+                published.push(*measurement);
+            }
+        }
+
+        for (name, value) in &cached.smoothed {
+            pub_msg!(&format!("smoothed/{name}"), value);
+        }
+
+        let stale_changed = pub_msg!("Stale", cached.stale, f32::from(u8::from(cached.stale)));
+        pub_msg!("Age", cached.age, cached.age as f32);
 
-        pub_msg!(
-            "Import_Total_Energy_Active",
-            values.import_total_energy_active
+        if !cached.stale {
+            cli.publish(
+                format!("{topic}/demand"),
+                cache.demand().to_string(),
+                config.retain,
+            )?;
+        }
+
+        if let Some(tariff) = tariff.as_mut().filter(|_| !cached.stale) {
+            if let Err(err) = tariff.record(values) {
+                log::warn!("{err:#}");
+            }
+            cli.publish(
+                format!("{topic}/tariff"),
+                serde_json::to_string(tariff.counters())?,
+                true,
+            )?;
+        }
+
+        if let Some(level) = config.payload_format.topic(no_json).filter(|_| changed) {
+            cli.publish(
+                format!("{topic}/{level}"),
+                config.payload_format.encode(&cached, &topic)?,
+                config.retain,
+            )?;
+        }
+        if let Some((topic, payload)) = edge_node
+            .as_mut()
+            .and_then(|node| node.publish(&cached, &published, stale_changed))
+        {
+            cli.publish(topic, payload, false)?;
+        }
+        if !limit.next_poll(!cached.stale, &mut ticker) {
+            break;
+        }
+    }
+    // The last will is only sent if the connection is lost
+    publish_availability(false)?;
+    if let Some(node) = &edge_node {
+        let (topic, payload) = node.death();
+        cli.publish(topic, payload, false)?;
+    }
+    cli.disconnect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(mode: PublishMode) -> ChangeFilter {
+        ChangeFilter {
+            mode,
+            deadband: Deadband::absolute(0.5),
+            heartbeat_interval: Duration::from_secs(60),
+            published: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn publish_on_change() {
+        let start = Instant::now();
+        let mut filter = filter(PublishMode::OnChange);
+        assert!(filter.check("Frequency", 50.0, start));
+        assert!(!filter.check("Frequency", 50.4, start));
+        assert!(!filter.check("Frequency", 49.6, start + Duration::from_secs(120)));
+        assert!(filter.check("Frequency", 50.6, start));
+        assert!(filter.check("Total_Power", 50.0, start));
+    }
+
+    #[test]
+    fn publish_on_change_with_heartbeat() {
+        let start = Instant::now();
+        let mut filter = filter(PublishMode::OnChangeWithHeartbeat);
+        assert!(filter.check("Frequency", 50.0, start));
+        assert!(!filter.check("Frequency", 50.0, start + Duration::from_secs(59)));
+        assert!(filter.check("Frequency", 50.0, start + Duration::from_secs(60)));
+        assert!(!filter.check("Frequency", 50.0, start + Duration::from_secs(61)));
+    }
+
+    #[test]
+    fn topic_templates() {
+        let config = |topic: &str| -> MqttConfig {
+            serde_json::from_value(serde_json::json!({
+                "uri": "tcp://localhost",
+                "topic": topic,
+                "group": "plant",
+            }))
+            .unwrap()
+        };
+        let serial_number = proto::SerialNumber::decode_from_holding_registers(&[0, 42]).ok();
+
+        let mut templated = config("site/{group}/{serial}-{slave}/{measurement}").for_meter("a");
+        templated.validate().unwrap();
+        templated.resolve_placeholders(serial_number, 3);
+        assert_eq!(
+            templated.value_topic("L1_Voltage"),
+            "site/plant/42-3/a/L1_Voltage"
         );
-        pub_msg!(
-            "Export_Total_Energy_Active",
-            values.export_total_energy_active
+        assert_eq!(
+            templated.availability_topic(),
+            "site/plant/42-3/a/availability"
         );
 
-        if !no_json {
-            let payload = serde_json::to_string(&values)?;
-            let msg =
-                paho_mqtt::Message::new(format!("{}/JSON", config.topic), payload, config.qos);
-            cli.publish(msg)
-                .with_context(|| "Cannot publish MQTT message")?;
-        }
-        std::thread::sleep(*delay.max(poll_interval));
+        let plain = config("sdm72").for_meter("a");
+        assert_eq!(plain.value_topic("L1_Voltage"), "sdm72/a/L1_Voltage");
+        assert_eq!(plain.alarm_topic("x"), "sdm72/a/alarms/x");
+
+        assert!(config("site/{unknown}").validate().is_err());
+        assert!(config("site/x{measurement}").validate().is_err());
+        assert!(config("{measurement}/{measurement}").validate().is_err());
+    }
+
+    #[test]
+    fn publish_always() {
+        let start = Instant::now();
+        let mut filter = filter(PublishMode::Always);
+        assert!(filter.check("Frequency", 50.0, start));
+        assert!(filter.check("Frequency", 50.0, start));
     }
 }
@@ -0,0 +1,81 @@
+//! The MQTT backend based on the Eclipse Paho C library.
+
+use super::MqttConfig;
+use anyhow::{Context, Result};
+use paho_mqtt::{Client, ConnectOptionsBuilder, CreateOptionsBuilder, Message};
+
+pub struct Connection {
+    client: Client,
+    qos: i32,
+}
+
+impl Connection {
+    pub fn publish(&self, topic: String, payload: impl Into<Vec<u8>>, retain: bool) -> Result<()> {
+        let msg = if retain {
+            Message::new_retained(topic, payload, self.qos)
+        } else {
+            Message::new(topic, payload, self.qos)
+        };
+        self.client
+            .publish(msg)
+            .with_context(|| "Cannot publish MQTT message")
+    }
+
+    /// Disconnects from the broker, the published messages were sent already.
+    pub fn disconnect(self) -> Result<()> {
+        self.client
+            .disconnect(None)
+            .with_context(|| "Cannot disconnect from MQTT broker")
+    }
+}
+
+pub fn connect(config: &MqttConfig) -> Result<Connection> {
+    let mut create_builder = CreateOptionsBuilder::new()
+        .server_uri(&config.uri)
+        .client_id(&config.client_id)
+        .persistence(None); // In-memory persistence
+    if let Some(max_buffered_messages) = config.max_buffered_messages {
+        create_builder = create_builder
+            .send_while_disconnected(true)
+            .max_buffered_messages(max_buffered_messages)
+            .delete_oldest_messages(true);
+    }
+    let create_opts = create_builder.finalize();
+
+    let mut client = Client::new(create_opts)
+        .with_context(|| format!("Error creating MQTT client for server: {}", config.uri))?;
+
+    client.set_timeout(config.oparation_timeout);
+
+    let (will_topic, will_payload, will_retain) = config.last_will();
+    let will_message = if will_retain {
+        Message::new_retained(will_topic, will_payload, config.qos)
+    } else {
+        Message::new(will_topic, will_payload, config.qos)
+    };
+    let mut conn_builder = ConnectOptionsBuilder::new();
+    conn_builder
+        .keep_alive_interval(config.keep_alive_interval)
+        .clean_session(true) // Typically true for telemetry publishers
+        .automatic_reconnect(
+            config.auto_reconnect_interval_min,
+            config.auto_reconnect_interval_max,
+        ) // Enable auto-reconnect
+        .will_message(will_message); // Marks the meter offline if the daemon dies
+
+    if let Some(user_name_str) = &config.username {
+        conn_builder.user_name(user_name_str.as_str());
+    }
+    if let Some(password_str) = &config.password {
+        conn_builder.password(password_str.as_str());
+    }
+    let conn_opts = conn_builder.finalize();
+
+    client
+        .connect(conn_opts)
+        .with_context(|| "Failed to connect to MQTT broker")?;
+    Ok(Connection {
+        client,
+        qos: config.qos,
+    })
+}
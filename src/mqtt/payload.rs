@@ -0,0 +1,182 @@
+//! The payload formats of the values, see [`PayloadFormat`].
+
+use crate::cache::CachedValues;
+use anyhow::{bail, Result};
+use sdm72_lib::protocol as proto;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How the values of a poll are published.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadFormat {
+    /// A topic per value with the plain value, and the JSON object of all
+    /// values in `<topic>/JSON` unless `--no-json` is given.
+    #[default]
+    Plain,
+    /// Only the JSON object of all values in `<topic>/JSON`.
+    Json,
+    /// The values as a SenML pack (RFC 8428) in `<topic>/SenML`.
+    Senml,
+    /// The JSON object of all values encoded as MessagePack in
+    /// `<topic>/MessagePack`.
+    Msgpack,
+    /// The meter as a Sparkplug B edge node, see [`EdgeNode`](super::sparkplug::EdgeNode).
+    Sparkplug,
+}
+
+impl PayloadFormat {
+    /// The topic level below the topic of the meter of the values of all
+    /// measurements, `None` if the values are only published per topic.
+    pub fn topic(&self, no_json: bool) -> Option<&'static str> {
+        match self {
+            Self::Plain if no_json => None,
+            Self::Sparkplug => None,
+            Self::Plain | Self::Json => Some("JSON"),
+            Self::Senml => Some("SenML"),
+            Self::Msgpack => Some("MessagePack"),
+        }
+    }
+
+    /// The payload of the values of all measurements, `base_name` is the
+    /// SenML base name.
+    pub fn encode(&self, values: &CachedValues, base_name: &str) -> Result<Vec<u8>> {
+        Ok(match self {
+            Self::Plain | Self::Json => serde_json::to_vec(values)?,
+            Self::Senml => serde_json::to_vec(&senml(values, base_name, SystemTime::now()))?,
+            Self::Msgpack => {
+                let mut buf = Vec::new();
+                msgpack(&serde_json::to_value(values)?, &mut buf);
+                buf
+            }
+            Self::Sparkplug => bail!("The Sparkplug B payloads are encoded by the edge node"),
+        })
+    }
+}
+
+/// The SenML pack of the values, with the time they were read as base time.
+fn senml(values: &CachedValues, base_name: &str, now: SystemTime) -> Value {
+    let now = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let base_time = now.as_secs_f64() - values.age;
+    let mut records: Vec<Value> = proto::Measurement::ALL
+        .iter()
+        .map(|measurement| {
+            json!({
+                "n": measurement.name(),
+                // The power factors are ratios, `/` in SenML
+                "u": match measurement.unit() {
+                    "" => "/",
+                    unit => unit,
+                },
+                "v": values.values.value(*measurement),
+            })
+        })
+        .collect();
+    records.push(json!({ "n": "stale", "vb": values.stale }));
+    records[0]["bn"] = json!(format!("{base_name}/"));
+    records[0]["bt"] = json!(base_time);
+    Value::Array(records)
+}
+
+/// Appends the marker and the length of a MessagePack string, array or map:
+/// the `fix` format holds lengths up to `fix_max`, `len8` is the marker with
+/// an 8-bit length, if the type has one, and `wide` the ones with a 16 and a
+/// 32-bit length.
+fn msgpack_header(
+    buf: &mut Vec<u8>,
+    len: usize,
+    fix: u8,
+    fix_max: usize,
+    len8: Option<u8>,
+    wide: [u8; 2],
+) {
+    if len <= fix_max {
+        buf.push(fix | len as u8);
+    } else if let Some(marker) = len8.filter(|_| len <= u8::MAX as usize) {
+        buf.extend([marker, len as u8]);
+    } else if let Ok(len) = u16::try_from(len) {
+        buf.push(wide[0]);
+        buf.extend(len.to_be_bytes());
+    } else {
+        buf.push(wide[1]);
+        buf.extend((len as u32).to_be_bytes());
+    }
+}
+
+/// Appends the MessagePack encoding of a JSON value.
+///
+/// Floats which are exact as 32-bit floats, like the values of the meter, are
+/// encoded as such.
+fn msgpack(value: &Value, buf: &mut Vec<u8>) {
+    match value {
+        Value::Null => buf.push(0xc0),
+        Value::Bool(value) => buf.push(if *value { 0xc3 } else { 0xc2 }),
+        Value::Number(number) => {
+            if let Some(value) = number.as_u64() {
+                if value <= 0x7f {
+                    buf.push(value as u8);
+                } else {
+                    buf.push(0xcf);
+                    buf.extend(value.to_be_bytes());
+                }
+            } else if let Some(value) = number.as_i64() {
+                buf.push(0xd3);
+                buf.extend(value.to_be_bytes());
+            } else {
+                let value = number.as_f64().unwrap_or(f64::NAN);
+                if f64::from(value as f32) == value {
+                    buf.push(0xca);
+                    buf.extend((value as f32).to_be_bytes());
+                } else {
+                    buf.push(0xcb);
+                    buf.extend(value.to_be_bytes());
+                }
+            }
+        }
+        Value::String(value) => {
+            msgpack_header(buf, value.len(), 0xa0, 31, Some(0xd9), [0xda, 0xdb]);
+            buf.extend(value.as_bytes());
+        }
+        Value::Array(values) => {
+            msgpack_header(buf, values.len(), 0x90, 15, None, [0xdc, 0xdd]);
+            for value in values {
+                msgpack(value, buf);
+            }
+        }
+        Value::Object(values) => {
+            msgpack_header(buf, values.len(), 0x80, 15, None, [0xde, 0xdf]);
+            for (key, value) in values {
+                msgpack(&Value::String(key.clone()), buf);
+                msgpack(value, buf);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_msgpack() {
+        let mut buf = Vec::new();
+        msgpack(
+            &json!({ "a": [1, -2, true, null], "b": 0.5, "c": "x".repeat(40), "d": 0.1 }),
+            &mut buf,
+        );
+        let mut expected = vec![0x84, 0xa1, b'a', 0x94, 0x01, 0xd3];
+        expected.extend((-2i64).to_be_bytes());
+        expected.extend([0xc3, 0xc0, 0xa1, b'b', 0xca]);
+        expected.extend(0.5f32.to_be_bytes());
+        expected.extend([0xa1, b'c', 0xd9, 40]);
+        expected.extend("x".repeat(40).as_bytes());
+        expected.extend([0xa1, b'd', 0xcb]);
+        expected.extend(0.1f64.to_be_bytes());
+        assert_eq!(buf, expected);
+
+        assert_eq!(PayloadFormat::Plain.topic(true), None);
+        assert_eq!(PayloadFormat::Json.topic(true), Some("JSON"));
+        assert_eq!(PayloadFormat::Senml.topic(false), Some("SenML"));
+    }
+}
@@ -0,0 +1,180 @@
+//! The pure Rust MQTT backend based on rumqttc, e.g. for fully static builds.
+
+use super::MqttConfig;
+use anyhow::{anyhow, bail, Context, Result};
+use rumqttc::{
+    Client, ClientError, Event, LastWill, MqttOptions, Outgoing, Packet, QoS, RecvTimeoutError,
+    Transport,
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// The capacity of the request channel if `max_buffered_messages` is not set.
+const DEFAULT_CAPACITY: usize = 100;
+
+pub struct Connection {
+    client: Client,
+    qos: QoS,
+    /// Set by the event loop thread while the broker is connected.
+    connected: Arc<AtomicBool>,
+    buffer_while_disconnected: bool,
+    /// Receives from the event loop thread once the disconnect was sent.
+    disconnected: Mutex<mpsc::Receiver<()>>,
+    operation_timeout: Duration,
+}
+
+impl Connection {
+    pub fn publish(&self, topic: String, payload: impl Into<Vec<u8>>, retain: bool) -> Result<()> {
+        if !self.buffer_while_disconnected && !self.connected.load(Ordering::Relaxed) {
+            bail!("Cannot publish MQTT message, the broker is not connected");
+        }
+        match self.client.try_publish(topic, self.qos, retain, payload) {
+            Ok(()) => Ok(()),
+            Err(ClientError::TryRequest(_)) => {
+                log::warn!("MQTT message buffer is full, dropping message");
+                Ok(())
+            }
+            Err(err) => Err(anyhow::Error::new(err).context("Cannot publish MQTT message")),
+        }
+    }
+
+    /// Disconnects from the broker after the queued messages were sent.
+    pub fn disconnect(self) -> Result<()> {
+        self.client
+            .disconnect()
+            .with_context(|| "Cannot disconnect from MQTT broker")?;
+        self.disconnected
+            .into_inner()
+            .unwrap()
+            .recv_timeout(self.operation_timeout)
+            .map_err(|_| anyhow!("Timeout disconnecting from MQTT broker"))
+    }
+}
+
+/// Splits an URI like `mqtts://broker:8883` into the transport, host and port.
+fn parse_uri(uri: &str) -> Result<(Transport, String, u16)> {
+    let (scheme, address) = uri
+        .split_once("://")
+        .ok_or_else(|| anyhow!("Invalid MQTT URI {uri:?}"))?;
+    let (transport, default_port) = match scheme {
+        "tcp" | "mqtt" => (Transport::Tcp, 1883),
+        "ssl" | "mqtts" => (Transport::tls_with_default_config(), 8883),
+        _ => bail!("The MQTT URI scheme {scheme:?} is not supported with rumqttc"),
+    };
+    let address = address.trim_end_matches('/');
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) if !port.ends_with(']') => (
+            host,
+            port.parse()
+                .with_context(|| format!("Invalid port in MQTT URI {uri:?}"))?,
+        ),
+        _ => (address, default_port),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    Ok((transport, host.to_string(), port))
+}
+
+pub fn connect(config: &MqttConfig) -> Result<Connection> {
+    let (transport, host, port) = parse_uri(&config.uri)?;
+    let qos =
+        rumqttc::qos(config.qos as u8).map_err(|_| anyhow!("Invalid MQTT QoS {}", config.qos))?;
+
+    let (will_topic, will_payload, will_retain) = config.last_will();
+    let mut options = MqttOptions::new(&config.client_id, host, port);
+    options
+        .set_transport(transport)
+        .set_keep_alive(config.keep_alive_interval)
+        .set_clean_session(true) // Typically true for telemetry publishers
+        .set_last_will(LastWill::new(will_topic, will_payload, qos, will_retain)); // Marks the meter offline if the daemon dies
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.as_deref().unwrap_or_default());
+    }
+
+    let capacity = config
+        .max_buffered_messages
+        .map_or(DEFAULT_CAPACITY, |max| max as usize);
+    let (client, mut connection) = Client::new(options, capacity);
+
+    // Wait for the first connection, like the Paho backend does
+    loop {
+        match connection.recv_timeout(config.oparation_timeout) {
+            Ok(Ok(Event::Incoming(Packet::ConnAck(_)))) => break,
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => {
+                return Err(anyhow::Error::new(err).context("Failed to connect to MQTT broker"))
+            }
+            Err(RecvTimeoutError::Timeout) => bail!("Timeout connecting to MQTT broker"),
+            Err(RecvTimeoutError::Disconnected) => bail!("Failed to connect to MQTT broker"),
+        }
+    }
+
+    let connected = Arc::new(AtomicBool::new(true));
+    let (disconnected_tx, disconnected) = mpsc::channel();
+    let min_interval = config.auto_reconnect_interval_min;
+    let max_interval = config.auto_reconnect_interval_max;
+    std::thread::Builder::new()
+        .name("mqtt".into())
+        .spawn({
+            let connected = connected.clone();
+            move || {
+                // The event loop reconnects with the next iteration after an error
+                let mut reconnect_interval = min_interval;
+                for event in connection.iter() {
+                    match event {
+                        Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                            log::info!("Reconnected to MQTT broker.");
+                            connected.store(true, Ordering::Relaxed);
+                            reconnect_interval = min_interval;
+                        }
+                        Ok(Event::Outgoing(Outgoing::Disconnect)) => {
+                            let _ = disconnected_tx.send(());
+                            break;
+                        }
+                        Ok(_) => {}
+                        Err(err) => {
+                            if connected.swap(false, Ordering::Relaxed) {
+                                log::warn!("Lost connection to MQTT broker: {err}");
+                            }
+                            std::thread::sleep(reconnect_interval);
+                            reconnect_interval = (reconnect_interval * 2).min(max_interval);
+                        }
+                    }
+                }
+            }
+        })
+        .with_context(|| "Cannot start MQTT event loop")?;
+
+    Ok(Connection {
+        client,
+        qos,
+        connected,
+        buffer_while_disconnected: config.max_buffered_messages.is_some(),
+        disconnected: Mutex::new(disconnected),
+        operation_timeout: config.oparation_timeout,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host_port(uri: &str) -> (String, u16) {
+        let (_, host, port) = parse_uri(uri).unwrap();
+        (host, port)
+    }
+
+    #[test]
+    fn parse_uris() {
+        assert_eq!(host_port("mqtt://localhost"), ("localhost".into(), 1883));
+        assert_eq!(host_port("tcp://10.0.0.1:1884/"), ("10.0.0.1".into(), 1884));
+        assert_eq!(host_port("mqtts://broker"), ("broker".into(), 8883));
+        assert_eq!(host_port("mqtt://[fd00::1]:1883"), ("fd00::1".into(), 1883));
+        assert_eq!(host_port("mqtt://[fd00::1]"), ("fd00::1".into(), 1883));
+        assert!(parse_uri("ws://broker").is_err());
+    }
+}
@@ -0,0 +1,265 @@
+//! The Sparkplug B payloads of the MQTT daemon, see [`EdgeNode`].
+//!
+//! The meter is published as an edge node: the NBIRTH message declares all
+//! metrics with their names, aliases and data types, the NDATA messages carry
+//! the changed values by alias and the NDEATH message is registered as the
+//! last will. The payloads are encoded as Protocol Buffers by hand, as there
+//! are only a few fields of the Sparkplug B schema in use.
+
+use crate::cache::CachedValues;
+use sdm72_lib::protocol as proto;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The namespace of the topics of Sparkplug B.
+const NAMESPACE: &str = "spBv1.0";
+
+/// The data types of the metrics in the Sparkplug B schema.
+const DATATYPE_UINT64: u64 = 8;
+const DATATYPE_FLOAT: u64 = 9;
+const DATATYPE_BOOLEAN: u64 = 11;
+
+/// The alias of the `Stale` metric, the measurements have their index in
+/// [`Measurement::ALL`](proto::Measurement::ALL) as alias.
+const STALE_ALIAS: u64 = proto::Measurement::ALL.len() as u64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    UInt64(u64),
+    Float(f32),
+    Boolean(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Metric {
+    /// The name, only sent in the NBIRTH and NDEATH messages.
+    name: &'static str,
+    alias: Option<u64>,
+    value: Value,
+}
+
+/// Appends a base 128 varint.
+fn varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Appends the key of a field with its wire type.
+fn key(buf: &mut Vec<u8>, field: u64, wire_type: u64) {
+    varint(buf, field << 3 | wire_type);
+}
+
+/// Appends a length-delimited field.
+fn bytes(buf: &mut Vec<u8>, field: u64, value: &[u8]) {
+    key(buf, field, 2);
+    varint(buf, value.len() as u64);
+    buf.extend(value);
+}
+
+impl Metric {
+    fn encode(&self, with_name: bool, timestamp: u64) -> Vec<u8> {
+        let mut buf = Vec::new();
+        if with_name {
+            bytes(&mut buf, 1, self.name.as_bytes());
+        }
+        if let Some(alias) = self.alias {
+            key(&mut buf, 2, 0);
+            varint(&mut buf, alias);
+        }
+        key(&mut buf, 3, 0);
+        varint(&mut buf, timestamp);
+        key(&mut buf, 4, 0);
+        match self.value {
+            Value::UInt64(value) => {
+                varint(&mut buf, DATATYPE_UINT64);
+                key(&mut buf, 11, 0);
+                varint(&mut buf, value);
+            }
+            Value::Float(value) => {
+                varint(&mut buf, DATATYPE_FLOAT);
+                key(&mut buf, 12, 5);
+                buf.extend(value.to_le_bytes());
+            }
+            Value::Boolean(value) => {
+                varint(&mut buf, DATATYPE_BOOLEAN);
+                key(&mut buf, 14, 0);
+                varint(&mut buf, u64::from(value));
+            }
+        }
+        buf
+    }
+}
+
+/// Encodes a payload, the names are sent with the NBIRTH and NDEATH messages
+/// without a sequence number.
+fn payload(metrics: &[Metric], with_name: bool, timestamp: u64, seq: Option<u8>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    key(&mut buf, 1, 0);
+    varint(&mut buf, timestamp);
+    for metric in metrics {
+        bytes(&mut buf, 2, &metric.encode(with_name, timestamp));
+    }
+    if let Some(seq) = seq {
+        key(&mut buf, 3, 0);
+        varint(&mut buf, seq.into());
+    }
+    buf
+}
+
+fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// The meter as a Sparkplug B edge node, which numbers the messages.
+#[derive(Debug)]
+pub struct EdgeNode {
+    group_id: String,
+    edge_node_id: String,
+    /// The birth/death sequence number, which pairs the NBIRTH with the
+    /// NDEATH message of a session.
+    bd_seq: u64,
+    /// The sequence number of the last message, `None` before the NBIRTH.
+    seq: Option<u8>,
+}
+
+impl EdgeNode {
+    /// Creates the edge node `sdm72-<serial number>-<slave address>` in the
+    /// group `group_id`.
+    pub fn new(group_id: &str, serial_number: proto::SerialNumber, slave: u8, bd_seq: u8) -> Self {
+        Self {
+            group_id: group_id.to_string(),
+            edge_node_id: format!("sdm72-{serial_number}-{slave}"),
+            bd_seq: bd_seq.into(),
+            seq: None,
+        }
+    }
+
+    /// The topic of a message type, e.g. `NBIRTH`.
+    pub fn topic(&self, message_type: &str) -> String {
+        format!(
+            "{NAMESPACE}/{}/{message_type}/{}",
+            self.group_id, self.edge_node_id
+        )
+    }
+
+    fn bd_seq(&self) -> Metric {
+        Metric {
+            name: "bdSeq",
+            alias: None,
+            value: Value::UInt64(self.bd_seq),
+        }
+    }
+
+    /// The topic and payload of the NDEATH message, the last will.
+    pub fn death(&self) -> (String, Vec<u8>) {
+        (
+            self.topic("NDEATH"),
+            payload(&[self.bd_seq()], true, millis(SystemTime::now()), None),
+        )
+    }
+
+    /// The topic and payload of the NBIRTH message with `metrics` before
+    /// the first message, else of an NDATA message.
+    fn message(&mut self, metrics: &[Metric], timestamp: u64) -> (String, Vec<u8>) {
+        let (message_type, seq) = match self.seq {
+            None => ("NBIRTH", 0),
+            Some(seq) => ("NDATA", seq.wrapping_add(1)),
+        };
+        self.seq = Some(seq);
+        let birth = message_type == "NBIRTH";
+        (
+            self.topic(message_type),
+            payload(metrics, birth, timestamp, Some(seq)),
+        )
+    }
+
+    /// The topic and payload of the message of a poll: the NBIRTH with all
+    /// metrics at the first call, then an NDATA message with the `changed`
+    /// measurements and the `Stale` flag if `stale_changed`, `None` if
+    /// nothing changed.
+    pub fn publish(
+        &mut self,
+        values: &CachedValues,
+        changed: &[proto::Measurement],
+        stale_changed: bool,
+    ) -> Option<(String, Vec<u8>)> {
+        let measurement = |measurement: proto::Measurement| Metric {
+            name: measurement.name(),
+            alias: proto::Measurement::ALL
+                .iter()
+                .position(|m| *m == measurement)
+                .map(|alias| alias as u64),
+            value: Value::Float(values.values.value(measurement)),
+        };
+        let stale = Metric {
+            name: "Stale",
+            alias: Some(STALE_ALIAS),
+            value: Value::Boolean(values.stale),
+        };
+        let mut metrics = Vec::new();
+        if self.seq.is_none() {
+            metrics.push(self.bd_seq());
+            metrics.push(Metric {
+                name: "Node Control/Rebirth",
+                alias: None,
+                value: Value::Boolean(false),
+            });
+            metrics.extend(proto::Measurement::ALL.iter().copied().map(measurement));
+            metrics.push(stale);
+        } else {
+            metrics.extend(changed.iter().copied().map(measurement));
+            if stale_changed {
+                metrics.push(stale);
+            }
+        }
+        if metrics.is_empty() {
+            return None;
+        }
+        let timestamp = millis(SystemTime::now() - Duration::from_secs_f64(values.age));
+        Some(self.message(&metrics, timestamp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_payloads() {
+        let mut buf = Vec::new();
+        varint(&mut buf, 300);
+        assert_eq!(buf, [0xac, 0x02]);
+
+        let metric = Metric {
+            name: "Frequency",
+            alias: Some(3),
+            value: Value::Float(50.0),
+        };
+        let mut expected = vec![0x0a, 9];
+        expected.extend(b"Frequency");
+        expected.extend([0x10, 3, 0x18, 1, 0x20, 9, 0x65]);
+        expected.extend(50.0f32.to_le_bytes());
+        assert_eq!(metric.encode(true, 1), expected);
+        assert_eq!(metric.encode(false, 1), expected[11..]);
+
+        let encoded = payload(&[metric], false, 1, Some(255));
+        assert_eq!(encoded[..4], [0x08, 1, 0x12, 11]);
+        assert_eq!(encoded[encoded.len() - 3..], [0x18, 0xff, 0x01]);
+
+        let serial_number = proto::SerialNumber::decode_from_holding_registers(&[0, 42]).unwrap();
+        let mut node = EdgeNode::new("plant", serial_number, 1, 7);
+        let (topic, birth) = node.message(&[node.bd_seq(), metric], 1);
+        assert_eq!(topic, "spBv1.0/plant/NBIRTH/sdm72-42-1");
+        assert_eq!(birth[birth.len() - 2..], [0x18, 0]);
+        assert!(birth.windows(5).any(|field| field == b"bdSeq"));
+        for seq in 1..=256u32 {
+            let (topic, _) = node.message(&[metric], 1);
+            assert_eq!(topic, "spBv1.0/plant/NDATA/sdm72-42-1");
+            assert_eq!(node.seq, Some((seq % 256) as u8));
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! The OpenMetrics text exposition of `read-all --format openmetrics`, e.g.
+//! for the textfile collector of the node_exporter.
+
+use sdm72_lib::{protocol as proto, tokio_common::Metric};
+use std::fmt::Write;
+
+/// Escapes a label value, see the OpenMetrics specification.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The name of the metric family, counters drop the `_total` suffix of their
+/// samples.
+fn family_name(metric: &Metric) -> &'static str {
+    let name = metric.descriptor.name;
+    match metric.descriptor.kind {
+        proto::MetricKind::Counter => name.strip_suffix("_total").unwrap_or(name),
+        proto::MetricKind::Gauge => name,
+    }
+}
+
+/// Formats the metrics with the `labels` added to every sample, e.g. the
+/// serial number of the meter.
+pub fn format(metrics: &[Metric], labels: &[(&str, String)]) -> String {
+    let mut text = String::new();
+    let mut previous = None;
+    for metric in metrics {
+        let family = family_name(metric);
+        if previous != Some(family) {
+            let _ = writeln!(
+                text,
+                "# TYPE {family} {}\n# HELP {family} {}",
+                metric.descriptor.kind.as_str(),
+                metric.descriptor.help,
+            );
+            previous = Some(family);
+        }
+        let labels = labels
+            .iter()
+            .map(|(name, value)| format!("{name}=\"{}\"", escape(value)))
+            .chain(
+                metric
+                    .descriptor
+                    .labels
+                    .iter()
+                    .map(|(name, value)| format!("{name}=\"{value}\"")),
+            )
+            .collect::<Vec<_>>();
+        let _ = write!(text, "{}", metric.descriptor.name);
+        if !labels.is_empty() {
+            let _ = write!(text, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(text, " {}", metric.value);
+    }
+    text.push_str("# EOF\n");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_metrics() {
+        let metric = |measurement: proto::Measurement, value| Metric {
+            descriptor: measurement.metric(),
+            measurement,
+            value,
+        };
+        let text = format(
+            &[
+                metric(proto::Measurement::L1Voltage, 230.5),
+                metric(proto::Measurement::L2Voltage, 231.0),
+                metric(proto::Measurement::TotalEnergyActive, 1234.5),
+            ],
+            &[("serial_number", "12345678".to_string())],
+        );
+        let mut lines = text.lines();
+        assert_eq!(lines.next(), Some("# TYPE sdm72_voltage_volts gauge"));
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("# HELP sdm72_voltage_volts "));
+        assert_eq!(
+            lines.next(),
+            Some(r#"sdm72_voltage_volts{serial_number="12345678",phase="l1"} 230.5"#)
+        );
+        assert_eq!(
+            lines.next(),
+            Some(r#"sdm72_voltage_volts{serial_number="12345678",phase="l2"} 231"#)
+        );
+        let counter = lines.next().unwrap();
+        assert!(counter.starts_with("# TYPE sdm72_") && counter.ends_with(" counter"));
+        assert!(!counter.contains("_total"));
+        assert_eq!(lines.last(), Some("# EOF"));
+
+        assert_eq!(escape("a\"b\\c"), r#"a\"b\\c"#);
+    }
+}
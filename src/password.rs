@@ -0,0 +1,187 @@
+//! This module provides the password of the Key Parameter Programming
+//! Authorization (KPPA) to the safe clients, see [`PasswordProvider`].
+//!
+//! A provider is only asked when the meter is not authorized, so a prompt or
+//! a command querying a keyring runs once per authorization and not with
+//! every write. Providers in a `Vec` are asked in order until one has a
+//! password.
+//!
+//! # Example
+//!
+//! ```
+//! use sdm72_lib::{
+//!     password::{EnvPassword, FilePassword, PasswordProvider},
+//!     protocol::Password,
+//! };
+//!
+//! let provider: Vec<Box<dyn PasswordProvider>> = vec![
+//!     Box::new(EnvPassword::new("SDM72_TEST_PASSWORD_UNSET")),
+//!     Box::new(Password::default()),
+//!     Box::new(FilePassword::new("/run/secrets/sdm72")),
+//! ];
+//! assert_eq!(provider.password().unwrap(), Some(Password::default()));
+//! ```
+
+use crate::{
+    protocol as proto,
+    tokio_common::{Error, Result},
+};
+use std::{path::PathBuf, sync::Arc};
+
+/// Provides the password to acquire the KPPA.
+pub trait PasswordProvider: Send + Sync {
+    /// Returns the password, `None` if this provider has none.
+    fn password(&self) -> Result<Option<proto::Password>>;
+}
+
+impl PasswordProvider for proto::Password {
+    fn password(&self) -> Result<Option<proto::Password>> {
+        Ok(Some(*self))
+    }
+}
+
+impl<P: PasswordProvider + ?Sized> PasswordProvider for Box<P> {
+    fn password(&self) -> Result<Option<proto::Password>> {
+        (**self).password()
+    }
+}
+
+impl<P: PasswordProvider + ?Sized> PasswordProvider for Arc<P> {
+    fn password(&self) -> Result<Option<proto::Password>> {
+        (**self).password()
+    }
+}
+
+impl<P: PasswordProvider> PasswordProvider for Vec<P> {
+    /// Returns the password of the first provider which has one.
+    fn password(&self) -> Result<Option<proto::Password>> {
+        for provider in self {
+            if let Some(password) = provider.password()? {
+                return Ok(Some(password));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Parses a password, `source` names its origin in the error.
+fn parse(value: &str, source: &str) -> Result<proto::Password> {
+    let value = value
+        .trim()
+        .parse::<u16>()
+        .map_err(|err| Error::Password(format!("{source}: {err}")))?;
+    proto::Password::try_from(value).map_err(|err| Error::Password(format!("{source}: {err}")))
+}
+
+/// The password in an environment variable, none if it is not set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvPassword {
+    var: String,
+}
+
+impl EnvPassword {
+    pub fn new(var: impl Into<String>) -> Self {
+        Self { var: var.into() }
+    }
+}
+
+impl PasswordProvider for EnvPassword {
+    fn password(&self) -> Result<Option<proto::Password>> {
+        match std::env::var(&self.var) {
+            Ok(value) => parse(&value, &self.var).map(Some),
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(err) => Err(Error::Password(format!("{}: {err}", self.var))),
+        }
+    }
+}
+
+/// The password in the first line of a file, e.g. a Docker or systemd secret.
+///
+/// A missing file is an error, not a missing password.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePassword {
+    path: PathBuf,
+}
+
+impl FilePassword {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl PasswordProvider for FilePassword {
+    fn password(&self) -> Result<Option<proto::Password>> {
+        let source = self.path.display().to_string();
+        let content = std::fs::read_to_string(&self.path)
+            .map_err(|err| Error::Password(format!("{source}: {err}")))?;
+        parse(content.lines().next().unwrap_or_default(), &source).map(Some)
+    }
+}
+
+/// The password printed by a command, e.g. `secret-tool lookup service sdm72`
+/// to query the keyring of the desktop.
+///
+/// The command is split at whitespace and run without a shell. A command
+/// which fails or prints nothing is an error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandPassword {
+    command: String,
+}
+
+impl CommandPassword {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+        }
+    }
+}
+
+impl PasswordProvider for CommandPassword {
+    fn password(&self) -> Result<Option<proto::Password>> {
+        let error = |msg: String| Error::Password(format!("{}: {msg}", self.command));
+        let mut words = self.command.split_whitespace();
+        let program = words.next().ok_or_else(|| error("Empty command".into()))?;
+        let output = std::process::Command::new(program)
+            .args(words)
+            .stderr(std::process::Stdio::inherit())
+            .output()
+            .map_err(|err| error(err.to_string()))?;
+        if !output.status.success() {
+            return Err(error(output.status.to_string()));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        parse(stdout.lines().next().unwrap_or_default(), &self.command).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn providers() {
+        let dir = std::env::temp_dir().join(format!("sdm72-password-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("password");
+        std::fs::write(&file, "1234\n").unwrap();
+
+        let provider: Vec<Box<dyn PasswordProvider>> = vec![
+            Box::new(EnvPassword::new("SDM72_TEST_PASSWORD_UNSET")),
+            Box::new(FilePassword::new(&file)),
+        ];
+        assert_eq!(
+            provider.password().unwrap(),
+            Some(proto::Password::try_from(1234).unwrap())
+        );
+
+        std::fs::write(&file, "12345").unwrap();
+        assert!(FilePassword::new(&file).password().is_err());
+        assert!(FilePassword::new(dir.join("missing")).password().is_err());
+        #[cfg(unix)]
+        assert_eq!(
+            CommandPassword::new("echo 42").password().unwrap(),
+            Some(proto::Password::try_from(42).unwrap())
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
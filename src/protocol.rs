@@ -5,6 +5,13 @@
 //!
 //! The documentation for this module is based on the "Eastron SDM72D-M-v2 Modbus Protocol"
 //! document.
+//!
+//! The module only depends on `core` and `alloc`, so it can be used without
+//! the `std` feature, e.g. on an embedded Modbus RTU master.
+
+use alloc::vec::Vec;
+
+pub mod requests;
 
 /// Represents errors that can occur within the SDM72 protocol logic.
 #[derive(Debug, thiserror::Error)]
@@ -41,6 +48,24 @@ pub enum Error {
     )]
     BacklitTimeOutOfRange(u8),
 
+    /// The provided number of decimals exceeds the maximum.
+    #[error(
+        "The precision of {0} decimals exceeds the maximum of {max}",
+        max = Precision::MAX_DECIMALS
+    )]
+    PrecisionOutOfRange(u8),
+
+    /// The provided pulse width is not supported by the device.
+    #[error("Pulse width {0} ms must be any value of 60, 100, 200.")]
+    InvalidPulseWidth(u16),
+
+    /// The pulse width cannot be changed at a pulse constant of 1000 imp/kWh.
+    #[error(
+        "The pulse width is fixed to {fixed} ms at a pulse constant of 1000 imp/kWh",
+        fixed = PulseWidth::FIXED
+    )]
+    PulseWidthFixed,
+
     /// The provided baud rate is not supported by the device.
     #[error("Baud rate must by any value of 1200, 2400, 4800, 9600, 19200.")]
     InvalidBaudRate,
@@ -53,6 +78,10 @@ pub enum Error {
     #[error("Unexpected value")]
     InvalidValue,
 
+    /// The device returned a value which does not map to any known variant.
+    #[error("Unknown variant value {0}")]
+    UnknownVariant(f32),
+
     /// The number of words received from the device is incorrect for the requested operation.
     #[error("Words count error")]
     WordsCountError,
@@ -61,6 +90,36 @@ pub enum Error {
 /// 16-bit value stored in Modbus register.
 pub type Word = u16;
 
+/// The order of the two words of 32-bit values on the wire.
+///
+/// The SDM72 sends the most significant word first, but some Modbus TCP
+/// gateways swap the two words of each value.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WordOrder {
+    /// Most significant word first, as sent by the device.
+    #[default]
+    BigEndian,
+
+    /// Least significant word first.
+    LittleEndianWords,
+}
+impl WordOrder {
+    /// Converts between this word order and the big endian order expected by
+    /// the `decode_*` and returned by the `encode_*` functions.
+    ///
+    /// The words of each 32-bit value are swapped, single word values (e.g.
+    /// [`MeterCode`]) are left unchanged.
+    pub fn apply(&self, mut words: Vec<Word>) -> Vec<Word> {
+        if *self == WordOrder::LittleEndianWords {
+            for pair in words.chunks_exact_mut(2) {
+                pair.swap(0, 1);
+            }
+        }
+        words
+    }
+}
+
 /// A trait for defining Modbus parameters.
 ///
 /// This trait provides a common interface for defining the properties of a Modbus
@@ -75,44 +134,127 @@ pub trait ModbusParam: Sized {
     type ProtocolType;
 }
 
-/// A macro to convert a slice of `u16` words into a protocol value (e.g., `f32`).
-macro_rules! words_to_protocol_value {
-    ($words:expr) => {{
-        let bytes = $words
-            .iter()
-            .copied()
-            .flat_map(u16::to_be_bytes)
-            .collect::<Vec<u8>>();
-        let array = bytes.try_into().or(Err(Error::WordsCountError))?;
-        Ok(<Self as ModbusParam>::ProtocolType::from_be_bytes(array))
-    }};
+/// A protocol value which converts from and into its big endian Modbus words
+/// without allocating.
+trait ProtocolValue: Sized {
+    fn from_words(words: &[Word]) -> Result<Self, Error>;
+    /// Writes the value into `words`, which must have the length of the value.
+    fn into_words(self, words: &mut [Word]) -> Result<(), Error>;
+}
+impl ProtocolValue for u16 {
+    fn from_words(words: &[Word]) -> Result<Self, Error> {
+        match words {
+            [word] => Ok(*word),
+            _ => Err(Error::WordsCountError),
+        }
+    }
+
+    fn into_words(self, words: &mut [Word]) -> Result<(), Error> {
+        match words {
+            [word] => {
+                *word = self;
+                Ok(())
+            }
+            _ => Err(Error::WordsCountError),
+        }
+    }
+}
+impl ProtocolValue for u32 {
+    fn from_words(words: &[Word]) -> Result<Self, Error> {
+        match words {
+            [high, low] => Ok(u32::from(*high) << 16 | u32::from(*low)),
+            _ => Err(Error::WordsCountError),
+        }
+    }
+
+    fn into_words(self, words: &mut [Word]) -> Result<(), Error> {
+        match words {
+            [high, low] => {
+                *high = (self >> 16) as Word;
+                *low = self as Word;
+                Ok(())
+            }
+            _ => Err(Error::WordsCountError),
+        }
+    }
+}
+impl ProtocolValue for f32 {
+    fn from_words(words: &[Word]) -> Result<Self, Error> {
+        u32::from_words(words).map(f32::from_bits)
+    }
+
+    fn into_words(self, words: &mut [Word]) -> Result<(), Error> {
+        self.to_bits().into_words(words)
+    }
 }
 
-/// A macro to convert a protocol value (e.g., `f32`) into a `Vec<u16>` of Modbus words.
-macro_rules! protocol_value_to_words {
-    ($val:expr) => {
-        $val.to_be_bytes()
-            .chunks(2)
-            .map(|chunk| {
-                let array = chunk.try_into().expect("unexpected encoding error");
-                u16::from_be_bytes(array)
-            })
-            .collect()
+/// A macro to convert a slice of `u16` words into the protocol value (e.g., `f32`)
+/// of the implementing type.
+macro_rules! words_to_protocol_value {
+    ($words:expr) => {
+        <<Self as ModbusParam>::ProtocolType as ProtocolValue>::from_words($words)
     };
 }
 
+/// Encodes a value of `quantity` words into a new vector.
+fn encode_to_vec(
+    quantity: u16,
+    encode: impl FnOnce(&mut [Word]) -> Result<(), Error>,
+) -> Vec<Word> {
+    let mut words = alloc::vec![0; quantity as usize];
+    encode(&mut words).expect("unexpected encoding error");
+    words
+}
+
+/// How the enum setting registers decode a code unknown to this library.
+///
+/// The safe clients decode all settings with the mode set with
+/// `set_decode_mode`. The low-level clients take the mode in
+/// `read_all_settings` only and decode the single settings strictly, like
+/// all reads of the `serialport_sync` client and the `MockClient`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum DecodeMode {
+    /// An unknown code is an [`UnknownVariant`](Error::UnknownVariant) error.
+    #[default]
+    Strict,
+    /// An unknown code is decoded as the `Unknown` variant with the raw value,
+    /// e.g. so all other settings can be read from a newer firmware.
+    Lenient,
+}
+impl DecodeMode {
+    fn unknown<T>(self, val: f32, variant: fn(f32) -> T) -> Result<T, Error> {
+        match self {
+            Self::Strict => Err(Error::UnknownVariant(val)),
+            Self::Lenient => Ok(variant(val)),
+        }
+    }
+}
+
 /// The system (wiring) type.
 ///
+/// Other Eastron meters support further system types, so new variants may be
+/// added in the future.
+///
 /// Note: To set the value you need ['KPPA'](enum@KPPA).
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum SystemType {
     /// 1 phase with 2 wire
     Type1P2W,
 
+    /// 3 phase with 3 wire, only decoded as the SDM72 doesn't support it
+    Type3P3W,
+
     #[default]
     /// 3 phase with 4 wire
     Type3P4W,
+
+    /// A value not known to this library, as returned by the device, only
+    /// decoded in the [`Lenient`](DecodeMode::Lenient) mode.
+    Unknown(f32),
 }
 impl ModbusParam for SystemType {
     type ProtocolType = f32;
@@ -120,28 +262,45 @@ impl ModbusParam for SystemType {
     const QUANTITY: u16 = 2;
 }
 impl SystemType {
+    /// Decodes the value strictly, an unknown code is an
+    /// [`UnknownVariant`](Error::UnknownVariant) error.
     pub fn decode_from_holding_registers(words: &[Word]) -> Result<Self, Error> {
+        Self::decode_with_mode(words, DecodeMode::Strict)
+    }
+
+    /// Decodes the value, an unknown code is decoded as `Unknown` in the
+    /// [`Lenient`](DecodeMode::Lenient) mode.
+    pub fn decode_with_mode(words: &[Word], mode: DecodeMode) -> Result<Self, Error> {
         let val = words_to_protocol_value!(words)?;
         match val {
             1.0 => Ok(SystemType::Type1P2W),
+            2.0 => Ok(SystemType::Type3P3W),
             3.0 => Ok(SystemType::Type3P4W),
-            _ => Err(Error::InvalidValue),
+            _ => mode.unknown(val, Self::Unknown),
         }
     }
 
+    pub fn encode_into(&self, words: &mut [Word]) -> Result<(), Error> {
+        let val: <Self as ModbusParam>::ProtocolType = match self {
+            SystemType::Type1P2W => 1.0,
+            SystemType::Type3P3W => 2.0,
+            SystemType::Type3P4W => 3.0,
+            SystemType::Unknown(val) => *val,
+        };
+        val.into_words(words)
+    }
+
     pub fn encode_for_write_registers(&self) -> Vec<Word> {
-        let val = match self {
-            SystemType::Type1P2W => 1,
-            SystemType::Type3P4W => 3,
-        } as <Self as ModbusParam>::ProtocolType;
-        protocol_value_to_words!(val)
+        encode_to_vec(Self::QUANTITY, |words| self.encode_into(words))
     }
 }
-impl std::fmt::Display for SystemType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for SystemType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             SystemType::Type1P2W => write!(f, "1 phase 2 wire"),
+            SystemType::Type3P3W => write!(f, "3 phase 3 wire"),
             SystemType::Type3P4W => write!(f, "3 phase 4 wire"),
+            SystemType::Unknown(val) => write!(f, "unknown ({val})"),
         }
     }
 }
@@ -149,6 +308,8 @@ impl std::fmt::Display for SystemType {
 /// Pulse width for the pulse output in milliseconds.
 ///
 /// Note: If pulse constant is 1000 imp/kWh, then the pulse width is fixed to 35ms and cannot be adjusted!
+/// The meter reports the fixed width, but it cannot be written, see
+/// [`check_pulse_constant`](Self::check_pulse_constant).
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PulseWidth(u16);
@@ -157,7 +318,7 @@ impl ModbusParam for PulseWidth {
     const ADDRESS: u16 = 0x000C;
     const QUANTITY: u16 = 2;
 }
-impl std::ops::Deref for PulseWidth {
+impl core::ops::Deref for PulseWidth {
     type Target = u16;
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -169,25 +330,46 @@ impl Default for PulseWidth {
     }
 }
 impl PulseWidth {
+    /// The pulse widths which can be written.
+    pub const ALLOWED: [u16; 3] = [60, 100, 200];
+    /// The fixed pulse width at a pulse constant of 1000 imp/kWh.
+    pub const FIXED: u16 = 35;
+
+    /// Checks that the pulse width can be written at the pulse constant.
+    pub fn check_pulse_constant(&self, pulse_constant: PulseConstant) -> Result<(), Error> {
+        match pulse_constant {
+            PulseConstant::PC1000 => Err(Error::PulseWidthFixed),
+            _ => Ok(()),
+        }
+    }
+
     pub fn decode_from_holding_registers(words: &[Word]) -> Result<Self, Error> {
         let val = words_to_protocol_value!(words)?;
         Ok(Self(val as u16))
     }
 
-    pub fn encode_for_write_registers(&self) -> Vec<Word> {
+    pub fn encode_into(&self, words: &mut [Word]) -> Result<(), Error> {
         let val = self.0 as <Self as ModbusParam>::ProtocolType;
-        protocol_value_to_words!(val)
+        val.into_words(words)
+    }
+
+    pub fn encode_for_write_registers(&self) -> Vec<Word> {
+        encode_to_vec(Self::QUANTITY, |words| self.encode_into(words))
     }
 }
 impl TryFrom<u16> for PulseWidth {
     type Error = Error;
 
     fn try_from(value: u16) -> Result<Self, Self::Error> {
-        Ok(Self(value))
+        if Self::ALLOWED.contains(&value) {
+            Ok(Self(value))
+        } else {
+            Err(Error::InvalidPulseWidth(value))
+        }
     }
 }
-impl std::fmt::Display for PulseWidth {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for PulseWidth {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{}", self.0)
     }
 }
@@ -199,6 +381,10 @@ impl std::fmt::Display for PulseWidth {
 pub enum KPPA {
     NotAuthorized,
     Authorized,
+
+    /// A value not known to this library, as returned by the device, only
+    /// decoded in the [`Lenient`](DecodeMode::Lenient) mode.
+    Unknown(f32),
 }
 impl ModbusParam for KPPA {
     type ProtocolType = f32;
@@ -206,24 +392,37 @@ impl ModbusParam for KPPA {
     const QUANTITY: u16 = 2;
 }
 impl KPPA {
+    /// Decodes the value strictly, an unknown code is an
+    /// [`UnknownVariant`](Error::UnknownVariant) error.
     pub fn decode_from_holding_registers(words: &[Word]) -> Result<Self, Error> {
+        Self::decode_with_mode(words, DecodeMode::Strict)
+    }
+
+    /// Decodes the value, an unknown code is decoded as `Unknown` in the
+    /// [`Lenient`](DecodeMode::Lenient) mode.
+    pub fn decode_with_mode(words: &[Word], mode: DecodeMode) -> Result<Self, Error> {
         let val = words_to_protocol_value!(words)?;
         match val {
             0.0 => Ok(Self::NotAuthorized),
             1.0 => Ok(Self::Authorized),
-            _ => Err(Error::InvalidValue),
+            _ => mode.unknown(val, Self::Unknown),
         }
     }
 
+    pub fn encode_into(password: Password, words: &mut [Word]) -> Result<(), Error> {
+        password.encode_into(words)
+    }
+
     pub fn encode_for_write_registers(password: Password) -> Vec<Word> {
         password.encode_for_write_registers()
     }
 }
-impl std::fmt::Display for KPPA {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for KPPA {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             KPPA::NotAuthorized => write!(f, "not authorized"),
             KPPA::Authorized => write!(f, "authorized"),
+            KPPA::Unknown(val) => write!(f, "unknown ({val})"),
         }
     }
 }
@@ -246,6 +445,10 @@ pub enum ParityAndStopBit {
 
     /// no parity, two stop bits
     NoParityTwoStopBits,
+
+    /// A value not known to this library, as returned by the device, only
+    /// decoded in the [`Lenient`](DecodeMode::Lenient) mode.
+    Unknown(f32),
 }
 impl ModbusParam for ParityAndStopBit {
     type ProtocolType = f32;
@@ -253,34 +456,48 @@ impl ModbusParam for ParityAndStopBit {
     const QUANTITY: u16 = 2;
 }
 impl ParityAndStopBit {
+    /// Decodes the value strictly, an unknown code is an
+    /// [`UnknownVariant`](Error::UnknownVariant) error.
     pub fn decode_from_holding_registers(words: &[Word]) -> Result<Self, Error> {
+        Self::decode_with_mode(words, DecodeMode::Strict)
+    }
+
+    /// Decodes the value, an unknown code is decoded as `Unknown` in the
+    /// [`Lenient`](DecodeMode::Lenient) mode.
+    pub fn decode_with_mode(words: &[Word], mode: DecodeMode) -> Result<Self, Error> {
         let val = words_to_protocol_value!(words)?;
         match val {
             0.0 => Ok(Self::NoParityOneStopBit),
             1.0 => Ok(Self::EvenParityOneStopBit),
             2.0 => Ok(Self::OddParityOneStopBit),
             3.0 => Ok(Self::NoParityTwoStopBits),
-            _ => Err(Error::InvalidValue),
+            _ => mode.unknown(val, Self::Unknown),
         }
     }
 
+    pub fn encode_into(&self, words: &mut [Word]) -> Result<(), Error> {
+        let val: <Self as ModbusParam>::ProtocolType = match self {
+            Self::NoParityOneStopBit => 0.0,
+            Self::EvenParityOneStopBit => 1.0,
+            Self::OddParityOneStopBit => 2.0,
+            Self::NoParityTwoStopBits => 3.0,
+            Self::Unknown(val) => *val,
+        };
+        val.into_words(words)
+    }
+
     pub fn encode_for_write_registers(&self) -> Vec<Word> {
-        let val = match self {
-            Self::NoParityOneStopBit => 0,
-            Self::EvenParityOneStopBit => 1,
-            Self::OddParityOneStopBit => 2,
-            Self::NoParityTwoStopBits => 3,
-        } as <Self as ModbusParam>::ProtocolType;
-        protocol_value_to_words!(val)
+        encode_to_vec(Self::QUANTITY, |words| self.encode_into(words))
     }
 }
-impl std::fmt::Display for ParityAndStopBit {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for ParityAndStopBit {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::NoParityOneStopBit => write!(f, "no parity one stop bit"),
             Self::EvenParityOneStopBit => write!(f, "even parity one stop bit"),
             Self::OddParityOneStopBit => write!(f, "odd parity one stop bit"),
             Self::NoParityTwoStopBits => write!(f, "no parity two stop bit"),
+            Self::Unknown(val) => write!(f, "unknown ({val})"),
         }
     }
 }
@@ -297,7 +514,7 @@ impl ModbusParam for Address {
     const ADDRESS: u16 = 0x0014;
     const QUANTITY: u16 = 2;
 }
-impl std::ops::Deref for Address {
+impl core::ops::Deref for Address {
     type Target = u8;
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -317,9 +534,13 @@ impl Address {
         Ok(Self(val as u8))
     }
 
-    pub fn encode_for_write_registers(&self) -> Vec<Word> {
+    pub fn encode_into(&self, words: &mut [Word]) -> Result<(), Error> {
         let val = self.0 as <Self as ModbusParam>::ProtocolType;
-        protocol_value_to_words!(val)
+        val.into_words(words)
+    }
+
+    pub fn encode_for_write_registers(&self) -> Vec<Word> {
+        encode_to_vec(Self::QUANTITY, |words| self.encode_into(words))
     }
 }
 impl TryFrom<u8> for Address {
@@ -333,8 +554,8 @@ impl TryFrom<u8> for Address {
         }
     }
 }
-impl std::fmt::Display for Address {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Address {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:#04x}", self.0)
     }
 }
@@ -357,6 +578,10 @@ pub enum PulseConstant {
 
     /// 1 imp/kWh
     PC1,
+
+    /// A value not known to this library, as returned by the device, only
+    /// decoded in the [`Lenient`](DecodeMode::Lenient) mode.
+    Unknown(f32),
 }
 impl ModbusParam for PulseConstant {
     type ProtocolType = f32;
@@ -364,34 +589,48 @@ impl ModbusParam for PulseConstant {
     const QUANTITY: u16 = 2;
 }
 impl PulseConstant {
+    /// Decodes the value strictly, an unknown code is an
+    /// [`UnknownVariant`](Error::UnknownVariant) error.
     pub fn decode_from_holding_registers(words: &[Word]) -> Result<Self, Error> {
+        Self::decode_with_mode(words, DecodeMode::Strict)
+    }
+
+    /// Decodes the value, an unknown code is decoded as `Unknown` in the
+    /// [`Lenient`](DecodeMode::Lenient) mode.
+    pub fn decode_with_mode(words: &[Word], mode: DecodeMode) -> Result<Self, Error> {
         let val = words_to_protocol_value!(words)?;
         match val {
             0.0 => Ok(Self::PC1000),
             1.0 => Ok(Self::PC100),
             2.0 => Ok(Self::PC10),
             3.0 => Ok(Self::PC1),
-            _ => Err(Error::InvalidValue),
+            _ => mode.unknown(val, Self::Unknown),
         }
     }
 
+    pub fn encode_into(&self, words: &mut [Word]) -> Result<(), Error> {
+        let val: <Self as ModbusParam>::ProtocolType = match self {
+            Self::PC1000 => 0.0,
+            Self::PC100 => 1.0,
+            Self::PC10 => 2.0,
+            Self::PC1 => 3.0,
+            Self::Unknown(val) => *val,
+        };
+        val.into_words(words)
+    }
+
     pub fn encode_for_write_registers(&self) -> Vec<Word> {
-        let val = match self {
-            Self::PC1000 => 0,
-            Self::PC100 => 1,
-            Self::PC10 => 2,
-            Self::PC1 => 3,
-        } as <Self as ModbusParam>::ProtocolType;
-        protocol_value_to_words!(val)
+        encode_to_vec(Self::QUANTITY, |words| self.encode_into(words))
     }
 }
-impl std::fmt::Display for PulseConstant {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for PulseConstant {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::PC1000 => write!(f, "1000 imp/kWh"),
             Self::PC100 => write!(f, "100 imp/kWh"),
             Self::PC10 => write!(f, "10 imp/kWh"),
             Self::PC1 => write!(f, "1 imp/kWh"),
+            Self::Unknown(val) => write!(f, "unknown ({val})"),
         }
     }
 }
@@ -407,7 +646,7 @@ impl ModbusParam for Password {
     const ADDRESS: u16 = 0x0018;
     const QUANTITY: u16 = 2;
 }
-impl std::ops::Deref for Password {
+impl core::ops::Deref for Password {
     type Target = u16;
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -427,9 +666,13 @@ impl Password {
         Ok(Self(val as u16))
     }
 
-    pub fn encode_for_write_registers(&self) -> Vec<Word> {
+    pub fn encode_into(&self, words: &mut [Word]) -> Result<(), Error> {
         let val = self.0 as <Self as ModbusParam>::ProtocolType;
-        protocol_value_to_words!(val)
+        val.into_words(words)
+    }
+
+    pub fn encode_for_write_registers(&self) -> Vec<Word> {
+        encode_to_vec(Self::QUANTITY, |words| self.encode_into(words))
     }
 }
 impl TryFrom<u16> for Password {
@@ -443,8 +686,8 @@ impl TryFrom<u16> for Password {
         }
     }
 }
-impl std::fmt::Display for Password {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Password {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:04}", self.0)
     }
 }
@@ -462,6 +705,10 @@ pub enum BaudRate {
     #[default]
     B9600,
     B19200,
+
+    /// A value not known to this library, as returned by the device, only
+    /// decoded in the [`Lenient`](DecodeMode::Lenient) mode.
+    Unknown(f32),
 }
 impl ModbusParam for BaudRate {
     type ProtocolType = f32;
@@ -469,7 +716,15 @@ impl ModbusParam for BaudRate {
     const QUANTITY: u16 = 2;
 }
 impl BaudRate {
+    /// Decodes the value strictly, an unknown code is an
+    /// [`UnknownVariant`](Error::UnknownVariant) error.
     pub fn decode_from_holding_registers(words: &[Word]) -> Result<Self, Error> {
+        Self::decode_with_mode(words, DecodeMode::Strict)
+    }
+
+    /// Decodes the value, an unknown code is decoded as `Unknown` in the
+    /// [`Lenient`](DecodeMode::Lenient) mode.
+    pub fn decode_with_mode(words: &[Word], mode: DecodeMode) -> Result<Self, Error> {
         let val = words_to_protocol_value!(words)?;
         match val {
             5.0 => Ok(Self::B1200),
@@ -477,19 +732,24 @@ impl BaudRate {
             1.0 => Ok(Self::B4800),
             2.0 => Ok(Self::B9600),
             3.0 => Ok(Self::B19200),
-            _ => Err(Error::InvalidValue),
+            _ => mode.unknown(val, Self::Unknown),
         }
     }
 
+    pub fn encode_into(&self, words: &mut [Word]) -> Result<(), Error> {
+        let val: <Self as ModbusParam>::ProtocolType = match self {
+            Self::B1200 => 5.0,
+            Self::B2400 => 0.0,
+            Self::B4800 => 1.0,
+            Self::B9600 => 2.0,
+            Self::B19200 => 3.0,
+            Self::Unknown(val) => *val,
+        };
+        val.into_words(words)
+    }
+
     pub fn encode_for_write_registers(&self) -> Vec<Word> {
-        let val = match self {
-            Self::B1200 => 5,
-            Self::B2400 => 0,
-            Self::B4800 => 1,
-            Self::B9600 => 2,
-            Self::B19200 => 3,
-        } as <Self as ModbusParam>::ProtocolType;
-        protocol_value_to_words!(val)
+        encode_to_vec(Self::QUANTITY, |words| self.encode_into(words))
     }
 
     pub fn decode(words: &[Word]) -> Result<u16, Error> {
@@ -511,6 +771,7 @@ impl TryFrom<u16> for BaudRate {
         }
     }
 }
+/// Converts to the baud rate in bit/s, an unknown baud rate code yields `0`.
 impl From<&BaudRate> for u16 {
     fn from(baud_rate: &BaudRate) -> u16 {
         match baud_rate {
@@ -519,12 +780,56 @@ impl From<&BaudRate> for u16 {
             BaudRate::B4800 => 4800,
             BaudRate::B9600 => 9600,
             BaudRate::B19200 => 19200,
+            BaudRate::Unknown(_) => 0,
         }
     }
 }
-impl std::fmt::Display for BaudRate {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", u16::from(self))
+impl BaudRate {
+    /// Returns the silent interval of 3.5 characters which must separate two
+    /// Modbus RTU frames, with a lower bound of 1.75 ms as recommended for
+    /// baud rates above 19200.
+    ///
+    /// See <https://minimalmodbus.readthedocs.io/en/stable/serialcommunication.html#timing-of-the-serial-communications>
+    pub fn silent_interval(&self) -> core::time::Duration {
+        let min_duration = core::time::Duration::from_micros(1_750);
+        let rate = u16::from(self) as f64;
+        if rate == 0.0 {
+            return min_duration;
+        }
+        let bit_time = core::time::Duration::from_secs_f64(1.0 / rate);
+        let char_time = bit_time * 11;
+        let result =
+            core::time::Duration::from_millis((char_time.as_secs_f64() * 3.5 * 1_000.0) as u64);
+        result.max(min_duration)
+    }
+}
+impl core::fmt::Display for BaudRate {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Unknown(val) => write!(f, "unknown ({val})"),
+            _ => write!(f, "{}", u16::from(self)),
+        }
+    }
+}
+
+/// Checks the serial settings before a port is opened with them, an unknown
+/// baud rate or parity code is an [`UnknownVariant`](Error::UnknownVariant)
+/// error instead of e.g. 0 bit/s.
+#[cfg(any(
+    test,
+    feature = "tokio-rtu-sync",
+    feature = "tokio-rtu",
+    feature = "serialport-sync"
+))]
+pub(crate) fn check_serial_settings(
+    baud_rate: &BaudRate,
+    parity_and_stop_bit: &ParityAndStopBit,
+) -> Result<(), Error> {
+    match (baud_rate, parity_and_stop_bit) {
+        (BaudRate::Unknown(val), _) | (_, ParityAndStopBit::Unknown(val)) => {
+            Err(Error::UnknownVariant(*val))
+        }
+        _ => Ok(()),
     }
 }
 
@@ -545,7 +850,7 @@ impl Default for AutoScrollTime {
         Self(5)
     }
 }
-impl std::ops::Deref for AutoScrollTime {
+impl core::ops::Deref for AutoScrollTime {
     type Target = u8;
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -560,9 +865,13 @@ impl AutoScrollTime {
         Ok(Self(val as u8))
     }
 
-    pub fn encode_for_write_registers(&self) -> Vec<Word> {
+    pub fn encode_into(&self, words: &mut [Word]) -> Result<(), Error> {
         let val = self.0 as <Self as ModbusParam>::ProtocolType;
-        protocol_value_to_words!(val)
+        val.into_words(words)
+    }
+
+    pub fn encode_for_write_registers(&self) -> Vec<Word> {
+        encode_to_vec(Self::QUANTITY, |words| self.encode_into(words))
     }
 
     pub fn decode(words: &[Word]) -> Result<u8, Error> {
@@ -581,8 +890,8 @@ impl TryFrom<u8> for AutoScrollTime {
         }
     }
 }
-impl std::fmt::Display for AutoScrollTime {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for AutoScrollTime {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{} sec", self.0)
     }
 }
@@ -621,13 +930,17 @@ impl BacklightTime {
         }
     }
 
-    pub fn encode_for_write_registers(&self) -> Vec<Word> {
+    pub fn encode_into(&self, words: &mut [Word]) -> Result<(), Error> {
         let val = match self {
             Self::AlwaysOn => 0,
             Self::AlwaysOff => 121,
             Self::Delayed(val) => *val,
         } as <Self as ModbusParam>::ProtocolType;
-        protocol_value_to_words!(val)
+        val.into_words(words)
+    }
+
+    pub fn encode_for_write_registers(&self) -> Vec<Word> {
+        encode_to_vec(Self::QUANTITY, |words| self.encode_into(words))
     }
 }
 impl TryFrom<u8> for BacklightTime {
@@ -645,8 +958,8 @@ impl TryFrom<u8> for BacklightTime {
         }
     }
 }
-impl std::fmt::Display for BacklightTime {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for BacklightTime {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::AlwaysOn => write!(f, "always on"),
             Self::AlwaysOff => write!(f, "always off"),
@@ -665,6 +978,10 @@ pub enum PulseEnergyType {
     TotalActiveEnergy,
 
     ExportActiveEnergy,
+
+    /// A value not known to this library, as returned by the device, only
+    /// decoded in the [`Lenient`](DecodeMode::Lenient) mode.
+    Unknown(f32),
 }
 impl ModbusParam for PulseEnergyType {
     type ProtocolType = f32;
@@ -672,36 +989,51 @@ impl ModbusParam for PulseEnergyType {
     const QUANTITY: u16 = 2;
 }
 impl PulseEnergyType {
+    /// Decodes the value strictly, an unknown code is an
+    /// [`UnknownVariant`](Error::UnknownVariant) error.
     pub fn decode_from_holding_registers(words: &[Word]) -> Result<Self, Error> {
+        Self::decode_with_mode(words, DecodeMode::Strict)
+    }
+
+    /// Decodes the value, an unknown code is decoded as `Unknown` in the
+    /// [`Lenient`](DecodeMode::Lenient) mode.
+    pub fn decode_with_mode(words: &[Word], mode: DecodeMode) -> Result<Self, Error> {
         let val = words_to_protocol_value!(words)?;
         match val {
             1.0 => Ok(Self::ImportActiveEnergy),
             2.0 => Ok(Self::TotalActiveEnergy),
             4.0 => Ok(Self::ExportActiveEnergy),
-            _ => Err(Error::InvalidValue),
+            _ => mode.unknown(val, Self::Unknown),
         }
     }
 
+    pub fn encode_into(&self, words: &mut [Word]) -> Result<(), Error> {
+        let val: <Self as ModbusParam>::ProtocolType = match self {
+            Self::ImportActiveEnergy => 1.0,
+            Self::TotalActiveEnergy => 2.0,
+            Self::ExportActiveEnergy => 4.0,
+            Self::Unknown(val) => *val,
+        };
+        val.into_words(words)
+    }
+
     pub fn encode_for_write_registers(&self) -> Vec<Word> {
-        let val = match self {
-            Self::ImportActiveEnergy => 1,
-            Self::TotalActiveEnergy => 2,
-            Self::ExportActiveEnergy => 4,
-        } as <Self as ModbusParam>::ProtocolType;
-        protocol_value_to_words!(val)
+        encode_to_vec(Self::QUANTITY, |words| self.encode_into(words))
     }
 }
-impl std::fmt::Display for PulseEnergyType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for PulseEnergyType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::ImportActiveEnergy => write!(f, "import active energy"),
             Self::TotalActiveEnergy => write!(f, "total active energy"),
             Self::ExportActiveEnergy => write!(f, "export active energy"),
+            Self::Unknown(val) => write!(f, "unknown ({val})"),
         }
     }
 }
 
-/// Reset the historical saved data.
+/// Reset the historical saved data, which are the resettable energies of the
+/// SDM72, see [`ResetTarget::ResettableEnergy`].
 ///
 /// Note: To reset the data you need ['KPPA'](enum@KPPA).
 pub struct ResetHistoricalData;
@@ -711,11 +1043,67 @@ impl ModbusParam for ResetHistoricalData {
     const QUANTITY: u16 = 1;
 }
 impl ResetHistoricalData {
+    pub fn encode_into(words: &mut [Word]) -> Result<(), Error> {
+        ResetTarget::ResettableEnergy.encode_into(words)
+    }
+
     pub fn encode_for_write_registers() -> Vec<Word> {
-        let val = 0x0003 as <Self as ModbusParam>::ProtocolType;
-        protocol_value_to_words!(val)
+        encode_to_vec(Self::QUANTITY, Self::encode_into)
+    }
+}
+
+/// The data reset by a code written to the reset register of
+/// [`ResetHistoricalData`].
+///
+/// Note: To reset the data you need ['KPPA'](enum@KPPA).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ResetTarget {
+    /// The maximum demand values, code `0x0000`. The SDM72 has no demand
+    /// registers, firmwares without them ignore the code or answer with an
+    /// exception.
+    MaxDemand,
+    /// The resettable energies, code `0x0003`, also known as the historical
+    /// data.
+    ResettableEnergy,
+}
+impl ResetTarget {
+    /// The code written to the reset register.
+    pub fn code(self) -> u16 {
+        match self {
+            Self::MaxDemand => 0x0000,
+            Self::ResettableEnergy => 0x0003,
+        }
+    }
+
+    /// The name of the reset as a register, e.g. `ResetMaxDemand`.
+    pub fn register(self) -> &'static str {
+        match self {
+            Self::MaxDemand => "ResetMaxDemand",
+            Self::ResettableEnergy => "ResetHistoricalData",
+        }
+    }
+
+    pub fn encode_into(self, words: &mut [Word]) -> Result<(), Error> {
+        self.code().into_words(words)
+    }
+
+    pub fn encode_for_write_registers(self) -> Vec<Word> {
+        encode_to_vec(ResetHistoricalData::QUANTITY, |words| {
+            self.encode_into(words)
+        })
     }
 }
+impl core::fmt::Display for ResetTarget {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::MaxDemand => write!(f, "maximum demand"),
+            Self::ResettableEnergy => write!(f, "resettable energy"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SerialNumber(u32);
@@ -730,14 +1118,14 @@ impl SerialNumber {
         Ok(Self(val))
     }
 }
-impl std::ops::Deref for SerialNumber {
+impl core::ops::Deref for SerialNumber {
     type Target = u32;
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
-impl std::fmt::Display for SerialNumber {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for SerialNumber {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{}", self.0)
     }
 }
@@ -757,17 +1145,40 @@ impl MeterCode {
         Ok(Self(val))
     }
 }
-impl std::ops::Deref for MeterCode {
+impl core::ops::Deref for MeterCode {
     type Target = u16;
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
-impl std::fmt::Display for MeterCode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for MeterCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:0>4x}", self.0)
     }
 }
+impl MeterCode {
+    /// Returns the model of the meter, `None` if the meter code is unknown.
+    pub fn model(&self) -> Option<MeterModel> {
+        match self.0 {
+            0x0089 => Some(MeterModel::SDM72DM2),
+            _ => None,
+        }
+    }
+}
+
+/// The meter model identified by its [`MeterCode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MeterModel {
+    SDM72DM2,
+}
+impl core::fmt::Display for MeterModel {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::SDM72DM2 => write!(f, "SDM72D-M-2"),
+        }
+    }
+}
 
 /// The software version showed on display
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -784,14 +1195,14 @@ impl SoftwareVersion {
         Ok(Self(val))
     }
 }
-impl std::ops::Deref for SoftwareVersion {
+impl core::ops::Deref for SoftwareVersion {
     type Target = u16;
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
-impl std::fmt::Display for SoftwareVersion {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for SoftwareVersion {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:0>2x}.{:0>2x}", (self.0 >> 8) as u8, self.0 as u8)
     }
 }
@@ -810,36 +1221,130 @@ pub trait ModbusInputRegister: ModbusParam {
     fn decode_from_input_register(words: &[Word]) -> Result<Self, Error>;
 }
 
-fn f32round(val: f32) -> f32 {
-    ((val as f64 * 100.).round() / 100.) as f32
+/// Rounds the value to the number of decimals.
+fn f32round(val: f32, decimals: u8) -> f32 {
+    let factor = libm::pow(10., decimals.into());
+    // `f64::round` requires `std`
+    (libm::round(val as f64 * factor) / factor) as f32
 }
 
-#[cfg(feature = "serde")]
-fn f32ser2<S>(fv: &f32, se: S) -> Result<S::Ok, S::Error>
-where
-    S: serde::Serializer,
-{
-    se.serialize_f32(f32round(*fv))
+/// The class of a measurement, which selects the number of decimals of its
+/// values, see [`Precision`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum MeasurementClass {
+    Voltage,
+    Current,
+    /// The active, apparent and reactive powers.
+    Power,
+    PowerFactor,
+    Frequency,
+    /// The active and reactive energies.
+    Energy,
+}
+impl MeasurementClass {
+    pub const ALL: [Self; 6] = [
+        Self::Voltage,
+        Self::Current,
+        Self::Power,
+        Self::PowerFactor,
+        Self::Frequency,
+        Self::Energy,
+    ];
+
+    /// The name of the class, e.g. `power_factor`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Voltage => "voltage",
+            Self::Current => "current",
+            Self::Power => "power",
+            Self::PowerFactor => "power_factor",
+            Self::Frequency => "frequency",
+            Self::Energy => "energy",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+impl core::fmt::Display for MeasurementClass {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The number of decimals of the measurement values when they are serialized
+/// or displayed, by [`MeasurementClass`].
+///
+/// The precision is a plain value: the safe clients record theirs in the
+/// values they read, and the `serde` and `Display` implementations of
+/// `AllValues` round with it. The register types and [`MeasurementValue`] on
+/// their own round with the default, two decimals for all classes, e.g.
+/// energy counters need three to show every Wh:
+///
+/// ```
+/// use sdm72_lib::protocol::{Measurement, MeasurementClass, Precision};
+///
+/// let mut precision = Precision::default();
+/// precision.set(MeasurementClass::Voltage, 1).unwrap();
+/// precision.set(MeasurementClass::Current, 3).unwrap();
+/// precision.set(MeasurementClass::Energy, 3).unwrap();
+/// assert_eq!(precision.decimals(MeasurementClass::Energy), 3);
+/// assert!(precision.set(MeasurementClass::Power, 7).is_err());
+/// assert_eq!(precision.round(Measurement::TotalEnergyActive, 1.23456), 1.235);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Precision {
+    decimals: [u8; 6],
+}
+impl Default for Precision {
+    fn default() -> Self {
+        Self {
+            decimals: [Self::DEFAULT_DECIMALS; 6],
+        }
+    }
+}
+impl Precision {
+    pub const DEFAULT_DECIMALS: u8 = 2;
+    /// The maximum number of decimals, a 32-bit float has about 7 significant
+    /// digits.
+    pub const MAX_DECIMALS: u8 = 6;
+
+    /// The number of decimals of the class.
+    pub fn decimals(&self, class: MeasurementClass) -> u8 {
+        self.decimals[class.index()]
+    }
+
+    /// Sets the number of decimals of the class, at most
+    /// [`MAX_DECIMALS`](Self::MAX_DECIMALS).
+    pub fn set(&mut self, class: MeasurementClass, decimals: u8) -> Result<(), Error> {
+        if decimals > Self::MAX_DECIMALS {
+            return Err(Error::PrecisionOutOfRange(decimals));
+        }
+        self.decimals[class.index()] = decimals;
+        Ok(())
+    }
+
+    /// Rounds a value of the measurement to the decimals of its class.
+    pub fn round(&self, measurement: Measurement, value: f32) -> f32 {
+        f32round(value, self.decimals(measurement.class()))
+    }
 }
 
 /// A macro to define a newtype struct for a Modbus input register.
 ///
 /// This macro generates a newtype struct that wraps a protocol type (e.g., `f32`)
 /// and implements the `ModbusParam` and `ModbusInputRegister` traits for it.
-/// It also implements `Display` and `Deref`.
+/// It also implements `Deref`.
 macro_rules! modbus_input_register {
     ($vis:vis $ty:ident, $address:expr, $quantity:expr, $protocol_type:ty) => {
+        // `Serialize` and `Display` round with the default precision of the
+        // measurement, see `measurements!`
         #[derive(Debug, Clone, Copy, PartialEq)]
-        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-        $vis struct $ty(
-            #[cfg_attr(feature = "serde", serde(serialize_with = "f32ser2"))]
-            $protocol_type,
-        );
-        impl std::fmt::Display for $ty {
-            fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-                write!(fmt, "{}", f32round(self.0))
-            }
-        }
+        #[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+        $vis struct $ty($protocol_type);
 
         impl ModbusParam for $ty {
             type ProtocolType = $protocol_type;
@@ -847,7 +1352,7 @@ macro_rules! modbus_input_register {
             const QUANTITY: u16 = $quantity;
         }
 
-        impl std::ops::Deref for $ty {
+        impl core::ops::Deref for $ty {
             type Target = $protocol_type;
             fn deref(&self) -> &Self::Target {
                 &self.0
@@ -859,6 +1364,16 @@ macro_rules! modbus_input_register {
                 let val = words_to_protocol_value!(words)?;
                 Ok(Self(val as $protocol_type))
             }
+
+            /// Returns the value multiplied by the given factor (e.g. a CT or VT ratio).
+            pub fn scaled(&self, factor: $protocol_type) -> Self {
+                Self(self.0 * factor)
+            }
+
+            /// Returns `value * multiplier + offset`, e.g. to calibrate a measurement.
+            pub fn calibrated(&self, multiplier: $protocol_type, offset: $protocol_type) -> Self {
+                Self(self.0 * multiplier + offset)
+            }
         }
     };
 }
@@ -883,6 +1398,12 @@ macro_rules! decode_subset_item_from_holding_register {
             &$rsp[$crate::get_subset_register_range!($offset, $register_name)],
         )
     }};
+    ($offset:expr, $register_name:ty, $rsp:expr, $mode:expr) => {{
+        <$register_name>::decode_with_mode(
+            &$rsp[$crate::get_subset_register_range!($offset, $register_name)],
+            $mode,
+        )
+    }};
 }
 
 /// A macro to decode an input register value from a response slice.
@@ -942,3 +1463,984 @@ modbus_input_register!(pub ResettableExportEnergyActive, 0x0186, 2, f32);
 modbus_input_register!(pub NetKwh, 0x018C, 2, f32);
 modbus_input_register!(pub ImportTotalPowerActive, 0x0500, 2, f32);
 modbus_input_register!(pub ExportTotalPowerActive, 0x0502, 2, f32);
+
+/// A macro to define the [`Measurement`] enum from the input register types.
+macro_rules! measurements {
+    ($($ty:ident => $name:literal, $unit:literal, $description:literal;)*) => {
+        /// A measurement of an input register, selectable at runtime.
+        ///
+        /// Unlike the register types (e.g. [`L1Voltage`]) this allows iterating
+        /// over all measurements, which is useful for generic exporters or
+        /// register dump tools.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub enum Measurement {
+            $($ty,)*
+        }
+        impl Measurement {
+            /// All measurements in register address order.
+            pub const ALL: &'static [Measurement] = &[$(Self::$ty,)*];
+
+            /// The Modbus input register address.
+            pub fn address(&self) -> u16 {
+                match self {
+                    $(Self::$ty => $ty::ADDRESS,)*
+                }
+            }
+
+            /// The quantity of Modbus words (16-bit).
+            pub fn quantity(&self) -> u16 {
+                match self {
+                    $(Self::$ty => $ty::QUANTITY,)*
+                }
+            }
+
+            /// The unit of the value, empty for dimensionless values like the power factor.
+            pub fn unit(&self) -> &'static str {
+                match self {
+                    $(Self::$ty => $unit,)*
+                }
+            }
+
+            /// The field name as used in the serialized `AllValues`.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$ty => $name,)*
+                }
+            }
+
+            /// The description of the register, as in the Eastron manual.
+            pub fn description(&self) -> &'static str {
+                match self {
+                    $(Self::$ty => $description,)*
+                }
+            }
+
+            /// Decodes the value of the measurement from the input register words.
+            ///
+            /// `words` must hold exactly [`quantity`](Self::quantity) words,
+            /// two per 32-bit float, otherwise [`Error::WordsCountError`] is
+            /// returned. The most significant word comes first, words read
+            /// from a gateway which swaps them must be reordered with
+            /// [`WordOrder::apply`] beforehand.
+            pub fn decode(&self, words: &[Word]) -> Result<MeasurementValue, Error> {
+                let value = match self {
+                    $(Self::$ty => *$ty::decode_from_input_register(words)?,)*
+                };
+                Ok(MeasurementValue {
+                    measurement: *self,
+                    value,
+                })
+            }
+        }
+        $(
+            impl core::fmt::Display for $ty {
+                fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+                    write!(fmt, "{}", Precision::default().round(Measurement::$ty, self.0))
+                }
+            }
+
+            #[cfg(feature = "serde")]
+            impl serde::Serialize for $ty {
+                fn serialize<S: serde::Serializer>(&self, se: S) -> Result<S::Ok, S::Error> {
+                    se.serialize_f32(Precision::default().round(Measurement::$ty, self.0))
+                }
+            }
+        )*
+    };
+}
+
+measurements! {
+    L1Voltage => "l1_voltage", "V", "Phase 1 line to neutral voltage";
+    L2Voltage => "l2_voltage", "V", "Phase 2 line to neutral voltage";
+    L3Voltage => "l3_voltage", "V", "Phase 3 line to neutral voltage";
+    L1Current => "l1_current", "A", "Phase 1 current";
+    L2Current => "l2_current", "A", "Phase 2 current";
+    L3Current => "l3_current", "A", "Phase 3 current";
+    L1PowerActive => "l1_power_active", "W", "Phase 1 active power";
+    L2PowerActive => "l2_power_active", "W", "Phase 2 active power";
+    L3PowerActive => "l3_power_active", "W", "Phase 3 active power";
+    L1PowerApparent => "l1_power_apparent", "VA", "Phase 1 apparent power";
+    L2PowerApparent => "l2_power_apparent", "VA", "Phase 2 apparent power";
+    L3PowerApparent => "l3_power_apparent", "VA", "Phase 3 apparent power";
+    L1PowerReactive => "l1_power_reactive", "var", "Phase 1 reactive power";
+    L2PowerReactive => "l2_power_reactive", "var", "Phase 2 reactive power";
+    L3PowerReactive => "l3_power_reactive", "var", "Phase 3 reactive power";
+    L1PowerFactor => "l1_power_factor", "", "Phase 1 power factor";
+    L2PowerFactor => "l2_power_factor", "", "Phase 2 power factor";
+    L3PowerFactor => "l3_power_factor", "", "Phase 3 power factor";
+    LtoNAverageVoltage => "l-n_average_voltage", "V", "Average line to neutral voltage";
+    LtoNAverageCurrent => "l-n_average_current", "A", "Average line current";
+    TotalLineCurrent => "total_line_current", "A", "Sum of line currents";
+    TotalPower => "total_power", "W", "Total system active power";
+    TotalPowerApparent => "total_power_apparent", "VA", "Total system apparent power";
+    TotalPowerReactive => "total_power_reactive", "var", "Total system reactive power";
+    TotalPowerFactor => "total_power_factor", "", "Total system power factor";
+    Frequency => "frequency", "Hz", "Frequency of supply voltages";
+    ImportEnergyActive => "import_energy_active", "kWh", "Total import active energy";
+    ExportEnergyActive => "export_energy_active", "kWh", "Total export active energy";
+    L1ToL2Voltage => "l1-l2_voltage", "V", "Line 1 to line 2 voltage";
+    L2ToL3Voltage => "l2-l3_voltage", "V", "Line 2 to line 3 voltage";
+    L3ToL1Voltage => "l3-l1_voltage", "V", "Line 3 to line 1 voltage";
+    LtoLAverageVoltage => "l-l_average_voltage", "V", "Average line to line voltage";
+    NeutralCurrent => "neutral_current", "A", "Neutral current";
+    TotalEnergyActive => "total_energy_active", "kWh", "Total active energy";
+    TotalEnergyReactive => "total_energy_reactive", "kvarh", "Total reactive energy";
+    ResettableTotalEnergyActive => "resettable_total_energy_active", "kWh", "Resettable total active energy";
+    ResettableTotalEnergyReactive => "resettable_total_energy_reactive", "kvarh", "Resettable total reactive energy";
+    ResettableImportEnergyActive => "resettable_import_energy_active", "kWh", "Resettable import active energy";
+    ResettableExportEnergyActive => "resettable_export_energy_active", "kWh", "Resettable export active energy";
+    NetKwh => "net_kwh_import_-_export", "kWh", "Net active energy, import minus export";
+    ImportTotalPowerActive => "import_total_energy_active", "W", "Total import active power";
+    ExportTotalPowerActive => "export_total_energy_active", "W", "Total export active power";
+}
+impl core::fmt::Display for Measurement {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The type of a metric, as in the Prometheus exposition format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum MetricKind {
+    Gauge,
+    /// A value which only increases, e.g. the energy counters.
+    Counter,
+}
+impl MetricKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gauge => "gauge",
+            Self::Counter => "counter",
+        }
+    }
+}
+
+/// The canonical metric of a [`Measurement`], so all metric exporters agree
+/// on the names, labels and units, see [`Measurement::metric`].
+///
+/// The names follow the Prometheus conventions: the `sdm72_` prefix, the
+/// unit as suffix and `_total` for counters. The phases and directions of a
+/// measurement share a name and are told apart by the `phase`, `lines` or
+/// `direction` label. The totals and averages of the meter have names of
+/// their own, so summing a metric over its labels doesn't count twice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MetricDescriptor {
+    /// The name, e.g. `sdm72_voltage_volts`.
+    pub name: &'static str,
+    /// The labels as `(name, value)`, e.g. `[("phase", "l1")]`.
+    pub labels: &'static [(&'static str, &'static str)],
+    pub kind: MetricKind,
+    /// The description of the metric, the same for all of its labels.
+    pub help: &'static str,
+}
+
+impl Measurement {
+    /// The range of plausible values of the register type, as read from the
+    /// meter before any scaling.
+    ///
+    /// The ranges are generous and only catch values which cannot be real
+    /// measurements, e.g. a voltage of 10 MV while the meter updates its
+    /// registers.
+    pub fn plausible_range(&self) -> core::ops::RangeInclusive<f32> {
+        match self.class() {
+            MeasurementClass::Voltage => 0.0..=1_000.0,
+            MeasurementClass::Current => -10_000.0..=10_000.0,
+            MeasurementClass::Power => -10_000_000.0..=10_000_000.0,
+            // With a tolerance for rounding
+            MeasurementClass::PowerFactor => -1.01..=1.01,
+            MeasurementClass::Frequency => 0.0..=100.0,
+            MeasurementClass::Energy => -1_000_000_000.0..=1_000_000_000.0,
+        }
+    }
+
+    /// The class of the measurement, which selects its [`Precision`].
+    pub fn class(&self) -> MeasurementClass {
+        match self {
+            Self::L1Voltage
+            | Self::L2Voltage
+            | Self::L3Voltage
+            | Self::LtoNAverageVoltage
+            | Self::L1ToL2Voltage
+            | Self::L2ToL3Voltage
+            | Self::L3ToL1Voltage
+            | Self::LtoLAverageVoltage => MeasurementClass::Voltage,
+            Self::L1Current
+            | Self::L2Current
+            | Self::L3Current
+            | Self::LtoNAverageCurrent
+            | Self::TotalLineCurrent
+            | Self::NeutralCurrent => MeasurementClass::Current,
+            Self::L1PowerActive
+            | Self::L2PowerActive
+            | Self::L3PowerActive
+            | Self::L1PowerApparent
+            | Self::L2PowerApparent
+            | Self::L3PowerApparent
+            | Self::L1PowerReactive
+            | Self::L2PowerReactive
+            | Self::L3PowerReactive
+            | Self::TotalPower
+            | Self::TotalPowerApparent
+            | Self::TotalPowerReactive
+            | Self::ImportTotalPowerActive
+            | Self::ExportTotalPowerActive => MeasurementClass::Power,
+            Self::L1PowerFactor
+            | Self::L2PowerFactor
+            | Self::L3PowerFactor
+            | Self::TotalPowerFactor => MeasurementClass::PowerFactor,
+            Self::Frequency => MeasurementClass::Frequency,
+            Self::ImportEnergyActive
+            | Self::ExportEnergyActive
+            | Self::TotalEnergyActive
+            | Self::TotalEnergyReactive
+            | Self::ResettableTotalEnergyActive
+            | Self::ResettableTotalEnergyReactive
+            | Self::ResettableImportEnergyActive
+            | Self::ResettableExportEnergyActive
+            | Self::NetKwh => MeasurementClass::Energy,
+        }
+    }
+
+    /// Returns the canonical metric of the measurement.
+    pub fn metric(&self) -> MetricDescriptor {
+        let (name, labels, kind, help): (_, &'static [(&str, &str)], _, _) = match self {
+            Self::L1Voltage => (
+                "sdm72_voltage_volts",
+                &[("phase", "l1")],
+                MetricKind::Gauge,
+                "The voltage between the phase and neutral",
+            ),
+            Self::L2Voltage => (
+                "sdm72_voltage_volts",
+                &[("phase", "l2")],
+                MetricKind::Gauge,
+                "The voltage between the phase and neutral",
+            ),
+            Self::L3Voltage => (
+                "sdm72_voltage_volts",
+                &[("phase", "l3")],
+                MetricKind::Gauge,
+                "The voltage between the phase and neutral",
+            ),
+            Self::L1Current => (
+                "sdm72_current_amperes",
+                &[("phase", "l1")],
+                MetricKind::Gauge,
+                "The current of the phase",
+            ),
+            Self::L2Current => (
+                "sdm72_current_amperes",
+                &[("phase", "l2")],
+                MetricKind::Gauge,
+                "The current of the phase",
+            ),
+            Self::L3Current => (
+                "sdm72_current_amperes",
+                &[("phase", "l3")],
+                MetricKind::Gauge,
+                "The current of the phase",
+            ),
+            Self::L1PowerActive => (
+                "sdm72_power_active_watts",
+                &[("phase", "l1")],
+                MetricKind::Gauge,
+                "The active power of the phase",
+            ),
+            Self::L2PowerActive => (
+                "sdm72_power_active_watts",
+                &[("phase", "l2")],
+                MetricKind::Gauge,
+                "The active power of the phase",
+            ),
+            Self::L3PowerActive => (
+                "sdm72_power_active_watts",
+                &[("phase", "l3")],
+                MetricKind::Gauge,
+                "The active power of the phase",
+            ),
+            Self::L1PowerApparent => (
+                "sdm72_power_apparent_voltamperes",
+                &[("phase", "l1")],
+                MetricKind::Gauge,
+                "The apparent power of the phase",
+            ),
+            Self::L2PowerApparent => (
+                "sdm72_power_apparent_voltamperes",
+                &[("phase", "l2")],
+                MetricKind::Gauge,
+                "The apparent power of the phase",
+            ),
+            Self::L3PowerApparent => (
+                "sdm72_power_apparent_voltamperes",
+                &[("phase", "l3")],
+                MetricKind::Gauge,
+                "The apparent power of the phase",
+            ),
+            Self::L1PowerReactive => (
+                "sdm72_power_reactive_vars",
+                &[("phase", "l1")],
+                MetricKind::Gauge,
+                "The reactive power of the phase",
+            ),
+            Self::L2PowerReactive => (
+                "sdm72_power_reactive_vars",
+                &[("phase", "l2")],
+                MetricKind::Gauge,
+                "The reactive power of the phase",
+            ),
+            Self::L3PowerReactive => (
+                "sdm72_power_reactive_vars",
+                &[("phase", "l3")],
+                MetricKind::Gauge,
+                "The reactive power of the phase",
+            ),
+            Self::L1PowerFactor => (
+                "sdm72_power_factor_ratio",
+                &[("phase", "l1")],
+                MetricKind::Gauge,
+                "The power factor of the phase",
+            ),
+            Self::L2PowerFactor => (
+                "sdm72_power_factor_ratio",
+                &[("phase", "l2")],
+                MetricKind::Gauge,
+                "The power factor of the phase",
+            ),
+            Self::L3PowerFactor => (
+                "sdm72_power_factor_ratio",
+                &[("phase", "l3")],
+                MetricKind::Gauge,
+                "The power factor of the phase",
+            ),
+            Self::LtoNAverageVoltage => (
+                "sdm72_voltage_average_volts",
+                &[],
+                MetricKind::Gauge,
+                "The average voltage between the phases and neutral",
+            ),
+            Self::LtoNAverageCurrent => (
+                "sdm72_current_average_amperes",
+                &[],
+                MetricKind::Gauge,
+                "The average current of the phases",
+            ),
+            Self::TotalLineCurrent => (
+                "sdm72_current_sum_amperes",
+                &[],
+                MetricKind::Gauge,
+                "The sum of the currents of the phases",
+            ),
+            Self::TotalPower => (
+                "sdm72_power_active_sum_watts",
+                &[],
+                MetricKind::Gauge,
+                "The active power of all phases, negative when exporting",
+            ),
+            Self::TotalPowerApparent => (
+                "sdm72_power_apparent_sum_voltamperes",
+                &[],
+                MetricKind::Gauge,
+                "The apparent power of all phases",
+            ),
+            Self::TotalPowerReactive => (
+                "sdm72_power_reactive_sum_vars",
+                &[],
+                MetricKind::Gauge,
+                "The reactive power of all phases",
+            ),
+            Self::TotalPowerFactor => (
+                "sdm72_power_factor_sum_ratio",
+                &[],
+                MetricKind::Gauge,
+                "The power factor of all phases",
+            ),
+            Self::Frequency => (
+                "sdm72_frequency_hertz",
+                &[],
+                MetricKind::Gauge,
+                "The frequency of the supply",
+            ),
+            Self::ImportEnergyActive => (
+                "sdm72_energy_active_kilowatt_hours_total",
+                &[("direction", "import")],
+                MetricKind::Counter,
+                "The active energy by direction",
+            ),
+            Self::ExportEnergyActive => (
+                "sdm72_energy_active_kilowatt_hours_total",
+                &[("direction", "export")],
+                MetricKind::Counter,
+                "The active energy by direction",
+            ),
+            Self::L1ToL2Voltage => (
+                "sdm72_line_voltage_volts",
+                &[("lines", "l1-l2")],
+                MetricKind::Gauge,
+                "The voltage between two phases",
+            ),
+            Self::L2ToL3Voltage => (
+                "sdm72_line_voltage_volts",
+                &[("lines", "l2-l3")],
+                MetricKind::Gauge,
+                "The voltage between two phases",
+            ),
+            Self::L3ToL1Voltage => (
+                "sdm72_line_voltage_volts",
+                &[("lines", "l3-l1")],
+                MetricKind::Gauge,
+                "The voltage between two phases",
+            ),
+            Self::LtoLAverageVoltage => (
+                "sdm72_line_voltage_average_volts",
+                &[],
+                MetricKind::Gauge,
+                "The average voltage between the phases",
+            ),
+            Self::NeutralCurrent => (
+                "sdm72_neutral_current_amperes",
+                &[],
+                MetricKind::Gauge,
+                "The current of the neutral conductor",
+            ),
+            Self::TotalEnergyActive => (
+                "sdm72_energy_active_sum_kilowatt_hours_total",
+                &[],
+                MetricKind::Counter,
+                "The imported and exported active energy",
+            ),
+            Self::TotalEnergyReactive => (
+                "sdm72_energy_reactive_sum_kilovar_hours_total",
+                &[],
+                MetricKind::Counter,
+                "The imported and exported reactive energy",
+            ),
+            Self::ResettableTotalEnergyActive => (
+                "sdm72_resettable_energy_active_sum_kilowatt_hours_total",
+                &[],
+                MetricKind::Counter,
+                "The imported and exported active energy since the last reset",
+            ),
+            Self::ResettableTotalEnergyReactive => (
+                "sdm72_resettable_energy_reactive_sum_kilovar_hours_total",
+                &[],
+                MetricKind::Counter,
+                "The imported and exported reactive energy since the last reset",
+            ),
+            Self::ResettableImportEnergyActive => (
+                "sdm72_resettable_energy_active_kilowatt_hours_total",
+                &[("direction", "import")],
+                MetricKind::Counter,
+                "The active energy by direction since the last reset",
+            ),
+            Self::ResettableExportEnergyActive => (
+                "sdm72_resettable_energy_active_kilowatt_hours_total",
+                &[("direction", "export")],
+                MetricKind::Counter,
+                "The active energy by direction since the last reset",
+            ),
+            Self::NetKwh => (
+                "sdm72_energy_active_net_kilowatt_hours",
+                &[],
+                MetricKind::Gauge,
+                "The imported minus the exported active energy",
+            ),
+            Self::ImportTotalPowerActive => (
+                "sdm72_power_active_directed_watts",
+                &[("direction", "import")],
+                MetricKind::Gauge,
+                "The active power of all phases by direction",
+            ),
+            Self::ExportTotalPowerActive => (
+                "sdm72_power_active_directed_watts",
+                &[("direction", "export")],
+                MetricKind::Gauge,
+                "The active power of all phases by direction",
+            ),
+        };
+        MetricDescriptor {
+            name,
+            labels,
+            kind,
+            help,
+        }
+    }
+}
+
+/// A decoded value tagged with its [`Measurement`].
+///
+/// It is serialized and displayed with the default [`Precision`], see
+/// [`rounded`](Self::rounded) for another one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct MeasurementValue {
+    pub measurement: Measurement,
+    pub value: f32,
+}
+impl MeasurementValue {
+    /// The value rounded with the precision, as displayed.
+    pub fn rounded(&self, precision: Precision) -> f32 {
+        precision.round(self.measurement, self.value)
+    }
+}
+#[cfg(feature = "serde")]
+impl serde::Serialize for MeasurementValue {
+    fn serialize<S: serde::Serializer>(&self, se: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut value = se.serialize_struct("MeasurementValue", 2)?;
+        value.serialize_field("measurement", &self.measurement)?;
+        value.serialize_field("value", &self.rounded(Precision::default()))?;
+        value.end()
+    }
+}
+impl core::fmt::Display for MeasurementValue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let value = self.rounded(Precision::default());
+        match self.measurement.unit() {
+            "" => write!(f, "{value}"),
+            unit => write!(f, "{value} {unit}"),
+        }
+    }
+}
+
+/// The table of a register, which selects the Modbus function codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum RegisterKind {
+    /// Read with function code 0x04.
+    Input,
+    /// Read with function code 0x03 and written with 0x10.
+    Holding,
+}
+impl core::fmt::Display for RegisterKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Input => write!(f, "input"),
+            Self::Holding => write!(f, "holding"),
+        }
+    }
+}
+
+/// The data type of a register value, in big endian words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum DataType {
+    U16,
+    U32,
+    /// An IEEE 754 floating point number.
+    F32,
+}
+impl core::fmt::Display for DataType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::U16 => write!(f, "u16"),
+            Self::U32 => write!(f, "u32"),
+            Self::F32 => write!(f, "f32"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+impl core::fmt::Display for Access {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Read => write!(f, "read"),
+            Self::Write => write!(f, "write"),
+            Self::ReadWrite => write!(f, "read_write"),
+        }
+    }
+}
+
+/// A register of the catalog, see [`register_catalog`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RegisterInfo {
+    /// The field name in the serialized `AllValues` or `AllSettings`.
+    pub name: &'static str,
+    pub kind: RegisterKind,
+    pub address: u16,
+    /// The quantity of Modbus words (16-bit).
+    pub quantity: u16,
+    pub data_type: DataType,
+    /// The unit of the value, empty for dimensionless and coded values.
+    pub unit: &'static str,
+    pub access: Access,
+    pub description: &'static str,
+}
+
+/// The holding registers of the catalog, in address order.
+const HOLDING_REGISTERS: [RegisterInfo; 15] = [
+    RegisterInfo {
+        name: "system_type",
+        kind: RegisterKind::Holding,
+        address: SystemType::ADDRESS,
+        quantity: SystemType::QUANTITY,
+        data_type: DataType::F32,
+        unit: "",
+        access: Access::ReadWrite,
+        description: "Wiring type: 1 = 1P2W, 2 = 3P3W, 3 = 3P4W, requires the KPPA",
+    },
+    RegisterInfo {
+        name: "pulse_width",
+        kind: RegisterKind::Holding,
+        address: PulseWidth::ADDRESS,
+        quantity: PulseWidth::QUANTITY,
+        data_type: DataType::F32,
+        unit: "ms",
+        access: Access::ReadWrite,
+        description: "Pulse width of the pulse output: 60, 100 or 200, requires the KPPA",
+    },
+    RegisterInfo {
+        name: "kppa",
+        kind: RegisterKind::Holding,
+        address: KPPA::ADDRESS,
+        quantity: KPPA::QUANTITY,
+        data_type: DataType::F32,
+        unit: "",
+        access: Access::ReadWrite,
+        description: "Key parameter programming authorization: write the password to get it, reads 0 = not authorized, 1 = authorized",
+    },
+    RegisterInfo {
+        name: "parity_and_stop_bit",
+        kind: RegisterKind::Holding,
+        address: ParityAndStopBit::ADDRESS,
+        quantity: ParityAndStopBit::QUANTITY,
+        data_type: DataType::F32,
+        unit: "",
+        access: Access::ReadWrite,
+        description: "Parity and stop bits: 0 = none/1, 1 = even/1, 2 = odd/1, 3 = none/2, requires the KPPA",
+    },
+    RegisterInfo {
+        name: "address",
+        kind: RegisterKind::Holding,
+        address: Address::ADDRESS,
+        quantity: Address::QUANTITY,
+        data_type: DataType::F32,
+        unit: "",
+        access: Access::ReadWrite,
+        description: "RS485 address from 1 to 247, requires the KPPA",
+    },
+    RegisterInfo {
+        name: "pulse_constant",
+        kind: RegisterKind::Holding,
+        address: PulseConstant::ADDRESS,
+        quantity: PulseConstant::QUANTITY,
+        data_type: DataType::F32,
+        unit: "",
+        access: Access::ReadWrite,
+        description: "Pulse constant: 0 = 1000, 1 = 100, 2 = 10, 3 = 1 imp/kWh, requires the KPPA",
+    },
+    RegisterInfo {
+        name: "password",
+        kind: RegisterKind::Holding,
+        address: Password::ADDRESS,
+        quantity: Password::QUANTITY,
+        data_type: DataType::F32,
+        unit: "",
+        access: Access::ReadWrite,
+        description: "Password from 0 to 9999, requires the KPPA",
+    },
+    RegisterInfo {
+        name: "baud_rate",
+        kind: RegisterKind::Holding,
+        address: BaudRate::ADDRESS,
+        quantity: BaudRate::QUANTITY,
+        data_type: DataType::F32,
+        unit: "",
+        access: Access::ReadWrite,
+        description: "Baud rate: 5 = 1200, 0 = 2400, 1 = 4800, 2 = 9600, 3 = 19200, requires the KPPA",
+    },
+    RegisterInfo {
+        name: "auto_scroll_time",
+        kind: RegisterKind::Holding,
+        address: AutoScrollTime::ADDRESS,
+        quantity: AutoScrollTime::QUANTITY,
+        data_type: DataType::F32,
+        unit: "s",
+        access: Access::ReadWrite,
+        description: "Automatic scroll time of the display from 0 to 60, 0 = off, requires the KPPA",
+    },
+    RegisterInfo {
+        name: "backlight_time",
+        kind: RegisterKind::Holding,
+        address: BacklightTime::ADDRESS,
+        quantity: BacklightTime::QUANTITY,
+        data_type: DataType::F32,
+        unit: "min",
+        access: Access::ReadWrite,
+        description: "Backlight time from 1 to 120, 0 = always on, 121 = always off, requires the KPPA",
+    },
+    RegisterInfo {
+        name: "pulse_energy_type",
+        kind: RegisterKind::Holding,
+        address: PulseEnergyType::ADDRESS,
+        quantity: PulseEnergyType::QUANTITY,
+        data_type: DataType::F32,
+        unit: "",
+        access: Access::ReadWrite,
+        description: "Energy of the pulse output: 1 = import, 2 = total, 4 = export active energy, requires the KPPA",
+    },
+    RegisterInfo {
+        name: "reset_historical_data",
+        kind: RegisterKind::Holding,
+        address: ResetHistoricalData::ADDRESS,
+        quantity: ResetHistoricalData::QUANTITY,
+        data_type: DataType::U16,
+        unit: "",
+        access: Access::Write,
+        description: "Write 0x0003 to reset the resettable energies or 0x0000 to reset the maximum demand, requires the KPPA",
+    },
+    RegisterInfo {
+        name: "serial_number",
+        kind: RegisterKind::Holding,
+        address: SerialNumber::ADDRESS,
+        quantity: SerialNumber::QUANTITY,
+        data_type: DataType::U32,
+        unit: "",
+        access: Access::Read,
+        description: "Serial number",
+    },
+    RegisterInfo {
+        name: "meter_code",
+        kind: RegisterKind::Holding,
+        address: MeterCode::ADDRESS,
+        quantity: MeterCode::QUANTITY,
+        data_type: DataType::U16,
+        unit: "",
+        access: Access::Read,
+        description: "Meter code of the model",
+    },
+    RegisterInfo {
+        name: "software_version",
+        kind: RegisterKind::Holding,
+        address: SoftwareVersion::ADDRESS,
+        quantity: SoftwareVersion::QUANTITY,
+        data_type: DataType::U16,
+        unit: "",
+        access: Access::Read,
+        description: "Software version",
+    },
+];
+
+/// Returns all registers of the meter this crate knows, the holding registers
+/// followed by the input registers of [`Measurement::ALL`], each in address
+/// order.
+///
+/// This is the register map the crate itself is built on, e.g. to generate the
+/// mappings of SCADA or PLC tools.
+pub fn register_catalog() -> impl Iterator<Item = RegisterInfo> {
+    HOLDING_REGISTERS
+        .into_iter()
+        .chain(Measurement::ALL.iter().map(|measurement| RegisterInfo {
+            name: measurement.name(),
+            kind: RegisterKind::Input,
+            address: measurement.address(),
+            quantity: measurement.quantity(),
+            data_type: DataType::F32,
+            unit: measurement.unit(),
+            access: Access::Read,
+            description: measurement.description(),
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f32_words(val: f32) -> Vec<Word> {
+        encode_to_vec(2, |words| val.into_words(words))
+    }
+
+    #[test]
+    fn rtu_silent_interval() {
+        assert_eq!(BaudRate::B1200.silent_interval().as_millis(), 32);
+        assert_eq!(BaudRate::B2400.silent_interval().as_millis(), 16);
+        assert_eq!(BaudRate::B4800.silent_interval().as_millis(), 8);
+        assert_eq!(BaudRate::B9600.silent_interval().as_millis(), 4);
+        assert_eq!(BaudRate::B19200.silent_interval().as_millis(), 2);
+        assert_eq!(
+            BaudRate::Unknown(9.0).silent_interval(),
+            core::time::Duration::from_micros(1_750)
+        );
+    }
+
+    #[test]
+    fn measurement_precision() {
+        assert_eq!(f32round(230.456, 1), 230.5);
+        assert_eq!(f32round(1234.5678, 3), 1234.568);
+        assert_eq!(f32round(0.987, 0), 1.0);
+        assert_eq!(Measurement::L1Voltage.class(), MeasurementClass::Voltage);
+        assert_eq!(Measurement::L1Current.class(), MeasurementClass::Current);
+        assert_eq!(
+            Measurement::TotalPowerFactor.class(),
+            MeasurementClass::PowerFactor
+        );
+        assert_eq!(Measurement::TotalPower.class(), MeasurementClass::Power);
+        assert_eq!(Measurement::Frequency.class(), MeasurementClass::Frequency);
+        assert_eq!(
+            Measurement::TotalEnergyActive.class(),
+            MeasurementClass::Energy
+        );
+
+        let mut precision = Precision::default();
+        assert_eq!(precision.decimals(MeasurementClass::Energy), 2);
+        precision.set(MeasurementClass::Energy, 3).unwrap();
+        assert_eq!(precision.decimals(MeasurementClass::Energy), 3);
+        assert_eq!(precision.decimals(MeasurementClass::Current), 2);
+        assert!(matches!(
+            precision.set(MeasurementClass::Energy, Precision::MAX_DECIMALS + 1),
+            Err(Error::PrecisionOutOfRange(7))
+        ));
+    }
+
+    #[test]
+    fn system_type_round_trip() {
+        for system_type in [
+            SystemType::Type1P2W,
+            SystemType::Type3P3W,
+            SystemType::Type3P4W,
+        ] {
+            let words = system_type.encode_for_write_registers();
+            assert_eq!(
+                SystemType::decode_from_holding_registers(&words).unwrap(),
+                system_type
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_variant_decode_modes() {
+        let words = f32_words(4.0);
+        assert!(matches!(
+            SystemType::decode_from_holding_registers(&words),
+            Err(Error::UnknownVariant(4.0))
+        ));
+        let system_type = SystemType::decode_with_mode(&words, DecodeMode::Lenient).unwrap();
+        assert_eq!(system_type, SystemType::Unknown(4.0));
+        assert_eq!(system_type.encode_for_write_registers(), words);
+        let words = f32_words(3.0);
+        assert!(matches!(
+            PulseEnergyType::decode_with_mode(&words, DecodeMode::Strict),
+            Err(Error::UnknownVariant(3.0))
+        ));
+        assert_eq!(
+            PulseEnergyType::decode_with_mode(&words, DecodeMode::Lenient).unwrap(),
+            PulseEnergyType::Unknown(3.0)
+        );
+        assert_eq!(BaudRate::Unknown(4.0).to_string(), "unknown (4)");
+        assert!(matches!(
+            check_serial_settings(&BaudRate::Unknown(4.0), &ParityAndStopBit::default()),
+            Err(Error::UnknownVariant(4.0))
+        ));
+        assert!(matches!(
+            check_serial_settings(&BaudRate::B9600, &ParityAndStopBit::Unknown(5.0)),
+            Err(Error::UnknownVariant(5.0))
+        ));
+        assert!(check_serial_settings(&BaudRate::B9600, &ParityAndStopBit::default()).is_ok());
+    }
+
+    #[test]
+    fn pulse_width_range() {
+        assert!(matches!(
+            PulseWidth::try_from(35),
+            Err(Error::InvalidPulseWidth(35))
+        ));
+        let pulse_width = PulseWidth::try_from(60).unwrap();
+        assert!(pulse_width
+            .check_pulse_constant(PulseConstant::PC100)
+            .is_ok());
+        assert!(matches!(
+            pulse_width.check_pulse_constant(PulseConstant::PC1000),
+            Err(Error::PulseWidthFixed)
+        ));
+        // The meter reports the fixed width
+        let words = f32_words(35.0);
+        assert_eq!(
+            *PulseWidth::decode_from_holding_registers(&words).unwrap(),
+            35
+        );
+    }
+
+    #[test]
+    fn encode_into_slice() {
+        assert_eq!(f32_words(230.5), vec![0x4366, 0x8000]);
+        assert_eq!(f32::from_words(&[0x4366, 0x8000]).unwrap(), 230.5);
+        let mut words = [0; 3];
+        BaudRate::B9600.encode_into(&mut words[1..]).unwrap();
+        assert_eq!(words[1..], BaudRate::B9600.encode_for_write_registers()[..]);
+        assert!(matches!(
+            BaudRate::B9600.encode_into(&mut words),
+            Err(Error::WordsCountError)
+        ));
+        assert!(matches!(
+            L1Voltage::decode_from_input_register(&words),
+            Err(Error::WordsCountError)
+        ));
+        assert_eq!(
+            ResetHistoricalData::encode_for_write_registers(),
+            vec![0x0003]
+        );
+        assert_eq!(
+            ResetTarget::MaxDemand.encode_for_write_registers(),
+            vec![0x0000]
+        );
+    }
+
+    #[test]
+    fn little_endian_words() {
+        let words = f32_words(230.5);
+        let swapped = WordOrder::LittleEndianWords.apply(words.clone());
+        assert_eq!(swapped, vec![words[1], words[0]]);
+        assert_eq!(WordOrder::LittleEndianWords.apply(swapped), words);
+        assert_eq!(WordOrder::BigEndian.apply(words.clone()), words);
+        assert_eq!(
+            WordOrder::LittleEndianWords.apply(vec![0x0089]),
+            vec![0x0089]
+        );
+    }
+
+    #[test]
+    fn measurement_metadata() {
+        assert_eq!(Measurement::ALL.len(), 42);
+        assert_eq!(Measurement::Frequency.address(), Frequency::ADDRESS);
+        assert_eq!(Measurement::Frequency.quantity(), 2);
+        assert_eq!(Measurement::Frequency.name(), "frequency");
+        let words = f32_words(50.004);
+        let value = Measurement::Frequency.decode(&words).unwrap();
+        assert_eq!(value.measurement, Measurement::Frequency);
+        assert_eq!(value.to_string(), "50 Hz");
+    }
+
+    #[test]
+    fn register_catalog_has_no_overlaps() {
+        let catalog: Vec<RegisterInfo> = register_catalog().collect();
+        assert_eq!(catalog.len(), 15 + Measurement::ALL.len());
+        for kind in [RegisterKind::Holding, RegisterKind::Input] {
+            let registers: Vec<&RegisterInfo> = catalog.iter().filter(|r| r.kind == kind).collect();
+            for pair in registers.windows(2) {
+                assert!(
+                    pair[0].address + pair[0].quantity <= pair[1].address,
+                    "{} overlaps {}",
+                    pair[0].name,
+                    pair[1].name
+                );
+            }
+        }
+        let frequency = catalog.iter().find(|r| r.name == "frequency").unwrap();
+        assert_eq!(frequency.address, 0x0046);
+        assert_eq!(frequency.unit, "Hz");
+    }
+}
@@ -0,0 +1,212 @@
+//! Low-level Modbus requests for users running their own Modbus stack, e.g.
+//! rmodbus or bindings of libmodbus, independent of tokio-modbus.
+//!
+//! A [`ReadRequest`] holds the function code, the first register and the
+//! quantity of words to read a register type, a [`WriteRequest`] the encoded
+//! words of a value in addition. The words are in the big endian order of
+//! the device, see [`WordOrder::apply`](super::WordOrder::apply) for
+//! gateways which swap them.
+//!
+//! # Example
+//!
+//! ```
+//! use sdm72_lib::protocol::{requests, BaudRate, SystemType};
+//!
+//! let read = requests::read_holding::<SystemType>();
+//! assert_eq!(read.as_tuple(), (0x03, 0x000A, 2));
+//!
+//! let write = requests::write(&BaudRate::B9600);
+//! assert_eq!(write.as_tuple(), (0x10, 0x001C, 2));
+//! assert_eq!(write.words, BaudRate::B9600.encode_for_write_registers());
+//! ```
+
+use super::{
+    Address, AutoScrollTime, BacklightTime, BaudRate, Measurement, ModbusParam, ParityAndStopBit,
+    Password, PulseConstant, PulseEnergyType, PulseWidth, RegisterInfo, RegisterKind,
+    ResetHistoricalData, ResetTarget, SystemType, Word, KPPA,
+};
+use alloc::vec::Vec;
+
+/// The Modbus function codes used by the SDM72.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionCode {
+    /// Read holding registers, code `0x03`.
+    ReadHoldingRegisters,
+    /// Read input registers, code `0x04`.
+    ReadInputRegisters,
+    /// Write multiple (holding) registers, code `0x10`.
+    WriteMultipleRegisters,
+}
+impl FunctionCode {
+    /// The function code on the wire.
+    pub fn code(self) -> u8 {
+        match self {
+            Self::ReadHoldingRegisters => 0x03,
+            Self::ReadInputRegisters => 0x04,
+            Self::WriteMultipleRegisters => 0x10,
+        }
+    }
+}
+
+/// A request to read the words of a register type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadRequest {
+    pub function: FunctionCode,
+    /// The first register.
+    pub address: u16,
+    /// The quantity of Modbus words (16-bit).
+    pub quantity: u16,
+}
+impl ReadRequest {
+    /// Returns the function code, the first register and the quantity.
+    pub fn as_tuple(&self) -> (u8, u16, u16) {
+        (self.function.code(), self.address, self.quantity)
+    }
+}
+
+/// A request to write the encoded words of a value, always with
+/// [`FunctionCode::WriteMultipleRegisters`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteRequest {
+    /// The first holding register.
+    pub address: u16,
+    /// The encoded words in big endian order.
+    pub words: Vec<Word>,
+}
+impl WriteRequest {
+    pub const FUNCTION: FunctionCode = FunctionCode::WriteMultipleRegisters;
+
+    /// Returns the function code, the first register and the quantity.
+    pub fn as_tuple(&self) -> (u8, u16, u16) {
+        (Self::FUNCTION.code(), self.address, self.words.len() as u16)
+    }
+}
+
+/// A holding register whose value can be written, see [`write`].
+pub trait WritableRegister: ModbusParam {
+    /// Encodes the value into the words of a write request.
+    fn encode(&self) -> Vec<Word>;
+}
+
+macro_rules! impl_writable_register {
+    ($($ty:ident),* $(,)?) => {
+        $(
+            impl WritableRegister for $ty {
+                fn encode(&self) -> Vec<Word> {
+                    self.encode_for_write_registers()
+                }
+            }
+        )*
+    };
+}
+impl_writable_register!(
+    SystemType,
+    PulseWidth,
+    ParityAndStopBit,
+    Address,
+    PulseConstant,
+    Password,
+    BaudRate,
+    AutoScrollTime,
+    BacklightTime,
+    PulseEnergyType,
+);
+
+/// The request to read a holding register type.
+pub fn read_holding<P: ModbusParam>() -> ReadRequest {
+    ReadRequest {
+        function: FunctionCode::ReadHoldingRegisters,
+        address: P::ADDRESS,
+        quantity: P::QUANTITY,
+    }
+}
+
+/// The request to read an input register type.
+pub fn read_input<P: ModbusParam>() -> ReadRequest {
+    ReadRequest {
+        function: FunctionCode::ReadInputRegisters,
+        address: P::ADDRESS,
+        quantity: P::QUANTITY,
+    }
+}
+
+/// The request to read the input register of a measurement, the words of
+/// the response are decoded by [`Measurement::decode`].
+pub fn read_measurement(measurement: Measurement) -> ReadRequest {
+    ReadRequest {
+        function: FunctionCode::ReadInputRegisters,
+        address: measurement.address(),
+        quantity: measurement.quantity(),
+    }
+}
+
+/// The request to read a register of the [catalog](super::register_catalog).
+pub fn read(register: &RegisterInfo) -> ReadRequest {
+    ReadRequest {
+        function: match register.kind {
+            RegisterKind::Input => FunctionCode::ReadInputRegisters,
+            RegisterKind::Holding => FunctionCode::ReadHoldingRegisters,
+        },
+        address: register.address,
+        quantity: register.quantity,
+    }
+}
+
+/// The request to write a value to its holding register.
+///
+/// Note: Most settings require the [`KPPA`](enum@KPPA), see [`set_kppa`].
+pub fn write<P: WritableRegister>(value: &P) -> WriteRequest {
+    WriteRequest {
+        address: P::ADDRESS,
+        words: value.encode(),
+    }
+}
+
+/// The request to set the Key Parameter Programming Authorization with the
+/// password.
+pub fn set_kppa(password: Password) -> WriteRequest {
+    WriteRequest {
+        address: KPPA::ADDRESS,
+        words: KPPA::encode_for_write_registers(password),
+    }
+}
+
+/// The request to reset the data of `target`.
+pub fn reset(target: ResetTarget) -> WriteRequest {
+    WriteRequest {
+        address: ResetHistoricalData::ADDRESS,
+        words: target.encode_for_write_registers(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{register_catalog, L1Voltage, SerialNumber};
+
+    #[test]
+    fn build_requests() {
+        assert_eq!(read_input::<L1Voltage>().as_tuple(), (0x04, 0x0000, 2));
+        assert_eq!(
+            read_measurement(Measurement::L1Voltage),
+            read_input::<L1Voltage>()
+        );
+        assert_eq!(read_holding::<SerialNumber>().as_tuple(), (0x03, 0xFC00, 2));
+        for register in register_catalog() {
+            let request = read(&register);
+            assert_eq!(request.address, register.address);
+            assert_eq!(request.quantity, register.quantity);
+        }
+
+        let address = Address::try_from(2).unwrap();
+        assert_eq!(write(&address).words, address.encode_for_write_registers());
+        assert_eq!(
+            set_kppa(Password::default()).as_tuple(),
+            (0x10, KPPA::ADDRESS, KPPA::QUANTITY)
+        );
+        assert_eq!(
+            reset(ResetTarget::ResettableEnergy).words,
+            ResetHistoricalData::encode_for_write_registers()
+        );
+    }
+}
@@ -0,0 +1,433 @@
+//! This module records the raw Modbus frames of a session and plays them back,
+//! e.g. to capture a faulty session in the field and reproduce a decode bug
+//! locally without the meter.
+//!
+//! A [`FrameLog`] writes every frame with its timestamp as a line of text:
+//!
+//! ```text
+//! 1760600000.123456 > 01 04 00 00 00 06 70 08
+//! 1760600000.187301 < 01 04 0c 43 66 00 00 43 66 00 00 43 66 00 00 ...
+//! ```
+//!
+//! `>` is a request to the meter and `<` a response, the bytes are in hex
+//! including the MBAP header or the CRC. A response which arrived in several
+//! chunks has a line per chunk.
+//!
+//! [`SDM72::connect_recording`](crate::tokio_async::SDM72::connect_recording)
+//! opens a context whose transport is wrapped in a [`RecordingTransport`],
+//! [`SDM72::replay`](crate::tokio_async::SDM72::replay) opens one on a
+//! [`ReplayTransport`] which answers the requests with the recorded responses.
+//!
+//! # Example
+//!
+//! ```
+//! use sdm72_lib::recording::{Direction, Frame, Recording};
+//!
+//! let recording = Recording::parse("1760600000.123456 > 01 04 00 00 00 06 70 08").unwrap();
+//! let frame = &recording.frames()[0];
+//! assert_eq!(frame.direction, Direction::Request);
+//! assert_eq!(frame.bytes, [0x01, 0x04, 0x00, 0x00, 0x00, 0x06, 0x70, 0x08]);
+//! assert_eq!(frame.to_string(), "1760600000.123456 > 01 04 00 00 00 06 70 08");
+//! ```
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, Write},
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Whether a frame was sent to or received from the meter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// A request to the meter, `>` in the log.
+    Request,
+    /// A response of the meter, `<` in the log.
+    Response,
+}
+
+/// A frame of a recorded session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub timestamp: SystemTime,
+    pub direction: Direction,
+    /// The raw bytes on the transport.
+    pub bytes: Vec<u8>,
+}
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let since_epoch = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        write!(
+            f,
+            "{}.{:06} {}",
+            since_epoch.as_secs(),
+            since_epoch.subsec_micros(),
+            match self.direction {
+                Direction::Request => '>',
+                Direction::Response => '<',
+            }
+        )?;
+        for byte in &self.bytes {
+            write!(f, " {byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The error of a line of a log which is not a [`Frame`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid frame {line:?}: {reason}")]
+pub struct ParseFrameError {
+    pub line: String,
+    pub reason: &'static str,
+}
+
+impl FromStr for Frame {
+    type Err = ParseFrameError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let error = |reason| ParseFrameError {
+            line: line.to_string(),
+            reason,
+        };
+        let mut fields = line.split_whitespace();
+        let (secs, micros) = fields
+            .next()
+            .and_then(|timestamp| timestamp.split_once('.'))
+            .ok_or_else(|| error("missing timestamp"))?;
+        let timestamp = match (secs.parse::<u64>(), micros.parse::<u32>()) {
+            (Ok(secs), Ok(subsec_micros)) if micros.len() == 6 => {
+                UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_micros(subsec_micros.into())
+            }
+            _ => return Err(error("invalid timestamp")),
+        };
+        let direction = match fields.next() {
+            Some(">") => Direction::Request,
+            Some("<") => Direction::Response,
+            _ => return Err(error("the direction must be > or <")),
+        };
+        let bytes = fields
+            .map(|byte| u8::from_str_radix(byte, 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| error("invalid byte"))?;
+        if bytes.is_empty() {
+            return Err(error("no bytes"));
+        }
+        Ok(Self {
+            timestamp,
+            direction,
+            bytes,
+        })
+    }
+}
+
+/// The frames of a recorded session, see the [module](self) documentation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    frames: Vec<Frame>,
+}
+impl Recording {
+    /// Parses the lines of a log, empty lines and lines starting with `#` are
+    /// skipped.
+    pub fn parse(log: &str) -> Result<Self, ParseFrameError> {
+        let frames = log
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Frame::from_str)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { frames })
+    }
+
+    /// Reads and parses a log file.
+    pub fn load(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+}
+
+/// Writes the frames of a session as lines of text, a clone appends to the
+/// same writer, e.g. for the contexts of the reconnects.
+#[derive(Clone)]
+pub struct FrameLog {
+    writer: Arc<Mutex<dyn Write + Send>>,
+}
+impl FrameLog {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Arc::new(Mutex::new(writer)),
+        }
+    }
+
+    /// Appends to the file at `path`, which is created if it doesn't exist.
+    pub fn create(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        Ok(Self::new(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?,
+        ))
+    }
+
+    /// Writes a frame with the current time.
+    ///
+    /// The line is flushed right away, so the log is complete up to the last
+    /// frame if the process is killed.
+    pub fn record(&self, direction: Direction, bytes: &[u8]) -> io::Result<()> {
+        let frame = Frame {
+            timestamp: SystemTime::now(),
+            direction,
+            bytes: bytes.to_vec(),
+        };
+        let mut writer = self.writer.lock().unwrap();
+        writeln!(writer, "{frame}")?;
+        writer.flush()
+    }
+}
+impl fmt::Debug for FrameLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameLog").finish_non_exhaustive()
+    }
+}
+
+/// A transport which writes all bytes passing through it to a [`FrameLog`].
+///
+/// A failed write of the log fails the I/O of the transport, a capture
+/// should not silently miss frames.
+#[derive(Debug)]
+pub struct RecordingTransport<T> {
+    inner: T,
+    log: FrameLog,
+}
+impl<T> RecordingTransport<T> {
+    pub fn new(inner: T, log: FrameLog) -> Self {
+        Self { inner, log }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+impl<T: AsyncRead + Unpin> AsyncRead for RecordingTransport<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            let bytes = &buf.filled()[filled..];
+            if !bytes.is_empty() {
+                self.log.record(Direction::Response, bytes)?;
+            }
+        }
+        result
+    }
+}
+impl<T: AsyncWrite + Unpin> AsyncWrite for RecordingTransport<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = result {
+            if written > 0 {
+                self.log.record(Direction::Request, &buf[..written])?;
+            }
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// A transport which answers the requests with the responses of a
+/// [`Recording`].
+///
+/// The requests must be the same as in the recording and come in the same
+/// order, otherwise the write fails with [`io::ErrorKind::InvalidData`]. The
+/// responses are available immediately, the timestamps are ignored. A request
+/// without a response in the recording is never answered, as the meter did
+/// not answer it, so use a timeout.
+#[derive(Debug, Default)]
+pub struct ReplayTransport {
+    frames: VecDeque<Frame>,
+    /// The bytes of the current request which were written so far.
+    request: Vec<u8>,
+    /// The bytes of the responses which were not read yet.
+    response: VecDeque<u8>,
+    read_waker: Option<Waker>,
+}
+impl ReplayTransport {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            frames: recording.frames.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Compares the written bytes with the next request and queues the
+    /// following responses once the request is complete.
+    fn advance(&mut self) -> io::Result<()> {
+        let Some(expected) = self
+            .frames
+            .iter()
+            .find(|frame| frame.direction == Direction::Request)
+        else {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The recording has no further request",
+            ));
+        };
+        if !expected.bytes.starts_with(&self.request) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "The request {:02x?} differs from the recorded {:02x?}",
+                    self.request, expected.bytes
+                ),
+            ));
+        }
+        if self.request.len() < expected.bytes.len() {
+            return Ok(());
+        }
+        self.request.clear();
+        // Responses before the request, e.g. late answers to a timed out
+        // request, are queued as well
+        while let Some(frame) = self.frames.pop_front() {
+            if frame.direction == Direction::Request {
+                break;
+            }
+            self.response.extend(frame.bytes);
+        }
+        while let Some(Direction::Response) = self.frames.front().map(|frame| frame.direction) {
+            self.response.extend(self.frames.pop_front().unwrap().bytes);
+        }
+        if let Some(waker) = self.read_waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+}
+impl AsyncRead for ReplayTransport {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.response.is_empty() {
+            self.read_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let len = buf.remaining().min(self.response.len());
+        let bytes: Vec<u8> = self.response.drain(..len).collect();
+        buf.put_slice(&bytes);
+        Poll::Ready(Ok(()))
+    }
+}
+impl AsyncWrite for ReplayTransport {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.request.extend_from_slice(buf);
+        Poll::Ready(self.advance().map(|()| buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// A writer whose bytes can be read after it was moved into a log.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn record_and_replay() {
+        let request = [0x01, 0x04, 0x00, 0x00, 0x00, 0x02, 0x71, 0xcb];
+        let response = [0x01, 0x04, 0x04, 0x43, 0x66, 0x00, 0x00, 0xda, 0x7b];
+
+        let shared = Shared::default();
+        let (client, mut meter) = tokio::io::duplex(64);
+        let mut transport = RecordingTransport::new(client, FrameLog::new(shared.clone()));
+        transport.write_all(&request).await.unwrap();
+        let mut buf = [0; 8];
+        meter.read_exact(&mut buf).await.unwrap();
+        meter.write_all(&response[..3]).await.unwrap();
+        let mut buf = [0; 9];
+        transport.read_exact(&mut buf[..3]).await.unwrap();
+        meter.write_all(&response[3..]).await.unwrap();
+        transport.read_exact(&mut buf[3..]).await.unwrap();
+
+        let log = String::from_utf8(shared.0.lock().unwrap().clone()).unwrap();
+        let recording = Recording::parse(&log).unwrap();
+        let directions: Vec<_> = recording
+            .frames()
+            .iter()
+            .map(|frame| frame.direction)
+            .collect();
+        assert_eq!(
+            directions,
+            [Direction::Request, Direction::Response, Direction::Response]
+        );
+
+        let mut replay = ReplayTransport::new(recording.clone());
+        replay.write_all(&request[..4]).await.unwrap();
+        replay.write_all(&request[4..]).await.unwrap();
+        let mut buf = [0; 9];
+        replay.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, response);
+        assert_eq!(
+            replay.write_all(&request).await.unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        let mut replay = ReplayTransport::new(recording);
+        assert_eq!(
+            replay.write_all(&[0x02]).await.unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert!("1760600000.1 ? 01".parse::<Frame>().is_err());
+    }
+}
@@ -0,0 +1,65 @@
+//! The `registers` command, which prints the register catalog of the library
+//! as JSON or CSV.
+
+use crate::commandline::RegisterFormat;
+use anyhow::Result;
+use sdm72_lib::protocol::{self as proto, RegisterInfo};
+use std::fmt::Write;
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv(registers: &[RegisterInfo]) -> String {
+    let mut text = "name,kind,address,quantity,data_type,unit,access,description\n".to_string();
+    for register in registers {
+        let _ = writeln!(
+            text,
+            "{},{},{},{},{},{},{},{}",
+            register.name,
+            register.kind,
+            register.address,
+            register.quantity,
+            register.data_type,
+            register.unit,
+            register.access,
+            csv_field(register.description),
+        );
+    }
+    text
+}
+
+pub fn print(format: RegisterFormat) -> Result<()> {
+    let registers: Vec<RegisterInfo> = proto::register_catalog().collect();
+    match format {
+        RegisterFormat::Json => println!("{}", serde_json::to_string_pretty(&registers)?),
+        RegisterFormat::Csv => print!("{}", csv(&registers)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_csv() {
+        let registers: Vec<RegisterInfo> = proto::register_catalog().collect();
+        let text = csv(&registers);
+        let mut lines = text.lines();
+        assert_eq!(
+            lines.next(),
+            Some("name,kind,address,quantity,data_type,unit,access,description")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("system_type,holding,10,2,f32,,read_write,\"Wiring type: 1 = 1P2W, 2 = 3P3W, 3 = 3P4W, requires the KPPA\"")
+        );
+        assert!(text.contains("\nfrequency,input,70,2,f32,Hz,read,Frequency of supply voltages\n"));
+    }
+}
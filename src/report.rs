@@ -0,0 +1,288 @@
+//! The `report` command, which summarizes the imported and exported energy
+//! per day or month from the stored history, as JSON or CSV.
+//!
+//! The history is the database of the `sqlite` daemon or a Parquet file of the
+//! `capture` command. Like the tariff of the MQTT daemon, the energy between
+//! two samples is the difference of the energy counters of the meter and is
+//! counted in the period of the later sample, in local time. A decreasing
+//! counter, e.g. after a reset of the meter, is not counted.
+
+use crate::{
+    commandline::{ReportFormat, ReportPeriod},
+    tariff::TariffConfig,
+};
+use anyhow::{Context, Result};
+use chrono::{Datelike, NaiveDateTime, TimeZone};
+use sdm72_lib::protocol::Measurement;
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+/// The energy counters of a sample of the history.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sample {
+    /// The meter of the sample, samples are only compared within a meter.
+    serial_number: i64,
+    /// Milliseconds since the Unix epoch.
+    timestamp: i64,
+    import_kwh: f64,
+    export_kwh: f64,
+}
+
+/// The energy of a day or month.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+struct Summary {
+    /// The day as `YYYY-MM-DD` or the month as `YYYY-MM`.
+    period: String,
+    import_kwh: f64,
+    export_kwh: f64,
+    /// The cost of the imported minus the revenue of the exported energy,
+    /// with a tariff.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cost: Option<f64>,
+}
+
+fn read_sqlite(path: &Path) -> Result<Vec<Sample>> {
+    let conn =
+        rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("Cannot open SQLite database {path:?}"))?;
+    let mut statement = conn
+        .prepare(&format!(
+            "SELECT serial_number, timestamp, \"{}\", \"{}\" FROM measurements \
+             ORDER BY serial_number, timestamp",
+            Measurement::ImportEnergyActive.name(),
+            Measurement::ExportEnergyActive.name(),
+        ))
+        .with_context(|| format!("{path:?} has no measurements of the sqlite daemon"))?;
+    let samples = statement
+        .query_map([], |row| {
+            Ok(Sample {
+                serial_number: row.get(0)?,
+                timestamp: row.get(1)?,
+                import_kwh: row.get(2)?,
+                export_kwh: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()
+        .with_context(|| format!("Cannot read the measurements of {path:?}"))?;
+    Ok(samples)
+}
+
+#[cfg(feature = "parquet")]
+fn read_parquet(path: &Path) -> Result<Vec<Sample>> {
+    use arrow_array::{cast::AsArray, types, Array};
+    use sdm72_lib::arrow::TIMESTAMP_COLUMN;
+
+    let file =
+        std::fs::File::open(path).with_context(|| format!("Cannot open {}", path.display()))?;
+    let reader =
+        parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file)?.build()?;
+    let mut samples = Vec::new();
+    for batch in reader {
+        let batch = batch?;
+        let column = |name: &str| {
+            batch
+                .column_by_name(name)
+                .with_context(|| format!("{} has no column {name}", path.display()))
+        };
+        let timestamps =
+            column(TIMESTAMP_COLUMN)?.as_primitive::<types::TimestampMillisecondType>();
+        let imports =
+            column(Measurement::ImportEnergyActive.name())?.as_primitive::<types::Float32Type>();
+        let exports =
+            column(Measurement::ExportEnergyActive.name())?.as_primitive::<types::Float32Type>();
+        for row in 0..batch.num_rows() {
+            // Rows without the energy counters were captured with a selection
+            if imports.is_null(row) || exports.is_null(row) {
+                continue;
+            }
+            samples.push(Sample {
+                serial_number: 0,
+                timestamp: timestamps.value(row),
+                import_kwh: imports.value(row).into(),
+                export_kwh: exports.value(row).into(),
+            });
+        }
+    }
+    samples.sort_by_key(|sample| sample.timestamp);
+    Ok(samples)
+}
+
+fn read(path: &Path) -> Result<Vec<Sample>> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        #[cfg(feature = "parquet")]
+        Some("parquet") => read_parquet(path),
+        #[cfg(not(feature = "parquet"))]
+        Some("parquet") => anyhow::bail!("Reading Parquet files requires the parquet feature"),
+        _ => read_sqlite(path),
+    }
+}
+
+/// The period of a local time.
+fn period(time: NaiveDateTime, by: ReportPeriod) -> String {
+    match by {
+        ReportPeriod::Day => format!("{:04}-{:02}-{:02}", time.year(), time.month(), time.day()),
+        ReportPeriod::Month => format!("{:04}-{:02}", time.year(), time.month()),
+    }
+}
+
+/// Rounds to three decimals, the Wh of the counters.
+fn round(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
+
+/// Sums the energy between successive samples of a meter per period, the
+/// samples are sorted by meter and time.
+fn summarize(
+    samples: &[Sample],
+    by: ReportPeriod,
+    tariff: Option<&TariffConfig>,
+    local_time: impl Fn(i64) -> Option<NaiveDateTime>,
+) -> Vec<Summary> {
+    let mut summaries: BTreeMap<String, Summary> = BTreeMap::new();
+    for pair in samples.windows(2) {
+        let [previous, sample] = pair else {
+            continue;
+        };
+        if previous.serial_number != sample.serial_number {
+            continue;
+        }
+        let imported = (sample.import_kwh - previous.import_kwh).max(0.0);
+        let exported = (sample.export_kwh - previous.export_kwh).max(0.0);
+        let Some(time) = local_time(sample.timestamp) else {
+            log::debug!(
+                "Invalid timestamp {}, the sample is skipped",
+                sample.timestamp
+            );
+            continue;
+        };
+        let key = period(time, by);
+        let summary = summaries.entry(key.clone()).or_insert_with(|| Summary {
+            period: key,
+            cost: tariff.map(|_| 0.0),
+            ..Summary::default()
+        });
+        summary.import_kwh += imported;
+        summary.export_kwh += exported;
+        if let (Some(cost), Some(tariff)) = (&mut summary.cost, tariff) {
+            match tariff.band_at(time) {
+                Some(band) => *cost += imported * band.import_price - exported * band.export_price,
+                None => log::debug!("No tariff band active at {time}, the energy has no cost"),
+            }
+        }
+    }
+    summaries
+        .into_values()
+        .map(|summary| Summary {
+            import_kwh: round(summary.import_kwh),
+            export_kwh: round(summary.export_kwh),
+            cost: summary.cost.map(|cost| (cost * 100.0).round() / 100.0),
+            ..summary
+        })
+        .collect()
+}
+
+fn csv(summaries: &[Summary]) -> String {
+    let with_cost = summaries.iter().any(|summary| summary.cost.is_some());
+    let mut text = format!(
+        "period,import_kwh,export_kwh{}\n",
+        if with_cost { ",cost" } else { "" }
+    );
+    for summary in summaries {
+        let _ = write!(
+            text,
+            "{},{},{}",
+            summary.period, summary.import_kwh, summary.export_kwh
+        );
+        if let Some(cost) = summary.cost {
+            let _ = write!(text, ",{cost}");
+        }
+        text.push('\n');
+    }
+    text
+}
+
+pub fn print(
+    history: &Path,
+    by: ReportPeriod,
+    format: ReportFormat,
+    tariff_file: Option<&str>,
+) -> Result<()> {
+    let tariff = tariff_file.map(TariffConfig::load).transpose()?;
+    if let Some(tariff) = &tariff {
+        tariff.validate()?;
+    }
+    let samples = read(history)?;
+    log::debug!("Read {} samples from {history:?}", samples.len());
+    let summaries = summarize(&samples, by, tariff.as_ref(), |timestamp| {
+        chrono::Local
+            .timestamp_millis_opt(timestamp)
+            .earliest()
+            .map(|time| time.naive_local())
+    });
+    match format {
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&summaries)?),
+        ReportFormat::Csv => print!("{}", csv(&summaries)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_file;
+
+    #[test]
+    fn summarize_per_period() {
+        let tariff: TariffConfig = config_file::parse(
+            config_file::Format::Yaml,
+            r#"
+state_file: tariff.json
+bands:
+  - name: day
+    import_price: 0.3
+    export_price: 0.1
+"#,
+        )
+        .unwrap();
+        let hour: i64 = 3_600_000;
+        let sample = |serial_number, hours, import_kwh, export_kwh| Sample {
+            serial_number,
+            timestamp: hours * hour,
+            import_kwh,
+            export_kwh,
+        };
+        let samples = [
+            sample(1, 0, 100.0, 10.0),
+            sample(1, 12, 110.0, 12.0),
+            sample(1, 36, 120.0, 12.0),
+            // A reset of the meter
+            sample(1, 37, 0.0, 0.0),
+            sample(1, 24 * 40, 5.0, 0.0),
+            sample(2, 12, 500.0, 0.0),
+            sample(2, 13, 501.0, 0.0),
+        ];
+        let utc = |timestamp| {
+            chrono::DateTime::from_timestamp_millis(timestamp).map(|time| time.naive_utc())
+        };
+
+        let days = summarize(&samples, ReportPeriod::Day, None, utc);
+        assert_eq!(
+            days.iter()
+                .map(|day| day.period.as_str())
+                .collect::<Vec<_>>(),
+            ["1970-01-01", "1970-01-02", "1970-02-10"]
+        );
+        assert_eq!((days[0].import_kwh, days[0].export_kwh), (11.0, 2.0));
+        assert_eq!(days[0].cost, None);
+        assert_eq!(days[1].import_kwh, 10.0);
+        assert_eq!(days[2].import_kwh, 5.0);
+
+        let months = summarize(&samples, ReportPeriod::Month, Some(&tariff), utc);
+        assert_eq!(months.len(), 2);
+        assert_eq!(months[0].import_kwh, 21.0);
+        assert_eq!(months[0].cost, Some(6.1));
+        assert_eq!(
+            csv(&months),
+            "period,import_kwh,export_kwh,cost\n1970-01,21,2,6.1\n1970-02,5,0,1.5\n"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! The bounded runs of the daemon with `--once`, `--count` and `--duration`,
+//! e.g. to publish 100 samples for a commissioning report and exit.
+
+use sdm72_lib::{schedule::Ticker, tokio_common::Clock};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Stops the loop of a daemon after a number of polls or after a duration.
+#[derive(Debug)]
+pub struct RunLimit {
+    max_polls: Option<u64>,
+    duration: Option<Duration>,
+    clock: Arc<dyn Clock>,
+    started: Instant,
+    polls: u64,
+    failures: u64,
+}
+
+impl RunLimit {
+    pub fn new(max_polls: Option<u64>, duration: Option<Duration>, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            max_polls,
+            duration,
+            started: clock.now(),
+            clock,
+            polls: 0,
+            failures: 0,
+        }
+    }
+
+    /// Returns `true` if the run stops by itself.
+    pub fn is_bounded(&self) -> bool {
+        self.max_polls.is_some() || self.duration.is_some()
+    }
+
+    /// Returns `true` if the run stops after a number of polls.
+    pub fn counts_polls(&self) -> bool {
+        self.max_polls.is_some()
+    }
+
+    /// The time left of the duration, `None` without a duration.
+    pub fn remaining(&self) -> Option<Duration> {
+        let elapsed = self.clock.now().saturating_duration_since(self.started);
+        self.duration
+            .map(|duration| duration.saturating_sub(elapsed))
+    }
+
+    /// Returns `true` once the duration has passed.
+    pub fn expired(&self) -> bool {
+        self.remaining()
+            .is_some_and(|remaining| remaining.is_zero())
+    }
+
+    /// Records a poll and waits for the next one with the ticker, returns
+    /// `false` if the run is over.
+    pub fn next_poll(&mut self, succeeded: bool, ticker: &mut Ticker) -> bool {
+        self.polls += 1;
+        if !succeeded {
+            self.failures += 1;
+        }
+        if self.max_polls.is_some_and(|max| self.polls >= max) || self.expired() {
+            return false;
+        }
+        ticker.wait();
+        !self.expired()
+    }
+
+    /// A summary of the run, e.g. "Stopped after 100 polls (2 failed) in 3m 20s".
+    pub fn summary(&self) -> String {
+        let elapsed = self.clock.now().saturating_duration_since(self.started);
+        format!(
+            "Stopped after {} polls ({} failed) in {}",
+            self.polls,
+            self.failures,
+            humantime::format_duration(Duration::from_secs(elapsed.as_secs()))
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Debug)]
+    struct FakeClock(Mutex<Instant>);
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+        fn sleep(&self, duration: Duration) {
+            *self.0.lock().unwrap() += duration;
+        }
+    }
+
+    #[test]
+    fn stop_after_polls_or_duration() {
+        let clock: Arc<dyn Clock> = Arc::new(FakeClock(Mutex::new(Instant::now())));
+        let mut ticker = Ticker::new(Duration::from_secs(10), clock.clone());
+        let mut limit = RunLimit::new(Some(3), None, clock.clone());
+        assert!(limit.is_bounded());
+        assert!(limit.next_poll(true, &mut ticker));
+        assert!(limit.next_poll(false, &mut ticker));
+        assert!(!limit.next_poll(true, &mut ticker));
+        assert_eq!(limit.summary(), "Stopped after 3 polls (1 failed) in 20s");
+
+        let mut ticker = Ticker::new(Duration::from_secs(10), clock.clone());
+        let mut limit = RunLimit::new(None, Some(Duration::from_secs(25)), clock.clone());
+        assert!(limit.next_poll(true, &mut ticker));
+        assert_eq!(limit.remaining(), Some(Duration::from_secs(15)));
+        assert!(limit.next_poll(true, &mut ticker));
+        assert!(!limit.next_poll(true, &mut ticker));
+        assert!(limit.expired());
+
+        assert!(!RunLimit::new(None, None, clock).is_bounded());
+    }
+}
@@ -0,0 +1,97 @@
+//! This module schedules periodic work, like the polls of the daemons, on the
+//! monotonic time of a [`Clock`].
+//!
+//! A [`Ticker`] keeps the ticks aligned to its start, so the time a poll takes
+//! doesn't add to the interval. It never looks at the system time, which can
+//! jump by NTP step corrections or manual changes. Daylight saving time only
+//! changes the local time and never affects the schedule.
+//!
+//! # Example
+//!
+//! ```
+//! use sdm72_lib::{schedule::Ticker, tokio_common::SystemClock};
+//! use std::{sync::Arc, time::Duration};
+//!
+//! let mut ticker = Ticker::new(Duration::from_millis(10), Arc::new(SystemClock));
+//! for _ in 0..3 {
+//!     // poll the meter
+//!     ticker.wait();
+//! }
+//! ```
+
+use crate::tokio_common::Clock;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Ticks in a fixed interval of monotonic time.
+#[derive(Debug)]
+pub struct Ticker {
+    clock: Arc<dyn Clock>,
+    interval: Duration,
+    next: Instant,
+}
+
+impl Ticker {
+    /// Creates a ticker, the first tick is one interval after now.
+    pub fn new(interval: Duration, clock: Arc<dyn Clock>) -> Self {
+        let next = clock.now() + interval;
+        Self {
+            clock,
+            interval,
+            next,
+        }
+    }
+
+    /// The interval of the ticks.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Blocks until the next tick.
+    ///
+    /// If the tick has already passed, e.g. because the work took longer than
+    /// the interval, it returns immediately and skips the missed ticks instead
+    /// of catching up with a burst.
+    pub fn wait(&mut self) {
+        let now = self.clock.now();
+        if now < self.next {
+            self.clock.sleep(self.next - now);
+            self.next += self.interval;
+        } else if self.interval.is_zero() {
+            self.next = now;
+        } else {
+            let missed = (now - self.next).as_nanos() / self.interval.as_nanos();
+            let skipped = u32::try_from(missed + 1).unwrap_or(u32::MAX);
+            self.next += self.interval.saturating_mul(skipped);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::MockClock;
+
+    #[test]
+    fn aligned_ticks() {
+        let clock = Arc::new(MockClock::new());
+        let start = clock.now();
+        let mut ticker = Ticker::new(Duration::from_secs(10), clock.clone());
+        // The work takes 3s
+        clock.advance(Duration::from_secs(3));
+        ticker.wait();
+        assert_eq!(clock.now() - start, Duration::from_secs(10));
+        // The work takes 25s, the ticks at 20s and 30s are skipped
+        clock.advance(Duration::from_secs(25));
+        ticker.wait();
+        assert_eq!(clock.now() - start, Duration::from_secs(35));
+        ticker.wait();
+        assert_eq!(clock.now() - start, Duration::from_secs(40));
+        assert_eq!(
+            clock.sleeps(),
+            vec![Duration::from_secs(7), Duration::from_secs(5)]
+        );
+    }
+}
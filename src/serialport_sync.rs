@@ -0,0 +1,512 @@
+//! This module provides a blocking Modbus RTU client for the SDM72 energy
+//! meter on top of the `serialport` crate, without `tokio`.
+//!
+//! It is meant for small binaries which read a meter once, e.g. from a cron
+//! job, where the `tokio` runtime of the other clients is too heavy. The
+//! requests are framed by a minimal RTU framer (function codes 03, 04 and 16),
+//! all registers are encoded and decoded by the [`protocol`](crate::protocol)
+//! module.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sdm72_lib::{
+//!     protocol::{Address, BaudRate, ParityAndStopBit},
+//!     serialport_sync::SDM72,
+//! };
+//! use std::time::Duration;
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let mut meter = SDM72::open(
+//!         "/dev/ttyUSB0",
+//!         BaudRate::B9600,
+//!         ParityAndStopBit::NoParityOneStopBit,
+//!         Address::default(),
+//!         Duration::from_millis(200),
+//!     )?;
+//!     for value in meter.read_all()? {
+//!         println!("{}: {} {}", value.measurement.name(), value.value, value.measurement.unit());
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use crate::protocol::{self as proto, ModbusParam};
+use std::{
+    io::{Read, Write},
+    time::{Duration, Instant},
+};
+
+/// The errors of the [`SDM72`] client.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// An error originating from the protocol logic, such as invalid data.
+    #[error(transparent)]
+    Protocol(#[from] proto::Error),
+
+    /// The serial port cannot be opened or configured.
+    #[error(transparent)]
+    Serial(#[from] serialport::Error),
+
+    /// An I/O error of the serial port, e.g. a timeout.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// The meter answered with an exception response.
+    #[error("The meter answered with the exception code {0:#04x}")]
+    Exception(u8),
+
+    /// The CRC of the response is invalid, usually a wiring problem like a
+    /// missing termination.
+    #[error("The CRC of the response is invalid")]
+    Crc,
+
+    /// The response doesn't match the request.
+    #[error("The response doesn't match the request")]
+    MalformedFrame,
+}
+
+/// A specialized `Result` type for the [`SDM72`] client.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The largest number of registers read in a single request, as the largest
+/// batch of `read_all` of the `tokio` clients.
+const MAX_QUANTITY: u16 = 76;
+
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+const READ_INPUT_REGISTERS: u8 = 0x04;
+const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+/// The Modbus CRC-16 of a frame.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFF_u16;
+    for byte in data {
+        crc ^= u16::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// Builds the frame of a request, the CRC is sent low byte first.
+fn frame(slave: u8, function: u8, data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(data.len() + 4);
+    frame.push(slave);
+    frame.push(function);
+    frame.extend_from_slice(data);
+    frame.extend_from_slice(&crc16(&frame).to_le_bytes());
+    frame
+}
+
+/// Sends a request and returns the data of its response, without the slave,
+/// the function code and the CRC.
+///
+/// `response_len` is the length of the data of a response of the given
+/// function code, based on the first byte of the data.
+fn transact(
+    port: &mut (impl Read + Write),
+    slave: u8,
+    function: u8,
+    data: &[u8],
+    response_len: impl Fn(u8) -> usize,
+) -> Result<Vec<u8>> {
+    port.write_all(&frame(slave, function, data))?;
+    port.flush()?;
+
+    let mut rsp = vec![0; 3];
+    port.read_exact(&mut rsp)?;
+    let len = if rsp[1] == function | 0x80 {
+        1
+    } else {
+        response_len(rsp[2])
+    };
+    rsp.resize(2 + len + 2, 0);
+    port.read_exact(&mut rsp[3..])?;
+
+    let (payload, crc) = rsp.split_at(rsp.len() - 2);
+    if crc16(payload).to_le_bytes() != crc {
+        return Err(Error::Crc);
+    }
+    if payload[0] != slave {
+        return Err(Error::MalformedFrame);
+    }
+    if payload[1] == function | 0x80 {
+        return Err(Error::Exception(payload[2]));
+    }
+    if payload[1] != function {
+        return Err(Error::MalformedFrame);
+    }
+    Ok(payload[2..].to_vec())
+}
+
+/// Reads `quantity` holding or input registers.
+fn read_registers(
+    port: &mut (impl Read + Write),
+    slave: u8,
+    function: u8,
+    address: u16,
+    quantity: u16,
+) -> Result<Vec<proto::Word>> {
+    let mut data = address.to_be_bytes().to_vec();
+    data.extend_from_slice(&quantity.to_be_bytes());
+    let rsp = transact(port, slave, function, &data, |count| 1 + usize::from(count))?;
+    if usize::from(rsp[0]) != rsp.len() - 1 || rsp.len() - 1 != 2 * usize::from(quantity) {
+        return Err(Error::MalformedFrame);
+    }
+    Ok(rsp[1..]
+        .chunks_exact(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]))
+        .collect())
+}
+
+/// Writes the words to consecutive holding registers.
+fn write_registers(
+    port: &mut (impl Read + Write),
+    slave: u8,
+    address: u16,
+    words: &[proto::Word],
+) -> Result<()> {
+    let quantity = u16::try_from(words.len()).map_err(|_| proto::Error::OutOfRange)?;
+    let mut data = address.to_be_bytes().to_vec();
+    data.extend_from_slice(&quantity.to_be_bytes());
+    data.push(u8::try_from(2 * words.len()).map_err(|_| proto::Error::OutOfRange)?);
+    for word in words {
+        data.extend_from_slice(&word.to_be_bytes());
+    }
+    let rsp = transact(port, slave, WRITE_MULTIPLE_REGISTERS, &data, |_| 4)?;
+    if rsp != data[..4] {
+        return Err(Error::MalformedFrame);
+    }
+    Ok(())
+}
+
+/// Groups the measurements into requests of at most [`MAX_QUANTITY`]
+/// registers, as `(address, quantity)`.
+fn batches(measurements: &[proto::Measurement]) -> Vec<(u16, u16)> {
+    let mut measurements = measurements.to_vec();
+    measurements.sort_by_key(proto::Measurement::address);
+    let mut batches: Vec<(u16, u16)> = Vec::new();
+    for measurement in measurements {
+        let end = measurement.address() + measurement.quantity();
+        match batches.last_mut() {
+            Some((address, quantity)) if end - *address <= MAX_QUANTITY => {
+                *quantity = (*quantity).max(end - *address);
+            }
+            _ => batches.push((measurement.address(), measurement.quantity())),
+        }
+    }
+    batches
+}
+
+/// A blocking Modbus RTU client for the SDM72 energy meter, see the
+/// [module](self) documentation.
+pub struct SDM72 {
+    port: Box<dyn serialport::SerialPort>,
+    slave: proto::Address,
+    word_order: proto::WordOrder,
+    silent_interval: Duration,
+    last_frame: Option<Instant>,
+}
+
+/// A macro to generate a function for reading a holding register.
+macro_rules! read_holding {
+    ($func_name:expr, $ty:ident) => {
+        paste::item! {
+            #[doc = "Reads the [`proto::" $ty "`] value from the Modbus holding register."]
+            pub fn $func_name(&mut self) -> Result<proto::$ty> {
+                let rsp = self.read_raw_holding(<proto::$ty>::ADDRESS, <proto::$ty>::QUANTITY)?;
+                Ok(<proto::$ty>::decode_from_holding_registers(&self.word_order.apply(rsp))?)
+            }
+        }
+    };
+}
+
+/// A macro to generate a function for writing a holding register.
+macro_rules! write_holding {
+    ($func_name:expr, $ty:ident) => {
+        paste::item! {
+            #[doc = "Writes the [`proto::" $ty "`] value to the Modbus holding register."]
+            pub fn [< set_ $func_name >](&mut self, value: proto::$ty) -> Result<()> {
+                let words = self.word_order.apply(value.encode_for_write_registers());
+                self.write_raw_holding(<proto::$ty>::ADDRESS, &words)
+            }
+        }
+    };
+}
+
+impl SDM72 {
+    /// Opens the serial port of the meter with the given settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The timeout of the responses of the meter.
+    pub fn open(
+        device: &str,
+        baud_rate: proto::BaudRate,
+        parity_and_stop_bit: proto::ParityAndStopBit,
+        address: proto::Address,
+        timeout: Duration,
+    ) -> Result<Self> {
+        proto::check_serial_settings(&baud_rate, &parity_and_stop_bit)?;
+        let (parity, stop_bits) = match parity_and_stop_bit {
+            proto::ParityAndStopBit::NoParityOneStopBit | proto::ParityAndStopBit::Unknown(_) => {
+                (serialport::Parity::None, serialport::StopBits::One)
+            }
+            proto::ParityAndStopBit::EvenParityOneStopBit => {
+                (serialport::Parity::Even, serialport::StopBits::One)
+            }
+            proto::ParityAndStopBit::OddParityOneStopBit => {
+                (serialport::Parity::Odd, serialport::StopBits::One)
+            }
+            proto::ParityAndStopBit::NoParityTwoStopBits => {
+                (serialport::Parity::None, serialport::StopBits::Two)
+            }
+        };
+        let port = serialport::new(device, u32::from(u16::from(&baud_rate)))
+            .parity(parity)
+            .stop_bits(stop_bits)
+            .data_bits(serialport::DataBits::Eight)
+            .flow_control(serialport::FlowControl::None)
+            .timeout(timeout)
+            .open()?;
+        let mut meter = Self::from_port(port, address);
+        meter.silent_interval = baud_rate.silent_interval();
+        Ok(meter)
+    }
+
+    /// Uses an already opened serial port, e.g. with settings which
+    /// [`open`](Self::open) doesn't support.
+    pub fn from_port(port: Box<dyn serialport::SerialPort>, address: proto::Address) -> Self {
+        Self {
+            port,
+            slave: address,
+            word_order: proto::WordOrder::default(),
+            silent_interval: proto::BaudRate::default().silent_interval(),
+            last_frame: None,
+        }
+    }
+
+    /// Sets the order of the two words of 32-bit values, see
+    /// [`WordOrder`](proto::WordOrder).
+    pub fn set_word_order(&mut self, word_order: proto::WordOrder) {
+        self.word_order = word_order;
+    }
+
+    /// Changes the address the requests are sent to, e.g. to poll several
+    /// meters on one serial bus with a single port.
+    pub fn set_slave(&mut self, address: proto::Address) {
+        self.slave = address;
+    }
+
+    /// The address the requests are sent to.
+    pub fn slave(&self) -> proto::Address {
+        self.slave
+    }
+
+    /// Sends a request after the silent interval of the baud rate has passed
+    /// since the previous frame, stale bytes of the port are discarded.
+    fn request<T>(
+        &mut self,
+        f: impl FnOnce(&mut dyn serialport::SerialPort, u8) -> Result<T>,
+    ) -> Result<T> {
+        if let Some(last_frame) = self.last_frame {
+            std::thread::sleep(self.silent_interval.saturating_sub(last_frame.elapsed()));
+        }
+        self.port.clear(serialport::ClearBuffer::Input)?;
+        let result = f(self.port.as_mut(), *self.slave);
+        self.last_frame = Some(Instant::now());
+        result
+    }
+
+    /// Reads raw words from Modbus input registers.
+    ///
+    /// This is an escape hatch for registers which are not covered by the
+    /// [`protocol`](crate::protocol) module, e.g. firmware specific ones.
+    pub fn read_raw_input(&mut self, address: u16, quantity: u16) -> Result<Vec<proto::Word>> {
+        self.request(|mut port, slave| {
+            read_registers(&mut port, slave, READ_INPUT_REGISTERS, address, quantity)
+        })
+    }
+
+    /// Reads raw words from Modbus holding registers.
+    ///
+    /// This is an escape hatch for registers which are not covered by the
+    /// [`protocol`](crate::protocol) module, e.g. firmware specific ones.
+    pub fn read_raw_holding(&mut self, address: u16, quantity: u16) -> Result<Vec<proto::Word>> {
+        self.request(|mut port, slave| {
+            read_registers(&mut port, slave, READ_HOLDING_REGISTERS, address, quantity)
+        })
+    }
+
+    /// Writes raw words to Modbus holding registers.
+    ///
+    /// This is an escape hatch for registers which are not covered by the
+    /// [`protocol`](crate::protocol) module, e.g. firmware specific ones.
+    pub fn write_raw_holding(&mut self, address: u16, words: &[proto::Word]) -> Result<()> {
+        self.request(|mut port, slave| write_registers(&mut port, slave, address, words))
+    }
+
+    read_holding!(system_type, SystemType);
+    write_holding!(system_type, SystemType);
+    read_holding!(pulse_width, PulseWidth);
+    write_holding!(pulse_width, PulseWidth);
+    read_holding!(kppa, KPPA);
+    /// Sets the Key Parameter Programming Authorization (KPPA).
+    ///
+    /// This is required to change settings on the meter.
+    pub fn set_kppa(&mut self, password: proto::Password) -> Result<()> {
+        let words = self
+            .word_order
+            .apply(proto::KPPA::encode_for_write_registers(password));
+        self.write_raw_holding(proto::KPPA::ADDRESS, &words)
+    }
+    read_holding!(parity_and_stop_bit, ParityAndStopBit);
+    write_holding!(parity_and_stop_bit, ParityAndStopBit);
+    read_holding!(address, Address);
+    write_holding!(address, Address);
+    read_holding!(pulse_constant, PulseConstant);
+    write_holding!(pulse_constant, PulseConstant);
+    read_holding!(password, Password);
+    write_holding!(password, Password);
+    read_holding!(baud_rate, BaudRate);
+    write_holding!(baud_rate, BaudRate);
+    read_holding!(auto_scroll_time, AutoScrollTime);
+    write_holding!(auto_scroll_time, AutoScrollTime);
+    read_holding!(backlight_time, BacklightTime);
+    write_holding!(backlight_time, BacklightTime);
+    read_holding!(pulse_energy_type, PulseEnergyType);
+    write_holding!(pulse_energy_type, PulseEnergyType);
+    read_holding!(serial_number, SerialNumber);
+    read_holding!(meter_code, MeterCode);
+    read_holding!(software_version, SoftwareVersion);
+
+    /// Reads the given measurements, the adjacent registers are read in a
+    /// single request. The values are in the order of the register addresses.
+    ///
+    /// The first generation SDM72D-M answers with an exception for the line
+    /// to line voltages, the neutral current and the import/export total
+    /// power, see `Firmware` of the `tokio` clients.
+    pub fn read_measurements(
+        &mut self,
+        measurements: &[proto::Measurement],
+    ) -> Result<Vec<proto::MeasurementValue>> {
+        let mut values = Vec::with_capacity(measurements.len());
+        for (address, quantity) in batches(measurements) {
+            let rsp = self.read_raw_input(address, quantity)?;
+            let mut in_batch = measurements
+                .iter()
+                .filter(|measurement| {
+                    (address..address + quantity).contains(&measurement.address())
+                })
+                .collect::<Vec<_>>();
+            in_batch.sort_by_key(|measurement| measurement.address());
+            for measurement in in_batch {
+                let start = usize::from(measurement.address() - address);
+                let words = rsp[start..start + usize::from(measurement.quantity())].to_vec();
+                values.push(measurement.decode(&self.word_order.apply(words))?);
+            }
+        }
+        Ok(values)
+    }
+
+    /// Reads all measurements, see [`read_measurements`](Self::read_measurements).
+    pub fn read_all(&mut self) -> Result<Vec<proto::MeasurementValue>> {
+        self.read_measurements(proto::Measurement::ALL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A serial port which records the requests and answers with the
+    /// prepared responses.
+    struct FakePort {
+        written: Vec<u8>,
+        responses: Cursor<Vec<u8>>,
+    }
+    impl Read for FakePort {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.responses.read(buf)
+        }
+    }
+    impl Write for FakePort {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    fn port(response: &[u8]) -> FakePort {
+        FakePort {
+            written: Vec::new(),
+            responses: Cursor::new(frame(response[0], response[1], &response[2..])),
+        }
+    }
+
+    #[test]
+    fn rtu_frames() {
+        assert_eq!(
+            frame(1, READ_INPUT_REGISTERS, &[0x00, 0x00, 0x00, 0x02]),
+            [0x01, 0x04, 0x00, 0x00, 0x00, 0x02, 0x71, 0xCB]
+        );
+
+        let mut fake = port(&[0x01, 0x04, 0x04, 0x43, 0x66, 0x00, 0x00]);
+        let words = read_registers(&mut fake, 1, READ_INPUT_REGISTERS, 0x0000, 2).unwrap();
+        assert_eq!(words, [0x4366, 0x0000]);
+        assert_eq!(
+            proto::Measurement::L1Voltage.decode(&words).unwrap().value,
+            230.0
+        );
+
+        let mut fake = port(&[0x01, 0x10, 0x00, 0x0A, 0x00, 0x02]);
+        write_registers(&mut fake, 1, 0x000A, &[0x4000, 0x0000]).unwrap();
+        assert_eq!(
+            fake.written[..7],
+            [0x01, 0x10, 0x00, 0x0A, 0x00, 0x02, 0x04]
+        );
+
+        let mut fake = port(&[0x01, 0x84, 0x02]);
+        assert!(matches!(
+            read_registers(&mut fake, 1, READ_INPUT_REGISTERS, 0x0500, 4),
+            Err(Error::Exception(0x02))
+        ));
+
+        let mut fake = port(&[0x02, 0x04, 0x02, 0x00, 0x00]);
+        assert!(matches!(
+            read_registers(&mut fake, 1, READ_INPUT_REGISTERS, 0x0000, 1),
+            Err(Error::MalformedFrame)
+        ));
+
+        let mut fake = port(&[0x01, 0x04, 0x02, 0x00, 0x00]);
+        let last = fake.responses.get_ref().len() - 1;
+        fake.responses.get_mut()[last] ^= 0xFF;
+        assert!(matches!(
+            read_registers(&mut fake, 1, READ_INPUT_REGISTERS, 0x0000, 1),
+            Err(Error::Crc)
+        ));
+    }
+
+    #[test]
+    fn measurement_batches() {
+        assert_eq!(
+            batches(&[
+                proto::Measurement::L1Current,
+                proto::Measurement::L1Voltage,
+                proto::Measurement::NetKwh,
+            ]),
+            [(0x0000, 8), (0x018C, 2)]
+        );
+        let all = batches(proto::Measurement::ALL);
+        assert!(all.iter().all(|(_, quantity)| *quantity <= MAX_QUANTITY));
+        assert_eq!(all.len(), 4);
+    }
+}
@@ -0,0 +1,130 @@
+use anyhow::{Context, Result};
+use sdm72_lib::{protocol as proto, tokio_common::AllSettings, tokio_sync_safe_client::SafeClient};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    #[serde(with = "humantime_serde")]
+    saved_at: SystemTime,
+    settings: AllSettings,
+}
+
+/// Caches the settings of meters on disk, one file per serial number, so a
+/// restarted daemon doesn't have to read the whole settings block again.
+#[derive(Debug)]
+pub struct SettingsCache {
+    dir: PathBuf,
+    ttl: Duration,
+    serial_number: Option<proto::SerialNumber>,
+}
+
+impl SettingsCache {
+    pub fn new(dir: PathBuf, ttl: Duration) -> Self {
+        Self {
+            dir,
+            ttl,
+            serial_number: None,
+        }
+    }
+
+    fn path(&self, serial_number: proto::SerialNumber) -> PathBuf {
+        self.dir.join(format!("{serial_number}.json"))
+    }
+
+    /// The serial number identifies the meter, it is read once.
+    fn serial_number(&mut self, client: &mut SafeClient) -> Result<proto::SerialNumber> {
+        if let Some(serial_number) = self.serial_number {
+            return Ok(serial_number);
+        }
+        let serial_number = client
+            .serial_number()
+            .with_context(|| "Cannot read serial number")?;
+        self.serial_number = Some(serial_number);
+        Ok(serial_number)
+    }
+
+    /// Returns the cached settings, if they are younger than the TTL.
+    fn load(&self, serial_number: proto::SerialNumber) -> Option<AllSettings> {
+        let file = std::fs::File::open(self.path(serial_number)).ok()?;
+        let entry: Entry = match serde_json::from_reader(file) {
+            Ok(entry) => entry,
+            Err(err) => {
+                log::warn!("Ignoring invalid settings cache of meter {serial_number}: {err}");
+                return None;
+            }
+        };
+        self.is_fresh(entry.saved_at, SystemTime::now())
+            .then_some(entry.settings)
+    }
+
+    /// Returns `true` if settings saved at `saved_at` are younger than the TTL.
+    ///
+    /// The age has to be measured with the system time, as the cache outlives
+    /// the process. Entries from the future, after the system time jumped
+    /// back, are expired, so they don't stay fresh until the clock catches up.
+    fn is_fresh(&self, saved_at: SystemTime, now: SystemTime) -> bool {
+        now.duration_since(saved_at).is_ok_and(|age| age < self.ttl)
+    }
+
+    fn store(&self, settings: &AllSettings) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let entry = Entry {
+            saved_at: SystemTime::now(),
+            settings: *settings,
+        };
+        std::fs::write(
+            self.path(settings.serial_number),
+            serde_json::to_vec_pretty(&entry)?,
+        )?;
+        Ok(())
+    }
+
+    /// Reads the settings of the meter, from the cache if possible.
+    pub fn read(&mut self, client: &mut SafeClient) -> Result<AllSettings> {
+        let serial_number = self.serial_number(client)?;
+        if let Some(settings) = self.load(serial_number) {
+            return Ok(settings);
+        }
+        let settings = client
+            .read_all_settings()
+            .with_context(|| "Cannot read all settings")?;
+        if let Err(err) = self.store(&settings) {
+            log::warn!("Cannot write settings cache to {:?}: {err:#}", self.dir);
+        }
+        Ok(settings)
+    }
+
+    /// Removes the cached settings, after a setting of the meter was changed.
+    pub fn invalidate(&mut self, client: &mut SafeClient) -> Result<()> {
+        let serial_number = self.serial_number(client)?;
+        let path = self.path(serial_number);
+        match std::fs::remove_file(&path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(anyhow::Error::new(err)
+                    .context(format!("Cannot remove settings cache {path:?}")))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_time_jumps() {
+        let cache = SettingsCache::new(PathBuf::new(), Duration::from_secs(3600));
+        let saved_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert!(cache.is_fresh(saved_at, saved_at));
+        assert!(cache.is_fresh(saved_at, saved_at + Duration::from_secs(3599)));
+        // The system time jumped forward past the TTL
+        assert!(!cache.is_fresh(saved_at, saved_at + Duration::from_secs(3600)));
+        // The system time jumped back before the entry was saved
+        assert!(!cache.is_fresh(saved_at, saved_at - Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,350 @@
+//! This module compares the current settings of a meter with a declarative set
+//! of desired settings.
+//!
+//! [`DesiredSettings`] can be loaded e.g. from a YAML file; every field is
+//! optional and only the given ones are compared. [`diff`] returns the
+//! [`SettingChange`]s which must be written to reach the desired state.
+//!
+//! The `SettingsSession` of the safe clients writes several settings with the
+//! authorization acquired once and returns a [`SettingsReport`].
+
+use crate::{
+    protocol::{self as proto, ModbusParam},
+    tokio_common::{AllSettings, Error},
+};
+use std::time::Duration;
+
+/// The default delay between two writes of a settings session, which gives
+/// the meter time to store the setting.
+pub const DEFAULT_WRITE_DELAY: Duration = Duration::from_millis(50);
+
+/// A single writable setting with its value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Setting {
+    SystemType(proto::SystemType),
+    PulseWidth(proto::PulseWidth),
+    PulseConstant(proto::PulseConstant),
+    PulseEnergyType(proto::PulseEnergyType),
+    Password(proto::Password),
+    AutoScrollTime(proto::AutoScrollTime),
+    BacklightTime(proto::BacklightTime),
+    ParityAndStopBit(proto::ParityAndStopBit),
+    BaudRate(proto::BaudRate),
+    Address(proto::Address),
+}
+impl Setting {
+    /// A human readable name of the setting.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SystemType(_) => "System type",
+            Self::PulseWidth(_) => "Pulse width",
+            Self::PulseConstant(_) => "Pulse constant",
+            Self::PulseEnergyType(_) => "Pulse energy type",
+            Self::Password(_) => "Password",
+            Self::AutoScrollTime(_) => "Auto scroll time",
+            Self::BacklightTime(_) => "Backlight time",
+            Self::ParityAndStopBit(_) => "Parity and stop bit",
+            Self::BaudRate(_) => "Baud rate",
+            Self::Address(_) => "Address",
+        }
+    }
+
+    /// Returns `true` for the settings which affect the communication, the
+    /// parity and stop bit, the baud rate and the address.
+    pub fn affects_link(&self) -> bool {
+        matches!(
+            self,
+            Self::ParityAndStopBit(_) | Self::BaudRate(_) | Self::Address(_)
+        )
+    }
+
+    /// The holding register address of the setting and its value encoded as
+    /// big endian words.
+    pub fn encode_for_write_registers(&self) -> (u16, Vec<proto::Word>) {
+        match self {
+            Self::SystemType(val) => (proto::SystemType::ADDRESS, val.encode_for_write_registers()),
+            Self::PulseWidth(val) => (proto::PulseWidth::ADDRESS, val.encode_for_write_registers()),
+            Self::PulseConstant(val) => (
+                proto::PulseConstant::ADDRESS,
+                val.encode_for_write_registers(),
+            ),
+            Self::PulseEnergyType(val) => (
+                proto::PulseEnergyType::ADDRESS,
+                val.encode_for_write_registers(),
+            ),
+            Self::Password(val) => (proto::Password::ADDRESS, val.encode_for_write_registers()),
+            Self::AutoScrollTime(val) => (
+                proto::AutoScrollTime::ADDRESS,
+                val.encode_for_write_registers(),
+            ),
+            Self::BacklightTime(val) => (
+                proto::BacklightTime::ADDRESS,
+                val.encode_for_write_registers(),
+            ),
+            Self::ParityAndStopBit(val) => (
+                proto::ParityAndStopBit::ADDRESS,
+                val.encode_for_write_registers(),
+            ),
+            Self::BaudRate(val) => (proto::BaudRate::ADDRESS, val.encode_for_write_registers()),
+            Self::Address(val) => (proto::Address::ADDRESS, val.encode_for_write_registers()),
+        }
+    }
+
+    /// The setting of the same kind in `settings`, e.g. the current value
+    /// before it is written.
+    pub fn current(&self, settings: &AllSettings) -> Self {
+        match self {
+            Self::SystemType(_) => Self::SystemType(settings.system_type),
+            Self::PulseWidth(_) => Self::PulseWidth(settings.pulse_width),
+            Self::PulseConstant(_) => Self::PulseConstant(settings.pulse_constant),
+            Self::PulseEnergyType(_) => Self::PulseEnergyType(settings.pulse_energy_type),
+            Self::Password(_) => Self::Password(settings.password),
+            Self::AutoScrollTime(_) => Self::AutoScrollTime(settings.auto_scroll_time),
+            Self::BacklightTime(_) => Self::BacklightTime(settings.backlight_time),
+            Self::ParityAndStopBit(_) => Self::ParityAndStopBit(settings.parity_and_stop_bit),
+            Self::BaudRate(_) => Self::BaudRate(settings.baud_rate),
+            Self::Address(_) => Self::Address(settings.address),
+        }
+    }
+
+    /// The position of the setting when several are written, like the
+    /// changes of [`diff`].
+    fn write_order(&self) -> u8 {
+        match self {
+            Self::SystemType(_) => 0,
+            Self::PulseConstant(_) => 1,
+            Self::PulseWidth(_) => 2,
+            Self::PulseEnergyType(_) => 3,
+            Self::Password(_) => 4,
+            Self::AutoScrollTime(_) => 5,
+            Self::BacklightTime(_) => 6,
+            Self::ParityAndStopBit(_) => 7,
+            Self::BaudRate(_) => 8,
+            Self::Address(_) => 9,
+        }
+    }
+}
+impl std::fmt::Display for Setting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SystemType(val) => write!(f, "{val}"),
+            Self::PulseWidth(val) => write!(f, "{val} ms"),
+            Self::PulseConstant(val) => write!(f, "{val}"),
+            Self::PulseEnergyType(val) => write!(f, "{val}"),
+            // Change reports end up in logs and terminals
+            Self::Password(_) => write!(f, "****"),
+            Self::AutoScrollTime(val) => write!(f, "{val}"),
+            Self::BacklightTime(val) => write!(f, "{val}"),
+            Self::ParityAndStopBit(val) => write!(f, "{val}"),
+            Self::BaudRate(val) => write!(f, "{val}"),
+            Self::Address(val) => write!(f, "{val}"),
+        }
+    }
+}
+
+/// A setting which differs between the current and the desired state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SettingChange {
+    pub from: Setting,
+    pub to: Setting,
+}
+impl std::fmt::Display for SettingChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} -> {}", self.to.name(), self.from, self.to)
+    }
+}
+
+/// The desired settings, settings which are `None` are left unchanged.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct DesiredSettings {
+    pub system_type: Option<proto::SystemType>,
+    pub pulse_width: Option<proto::PulseWidth>,
+    pub pulse_constant: Option<proto::PulseConstant>,
+    pub pulse_energy_type: Option<proto::PulseEnergyType>,
+    pub password: Option<proto::Password>,
+    pub auto_scroll_time: Option<proto::AutoScrollTime>,
+    pub backlight_time: Option<proto::BacklightTime>,
+    pub parity_and_stop_bit: Option<proto::ParityAndStopBit>,
+    pub baud_rate: Option<proto::BaudRate>,
+    pub address: Option<proto::Address>,
+}
+impl DesiredSettings {
+    /// Checks the value ranges, which are not enforced when deserializing.
+    ///
+    /// The [`Type3P3W`](proto::SystemType::Type3P3W) system type, which the
+    /// SDM72 doesn't support, is [`OutOfRange`](proto::Error::OutOfRange).
+    pub fn validate(&self) -> Result<(), proto::Error> {
+        if self.system_type == Some(proto::SystemType::Type3P3W) {
+            return Err(proto::Error::OutOfRange);
+        }
+        if let Some(address) = self.address {
+            proto::Address::try_from(*address)?;
+        }
+        if let Some(password) = self.password {
+            proto::Password::try_from(*password)?;
+        }
+        if let Some(auto_scroll_time) = self.auto_scroll_time {
+            proto::AutoScrollTime::try_from(*auto_scroll_time)?;
+        }
+        if let Some(proto::BacklightTime::Delayed(minutes)) = self.backlight_time {
+            proto::BacklightTime::try_from(minutes)?;
+        }
+        if let Some(pulse_width) = self.pulse_width {
+            proto::PulseWidth::try_from(*pulse_width)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the settings which depend on others against the `current`
+    /// settings, e.g. the pulse width which is fixed at a pulse constant of
+    /// 1000 imp/kWh.
+    pub fn validate_for(&self, current: &AllSettings) -> Result<(), proto::Error> {
+        self.validate()?;
+        if let Some(pulse_width) = self.pulse_width {
+            if pulse_width != current.pulse_width {
+                pulse_width
+                    .check_pulse_constant(self.pulse_constant.unwrap_or(current.pulse_constant))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the changes required to get from the `current` to the `desired` settings.
+///
+/// The changes are ordered so that the settings which affect the
+/// communication (parity and stop bit, baud rate and address) come last, and
+/// the pulse constant comes before the pulse width which depends on it.
+pub fn diff(current: &AllSettings, desired: &DesiredSettings) -> Vec<SettingChange> {
+    let mut changes = Vec::new();
+    macro_rules! compare {
+        ($field:ident, $variant:ident) => {
+            if let Some(to) = desired.$field {
+                if to != current.$field {
+                    changes.push(SettingChange {
+                        from: Setting::$variant(current.$field),
+                        to: Setting::$variant(to),
+                    });
+                }
+            }
+        };
+    }
+    compare!(system_type, SystemType);
+    compare!(pulse_constant, PulseConstant);
+    compare!(pulse_width, PulseWidth);
+    compare!(pulse_energy_type, PulseEnergyType);
+    compare!(password, Password);
+    compare!(auto_scroll_time, AutoScrollTime);
+    compare!(backlight_time, BacklightTime);
+    compare!(parity_and_stop_bit, ParityAndStopBit);
+    compare!(baud_rate, BaudRate);
+    compare!(address, Address);
+    changes
+}
+
+/// Sorts settings into the order of [`diff`], as written by a settings
+/// session.
+pub fn sort_for_write(settings: &mut [Setting]) {
+    settings.sort_by_key(Setting::write_order);
+}
+
+/// The outcome of a write of a settings session.
+#[derive(Debug)]
+pub enum WriteOutcome {
+    Written,
+    Failed(Error),
+    /// Not written because an earlier write failed.
+    Skipped,
+}
+
+/// The outcome of every setting of a settings session, in the order they
+/// were written.
+#[derive(Debug, Default)]
+pub struct SettingsReport {
+    pub outcomes: Vec<(Setting, WriteOutcome)>,
+}
+impl SettingsReport {
+    /// Returns `true` if all settings were written.
+    pub fn is_ok(&self) -> bool {
+        self.outcomes
+            .iter()
+            .all(|(_, outcome)| matches!(outcome, WriteOutcome::Written))
+    }
+
+    /// The setting which failed with its error, the following ones were skipped.
+    pub fn failure(&self) -> Option<(Setting, &Error)> {
+        self.outcomes
+            .iter()
+            .find_map(|(setting, outcome)| match outcome {
+                WriteOutcome::Failed(err) => Some((*setting, err)),
+                _ => None,
+            })
+    }
+
+    /// Returns the error of the failed setting, if any.
+    pub fn into_result(self) -> Result<(), Error> {
+        self.outcomes
+            .into_iter()
+            .find_map(|(_, outcome)| match outcome {
+                WriteOutcome::Failed(err) => Some(err),
+                _ => None,
+            })
+            .map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_report() {
+        let mut settings = [
+            Setting::Address(proto::Address::try_from(2).unwrap()),
+            Setting::PulseWidth(proto::PulseWidth::try_from(60).unwrap()),
+            Setting::BaudRate(proto::BaudRate::B19200),
+            Setting::PulseConstant(proto::PulseConstant::PC1000),
+        ];
+        sort_for_write(&mut settings);
+        assert_eq!(
+            settings.map(|setting| setting.name()),
+            ["Pulse constant", "Pulse width", "Baud rate", "Address"]
+        );
+
+        let report = SettingsReport {
+            outcomes: vec![
+                (settings[0], WriteOutcome::Written),
+                (settings[1], WriteOutcome::Failed(Error::Unauthorized)),
+                (settings[2], WriteOutcome::Skipped),
+            ],
+        };
+        assert!(!report.is_ok());
+        assert_eq!(report.failure().unwrap().0, settings[1]);
+        assert!(matches!(report.into_result(), Err(Error::Unauthorized)));
+        assert!(SettingsReport::default().is_ok());
+
+        let change = SettingChange {
+            from: Setting::Password(proto::Password::try_from(1000).unwrap()),
+            to: Setting::Password(proto::Password::try_from(1234).unwrap()),
+        };
+        assert_eq!(change.to_string(), "Password: **** -> ****");
+    }
+
+    #[test]
+    fn validate_system_type() {
+        let mut desired = DesiredSettings {
+            system_type: Some(proto::SystemType::Type1P2W),
+            ..Default::default()
+        };
+        assert!(desired.validate().is_ok());
+        desired.system_type = Some(proto::SystemType::Type3P3W);
+        assert!(matches!(desired.validate(), Err(proto::Error::OutOfRange)));
+    }
+}
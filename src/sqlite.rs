@@ -0,0 +1,161 @@
+use crate::{events, run_limit::RunLimit};
+use anyhow::{Context, Result};
+use rusqlite::{params_from_iter, types::Value, Connection};
+use sdm72_lib::{protocol::Measurement, schedule::Ticker, tokio_common::SystemClock};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Retention limits of the `measurements` table, applied after every insert.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Retention {
+    pub max_rows: Option<u64>,
+    pub max_age: Option<Duration>,
+}
+
+fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// A measurement value as SQL value, `NULL` if it is not finite.
+fn sql_value(value: f32) -> Value {
+    if value.is_finite() {
+        Value::Real(value as f64)
+    } else {
+        Value::Null
+    }
+}
+
+fn open_database(db_path: &str) -> Result<Connection> {
+    log::debug!("Opening SQLite database {db_path:?}");
+    let conn = Connection::open(db_path)
+        .with_context(|| format!("Cannot open SQLite database {db_path:?}"))?;
+    let columns = Measurement::ALL
+        .iter()
+        // NULL for values which are not finite, e.g. a NaN of a meter updating its registers
+        .map(|measurement| format!("\"{}\" REAL", measurement.name()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS measurements (\
+            id INTEGER PRIMARY KEY AUTOINCREMENT, \
+            timestamp INTEGER NOT NULL, \
+            serial_number INTEGER NOT NULL, \
+            {columns});\
+         CREATE INDEX IF NOT EXISTS measurements_timestamp ON measurements (timestamp);"
+    ))
+    .with_context(|| "Cannot create the measurements table")?;
+    Ok(conn)
+}
+
+fn apply_retention(conn: &Connection, retention: &Retention, now: SystemTime) -> Result<()> {
+    // A maximum age beyond the epoch expires nothing
+    if let Some(oldest) = retention
+        .max_age
+        .and_then(|max_age| now.checked_sub(max_age))
+    {
+        let oldest = unix_millis(oldest);
+        conn.execute("DELETE FROM measurements WHERE timestamp < ?1", [oldest])
+            .with_context(|| "Cannot delete expired measurements")?;
+    }
+    if let Some(max_rows) = retention.max_rows {
+        conn.execute(
+            "DELETE FROM measurements WHERE id NOT IN \
+             (SELECT id FROM measurements ORDER BY id DESC LIMIT ?1)",
+            [max_rows as i64],
+        )
+        .with_context(|| "Cannot delete surplus measurements")?;
+    }
+    Ok(())
+}
+
+pub fn run_sqlite_daemon(
+    client: &mut sdm72_lib::tokio_sync_safe_client::SafeClient,
+    delay: &Duration,
+    poll_interval: &Duration,
+    db_path: &str,
+    retention: &Retention,
+    limit: &mut RunLimit,
+) -> Result<()> {
+    let conn = open_database(db_path)?;
+    let serial_number = client
+        .serial_number()
+        .with_context(|| "Cannot read the serial number")?;
+
+    let columns = Measurement::ALL
+        .iter()
+        .map(|measurement| format!("\"{}\"", measurement.name()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let placeholders = (1..=Measurement::ALL.len() + 2)
+        .map(|i| format!("?{i}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let insert = format!(
+        "INSERT INTO measurements (timestamp, serial_number, {columns}) VALUES ({placeholders})"
+    );
+
+    let mut ticker = Ticker::new(*delay.max(poll_interval), Arc::new(SystemClock));
+    loop {
+        let values = events::read_all(client)?;
+        let now = SystemTime::now();
+
+        let row = [
+            Value::Integer(unix_millis(now)),
+            Value::Integer(*serial_number as i64),
+        ]
+        .into_iter()
+        .chain(
+            Measurement::ALL
+                .iter()
+                .map(|measurement| sql_value(values.value(*measurement))),
+        );
+        conn.execute(&insert, params_from_iter(row))
+            .with_context(|| "Cannot insert measurement")?;
+        apply_retention(&conn, retention, now)?;
+
+        if !limit.next_poll(true, &mut ticker) {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn not_finite_values_and_huge_max_age() {
+        let conn = open_database(":memory:").unwrap();
+        let columns = Measurement::ALL
+            .iter()
+            .map(|measurement| format!("\"{}\"", measurement.name()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let values = Measurement::ALL
+            .iter()
+            .map(|_| sql_value(f32::NAN))
+            .collect::<Vec<_>>();
+        conn.execute(
+            &format!(
+                "INSERT INTO measurements (timestamp, serial_number, {columns}) VALUES (1, 2, {})",
+                vec!["?"; values.len()].join(", ")
+            ),
+            params_from_iter(values),
+        )
+        .unwrap();
+
+        let retention = Retention {
+            max_rows: None,
+            max_age: Some(Duration::MAX),
+        };
+        apply_retention(&conn, &retention, SystemTime::now()).unwrap();
+        let rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM measurements", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 1);
+    }
+}
@@ -0,0 +1,433 @@
+//! This module computes sliding-window statistics of polled values, like the
+//! demand values which larger Eastron meters provide in hardware.
+//!
+//! A [`SlidingWindow`] keeps the samples of a single value within its
+//! duration. The [`Demand`] engine keeps a window per measurement and window
+//! duration, by default the 1 and 15 minute windows of the total power and the
+//! phase currents.
+//!
+//! [`SmoothedValues`] smooths noisy measurements like the phase currents of
+//! cheap current transformers with a moving average or a median.
+//!
+//! The average is the mean of the samples, so the poll interval should be
+//! constant for it to match the demand of a meter.
+//!
+//! # Example
+//!
+//! ```
+//! use sdm72_lib::stats::SlidingWindow;
+//! use std::time::{Duration, Instant};
+//!
+//! let mut window = SlidingWindow::new(Duration::from_secs(60));
+//! let start = Instant::now();
+//! window.push(100.0, start);
+//! window.push(300.0, start + Duration::from_secs(30));
+//! assert_eq!(window.stats().unwrap().average, 200.0);
+//! // The first sample falls out of the window
+//! window.push(500.0, start + Duration::from_secs(61));
+//! assert_eq!(window.stats().unwrap().min, 300.0);
+//! ```
+
+use crate::{
+    protocol as proto,
+    tokio_common::{AllValues, Error, LinkError},
+};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// The measurements of the default [`Demand`].
+pub const DEMAND_MEASUREMENTS: [proto::Measurement; 4] = [
+    proto::Measurement::TotalPower,
+    proto::Measurement::L1Current,
+    proto::Measurement::L2Current,
+    proto::Measurement::L3Current,
+];
+
+/// The window durations of the default [`Demand`].
+pub const DEMAND_WINDOWS: [Duration; 2] = [Duration::from_secs(60), Duration::from_secs(15 * 60)];
+
+/// The statistics of the samples in a window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowStats {
+    pub average: f32,
+    pub min: f32,
+    pub max: f32,
+    /// The number of samples in the window.
+    pub samples: usize,
+}
+
+/// The samples of a single value within a duration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlidingWindow {
+    duration: Duration,
+    samples: VecDeque<(Instant, f32)>,
+}
+
+impl SlidingWindow {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// The duration of the window.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Adds a sample and removes the samples older than the duration of the
+    /// window, `NaN` values are ignored.
+    pub fn push(&mut self, value: f32, now: Instant) {
+        if !value.is_nan() {
+            self.samples.push_back((now, value));
+        }
+        while let Some((time, _)) = self.samples.front() {
+            if now.saturating_duration_since(*time) < self.duration {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// Returns the statistics of the window, `None` if it has no samples.
+    pub fn stats(&self) -> Option<WindowStats> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let (sum, min, max) = self.samples.iter().fold(
+            (0.0f64, f32::INFINITY, f32::NEG_INFINITY),
+            |(sum, min, max), (_, value)| {
+                (sum + f64::from(*value), min.min(*value), max.max(*value))
+            },
+        );
+        Some(WindowStats {
+            average: (sum / self.samples.len() as f64) as f32,
+            min,
+            max,
+            samples: self.samples.len(),
+        })
+    }
+}
+
+/// The statistics of a measurement in a window of a [`Demand`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DemandValue {
+    pub measurement: proto::Measurement,
+    pub window: Duration,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub stats: WindowStats,
+}
+
+/// Sliding windows of several measurements, updated with every read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Demand {
+    windows: Vec<(proto::Measurement, SlidingWindow)>,
+}
+
+impl Default for Demand {
+    /// The 1 and 15 minute windows of the total power and the phase currents.
+    fn default() -> Self {
+        Self::new(&DEMAND_MEASUREMENTS, &DEMAND_WINDOWS)
+    }
+}
+
+impl Demand {
+    /// Creates a window of every duration for every measurement.
+    pub fn new(measurements: &[proto::Measurement], durations: &[Duration]) -> Self {
+        Self {
+            windows: measurements
+                .iter()
+                .flat_map(|measurement| {
+                    durations
+                        .iter()
+                        .map(|duration| (*measurement, SlidingWindow::new(*duration)))
+                })
+                .collect(),
+        }
+    }
+
+    /// Adds the values of a read to the windows.
+    pub fn update(&mut self, values: &AllValues, now: Instant) {
+        for (measurement, window) in &mut self.windows {
+            window.push(values.value(*measurement), now);
+        }
+    }
+
+    /// Returns the statistics of all windows with samples.
+    pub fn values(&self) -> Vec<DemandValue> {
+        self.windows
+            .iter()
+            .filter_map(|(measurement, window)| {
+                window.stats().map(|stats| DemandValue {
+                    measurement: *measurement,
+                    window: window.duration(),
+                    stats,
+                })
+            })
+            .collect()
+    }
+}
+
+/// How the values of a measurement are smoothed, see [`Smoother`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Smoothing {
+    /// The exponential moving average with the weight of the new value, from
+    /// 0 (exclusive) to 1. Small weights smooth more but follow changes slower.
+    Ema(f32),
+    /// The median of the last N values, which ignores single outliers.
+    Median(usize),
+}
+
+impl Smoothing {
+    /// Checks the weight of the moving average and the number of values of
+    /// the median.
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            Self::Ema(weight) if !(weight > 0.0 && weight <= 1.0) => Err(format!(
+                "The weight {weight} of the moving average must be greater than 0 and at most 1"
+            )),
+            Self::Median(0) => Err("The median needs at least one value".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl std::str::FromStr for Smoothing {
+    type Err = String;
+
+    /// Parses `ema:<WEIGHT>` or `median:<N>`, e.g. `ema:0.2` or `median:5`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let smoothing = match s.split_once(':') {
+            Some(("ema", weight)) => Self::Ema(weight.parse().map_err(|e| format!("{e}"))?),
+            Some(("median", values)) => Self::Median(values.parse().map_err(|e| format!("{e}"))?),
+            _ => {
+                return Err(format!(
+                    "Invalid smoothing {s:?}, expected ema:<WEIGHT> or median:<N>"
+                ))
+            }
+        };
+        smoothing.validate()?;
+        Ok(smoothing)
+    }
+}
+
+/// Smooths the values of a single measurement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Smoother {
+    smoothing: Smoothing,
+    average: Option<f32>,
+    /// The last values for the median.
+    values: VecDeque<f32>,
+}
+
+impl Smoother {
+    pub fn new(smoothing: Smoothing) -> Self {
+        Self {
+            smoothing,
+            average: None,
+            values: VecDeque::new(),
+        }
+    }
+
+    /// Adds a value and returns the smoothed value, `NaN` values are ignored.
+    ///
+    /// Returns `None` until the first value which is not `NaN`.
+    pub fn push(&mut self, value: f32) -> Option<f32> {
+        match self.smoothing {
+            Smoothing::Ema(weight) => {
+                if !value.is_nan() {
+                    self.average = Some(match self.average {
+                        Some(average) => average + weight * (value - average),
+                        None => value,
+                    });
+                }
+                self.average
+            }
+            Smoothing::Median(count) => {
+                if !value.is_nan() {
+                    if self.values.len() == count {
+                        self.values.pop_front();
+                    }
+                    self.values.push_back(value);
+                }
+                let mut sorted: Vec<f32> = self.values.iter().copied().collect();
+                sorted.sort_by(f32::total_cmp);
+                let middle = sorted.len() / 2;
+                match sorted.len() {
+                    0 => None,
+                    len if len % 2 == 0 => Some((sorted[middle - 1] + sorted[middle]) / 2.0),
+                    _ => Some(sorted[middle]),
+                }
+            }
+        }
+    }
+}
+
+/// The smoothers of several measurements, updated with every read.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SmoothedValues {
+    smoothers: Vec<(proto::Measurement, Smoother)>,
+    last: Vec<proto::MeasurementValue>,
+}
+
+impl SmoothedValues {
+    pub fn new(smoothings: &[(proto::Measurement, Smoothing)]) -> Self {
+        Self {
+            smoothers: smoothings
+                .iter()
+                .map(|(measurement, smoothing)| (*measurement, Smoother::new(*smoothing)))
+                .collect(),
+            last: Vec::new(),
+        }
+    }
+
+    /// Returns `true` if no measurement is smoothed.
+    pub fn is_empty(&self) -> bool {
+        self.smoothers.is_empty()
+    }
+
+    /// Adds the values of a read to the smoothers.
+    pub fn update(&mut self, values: &AllValues) {
+        self.last = self
+            .smoothers
+            .iter_mut()
+            .filter_map(|(measurement, smoother)| {
+                smoother
+                    .push(values.value(*measurement))
+                    .map(|value| proto::MeasurementValue {
+                        measurement: *measurement,
+                        value,
+                    })
+            })
+            .collect();
+    }
+
+    /// The smoothed values after the last update.
+    pub fn values(&self) -> &[proto::MeasurementValue] {
+        &self.last
+    }
+}
+
+/// Counts the outcomes of the requests on the link to the meter, so wiring
+/// problems (CRC errors and malformed frames) can be told apart from a slow
+/// or absent meter (timeouts).
+///
+/// Replayed requests after a reconnection are counted separately.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkStats {
+    pub requests: u64,
+    pub timeouts: u64,
+    pub crc_errors: u64,
+    pub malformed_frames: u64,
+    /// The exception responses, which are valid frames.
+    pub exceptions: u64,
+    /// Other transport errors, see [`LinkError::Transport`].
+    pub transport_errors: u64,
+}
+
+impl LinkStats {
+    /// Counts the result of a request.
+    pub fn record<T>(&mut self, result: &Result<T, Error>) {
+        self.requests += 1;
+        let Err(err) = result else {
+            return;
+        };
+        let counter = match err.link_error() {
+            Some(LinkError::Timeout) => &mut self.timeouts,
+            Some(LinkError::Crc) => &mut self.crc_errors,
+            Some(LinkError::MalformedFrame) => &mut self.malformed_frames,
+            Some(LinkError::Transport) => &mut self.transport_errors,
+            None if matches!(err, Error::ModbusException(_)) => &mut self.exceptions,
+            None => return,
+        };
+        *counter += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sliding_window() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut window = SlidingWindow::new(Duration::from_secs(10));
+        assert_eq!(window.stats(), None);
+        window.push(1.0, at(0));
+        window.push(f32::NAN, at(1));
+        window.push(5.0, at(2));
+        window.push(3.0, at(9));
+        assert_eq!(
+            window.stats(),
+            Some(WindowStats {
+                average: 3.0,
+                min: 1.0,
+                max: 5.0,
+                samples: 3,
+            })
+        );
+        window.push(f32::NAN, at(12));
+        assert_eq!(window.stats().unwrap().samples, 1);
+        window.push(4.0, at(30));
+        assert_eq!(window.stats().unwrap().max, 4.0);
+    }
+
+    #[test]
+    fn smoothing() {
+        let smoothed = |smoothing: &str, values: &[f32]| -> Vec<Option<f32>> {
+            let mut smoother = Smoother::new(smoothing.parse().unwrap());
+            values.iter().map(|value| smoother.push(*value)).collect()
+        };
+        assert_eq!(
+            smoothed("ema:0.5", &[f32::NAN, 10.0, 20.0, f32::NAN, 5.0]),
+            [None, Some(10.0), Some(15.0), Some(15.0), Some(10.0)]
+        );
+        assert_eq!(
+            smoothed("median:3", &[1.0, 100.0, 2.0, 3.0, 4.0]),
+            [Some(1.0), Some(50.5), Some(2.0), Some(3.0), Some(3.0)]
+        );
+        assert!("ema:0".parse::<Smoothing>().is_err());
+        assert!("median:0".parse::<Smoothing>().is_err());
+        assert!("mean:3".parse::<Smoothing>().is_err());
+    }
+
+    #[test]
+    fn link_stats() {
+        let transport = |kind, msg: &str| -> Result<(), Error> {
+            Err(Error::Modbus(tokio_modbus::Error::Transport(
+                std::io::Error::new(kind, msg.to_string()),
+            )))
+        };
+        let mut stats = LinkStats::default();
+        stats.record(&Ok(()));
+        stats.record(&transport(std::io::ErrorKind::TimedOut, "timeout"));
+        stats.record(&transport(
+            std::io::ErrorKind::InvalidData,
+            "Invalid CRC: expected = 0x1234, actual = 0x4321",
+        ));
+        stats.record(&transport(std::io::ErrorKind::InvalidData, "Invalid frame"));
+        stats.record(&Err::<(), _>(Error::ModbusException(
+            crate::modbus::ExceptionCode::IllegalDataAddress,
+        )));
+        assert_eq!(
+            stats,
+            LinkStats {
+                requests: 5,
+                timeouts: 1,
+                crc_errors: 1,
+                malformed_frames: 1,
+                exceptions: 1,
+                transport_errors: 0,
+            }
+        );
+    }
+}
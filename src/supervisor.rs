@@ -0,0 +1,125 @@
+use crate::commandline::PanicBehavior;
+use anyhow::Result;
+use log::*;
+use std::{
+    ops::Deref,
+    panic::{self, AssertUnwindSafe},
+    time::Duration,
+};
+
+/// Installs a panic hook which logs the panic and, if `abort` is set, aborts
+/// the whole process so a service manager like systemd can restart it.
+pub fn install_panic_hook(abort: bool) {
+    panic::set_hook(Box::new(move |panic_info| {
+        let (filename, line, column) = panic_info
+            .location()
+            .map(|loc| (loc.file(), loc.line(), loc.column()))
+            .unwrap_or(("<unknown>", 0, 0));
+        let cause = panic_info
+            .payload()
+            .downcast_ref::<String>()
+            .map(String::deref);
+        let cause = cause.unwrap_or_else(|| {
+            panic_info
+                .payload()
+                .downcast_ref::<&str>()
+                .copied()
+                .unwrap_or("<cause unknown>")
+        });
+
+        error!(
+            "Thread '{}' panicked at {}:{}:{}: {}",
+            std::thread::current().name().unwrap_or("<unknown>"),
+            filename,
+            line,
+            column,
+            cause
+        );
+        if abort {
+            std::process::abort();
+        }
+    }));
+}
+
+/// Runs a daemon worker, with [`PanicBehavior::Restart`] the worker is
+/// restarted after `restart_delay` if it panics.
+///
+/// Errors returned by the worker are never retried.
+pub fn supervise<F>(
+    behavior: Option<PanicBehavior>,
+    restart_delay: Duration,
+    mut worker: F,
+) -> Result<()>
+where
+    F: FnMut() -> Result<()>,
+{
+    loop {
+        match panic::catch_unwind(AssertUnwindSafe(&mut worker)) {
+            Ok(result) => return result,
+            Err(_) if behavior == Some(PanicBehavior::Restart) => {
+                warn!("Worker panicked, restarting in {restart_delay:?}");
+                std::thread::sleep(restart_delay);
+            }
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABORT_CHILD: &str = "SDM72_TEST_ABORT_CHILD";
+
+    #[test]
+    fn restart_panicking_worker() {
+        let mut runs = 0;
+        supervise(Some(PanicBehavior::Restart), Duration::ZERO, || {
+            runs += 1;
+            if runs < 3 {
+                panic!("sink failed");
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(runs, 3);
+    }
+
+    #[test]
+    fn errors_are_not_restarted() {
+        let mut runs = 0;
+        let result = supervise(Some(PanicBehavior::Restart), Duration::ZERO, || {
+            runs += 1;
+            anyhow::bail!("sink failed")
+        });
+        assert!(result.is_err());
+        assert_eq!(runs, 1);
+    }
+
+    /// Runs in a child process started by `abort_on_panic`.
+    #[test]
+    #[ignore]
+    fn abort_child() {
+        if std::env::var_os(ABORT_CHILD).is_none() {
+            return;
+        }
+        install_panic_hook(true);
+        let _ = supervise(Some(PanicBehavior::Abort), Duration::ZERO, || {
+            panic!("sink failed")
+        });
+    }
+
+    #[test]
+    fn abort_on_panic() {
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "supervisor::tests::abort_child", "--ignored"])
+            .env(ABORT_CHILD, "1")
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(!status.success());
+        // A panic which is not aborted fails the test with exit code 101
+        assert_ne!(status.code(), Some(101));
+    }
+}
@@ -0,0 +1,132 @@
+//! This module decides when to switch a load, e.g. a heat pump or a wallbox,
+//! on or off to consume the surplus of exported power.
+//!
+//! The [`SurplusSwitch`] switches on at a surplus of at least `on_surplus` and
+//! off below `off_surplus`. The gap between both thresholds is the hysteresis,
+//! which must be larger than the consumption of the load because switching it
+//! on reduces the surplus by it. The minimum on and off times protect loads
+//! like compressors from short cycling.
+//!
+//! # Example
+//!
+//! ```
+//! use sdm72_lib::surplus::{SurplusConfig, SurplusSwitch};
+//! use std::time::{Duration, Instant};
+//!
+//! let mut switch = SurplusSwitch::new(SurplusConfig {
+//!     on_surplus: 1500.0,
+//!     off_surplus: -200.0,
+//!     min_on_time: Duration::from_secs(600),
+//!     min_off_time: Duration::from_secs(300),
+//! });
+//! let start = Instant::now();
+//! assert_eq!(switch.update(2000.0, start), Some(true));
+//! // The load consumes 1.8 kW, but must run for at least 10 minutes
+//! assert_eq!(switch.update(-300.0, start + Duration::from_secs(60)), None);
+//! assert_eq!(switch.update(-300.0, start + Duration::from_secs(600)), Some(false));
+//! ```
+
+use crate::tokio_common::AllValues;
+use std::time::{Duration, Instant};
+
+/// Returns the surplus of exported power in W, the SDM72 reports exported
+/// power as negative total power.
+pub fn surplus_power(values: &AllValues) -> f32 {
+    -*values.total_power
+}
+
+/// The thresholds and minimum times of a [`SurplusSwitch`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurplusConfig {
+    /// The surplus in W at which the load is switched on.
+    pub on_surplus: f32,
+    /// The surplus in W below which the load is switched off, it must be less
+    /// than `on_surplus` and is negative if the load may draw from the grid.
+    pub off_surplus: f32,
+    /// The minimum time the load runs once it was switched on.
+    pub min_on_time: Duration,
+    /// The minimum time the load stays off once it was switched off.
+    pub min_off_time: Duration,
+}
+
+/// Switches a load by the surplus of exported power, with hysteresis and
+/// minimum on and off times.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurplusSwitch {
+    config: SurplusConfig,
+    on: bool,
+    /// The time of the last switch, `None` before the first one.
+    switched_at: Option<Instant>,
+}
+
+impl SurplusSwitch {
+    /// Creates a switch in the off state, which may switch on with the first
+    /// update.
+    pub fn new(config: SurplusConfig) -> Self {
+        Self {
+            config,
+            on: false,
+            switched_at: None,
+        }
+    }
+
+    /// The thresholds and minimum times of the switch.
+    pub fn config(&self) -> SurplusConfig {
+        self.config
+    }
+
+    /// Returns `true` if the load is switched on.
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    /// Updates the switch with the current surplus in W, see [`surplus_power`].
+    ///
+    /// Returns the new state if the load must be switched, `NaN` values are
+    /// ignored.
+    pub fn update(&mut self, surplus: f32, now: Instant) -> Option<bool> {
+        if surplus.is_nan() {
+            return None;
+        }
+        let (threshold_crossed, min_time) = if self.on {
+            (surplus < self.config.off_surplus, self.config.min_on_time)
+        } else {
+            (surplus >= self.config.on_surplus, self.config.min_off_time)
+        };
+        let min_time_elapsed = self
+            .switched_at
+            .is_none_or(|switched_at| now.saturating_duration_since(switched_at) >= min_time);
+        if threshold_crossed && min_time_elapsed {
+            self.on = !self.on;
+            self.switched_at = Some(now);
+            Some(self.on)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_off_time() {
+        let mut switch = SurplusSwitch::new(SurplusConfig {
+            on_surplus: 1000.0,
+            off_surplus: 0.0,
+            min_on_time: Duration::ZERO,
+            min_off_time: Duration::from_secs(60),
+        });
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        assert_eq!(switch.update(500.0, at(0)), None);
+        assert_eq!(switch.update(f32::NAN, at(1)), None);
+        assert_eq!(switch.update(1000.0, at(2)), Some(true));
+        assert_eq!(switch.update(0.0, at(3)), None);
+        assert_eq!(switch.update(-1.0, at(4)), Some(false));
+        assert_eq!(switch.update(2000.0, at(30)), None);
+        assert_eq!(switch.update(2000.0, at(64)), Some(true));
+        assert!(switch.is_on());
+    }
+}
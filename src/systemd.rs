@@ -0,0 +1,78 @@
+//! The notifications of the daemon to systemd, so it can run as a service of
+//! `Type=notify` which systemd restarts if it hangs (`WatchdogSec=`).
+//!
+//! The daemon reports `READY=1` after the first successful poll, `WATCHDOG=1`
+//! in every loop and the last values or the last error as `STATUS=`. Without
+//! the `systemd` feature, or if the daemon wasn't started by systemd, the
+//! notifications are ignored.
+
+use crate::events;
+use sdm72_lib::tokio_common::AllValues;
+use std::sync::Once;
+
+static READY: Once = Once::new();
+
+#[cfg(feature = "systemd")]
+fn notify(ready: bool, status: Option<&str>) {
+    use sd_notify::NotifyState;
+    let mut states = vec![NotifyState::Watchdog];
+    if ready {
+        states.push(NotifyState::Ready);
+    }
+    if let Some(status) = status {
+        states.push(NotifyState::Status(status));
+    }
+    if let Err(err) = sd_notify::notify(false, &states) {
+        log::debug!("Cannot notify systemd: {err}");
+    }
+}
+
+#[cfg(not(feature = "systemd"))]
+fn notify(_ready: bool, _status: Option<&str>) {}
+
+/// Prefixes the status with the meter of the current thread, if the daemon
+/// configuration file has several.
+fn with_meter(status: String) -> String {
+    match events::meter() {
+        Some(meter) => format!("[{meter}] {status}"),
+        None => status,
+    }
+}
+
+fn values_status(values: &AllValues) -> String {
+    format!(
+        "Total power {:.1} W, import {:.2} kWh, export {:.2} kWh",
+        *values.total_power, *values.import_energy_active, *values.export_energy_active
+    )
+}
+
+/// Returns `true` only for the first call.
+fn first_ready() -> bool {
+    let mut first = false;
+    READY.call_once(|| first = true);
+    first
+}
+
+/// Reports a successful poll, the first one also reports the daemon as ready.
+pub fn poll_succeeded(values: &AllValues) {
+    notify(first_ready(), Some(&with_meter(values_status(values))));
+}
+
+/// Reports a failed poll, the daemon itself is still alive.
+pub fn poll_failed(err: &impl std::fmt::Display) {
+    notify(false, Some(&with_meter(format!("Poll failed: {err}"))));
+}
+
+/// Reports the daemon as ready if it wasn't yet, e.g. after a successful
+/// connection probe of the REST API, which doesn't poll.
+pub fn ready() {
+    if first_ready() {
+        notify(true, None);
+    }
+}
+
+/// Reports that the loop of a daemon which doesn't poll, like the REST API,
+/// is alive.
+pub fn watchdog() {
+    notify(false, None);
+}
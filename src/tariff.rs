@@ -0,0 +1,337 @@
+//! Accumulates the imported and exported energy and its cost per tariff band,
+//! e.g. for time-of-use tariffs with peak and off-peak prices.
+//!
+//! The energy between two reads is counted in the band which is active at the
+//! time of the later read, in local time. The counters are stored in the state
+//! file after every update, so a restarted daemon continues counting.
+//!
+//! The energy is the difference of the energy counters of the meter, so a
+//! local time which repeats, at the end of daylight saving time or after a
+//! step back of the system time, never counts the same energy twice.
+
+use crate::config_file;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use sdm72_lib::tokio_common::AllValues;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf};
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Day {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Day {
+    const ALL: [Day; 7] = [
+        Day::Mon,
+        Day::Tue,
+        Day::Wed,
+        Day::Thu,
+        Day::Fri,
+        Day::Sat,
+        Day::Sun,
+    ];
+
+    fn previous(self) -> Self {
+        Self::ALL[(self as usize + 6) % 7]
+    }
+}
+
+impl From<chrono::Weekday> for Day {
+    fn from(weekday: chrono::Weekday) -> Self {
+        Self::ALL[weekday.num_days_from_monday() as usize]
+    }
+}
+
+/// A time of day in minutes since midnight, written as `HH:MM`.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(try_from = "String")]
+struct TimeOfDay(u16);
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("Invalid time of day {value:?}, expected HH:MM");
+        let (hours, minutes) = value.split_once(':').ok_or_else(invalid)?;
+        let hours: u16 = hours.parse().map_err(|_| invalid())?;
+        let minutes: u16 = minutes.parse().map_err(|_| invalid())?;
+        // 24:00 is allowed as the end of a period
+        if minutes >= 60 || hours * 60 + minutes > 24 * 60 {
+            return Err(invalid());
+        }
+        Ok(Self(hours * 60 + minutes))
+    }
+}
+
+/// A recurring period of a band, the period from `start` to `end` wraps
+/// around midnight if `end` is before `start`.
+#[derive(Debug, Deserialize, Clone)]
+struct Period {
+    /// The days the period starts on, every day if empty.
+    #[serde(default)]
+    days: Vec<Day>,
+    start: TimeOfDay,
+    end: TimeOfDay,
+}
+
+impl Period {
+    fn starts_on(&self, day: Day) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    fn contains(&self, day: Day, time: TimeOfDay) -> bool {
+        if self.start < self.end {
+            self.starts_on(day) && self.start <= time && time < self.end
+        } else {
+            (self.starts_on(day) && time >= self.start)
+                || (self.starts_on(day.previous()) && time < self.end)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Band {
+    pub name: String,
+    /// The price per imported kWh.
+    pub import_price: f64,
+    /// The price paid per exported kWh.
+    #[serde(default)]
+    pub export_price: f64,
+    /// The periods the band is active, a band without periods is active
+    /// whenever no other band is.
+    #[serde(default)]
+    periods: Vec<Period>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TariffConfig {
+    /// The file the counters are stored in.
+    pub state_file: PathBuf,
+    /// The bands in order of precedence.
+    bands: Vec<Band>,
+}
+
+impl TariffConfig {
+    pub fn load(config_file_path: &str) -> Result<Self> {
+        log::debug!("Loading tariff file from {config_file_path:?}");
+        config_file::load(config_file_path).with_context(|| "Cannot read tariff")
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.bands.is_empty() {
+            bail!("The tariff has no bands");
+        }
+        for (index, band) in self.bands.iter().enumerate() {
+            if self.bands[..index]
+                .iter()
+                .any(|other| other.name == band.name)
+            {
+                bail!("The band {:?} is defined twice", band.name);
+            }
+            if !band.import_price.is_finite() || !band.export_price.is_finite() {
+                bail!("Invalid price of band {:?}", band.name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the band active at the local time.
+    pub fn band_at(&self, time: NaiveDateTime) -> Option<&Band> {
+        let day = Day::from(time.weekday());
+        let time_of_day = TimeOfDay((time.hour() * 60 + time.minute()) as u16);
+        self.bands
+            .iter()
+            .find(|band| {
+                band.periods
+                    .iter()
+                    .any(|period| period.contains(day, time_of_day))
+            })
+            .or_else(|| self.bands.iter().find(|band| band.periods.is_empty()))
+    }
+}
+
+/// The accumulated energy and cost of a band.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct BandCounters {
+    pub import_kwh: f64,
+    pub export_kwh: f64,
+    /// The cost of the imported minus the revenue of the exported energy.
+    pub cost: f64,
+}
+
+/// The persisted state of a [`Tariff`].
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct State {
+    /// The energy counters of the meter at the last update.
+    last_import_kwh: Option<f64>,
+    last_export_kwh: Option<f64>,
+    bands: BTreeMap<String, BandCounters>,
+}
+
+/// Counts the energy of successive reads per tariff band.
+#[derive(Debug)]
+pub struct Tariff {
+    config: TariffConfig,
+    state: State,
+}
+
+impl Tariff {
+    /// Continues with the counters of the state file, if it exists.
+    pub fn open(config: TariffConfig) -> Result<Self> {
+        config.validate()?;
+        let state = match std::fs::read(&config.state_file) {
+            Ok(content) => serde_json::from_slice(&content)
+                .with_context(|| format!("Invalid tariff state file {:?}", config.state_file))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(err) => {
+                return Err(anyhow!(err).context(format!(
+                    "Cannot read tariff state file {:?}",
+                    config.state_file
+                )))
+            }
+        };
+        Ok(Self { config, state })
+    }
+
+    /// The counters by band name.
+    pub fn counters(&self) -> &BTreeMap<String, BandCounters> {
+        &self.state.bands
+    }
+
+    /// Counts the energy since the last update in the band active at `time`.
+    ///
+    /// Decreasing energy counters, e.g. after a reset of the meter, only
+    /// update the reference.
+    fn update(&mut self, import_kwh: f64, export_kwh: f64, time: NaiveDateTime) {
+        let delta =
+            |last: Option<f64>, current: f64| last.map_or(0.0, |last| current - last).max(0.0);
+        let imported = delta(self.state.last_import_kwh, import_kwh);
+        let exported = delta(self.state.last_export_kwh, export_kwh);
+        self.state.last_import_kwh = Some(import_kwh);
+        self.state.last_export_kwh = Some(export_kwh);
+        if imported == 0.0 && exported == 0.0 {
+            return;
+        }
+        let Some(band) = self.config.band_at(time) else {
+            log::debug!("No tariff band active at {time}, the energy is not counted");
+            return;
+        };
+        let counters = self.state.bands.entry(band.name.clone()).or_default();
+        counters.import_kwh += imported;
+        counters.export_kwh += exported;
+        counters.cost += imported * band.import_price - exported * band.export_price;
+    }
+
+    /// Counts the energy of the values read now and stores the counters.
+    pub fn record(&mut self, values: &AllValues) -> Result<()> {
+        self.update(
+            f64::from(*values.import_energy_active),
+            f64::from(*values.export_energy_active),
+            chrono::Local::now().naive_local(),
+        );
+        let path = &self.config.state_file;
+        // Replace the file atomically, so a crash doesn't lose the counters
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&self.state)?)
+            .and_then(|()| std::fs::rename(&tmp, path))
+            .with_context(|| format!("Cannot write tariff state file {path:?}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulate_per_band() {
+        let config: TariffConfig = config_file::parse(
+            config_file::Format::Yaml,
+            r#"
+state_file: tariff.json
+bands:
+  - name: night
+    import_price: 0.2
+    periods:
+      - start: "22:00"
+        end: "06:00"
+  - name: day
+    import_price: 0.3
+    export_price: 0.1
+"#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let mut tariff = Tariff {
+            config,
+            state: State::default(),
+        };
+        let at = |day, hour| {
+            chrono::NaiveDate::from_ymd_opt(2024, 1, day)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+        };
+        let mut read = |import, export, time| tariff.update(import, export, time);
+        read(100.0, 10.0, at(1, 12));
+        read(110.0, 12.0, at(1, 18));
+        read(120.0, 12.0, at(2, 3));
+        // A reset of the meter
+        read(0.0, 0.0, at(2, 4));
+
+        let day = &tariff.counters()["day"];
+        assert_eq!(day.import_kwh, 10.0);
+        assert_eq!(day.export_kwh, 2.0);
+        assert!((day.cost - 2.8).abs() < 1e-9);
+        let night = &tariff.counters()["night"];
+        assert_eq!(night.import_kwh, 10.0);
+        assert!((night.cost - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn repeated_local_time() {
+        let config: TariffConfig = config_file::parse(
+            config_file::Format::Yaml,
+            r#"
+state_file: tariff.json
+bands:
+  - name: night
+    import_price: 0.2
+    periods:
+      - start: "00:00"
+        end: "06:00"
+  - name: day
+    import_price: 0.3
+"#,
+        )
+        .unwrap();
+        let mut tariff = Tariff {
+            config,
+            state: State::default(),
+        };
+        let at = |hour, minute| {
+            chrono::NaiveDate::from_ymd_opt(2024, 10, 27)
+                .unwrap()
+                .and_hms_opt(hour, minute, 0)
+                .unwrap()
+        };
+        tariff.update(100.0, 0.0, at(1, 50));
+        tariff.update(101.0, 0.0, at(2, 30));
+        // The end of daylight saving time repeats 02:00 to 03:00
+        tariff.update(102.0, 0.0, at(2, 10));
+        tariff.update(103.0, 0.0, at(2, 50));
+        // A step back of the system time into the day band
+        tariff.update(104.0, 0.0, at(6, 30));
+        tariff.update(105.0, 0.0, at(6, 10));
+
+        assert_eq!(tariff.counters()["night"].import_kwh, 3.0);
+        assert_eq!(tariff.counters()["day"].import_kwh, 2.0);
+    }
+}
@@ -0,0 +1,515 @@
+//! This module provides a [`MockClient`] to unit-test applications without a
+//! meter or a Modbus connection.
+//!
+//! The mock has the same methods as the safe clients, but serves them from an
+//! in-memory register map. Faults like timeouts or exception responses can be
+//! scripted with [`MockClient::inject_fault`]. [`MockClock`] replaces the
+//! [`Clock`] of the synchronous safe client to test pacing, reconnection
+//! backoff and scheduling without waiting.
+//!
+//! # Example
+//!
+//! ```
+//! use sdm72_lib::{
+//!     protocol::{Measurement, SystemType},
+//!     testing::{Fault, MockClient},
+//! };
+//!
+//! let mut client = MockClient::new().with_measurement(Measurement::L1Voltage, 230.0);
+//! assert_eq!(*client.read_all().unwrap().l1_voltage, 230.0);
+//!
+//! client.inject_fault(Fault::Timeout);
+//! assert!(client.read_all().is_err());
+//!
+//! client.set_system_type(SystemType::Type1P2W).unwrap();
+//! assert_eq!(client.system_type().unwrap(), SystemType::Type1P2W);
+//! ```
+
+use crate::{
+    modbus::ExceptionCode,
+    protocol::{self as proto, ModbusParam},
+    settings_diff::Setting,
+    tokio_common::{
+        AllSettings, AllValues, Clock, Error, MeterIdentity, Result, ScalingConfig, ScalingProfile,
+        Timestamp, TimestampedAllValues, ValueScaling,
+    },
+};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A fault returned instead of the response of the next request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The request times out.
+    Timeout,
+    /// The connection is lost.
+    Disconnected,
+    /// The meter answers with an exception response.
+    Exception(ExceptionCode),
+}
+impl From<Fault> for Error {
+    fn from(fault: Fault) -> Self {
+        let transport = |kind, msg| {
+            Error::Modbus(tokio_modbus::Error::Transport(std::io::Error::new(
+                kind, msg,
+            )))
+        };
+        match fault {
+            Fault::Timeout => transport(std::io::ErrorKind::TimedOut, "injected timeout"),
+            Fault::Disconnected => transport(std::io::ErrorKind::BrokenPipe, "injected disconnect"),
+            Fault::Exception(code) => Error::ModbusException(code),
+        }
+    }
+}
+
+/// A [`Clock`] which only advances when slept on or advanced explicitly.
+///
+/// ```
+/// use sdm72_lib::{testing::MockClock, tokio_common::Clock};
+/// use std::time::Duration;
+///
+/// let clock = MockClock::new();
+/// let start = clock.now();
+/// clock.sleep(Duration::from_secs(30));
+/// assert_eq!(clock.now() - start, Duration::from_secs(30));
+/// assert_eq!(clock.sleeps(), vec![Duration::from_secs(30)]);
+/// ```
+#[derive(Debug)]
+pub struct MockClock {
+    start: Instant,
+    elapsed: Mutex<Duration>,
+    sleeps: Mutex<Vec<Duration>>,
+}
+impl MockClock {
+    /// Creates a clock, which starts at the current instant.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            elapsed: Mutex::default(),
+            sleeps: Mutex::default(),
+        }
+    }
+
+    /// Advances the clock without recording a sleep.
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+
+    /// Returns all sleeps in the order they were made.
+    pub fn sleeps(&self) -> Vec<Duration> {
+        self.sleeps.lock().unwrap().clone()
+    }
+}
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + *self.elapsed.lock().unwrap()
+    }
+    fn sleep(&self, duration: Duration) {
+        self.sleeps.lock().unwrap().push(duration);
+        self.advance(duration);
+    }
+}
+
+fn f32_to_words(val: f32) -> Vec<proto::Word> {
+    let bytes = val.to_be_bytes();
+    vec![
+        u16::from_be_bytes([bytes[0], bytes[1]]),
+        u16::from_be_bytes([bytes[2], bytes[3]]),
+    ]
+}
+
+/// An in-memory meter with the methods of the safe clients.
+///
+/// Registers which were never set read as zero.
+#[derive(Debug, Default, Clone)]
+pub struct MockClient {
+    holding: BTreeMap<u16, proto::Word>,
+    input: BTreeMap<u16, proto::Word>,
+    faults: VecDeque<Fault>,
+    writes: Vec<(u16, Vec<proto::Word>)>,
+    scaling: ValueScaling,
+}
+
+macro_rules! read_holding {
+    ($func_name:ident, $ty:ident) => {
+        paste::item! {
+            #[doc = "Reads the [`proto::" $ty "`] value from the holding register map."]
+            pub fn $func_name(&mut self) -> Result<proto::$ty> {
+                let words = self.read_raw_holding(<proto::$ty>::ADDRESS, <proto::$ty>::QUANTITY)?;
+                Ok(<proto::$ty>::decode_from_holding_registers(&words)?)
+            }
+        }
+    };
+}
+
+macro_rules! write_holding {
+    ($func_name:ident, $ty:ident) => {
+        paste::item! {
+            #[doc = "Writes the [`proto::" $ty "`] value to the holding register map."]
+            pub fn [< set_ $func_name >](&mut self, value: proto::$ty) -> Result<()> {
+                self.write_raw_holding(<proto::$ty>::ADDRESS, &value.encode_for_write_registers())
+            }
+        }
+    };
+}
+
+macro_rules! read_input {
+    ($self:ident, $ty:ident) => {{
+        let words = $self.read_registers(false, proto::$ty::ADDRESS, proto::$ty::QUANTITY)?;
+        proto::$ty::decode_from_input_register(&words)?
+    }};
+}
+
+impl MockClient {
+    /// Creates a mock with an empty register map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fills the holding registers with the given settings.
+    pub fn with_settings(mut self, settings: &AllSettings) -> Self {
+        let mut set = |address, words: Vec<proto::Word>| self.set_holding(address, &words);
+        set(
+            proto::SystemType::ADDRESS,
+            settings.system_type.encode_for_write_registers(),
+        );
+        set(
+            proto::PulseWidth::ADDRESS,
+            settings.pulse_width.encode_for_write_registers(),
+        );
+        let kppa = match settings.kppa {
+            proto::KPPA::NotAuthorized => 0.0,
+            proto::KPPA::Authorized => 1.0,
+            proto::KPPA::Unknown(val) => val,
+        };
+        set(proto::KPPA::ADDRESS, f32_to_words(kppa));
+        set(
+            proto::ParityAndStopBit::ADDRESS,
+            settings.parity_and_stop_bit.encode_for_write_registers(),
+        );
+        set(
+            proto::Address::ADDRESS,
+            settings.address.encode_for_write_registers(),
+        );
+        set(
+            proto::PulseConstant::ADDRESS,
+            settings.pulse_constant.encode_for_write_registers(),
+        );
+        set(
+            proto::Password::ADDRESS,
+            settings.password.encode_for_write_registers(),
+        );
+        set(
+            proto::BaudRate::ADDRESS,
+            settings.baud_rate.encode_for_write_registers(),
+        );
+        set(
+            proto::AutoScrollTime::ADDRESS,
+            settings.auto_scroll_time.encode_for_write_registers(),
+        );
+        set(
+            proto::BacklightTime::ADDRESS,
+            settings.backlight_time.encode_for_write_registers(),
+        );
+        set(
+            proto::PulseEnergyType::ADDRESS,
+            settings.pulse_energy_type.encode_for_write_registers(),
+        );
+        let serial_number = settings.serial_number.to_be_bytes();
+        set(
+            proto::SerialNumber::ADDRESS,
+            vec![
+                u16::from_be_bytes([serial_number[0], serial_number[1]]),
+                u16::from_be_bytes([serial_number[2], serial_number[3]]),
+            ],
+        );
+        set(proto::MeterCode::ADDRESS, vec![*settings.meter_code]);
+        set(
+            proto::SoftwareVersion::ADDRESS,
+            vec![*settings.software_version],
+        );
+        self
+    }
+
+    /// Fills the input registers with the given measurement values.
+    pub fn with_values(mut self, values: &AllValues) -> Self {
+        for measurement in proto::Measurement::ALL {
+            self.set_measurement(*measurement, values.value(*measurement));
+        }
+        self
+    }
+
+    /// Sets the input registers of a single measurement.
+    pub fn with_measurement(mut self, measurement: proto::Measurement, value: f32) -> Self {
+        self.set_measurement(measurement, value);
+        self
+    }
+
+    /// Sets the input registers of a single measurement.
+    pub fn set_measurement(&mut self, measurement: proto::Measurement, value: f32) {
+        for (address, word) in (measurement.address()..).zip(f32_to_words(value)) {
+            self.input.insert(address, word);
+        }
+    }
+
+    /// Sets the ratios of external current and voltage transformers, applied
+    /// to the values returned by [`read_all`](Self::read_all) like by the safe
+    /// clients.
+    pub fn set_scaling_profile(&mut self, profile: Option<ScalingProfile>) {
+        self.scaling.profile = profile;
+    }
+
+    /// Sets the per-measurement calibrations, applied after the [scaling
+    /// profile](Self::set_scaling_profile).
+    pub fn set_scaling_config(&mut self, config: Option<ScalingConfig>) {
+        self.scaling.calibration = config.filter(|config| !config.is_empty()).map(Arc::new);
+    }
+
+    /// Sets the decimals of the serialized and displayed values returned by
+    /// [`read_all`](Self::read_all).
+    pub fn set_precision(&mut self, precision: proto::Precision) {
+        self.scaling.precision = precision;
+    }
+
+    /// Sets raw words of the holding register map, without recording a write.
+    pub fn set_holding(&mut self, address: u16, words: &[proto::Word]) {
+        for (address, word) in (address..).zip(words.iter().copied()) {
+            self.holding.insert(address, word);
+        }
+    }
+
+    /// Queues a fault, which is returned instead of the response of the next
+    /// request. Multiple faults are returned in the order they were injected.
+    pub fn inject_fault(&mut self, fault: Fault) {
+        self.faults.push_back(fault);
+    }
+
+    /// Returns all writes as start address and words, in the order they were made.
+    pub fn writes(&self) -> &[(u16, Vec<proto::Word>)] {
+        &self.writes
+    }
+
+    fn next_fault(&mut self) -> Result<()> {
+        match self.faults.pop_front() {
+            Some(fault) => Err(fault.into()),
+            None => Ok(()),
+        }
+    }
+
+    fn read_registers(
+        &mut self,
+        holding: bool,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<proto::Word>> {
+        self.next_fault()?;
+        let map = if holding { &self.holding } else { &self.input };
+        Ok((address..address.saturating_add(quantity))
+            .map(|address| map.get(&address).copied().unwrap_or_default())
+            .collect())
+    }
+
+    read_holding!(system_type, SystemType);
+    write_holding!(system_type, SystemType);
+    read_holding!(pulse_width, PulseWidth);
+    write_holding!(pulse_width, PulseWidth);
+    read_holding!(kppa, KPPA);
+
+    /// Sets the Key Parameter Programming Authorization (KPPA).
+    ///
+    /// The KPPA is granted if the password matches the password register.
+    pub fn set_kppa(&mut self, password: proto::Password) -> Result<()> {
+        self.write_raw_holding(
+            proto::KPPA::ADDRESS,
+            &proto::KPPA::encode_for_write_registers(password),
+        )?;
+        let authorized = self.password().is_ok_and(|current| current == password);
+        self.set_holding(
+            proto::KPPA::ADDRESS,
+            &f32_to_words(if authorized { 1.0 } else { 0.0 }),
+        );
+        Ok(())
+    }
+
+    read_holding!(parity_and_stop_bit, ParityAndStopBit);
+    write_holding!(parity_and_stop_bit, ParityAndStopBit);
+    read_holding!(address, Address);
+    write_holding!(address, Address);
+    read_holding!(pulse_constant, PulseConstant);
+    write_holding!(pulse_constant, PulseConstant);
+    read_holding!(password, Password);
+    write_holding!(password, Password);
+    read_holding!(baud_rate, BaudRate);
+    write_holding!(baud_rate, BaudRate);
+    read_holding!(auto_scroll_time, AutoScrollTime);
+    write_holding!(auto_scroll_time, AutoScrollTime);
+    read_holding!(backlight_time, BacklightTime);
+    write_holding!(backlight_time, BacklightTime);
+    read_holding!(pulse_energy_type, PulseEnergyType);
+    write_holding!(pulse_energy_type, PulseEnergyType);
+
+    /// Resets the resettable energy counters.
+    pub fn reset_historical_data(&mut self) -> Result<()> {
+        self.reset(proto::ResetTarget::ResettableEnergy)
+    }
+
+    /// Resets the data of the target, the mock has no demand values.
+    pub fn reset(&mut self, target: proto::ResetTarget) -> Result<()> {
+        self.write_raw_holding(
+            proto::ResetHistoricalData::ADDRESS,
+            &target.encode_for_write_registers(),
+        )?;
+        if target != proto::ResetTarget::ResettableEnergy {
+            return Ok(());
+        }
+        for measurement in [
+            proto::Measurement::ResettableTotalEnergyActive,
+            proto::Measurement::ResettableTotalEnergyReactive,
+            proto::Measurement::ResettableImportEnergyActive,
+            proto::Measurement::ResettableExportEnergyActive,
+        ] {
+            self.set_measurement(measurement, 0.0);
+        }
+        Ok(())
+    }
+
+    /// Writes a single [`Setting`].
+    pub fn write_setting(&mut self, setting: Setting) -> Result<()> {
+        match setting {
+            Setting::SystemType(val) => self.set_system_type(val),
+            Setting::PulseWidth(val) => self.set_pulse_width(val),
+            Setting::PulseConstant(val) => self.set_pulse_constant(val),
+            Setting::PulseEnergyType(val) => self.set_pulse_energy_type(val),
+            Setting::Password(val) => self.set_password(val),
+            Setting::AutoScrollTime(val) => self.set_auto_scroll_time(val),
+            Setting::BacklightTime(val) => self.set_backlight_time(val),
+            Setting::ParityAndStopBit(val) => self.set_parity_and_stop_bit(val),
+            Setting::BaudRate(val) => self.set_baud_rate(val),
+            Setting::Address(val) => self.set_address(val),
+        }
+    }
+
+    read_holding!(serial_number, SerialNumber);
+    read_holding!(meter_code, MeterCode);
+    read_holding!(software_version, SoftwareVersion);
+
+    /// Reads raw words from the input register map.
+    pub fn read_raw_input(&mut self, address: u16, quantity: u16) -> Result<Vec<proto::Word>> {
+        self.read_registers(false, address, quantity)
+    }
+
+    /// Reads raw words from the holding register map.
+    pub fn read_raw_holding(&mut self, address: u16, quantity: u16) -> Result<Vec<proto::Word>> {
+        self.read_registers(true, address, quantity)
+    }
+
+    /// Writes raw words to the holding register map and records the write.
+    pub fn write_raw_holding(&mut self, address: u16, words: &[proto::Word]) -> Result<()> {
+        self.next_fault()?;
+        self.writes.push((address, words.to_vec()));
+        self.set_holding(address, words);
+        Ok(())
+    }
+
+    /// Reads the identity of the meter from the holding register map.
+    pub fn identify(&mut self) -> Result<MeterIdentity> {
+        let meter_code = self.meter_code()?;
+        Ok(MeterIdentity {
+            serial_number: self.serial_number()?,
+            meter_code,
+            software_version: self.software_version()?,
+            model: meter_code.model(),
+        })
+    }
+
+    /// Reads all settings from the holding register map.
+    pub fn read_all_settings(&mut self) -> Result<AllSettings> {
+        Ok(AllSettings {
+            system_type: self.system_type()?,
+            pulse_width: self.pulse_width()?,
+            kppa: self.kppa()?,
+            parity_and_stop_bit: self.parity_and_stop_bit()?,
+            address: self.address()?,
+            pulse_constant: self.pulse_constant()?,
+            password: self.password()?,
+            baud_rate: self.baud_rate()?,
+            auto_scroll_time: self.auto_scroll_time()?,
+            backlight_time: self.backlight_time()?,
+            pulse_energy_type: self.pulse_energy_type()?,
+            serial_number: self.serial_number()?,
+            meter_code: self.meter_code()?,
+            software_version: self.software_version()?,
+        })
+    }
+
+    /// Reads all measurement values from the input register map, scaled like
+    /// by the safe clients.
+    pub fn read_all(&mut self) -> Result<AllValues> {
+        let values = AllValues {
+            l1_voltage: read_input!(self, L1Voltage),
+            l2_voltage: read_input!(self, L2Voltage),
+            l3_voltage: read_input!(self, L3Voltage),
+            l1_current: read_input!(self, L1Current),
+            l2_current: read_input!(self, L2Current),
+            l3_current: read_input!(self, L3Current),
+            l1_power_active: read_input!(self, L1PowerActive),
+            l2_power_active: read_input!(self, L2PowerActive),
+            l3_power_active: read_input!(self, L3PowerActive),
+            l1_power_apparent: read_input!(self, L1PowerApparent),
+            l2_power_apparent: read_input!(self, L2PowerApparent),
+            l3_power_apparent: read_input!(self, L3PowerApparent),
+            l1_power_reactive: read_input!(self, L1PowerReactive),
+            l2_power_reactive: read_input!(self, L2PowerReactive),
+            l3_power_reactive: read_input!(self, L3PowerReactive),
+            l1_power_factor: read_input!(self, L1PowerFactor),
+            l2_power_factor: read_input!(self, L2PowerFactor),
+            l3_power_factor: read_input!(self, L3PowerFactor),
+            ln_average_voltage: read_input!(self, LtoNAverageVoltage),
+            ln_average_current: read_input!(self, LtoNAverageCurrent),
+            total_line_current: read_input!(self, TotalLineCurrent),
+            total_power: read_input!(self, TotalPower),
+            total_power_apparent: read_input!(self, TotalPowerApparent),
+            total_power_reactive: read_input!(self, TotalPowerReactive),
+            total_power_factor: read_input!(self, TotalPowerFactor),
+            frequency: read_input!(self, Frequency),
+            import_energy_active: read_input!(self, ImportEnergyActive),
+            export_energy_active: read_input!(self, ExportEnergyActive),
+            l1l2_voltage: read_input!(self, L1ToL2Voltage),
+            l2l3_voltage: read_input!(self, L2ToL3Voltage),
+            l3l1_voltage: read_input!(self, L3ToL1Voltage),
+            ll_average_voltage: read_input!(self, LtoLAverageVoltage),
+            neutral_current: read_input!(self, NeutralCurrent),
+            total_energy_active: read_input!(self, TotalEnergyActive),
+            total_energy_reactive: read_input!(self, TotalEnergyReactive),
+            resettable_total_energy_active: read_input!(self, ResettableTotalEnergyActive),
+            resettable_total_energy_reactive: read_input!(self, ResettableTotalEnergyReactive),
+            resettable_import_energy_active: read_input!(self, ResettableImportEnergyActive),
+            resettable_export_energy_active: read_input!(self, ResettableExportEnergyActive),
+            net_kwh: read_input!(self, NetKwh),
+            import_total_energy_active: read_input!(self, ImportTotalPowerActive),
+            export_total_energy_active: read_input!(self, ExportTotalPowerActive),
+            scaling: None,
+            precision: proto::Precision::default(),
+        };
+        Ok(self.scaling.apply(values))
+    }
+
+    /// Reads all measurement values with the times the read started and
+    /// completed, which are the same for the mock.
+    pub fn read_all_timestamped(&mut self) -> Result<TimestampedAllValues> {
+        let now = Timestamp::now();
+        Ok(TimestampedAllValues {
+            read_started: now,
+            read_completed: now,
+            values: self.read_all()?,
+        })
+    }
+}
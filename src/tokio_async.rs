@@ -34,10 +34,16 @@
 //! ```
 
 use crate::{
+    modbus::Slave,
     protocol::{self as proto, ModbusParam},
-    tokio_common::{AllSettings, AllValues, Result},
+    recording::{FrameLog, Recording, ReplayTransport},
+    settings_diff::Setting,
+    tokio_common::{
+        self, AllSettings, AllValues, AllValuesPartial, ConnectionParams, Error, Firmware,
+        MeterIdentity, Result,
+    },
 };
-use tokio_modbus::prelude::{Reader, Writer};
+use tokio_modbus::prelude::{Reader, SlaveContext, Writer};
 
 /// An asynchronous client for the SDM72 energy meter.
 ///
@@ -52,9 +58,14 @@ macro_rules! read_holding {
         paste::item! {
             #[doc = "Reads the [`proto::" $ty "`] value from the Modbus holding register."]
             pub async fn $func_name(ctx: &mut tokio_modbus::client::Context) -> Result<proto::$ty> {
+                Self::[< $func_name _with_word_order >](ctx, proto::WordOrder::BigEndian).await
+            }
+
+            #[doc = "Reads the [`proto::" $ty "`] value from the Modbus holding register with the given [`WordOrder`](proto::WordOrder)."]
+            pub async fn [< $func_name _with_word_order >](ctx: &mut tokio_modbus::client::Context, word_order: proto::WordOrder) -> Result<proto::$ty> {
                 let rsp = ctx
                     .read_holding_registers(<proto::$ty>::ADDRESS, <proto::$ty>::QUANTITY).await??;
-                Ok(<proto::$ty>::decode_from_holding_registers(&rsp)?)
+                Ok(<proto::$ty>::decode_from_holding_registers(&word_order.apply(rsp))?)
             }
         }
     };
@@ -66,16 +77,205 @@ macro_rules! write_holding {
         paste::item! {
             #[doc = "Writes the [`proto::" $ty "`] value to the Modbus holding register."]
             pub async fn [< set_ $func_name >](ctx: &mut tokio_modbus::client::Context, value: proto::$ty) -> Result<()> {
+                Self::[< set_ $func_name _with_word_order >](ctx, value, proto::WordOrder::BigEndian).await
+            }
+
+            #[doc = "Writes the [`proto::" $ty "`] value to the Modbus holding register with the given [`WordOrder`](proto::WordOrder)."]
+            pub async fn [< set_ $func_name _with_word_order >](ctx: &mut tokio_modbus::client::Context, value: proto::$ty, word_order: proto::WordOrder) -> Result<()> {
                 Ok(ctx.write_multiple_registers(
                     <proto::$ty>::ADDRESS,
-                    &value.encode_for_write_registers(),
+                    &word_order.apply(value.encode_for_write_registers()),
                 ).await??)
             }
         }
     };
 }
 
+/// Opens a TCP stream with the given socket options.
+#[cfg(feature = "tokio-tcp")]
+async fn connect_tcp_stream(
+    socket_addr: std::net::SocketAddr,
+    options: &crate::tokio_common::TcpOptions,
+) -> std::io::Result<tokio::net::TcpStream> {
+    let socket = if socket_addr.is_ipv4() {
+        tokio::net::TcpSocket::new_v4()?
+    } else {
+        tokio::net::TcpSocket::new_v6()?
+    };
+    if let Some(bind_addr) = options.bind_addr {
+        socket.bind(std::net::SocketAddr::new(bind_addr, 0))?;
+    }
+    if let Some(keepalive) = options.keepalive {
+        let params = socket2::TcpKeepalive::new().with_time(keepalive.time);
+        #[cfg(any(
+            target_os = "android",
+            target_os = "freebsd",
+            target_os = "ios",
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "netbsd",
+            target_os = "windows"
+        ))]
+        let params = params.with_interval(keepalive.interval);
+        socket2::SockRef::from(&socket).set_tcp_keepalive(&params)?;
+    }
+    socket.connect(socket_addr).await
+}
+
+/// Attaches a Modbus context to a transport, which is wrapped in a
+/// [`RecordingTransport`] if there is a `log`.
+macro_rules! attach {
+    ($attach:path, $transport:expr, $slave:expr, $log:expr) => {
+        match $log {
+            Some(log) => $attach(
+                $crate::recording::RecordingTransport::new($transport, log.clone()),
+                $slave,
+            ),
+            None => $attach($transport, $slave),
+        }
+    };
+}
+
 impl SDM72 {
+    /// Opens a new asynchronous Modbus context for the given connection parameters.
+    pub async fn connect(params: &ConnectionParams) -> Result<tokio_modbus::client::Context> {
+        Self::open(params, None).await
+    }
+
+    /// Opens a new asynchronous Modbus context like [`connect`](Self::connect),
+    /// which writes every raw request and response to `log`, see the
+    /// [`recording`](crate::recording) module.
+    pub async fn connect_recording(
+        params: &ConnectionParams,
+        log: FrameLog,
+    ) -> Result<tokio_modbus::client::Context> {
+        Self::open(params, Some(&log)).await
+    }
+
+    /// Opens a Modbus context which answers the requests with the responses
+    /// of a recorded session instead of a meter, e.g. to reproduce a decode
+    /// bug. The framing and the slave are the ones of `params`, which must be
+    /// the connection of the recording.
+    pub fn replay(
+        params: &ConnectionParams,
+        recording: Recording,
+    ) -> Result<tokio_modbus::client::Context> {
+        let transport = ReplayTransport::new(recording);
+        let ctx = match params {
+            #[cfg(feature = "tokio-tcp")]
+            ConnectionParams::Tcp { slave, .. } => Ok(tokio_modbus::client::tcp::attach_slave(
+                transport,
+                (*slave).into(),
+            )),
+            #[cfg(feature = "tokio-tcp-tls")]
+            ConnectionParams::TcpTls { slave, .. } => Ok(tokio_modbus::client::tcp::attach_slave(
+                transport,
+                (*slave).into(),
+            )),
+            #[cfg(feature = "tokio-rtu")]
+            ConnectionParams::Rtu { slave, .. } | ConnectionParams::RtuOverTcp { slave, .. } => Ok(
+                tokio_modbus::client::rtu::attach_slave(transport, (*slave).into()),
+            ),
+            #[allow(unreachable_patterns)]
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("Connection type is not enabled: {params}"),
+            )),
+        };
+        Ok(ctx.map_err(tokio_modbus::Error::Transport)?)
+    }
+
+    async fn open(
+        params: &ConnectionParams,
+        log: Option<&FrameLog>,
+    ) -> Result<tokio_modbus::client::Context> {
+        let ctx = match params {
+            #[cfg(feature = "tokio-tcp")]
+            ConnectionParams::Tcp {
+                socket_addr,
+                slave,
+                options,
+            } => connect_tcp_stream(*socket_addr, options)
+                .await
+                .map(|stream| {
+                    attach!(
+                        tokio_modbus::client::tcp::attach_slave,
+                        stream,
+                        (*slave).into(),
+                        log
+                    )
+                }),
+            #[cfg(feature = "tokio-rtu")]
+            ConnectionParams::Rtu {
+                device,
+                baud_rate,
+                parity_and_stop_bit,
+                slave,
+            } => {
+                proto::check_serial_settings(baud_rate, parity_and_stop_bit)?;
+                tokio_serial::SerialStream::open(&crate::tokio_common::serial_port_builder(
+                    device,
+                    baud_rate,
+                    parity_and_stop_bit,
+                ))
+                .map(|port| {
+                    attach!(
+                        tokio_modbus::client::rtu::attach_slave,
+                        port,
+                        (*slave).into(),
+                        log
+                    )
+                })
+                .map_err(std::io::Error::from)
+            }
+            #[cfg(all(feature = "tokio-tcp", feature = "tokio-rtu"))]
+            ConnectionParams::RtuOverTcp {
+                socket_addr,
+                slave,
+                options,
+            } => connect_tcp_stream(*socket_addr, options)
+                .await
+                .map(|stream| {
+                    attach!(
+                        tokio_modbus::client::rtu::attach_slave,
+                        stream,
+                        (*slave).into(),
+                        log
+                    )
+                }),
+            #[cfg(feature = "tokio-tcp-tls")]
+            ConnectionParams::TcpTls {
+                socket_addr,
+                server_name,
+                tls,
+                slave,
+                options,
+            } => match tokio_rustls::rustls::pki_types::ServerName::try_from(server_name.clone()) {
+                Ok(server_name) => match connect_tcp_stream(*socket_addr, options).await {
+                    Ok(stream) => tokio_rustls::TlsConnector::from(tls.0.clone())
+                        .connect(server_name, stream)
+                        .await
+                        .map(|stream| {
+                            attach!(
+                                tokio_modbus::client::tcp::attach_slave,
+                                stream,
+                                (*slave).into(),
+                                log
+                            )
+                        }),
+                    Err(err) => Err(err),
+                },
+                Err(err) => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, err)),
+            },
+            #[allow(unreachable_patterns)]
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("Connection type is not enabled: {params}"),
+            )),
+        };
+        Ok(ctx.map_err(tokio_modbus::Error::Transport)?)
+    }
+
     read_holding!(system_type, SystemType);
     write_holding!(system_type, SystemType);
     read_holding!(pulse_width, PulseWidth);
@@ -87,11 +287,21 @@ impl SDM72 {
     pub async fn set_kppa(
         ctx: &mut tokio_modbus::client::Context,
         password: proto::Password,
+    ) -> Result<()> {
+        Self::set_kppa_with_word_order(ctx, password, proto::WordOrder::BigEndian).await
+    }
+
+    /// Like [`set_kppa`](Self::set_kppa), with the given
+    /// [`WordOrder`](proto::WordOrder) of the 32-bit values.
+    pub async fn set_kppa_with_word_order(
+        ctx: &mut tokio_modbus::client::Context,
+        password: proto::Password,
+        word_order: proto::WordOrder,
     ) -> Result<()> {
         Ok(ctx
             .write_multiple_registers(
                 proto::KPPA::ADDRESS,
-                &proto::KPPA::encode_for_write_registers(password),
+                &word_order.apply(proto::KPPA::encode_for_write_registers(password)),
             )
             .await??)
     }
@@ -122,10 +332,177 @@ impl SDM72 {
             )
             .await??)
     }
+
+    /// Resets the data of the target on the meter.
+    ///
+    /// This requires KPPA authorization.
+    pub async fn reset(
+        ctx: &mut tokio_modbus::client::Context,
+        target: proto::ResetTarget,
+    ) -> Result<()> {
+        Ok(ctx
+            .write_multiple_registers(
+                proto::ResetHistoricalData::ADDRESS,
+                &target.encode_for_write_registers(),
+            )
+            .await??)
+    }
+
+    /// Writes a setting to the Modbus broadcast address, which all meters on
+    /// the bus execute without a response, e.g. to set the baud rate of many
+    /// meters at once during commissioning.
+    ///
+    /// The KPPA is written with `password` first, then the setting. Each
+    /// request waits `turnaround` for the meters, see
+    /// [`BROADCAST_TURNAROUND`](tokio_common::BROADCAST_TURNAROUND). The
+    /// slave of the context is set to `slave` afterwards.
+    ///
+    /// # Warning
+    ///
+    /// Nothing confirms that a meter executed the write: a meter with another
+    /// password, parity or baud rate silently ignores it, so read the setting
+    /// back from every meter afterwards. After a change of the parity or the
+    /// baud rate the meters only answer with the new setting, the context must
+    /// be reopened before the next broadcast. The address would be the same on
+    /// all meters, it fails with [`Error::NotBroadcastable`].
+    pub async fn broadcast_setting(
+        ctx: &mut tokio_modbus::client::Context,
+        password: proto::Password,
+        setting: Setting,
+        turnaround: std::time::Duration,
+        slave: Slave,
+    ) -> Result<()> {
+        Self::broadcast_setting_with_word_order(
+            ctx,
+            password,
+            setting,
+            proto::WordOrder::BigEndian,
+            turnaround,
+            slave,
+        )
+        .await
+    }
+
+    /// Like [`broadcast_setting`](Self::broadcast_setting), with the given
+    /// [`WordOrder`](proto::WordOrder) of the 32-bit values.
+    pub async fn broadcast_setting_with_word_order(
+        ctx: &mut tokio_modbus::client::Context,
+        password: proto::Password,
+        setting: Setting,
+        word_order: proto::WordOrder,
+        turnaround: std::time::Duration,
+        slave: Slave,
+    ) -> Result<()> {
+        if matches!(setting, Setting::Address(_)) {
+            return Err(Error::NotBroadcastable(setting));
+        }
+        let (address, words) = setting.encode_for_write_registers();
+        ctx.set_slave(tokio_modbus::Slave::broadcast());
+        let mut result = Self::broadcast(
+            ctx,
+            proto::KPPA::ADDRESS,
+            &word_order.apply(proto::KPPA::encode_for_write_registers(password)),
+            turnaround,
+        )
+        .await;
+        if result.is_ok() {
+            result = Self::broadcast(ctx, address, &word_order.apply(words), turnaround).await;
+        }
+        ctx.set_slave(slave.into());
+        result
+    }
+
+    /// Writes to the broadcast address and waits `turnaround` for the meters.
+    async fn broadcast(
+        ctx: &mut tokio_modbus::client::Context,
+        address: u16,
+        words: &[proto::Word],
+        turnaround: std::time::Duration,
+    ) -> Result<()> {
+        let started = tokio::time::Instant::now();
+        let result =
+            match tokio::time::timeout(turnaround, ctx.write_multiple_registers(address, words))
+                .await
+            {
+                Ok(rsp) => tokio_common::broadcast_result(
+                    rsp.map_err(Error::from).and_then(|rsp| Ok(rsp?)),
+                ),
+                // No meter responds to a broadcast
+                Err(_) => Ok(()),
+            };
+        tokio::time::sleep_until(started + turnaround).await;
+        result
+    }
+
     read_holding!(serial_number, SerialNumber);
     read_holding!(meter_code, MeterCode);
     read_holding!(software_version, SoftwareVersion);
 
+    /// Reads raw words from Modbus input registers.
+    ///
+    /// This is an escape hatch for registers which are not covered by the
+    /// [`protocol`](crate::protocol) module, e.g. firmware specific ones.
+    pub async fn read_raw_input(
+        ctx: &mut tokio_modbus::client::Context,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<proto::Word>> {
+        Ok(ctx.read_input_registers(address, quantity).await??)
+    }
+
+    /// Reads raw words from Modbus holding registers.
+    ///
+    /// This is an escape hatch for registers which are not covered by the
+    /// [`protocol`](crate::protocol) module, e.g. firmware specific ones.
+    pub async fn read_raw_holding(
+        ctx: &mut tokio_modbus::client::Context,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<proto::Word>> {
+        Ok(ctx.read_holding_registers(address, quantity).await??)
+    }
+
+    /// Writes raw words to Modbus holding registers.
+    ///
+    /// This is an escape hatch for registers which are not covered by the
+    /// [`protocol`](crate::protocol) module, e.g. firmware specific ones.
+    pub async fn write_raw_holding(
+        ctx: &mut tokio_modbus::client::Context,
+        address: u16,
+        words: &[proto::Word],
+    ) -> Result<()> {
+        Ok(ctx.write_multiple_registers(address, words).await??)
+    }
+
+    /// Reads the identity of the meter, the serial number and the meter code
+    /// are read in a single request, the software version in a second one.
+    ///
+    /// # Arguments
+    ///
+    /// * `delay` - The delay to be inserted between the two Modbus requests.
+    pub async fn identify(
+        ctx: &mut tokio_modbus::client::Context,
+        delay: &std::time::Duration,
+    ) -> Result<MeterIdentity> {
+        Self::identify_with_word_order(ctx, delay, proto::WordOrder::BigEndian).await
+    }
+
+    /// Like [`identify`](Self::identify), with the given
+    /// [`WordOrder`](proto::WordOrder) of the 32-bit values.
+    pub async fn identify_with_word_order(
+        ctx: &mut tokio_modbus::client::Context,
+        delay: &std::time::Duration,
+        word_order: proto::WordOrder,
+    ) -> Result<MeterIdentity> {
+        let (offset, quantity) = tokio_common::IDENTITY_BATCH;
+        let rsp = word_order.apply(ctx.read_holding_registers(offset, quantity).await??);
+
+        tokio::time::sleep(*delay).await;
+        let software_version = Self::software_version_with_word_order(ctx, word_order).await?;
+
+        tokio_common::decode_identity(&rsp, software_version)
+    }
+
     /// Reads all settings from the meter in a single batch operation.
     ///
     /// This method is more efficient than reading each setting individually because
@@ -135,6 +512,8 @@ impl SDM72 {
     ///
     /// # Arguments
     ///
+    /// * `mode` - How unknown codes of the enum settings are decoded, see
+    ///   [`DecodeMode`](proto::DecodeMode).
     /// * `delay` - The delay to be inserted between Modbus requests. This is
     ///   necessary for some Modbus devices, which may need a short pause to
     ///   process a request before they are ready to accept the next one. A
@@ -142,76 +521,31 @@ impl SDM72 {
     ///   device and network conditions.
     pub async fn read_all_settings(
         ctx: &mut tokio_modbus::client::Context,
+        mode: proto::DecodeMode,
+        delay: &std::time::Duration,
+    ) -> Result<AllSettings> {
+        Self::read_all_settings_with_word_order(ctx, mode, delay, proto::WordOrder::BigEndian).await
+    }
+
+    /// Like [`read_all_settings`](Self::read_all_settings), with the given
+    /// [`WordOrder`](proto::WordOrder) of the 32-bit values.
+    pub async fn read_all_settings_with_word_order(
+        ctx: &mut tokio_modbus::client::Context,
+        mode: proto::DecodeMode,
         delay: &std::time::Duration,
+        word_order: proto::WordOrder,
     ) -> Result<AllSettings> {
-        let offset1 = proto::SystemType::ADDRESS;
-        let quantity =
-            { proto::PulseEnergyType::ADDRESS - offset1 + proto::PulseEnergyType::QUANTITY };
-        let rsp1 = ctx.read_holding_registers(offset1, quantity).await??;
+        let (offset, quantity) = tokio_common::SETTINGS_BATCH;
+        let rsp = word_order.apply(ctx.read_holding_registers(offset, quantity).await??);
 
         tokio::time::sleep(*delay).await;
-        let serial_number = Self::serial_number(ctx).await?;
+        let serial_number = Self::serial_number_with_word_order(ctx, word_order).await?;
         tokio::time::sleep(*delay).await;
-        let meter_code = Self::meter_code(ctx).await?;
+        let meter_code = Self::meter_code_with_word_order(ctx, word_order).await?;
         tokio::time::sleep(*delay).await;
-        let software_version = Self::software_version(ctx).await?;
-
-        Ok(AllSettings {
-            system_type: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::SystemType,
-                &rsp1
-            )?,
-            pulse_width: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::PulseWidth,
-                &rsp1
-            )?,
-            kppa: crate::decode_subset_item_from_holding_register!(offset1, proto::KPPA, &rsp1)?,
-            parity_and_stop_bit: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::ParityAndStopBit,
-                &rsp1
-            )?,
-            address: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::Address,
-                &rsp1
-            )?,
-            pulse_constant: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::PulseConstant,
-                &rsp1
-            )?,
-            password: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::Password,
-                &rsp1
-            )?,
-            baud_rate: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::BaudRate,
-                &rsp1
-            )?,
-            auto_scroll_time: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::AutoScrollTime,
-                &rsp1
-            )?,
-            backlight_time: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::BacklightTime,
-                &rsp1
-            )?,
-            pulse_energy_type: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::PulseEnergyType,
-                &rsp1
-            )?,
-            serial_number,
-            meter_code,
-            software_version,
-        })
+        let software_version = Self::software_version_with_word_order(ctx, word_order).await?;
+
+        tokio_common::decode_settings(&rsp, serial_number, meter_code, software_version, mode)
     }
 
     /// Reads all measurement values from the meter in a single batch operation.
@@ -221,6 +555,9 @@ impl SDM72 {
     /// meter has a limit of 30 parameters per request, so this function splits
     /// the reads into multiple batches.
     ///
+    /// The values are returned as decoded, the scaling, calibrations and
+    /// precision of the safe clients are not applied.
+    ///
     /// # Arguments
     ///
     /// * `delay` - The delay to be inserted between Modbus requests. This is
@@ -232,243 +569,140 @@ impl SDM72 {
         ctx: &mut tokio_modbus::client::Context,
         delay: &std::time::Duration,
     ) -> Result<AllValues> {
-        let offset1 = proto::L1Voltage::ADDRESS;
-        let quantity =
-            { proto::ExportEnergyActive::ADDRESS - offset1 + proto::ExportEnergyActive::QUANTITY };
-        let rsp1 = ctx.read_input_registers(offset1, quantity).await??;
+        Self::read_all_with_word_order(ctx, delay, proto::WordOrder::BigEndian).await
+    }
 
-        tokio::time::sleep(*delay).await;
+    /// Like [`read_all`](Self::read_all), with the given
+    /// [`WordOrder`](proto::WordOrder) of the 32-bit values.
+    pub async fn read_all_with_word_order(
+        ctx: &mut tokio_modbus::client::Context,
+        delay: &std::time::Duration,
+        word_order: proto::WordOrder,
+    ) -> Result<AllValues> {
+        let mut rsp: [Vec<proto::Word>; 4] = Default::default();
+        for (i, (offset, quantity)) in tokio_common::VALUE_BATCHES.into_iter().enumerate() {
+            if i > 0 {
+                tokio::time::sleep(*delay).await;
+            }
+            rsp[i] = word_order.apply(ctx.read_input_registers(offset, quantity).await??);
+        }
+        tokio_common::decode_values(&rsp)
+    }
 
-        let offset2 = proto::L1ToL2Voltage::ADDRESS;
-        let quantity =
-            { proto::NeutralCurrent::ADDRESS - offset2 + proto::NeutralCurrent::QUANTITY };
-        let rsp2 = ctx.read_input_registers(offset2, quantity).await??;
+    /// Reads all measurement values over several connections to the same
+    /// Modbus/TCP meter or gateway at once, without a delay between the
+    /// batches.
+    ///
+    /// The batches are distributed over `ctx` and the `extra` contexts, so
+    /// their number bounds the requests in flight. Each context reads its
+    /// batches one after the other.
+    ///
+    /// # Arguments
+    ///
+    /// * `extra` - Further connections to the meter, e.g. opened with
+    ///   [`connect`](Self::connect). Without any the batches are read like
+    ///   [`read_all`](Self::read_all) without a delay.
+    #[cfg(feature = "tokio-tcp")]
+    pub async fn read_all_concurrent(
+        ctx: &mut tokio_modbus::client::Context,
+        extra: &mut [tokio_modbus::client::Context],
+    ) -> Result<AllValues> {
+        Self::read_all_concurrent_with_word_order(ctx, extra, proto::WordOrder::BigEndian).await
+    }
 
-        tokio::time::sleep(*delay).await;
+    /// Like [`read_all_concurrent`](Self::read_all_concurrent), with the given
+    /// [`WordOrder`](proto::WordOrder) of the 32-bit values.
+    #[cfg(feature = "tokio-tcp")]
+    pub async fn read_all_concurrent_with_word_order(
+        ctx: &mut tokio_modbus::client::Context,
+        extra: &mut [tokio_modbus::client::Context],
+        word_order: proto::WordOrder,
+    ) -> Result<AllValues> {
+        let rsp = Self::read_value_batches_concurrent(
+            std::iter::once(ctx).chain(extra),
+            false,
+            |_, _, _| {},
+        )
+        .await?;
+        tokio_common::decode_values(&rsp.map(|words| word_order.apply(words)))
+    }
 
-        let offset3 = proto::TotalEnergyActive::ADDRESS;
-        let quantity = { proto::NetKwh::ADDRESS - offset3 + proto::NetKwh::QUANTITY };
-        let rsp3 = ctx.read_input_registers(offset3, quantity).await??;
+    /// Reads the batches of the values distributed over the contexts, the
+    /// address, duration and outcome of every request are passed to `record`.
+    /// The words are returned in the order of the meter.
+    ///
+    /// With `via_holding` the batches are read as holding registers.
+    #[cfg(feature = "tokio-tcp")]
+    pub(crate) async fn read_value_batches_concurrent<'a>(
+        ctxs: impl Iterator<Item = &'a mut tokio_modbus::client::Context>,
+        via_holding: bool,
+        record: impl Fn(u16, std::time::Duration, &Result<Vec<proto::Word>>),
+    ) -> Result<[Vec<proto::Word>; 4]> {
+        let ctxs: Vec<_> = ctxs.collect();
+        let lanes = ctxs.len();
+        let record = &record;
+        let reads = ctxs.into_iter().enumerate().map(|(lane, ctx)| async move {
+            let mut words = Vec::new();
+            for i in (lane..tokio_common::VALUE_BATCHES.len()).step_by(lanes) {
+                let (offset, quantity) = tokio_common::VALUE_BATCHES[i];
+                let started = std::time::Instant::now();
+                let result = if via_holding {
+                    Self::read_raw_holding(ctx, offset, quantity).await
+                } else {
+                    Self::read_raw_input(ctx, offset, quantity).await
+                };
+                record(offset, started.elapsed(), &result);
+                words.push((i, result?));
+            }
+            Ok::<_, tokio_common::Error>(words)
+        });
+        let mut rsp: [Vec<proto::Word>; 4] = Default::default();
+        for (i, words) in futures_util::future::try_join_all(reads)
+            .await?
+            .into_iter()
+            .flatten()
+        {
+            rsp[i] = words;
+        }
+        Ok(rsp)
+    }
 
-        tokio::time::sleep(*delay).await;
+    /// Reads the measurement values supported by the firmware, the batches of
+    /// unsupported registers are skipped and their values are `None`.
+    ///
+    /// # Arguments
+    ///
+    /// * `delay` - The delay to be inserted between Modbus requests, see
+    ///   [`read_all`](Self::read_all).
+    /// * `firmware` - The firmware of the meter, see [`Firmware::detect`].
+    pub async fn read_all_partial(
+        ctx: &mut tokio_modbus::client::Context,
+        delay: &std::time::Duration,
+        firmware: Firmware,
+    ) -> Result<AllValuesPartial> {
+        Self::read_all_partial_with_word_order(ctx, delay, proto::WordOrder::BigEndian, firmware)
+            .await
+    }
 
-        let offset4 = proto::ImportTotalPowerActive::ADDRESS;
-        let quantity = {
-            proto::ExportTotalPowerActive::ADDRESS - offset4
-                + proto::ExportTotalPowerActive::QUANTITY
-        };
-        let rsp4 = ctx.read_input_registers(offset4, quantity).await??;
-
-        Ok(AllValues {
-            l1_voltage: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L1Voltage,
-                &rsp1
-            )?,
-            l2_voltage: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L2Voltage,
-                &rsp1
-            )?,
-            l3_voltage: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L3Voltage,
-                &rsp1
-            )?,
-            l1_current: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L1Current,
-                &rsp1
-            )?,
-            l2_current: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L2Current,
-                &rsp1
-            )?,
-            l3_current: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L3Current,
-                &rsp1
-            )?,
-            l1_power_active: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L1PowerActive,
-                &rsp1
-            )?,
-            l2_power_active: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L2PowerActive,
-                &rsp1
-            )?,
-            l3_power_active: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L3PowerActive,
-                &rsp1
-            )?,
-            l1_power_apparent: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L1PowerApparent,
-                &rsp1
-            )?,
-            l2_power_apparent: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L2PowerApparent,
-                &rsp1
-            )?,
-            l3_power_apparent: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L3PowerApparent,
-                &rsp1
-            )?,
-            l1_power_reactive: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L1PowerReactive,
-                &rsp1
-            )?,
-            l2_power_reactive: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L2PowerReactive,
-                &rsp1
-            )?,
-            l3_power_reactive: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L3PowerReactive,
-                &rsp1
-            )?,
-            l1_power_factor: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L1PowerFactor,
-                &rsp1
-            )?,
-            l2_power_factor: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L2PowerFactor,
-                &rsp1
-            )?,
-            l3_power_factor: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L3PowerFactor,
-                &rsp1
-            )?,
-            ln_average_voltage: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::LtoNAverageVoltage,
-                &rsp1
-            )?,
-            ln_average_current: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::LtoNAverageCurrent,
-                &rsp1
-            )?,
-            total_line_current: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::TotalLineCurrent,
-                &rsp1
-            )?,
-            total_power: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::TotalPower,
-                &rsp1
-            )?,
-            total_power_apparent: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::TotalPowerApparent,
-                &rsp1
-            )?,
-            total_power_reactive: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::TotalPowerReactive,
-                &rsp1
-            )?,
-            total_power_factor: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::TotalPowerFactor,
-                &rsp1
-            )?,
-            frequency: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::Frequency,
-                &rsp1
-            )?,
-            import_energy_active: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::ImportEnergyActive,
-                &rsp1
-            )?,
-            export_energy_active: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::ExportEnergyActive,
-                &rsp1
-            )?,
-
-            l1l2_voltage: crate::decode_subset_item_from_input_register!(
-                offset2,
-                proto::L1ToL2Voltage,
-                &rsp2
-            )?,
-            l2l3_voltage: crate::decode_subset_item_from_input_register!(
-                offset2,
-                proto::L2ToL3Voltage,
-                &rsp2
-            )?,
-            l3l1_voltage: crate::decode_subset_item_from_input_register!(
-                offset2,
-                proto::L3ToL1Voltage,
-                &rsp2
-            )?,
-            ll_average_voltage: crate::decode_subset_item_from_input_register!(
-                offset2,
-                proto::LtoLAverageVoltage,
-                &rsp2
-            )?,
-            neutral_current: crate::decode_subset_item_from_input_register!(
-                offset2,
-                proto::NeutralCurrent,
-                &rsp2
-            )?,
-
-            total_energy_active: crate::decode_subset_item_from_input_register!(
-                offset3,
-                proto::TotalEnergyActive,
-                &rsp3
-            )?,
-            total_energy_reactive: crate::decode_subset_item_from_input_register!(
-                offset3,
-                proto::TotalEnergyReactive,
-                &rsp3
-            )?,
-            resettable_total_energy_active: crate::decode_subset_item_from_input_register!(
-                offset3,
-                proto::ResettableTotalEnergyActive,
-                &rsp3
-            )?,
-            resettable_total_energy_reactive: crate::decode_subset_item_from_input_register!(
-                offset3,
-                proto::ResettableTotalEnergyReactive,
-                &rsp3
-            )?,
-            resettable_import_energy_active: crate::decode_subset_item_from_input_register!(
-                offset3,
-                proto::ResettableImportEnergyActive,
-                &rsp3
-            )?,
-            resettable_export_energy_active: crate::decode_subset_item_from_input_register!(
-                offset3,
-                proto::ResettableExportEnergyActive,
-                &rsp3
-            )?,
-            net_kwh: crate::decode_subset_item_from_input_register!(offset3, proto::NetKwh, &rsp3)?,
-
-            import_total_energy_active: crate::decode_subset_item_from_input_register!(
-                offset4,
-                proto::ImportTotalPowerActive,
-                &rsp4
-            )?,
-            export_total_energy_active: crate::decode_subset_item_from_input_register!(
-                offset4,
-                proto::ExportTotalPowerActive,
-                &rsp4
-            )?,
-        })
+    /// Like [`read_all_partial`](Self::read_all_partial), with the given
+    /// [`WordOrder`](proto::WordOrder) of the 32-bit values.
+    pub async fn read_all_partial_with_word_order(
+        ctx: &mut tokio_modbus::client::Context,
+        delay: &std::time::Duration,
+        word_order: proto::WordOrder,
+        firmware: Firmware,
+    ) -> Result<AllValuesPartial> {
+        let mut rsp: [Option<Vec<proto::Word>>; 4] = Default::default();
+        let batches = tokio_common::VALUE_BATCHES.into_iter().enumerate();
+        for (n, (i, (offset, quantity))) in batches
+            .filter(|(i, _)| firmware.supports_batch(*i))
+            .enumerate()
+        {
+            if n > 0 {
+                tokio::time::sleep(*delay).await;
+            }
+            rsp[i] = Some(word_order.apply(ctx.read_input_registers(offset, quantity).await??));
+        }
+        tokio_common::decode_values_partial(&rsp)
     }
 }
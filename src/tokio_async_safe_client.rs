@@ -2,6 +2,8 @@
 //!
 //! The [`SafeClient`] struct wraps the core asynchronous API and manages the Modbus
 //! context within an `Arc<Mutex>`, allowing it to be shared across tasks safely.
+//! The [`BusManager`] hands out the clients of several meters sharing one
+//! Modbus context, e.g. of an RS485 bus.
 //!
 //! # Example
 //!
@@ -12,7 +14,6 @@
 //! };
 //! use tokio_modbus::client::tcp;
 //! use tokio_modbus::Slave;
-//! use std::time::Duration;
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -20,7 +21,7 @@
 //!     let ctx = tcp::connect_slave(socket_addr, Slave(*Address::default())).await?;
 //!     let mut client = SafeClient::new(ctx);
 //!
-//!     let values = client.read_all(&Duration::from_millis(100)).await?;
+//!     let values = client.read_all().await?;
 //!
 //!     println!("Successfully read values: {:#?}", values);
 //!
@@ -29,27 +30,356 @@
 //! ```
 
 use crate::{
+    instrumentation::{Instrumentation, RequestOutcome, RequestRecord},
+    modbus::Slave,
+    password::PasswordProvider,
     protocol as proto,
+    settings_diff::{self, Setting, SettingsReport, WriteOutcome},
+    stats::LinkStats,
     tokio_async::SDM72,
-    tokio_common::{AllSettings, AllValues, Result},
+    tokio_common::{
+        self, AllSettings, AllValues, AllValuesPartial, BatchRetryPolicy, ConnectionParams, Error,
+        Firmware, MeterIdentity, Operation, ReadReport, ReconnectPolicy, RegisterContext, Result,
+        ScalingConfig, ScalingProfile, Timestamp, TimestampedAllValues, TimestampedValues,
+        ValueScaling, ValueValidation,
+    },
+    watch::{Threshold, Watch, WatchEvent},
+};
+use futures_util::Stream;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    sync::{mpsc, Mutex},
+    time::Instant,
 };
-use std::sync::Arc;
-use tokio::sync::Mutex;
 use tokio_modbus::{client::Context, prelude::SlaveContext};
 
+/// The state required to re-establish a lost connection.
+struct Reconnect {
+    params: std::sync::Mutex<ConnectionParams>,
+    policy: ReconnectPolicy,
+}
+
+/// The raw words of the settings by register address, with the time they were read.
+type SettingsCache = HashMap<u16, (Vec<proto::Word>, Instant)>;
+
+/// The registered watches with the channels of their events.
+type Watches = Vec<(Watch, mpsc::UnboundedSender<WatchEvent>)>;
+
 /// A thread-safe asynchronous client for the SDM72 energy meter.
 #[derive(Clone)]
 pub struct SafeClient {
     ctx: Arc<Mutex<Context>>,
+    decode_mode: proto::DecodeMode,
+    reconnect: Option<Arc<Reconnect>>,
+    /// The slave id of the requests, shared by all clones of the client.
+    slave: Arc<std::sync::Mutex<Option<Slave>>>,
+    scaling: ValueScaling,
+    word_order: proto::WordOrder,
+    input_via_holding: bool,
+    reopen_after_comm_change: bool,
+    password: Option<Arc<dyn PasswordProvider>>,
+    instrumentation: Option<Arc<dyn Instrumentation>>,
+    write_verify: bool,
+    batch_retry: BatchRetryPolicy,
+    value_validation: ValueValidation,
+    firmware: Option<Firmware>,
+    /// The identity of the meter, read once per connection.
+    identity: Arc<std::sync::Mutex<Option<MeterIdentity>>>,
+    settings_cache: Arc<std::sync::Mutex<SettingsCache>>,
+    settings_cache_ttl: Option<Duration>,
+    watches: Arc<std::sync::Mutex<Watches>>,
+    last_contact: Arc<std::sync::Mutex<Option<Instant>>>,
+    link_stats: Arc<std::sync::Mutex<LinkStats>>,
+    min_request_interval: Duration,
+    tcp_parallelism: usize,
+    /// The connections besides `ctx` of the concurrent reads.
+    #[cfg(feature = "tokio-tcp")]
+    extra_ctxs: Arc<Mutex<Vec<Context>>>,
+}
+
+/// Shares one Modbus context, e.g. of an RS485 bus, between the clients of
+/// several meters.
+///
+/// Every [`client`](Self::client) addresses its own meter. The requests of all
+/// clients are serialized by the lock of the context and separated by the
+/// minimum request interval since the last response on the bus, so the
+/// clients can poll their meters concurrently from several tasks.
+pub struct BusManager {
+    ctx: Arc<Mutex<Context>>,
+    last_contact: Arc<std::sync::Mutex<Option<Instant>>>,
+    min_request_interval: Duration,
+}
+
+impl BusManager {
+    /// Creates a bus manager for a connected context.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx`: The Modbus context of the bus.
+    /// * `min_request_interval`: The interval between the last response and
+    ///   the next request, the [silent interval](proto::BaudRate::silent_interval)
+    ///   of the baud rate for Modbus/RTU.
+    pub fn new(ctx: Context, min_request_interval: Duration) -> Self {
+        Self {
+            ctx: Arc::new(Mutex::new(ctx)),
+            last_contact: Arc::default(),
+            min_request_interval,
+        }
+    }
+
+    /// Connects to the bus, the minimum request interval is the one of the
+    /// connection parameters, whose slave is ignored.
+    pub async fn connect(params: &ConnectionParams) -> Result<Self> {
+        Ok(Self::new(
+            SDM72::connect(params).await?,
+            params.min_request_interval(),
+        ))
+    }
+
+    /// Returns a client of the meter with the address, which shares the
+    /// context with all other clients of the bus.
+    ///
+    /// The identity, the settings cache and the link statistics are per
+    /// client.
+    pub fn client(&self, address: proto::Address) -> SafeClient {
+        let mut client = SafeClient::from_shared(self.ctx.clone());
+        client.slave = Arc::new(std::sync::Mutex::new(Some(address.into())));
+        client.last_contact = self.last_contact.clone();
+        client.min_request_interval = self.min_request_interval;
+        client
+    }
+
+    /// The minimum interval between the last response and the next request.
+    pub fn min_request_interval(&self) -> Duration {
+        self.min_request_interval
+    }
+}
+
+/// Writes several settings with the authorization acquired once, see
+/// [`SafeClient::begin_settings`].
+pub struct SettingsSession<'a> {
+    client: &'a mut SafeClient,
+    settings: Vec<Setting>,
+    delay: Duration,
+}
+
+impl SettingsSession<'_> {
+    /// Queues a setting, which is written by [`apply`](Self::apply).
+    pub fn queue(&mut self, setting: Setting) -> &mut Self {
+        self.settings.push(setting);
+        self
+    }
+
+    /// Sets the delay between two writes,
+    /// [`DEFAULT_WRITE_DELAY`](settings_diff::DEFAULT_WRITE_DELAY) by default.
+    pub fn set_delay(&mut self, delay: Duration) -> &mut Self {
+        self.delay = delay;
+        self
+    }
+
+    /// The queued settings.
+    pub fn queued(&self) -> &[Setting] {
+        &self.settings
+    }
+
+    /// Writes the queued settings in the order of
+    /// [`diff`](settings_diff::diff), so the settings which affect the
+    /// communication come last.
+    ///
+    /// After a failed write the remaining settings are skipped.
+    pub async fn apply(mut self) -> SettingsReport {
+        settings_diff::sort_for_write(&mut self.settings);
+        let mut report = SettingsReport::default();
+        let mut failed = false;
+        for (index, setting) in self.settings.into_iter().enumerate() {
+            let outcome = if failed {
+                WriteOutcome::Skipped
+            } else {
+                if index > 0 {
+                    self.client.pace(self.delay).await;
+                }
+                match self.client.write_setting(setting).await {
+                    Ok(()) => WriteOutcome::Written,
+                    Err(err) => {
+                        failed = true;
+                        WriteOutcome::Failed(err)
+                    }
+                }
+            };
+            report.outcomes.push((setting, outcome));
+        }
+        report
+    }
+}
+
+/// A macro to run an operation on the locked context.
+///
+/// Errors are wrapped in [`Error::Register`] with the given operation,
+/// [register kind](proto::RegisterKind), register name and address.
+///
+/// The operation is delayed until the minimum request interval has passed
+/// since the last response, the lock is not held while waiting. Every
+/// response of the meter, including exception responses, updates the time of
+/// the last contact.
+///
+/// If the client was created with [`SafeClient::connect`] and the operation
+/// fails with a transport error, the connection is re-established according
+/// to the [`ReconnectPolicy`]. Only reads are replayed, a failed write is
+/// returned after the first reconnection. A timeout of a serial port fails the
+/// operation without a reconnection. Every attempt is counted in the
+/// [`LinkStats`], the request with all its attempts is reported to the
+/// [`Instrumentation`].
+macro_rules! with_ctx {
+    ($self:ident, $operation:ident, $kind:ident, $register:expr, $address:expr, $ctx:ident => $op:expr) => {{
+        let mut $ctx = loop {
+            let ctx = $self.ctx.lock().await;
+            match $self.pacing_wait($self.min_request_interval) {
+                Some(wait) => {
+                    drop(ctx);
+                    tokio::time::sleep(wait).await;
+                }
+                None => break ctx,
+            }
+        };
+        if let Some(slave) = $self.slave() {
+            $ctx.set_slave(slave.into());
+        }
+        let started = Instant::now();
+        #[cfg(feature = "tracing")]
+        let span = crate::instrumentation::request_span(
+            Operation::$operation,
+            $register,
+            $address,
+            $self.slave().map(|slave| slave.0),
+        );
+        let mut attempt = 0;
+        let result = loop {
+            let outcome = $op;
+            $self.link_stats.lock().unwrap().record(&outcome);
+            match outcome {
+                Err(err) => match &$self.reconnect {
+                    Some(reconnect)
+                        if attempt < reconnect.policy.max_attempts
+                            && reconnect.params.lock().unwrap().reconnects_after(&err) =>
+                    {
+                        tokio::time::sleep(reconnect.policy.backoff(attempt)).await;
+                        attempt += 1;
+                        let params = reconnect.params.lock().unwrap().clone();
+                        if let Ok(new_ctx) = SDM72::connect(&params).await {
+                            *$ctx = new_ctx;
+                            *$self.identity.lock().unwrap() = None;
+                        }
+                        // The meter may have executed a write before the connection broke
+                        if !matches!(Operation::$operation, Operation::Read) {
+                            break Err(err);
+                        }
+                    }
+                    _ => break Err(err),
+                },
+                ok => break ok,
+            }
+        };
+        let record = RequestRecord {
+            operation: Operation::$operation,
+            register: $register,
+            address: $address,
+            duration: Instant::now().saturating_duration_since(started),
+            retries: attempt,
+            outcome: RequestOutcome::of(&result),
+        };
+        if let Some(instrumentation) = &$self.instrumentation {
+            instrumentation.record(&record);
+        }
+        #[cfg(feature = "tracing")]
+        record.trace(&span);
+        if !matches!(&result, Err(err) if err.is_transport()) {
+            *$self.last_contact.lock().unwrap() = Some(Instant::now());
+        }
+        result.map_err(|source| Error::Register {
+            context: RegisterContext {
+                operation: Operation::$operation,
+                kind: proto::RegisterKind::$kind,
+                register: $register,
+                address: $address,
+                slave: $self.slave().map(|slave| slave.0),
+            },
+            source: Box::new(source),
+        })
+    }};
+}
+
+/// Checks the value read back after a write.
+fn verify(expected: Setting, actual: Setting) -> Result<()> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(Error::VerificationFailed { expected, actual })
+    }
 }
 
 macro_rules! read_holding {
     ($func_name:ident, $ty:ident) => {
+        read_holding!($func_name, $ty, decode |words, _| proto::$ty::decode_from_holding_registers(words));
+    };
+    ($func_name:ident, $ty:ident, mode) => {
+        read_holding!($func_name, $ty, decode proto::$ty::decode_with_mode);
+    };
+    ($func_name:ident, $ty:ident, decode $decode:expr) => {
+        paste::item! {
+            #[doc = "Reads the [`proto::" $ty "`] value from the Modbus holding register."]
+            ///
+            /// The value is served from the [settings
+            /// cache](Self::set_settings_cache_ttl) if it is enabled.
+            pub async fn $func_name(&mut self) -> Result<proto::$ty> {
+                const ADDRESS: u16 = <proto::$ty as proto::ModbusParam>::ADDRESS;
+                const QUANTITY: u16 = <proto::$ty as proto::ModbusParam>::QUANTITY;
+                if let Some(words) = self.cached_setting(ADDRESS) {
+                    return Ok(($decode)(&words, self.decode_mode)?);
+                }
+                let words = with_ctx!(self, Read, Holding, stringify!($ty), Some(ADDRESS), ctx => SDM72::read_raw_holding(&mut ctx, ADDRESS, QUANTITY).await)?;
+                let words = self.word_order.apply(words);
+                let value = ($decode)(&words, self.decode_mode)?;
+                self.cache_setting(ADDRESS, words);
+                Ok(value)
+            }
+        }
+    };
+    ($func_name:ident, $ty:ident, identity) => {
         paste::item! {
             #[doc = "Reads the [`proto::" $ty "`] value from the Modbus holding register."]
+            ///
+            /// The value is served from the [identity](Self::identify) if it
+            /// was read on this connection, otherwise from the [settings
+            /// cache](Self::set_settings_cache_ttl) if it is enabled.
             pub async fn $func_name(&mut self) -> Result<proto::$ty> {
-                let mut ctx = self.ctx.lock().await;
-                SDM72::$func_name(&mut ctx).await
+                const ADDRESS: u16 = <proto::$ty as proto::ModbusParam>::ADDRESS;
+                const QUANTITY: u16 = <proto::$ty as proto::ModbusParam>::QUANTITY;
+                if let Some(identity) = *self.identity.lock().unwrap() {
+                    return Ok(identity.$func_name);
+                }
+                if let Some(words) = self.cached_setting(ADDRESS) {
+                    return Ok(proto::$ty::decode_from_holding_registers(&words)?);
+                }
+                let words = with_ctx!(self, Read, Holding, stringify!($ty), Some(ADDRESS), ctx => SDM72::read_raw_holding(&mut ctx, ADDRESS, QUANTITY).await)?;
+                let words = self.word_order.apply(words);
+                let value = proto::$ty::decode_from_holding_registers(&words)?;
+                self.cache_setting(ADDRESS, words);
+                Ok(value)
+            }
+        }
+    };
+    ($func_name:ident, $ty:ident, uncached) => {
+        paste::item! {
+            #[doc = "Reads the [`proto::" $ty "`] value from the Modbus holding register."]
+            pub async fn $func_name(&mut self) -> Result<proto::$ty> {
+                const ADDRESS: u16 = <proto::$ty as proto::ModbusParam>::ADDRESS;
+                const QUANTITY: u16 = <proto::$ty as proto::ModbusParam>::QUANTITY;
+                let words = with_ctx!(self, Read, Holding, stringify!($ty), Some(ADDRESS), ctx => SDM72::read_raw_holding(&mut ctx, ADDRESS, QUANTITY).await)?;
+                Ok(proto::$ty::decode_with_mode(&self.word_order.apply(words), self.decode_mode)?)
             }
         }
     };
@@ -60,8 +390,15 @@ macro_rules! write_holding {
         paste::item! {
             #[doc = "Writes the [`proto::" $ty "`] value to the Modbus holding register."]
             pub async fn [< set_ $func_name >](&mut self, value: proto::$ty) -> Result<()> {
-                let mut ctx = self.ctx.lock().await;
-                SDM72::[< set_ $func_name >](&mut ctx, value).await
+                self.authorize().await?;
+                with_ctx!(self, Write, Holding, stringify!($ty), Some(<proto::$ty as proto::ModbusParam>::ADDRESS), ctx => SDM72::[< set_ $func_name _with_word_order >](&mut ctx, value, self.word_order).await)?;
+                self.invalidate_settings_cache();
+                self.follow_comm_change(Setting::$ty(value)).await?;
+                if self.write_verify {
+                    let actual = self.$func_name().await?;
+                    verify(Setting::$ty(value), Setting::$ty(actual))?;
+                }
+                Ok(())
             }
         }
     };
@@ -76,15 +413,503 @@ impl SafeClient {
     pub fn new(ctx: Context) -> Self {
         Self {
             ctx: Arc::new(Mutex::new(ctx)),
+            decode_mode: proto::DecodeMode::default(),
+            reconnect: None,
+            slave: Arc::default(),
+            scaling: ValueScaling::default(),
+            word_order: proto::WordOrder::default(),
+            input_via_holding: false,
+            reopen_after_comm_change: false,
+            password: None,
+            instrumentation: None,
+            write_verify: false,
+            batch_retry: BatchRetryPolicy::default(),
+            value_validation: ValueValidation::default(),
+            firmware: None,
+            identity: Arc::default(),
+            settings_cache: Arc::default(),
+            settings_cache_ttl: None,
+            watches: Arc::default(),
+            last_contact: Arc::default(),
+            link_stats: Arc::default(),
+            min_request_interval: Duration::ZERO,
+            tcp_parallelism: 1,
+            #[cfg(feature = "tokio-tcp")]
+            extra_ctxs: Arc::default(),
         }
     }
 
+    /// Connects to the meter and creates a new `SafeClient` instance that
+    /// automatically reconnects.
+    ///
+    /// If an operation fails with a transport error (e.g. because a Modbus TCP
+    /// gateway was rebooted), the connection is re-established according to
+    /// the `policy` and a failed read is replayed. A failed write is not
+    /// replayed, as the meter may have executed it, and a timeout of a serial
+    /// port is returned without reconnecting.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: The parameters used to (re-)establish the connection.
+    /// * `policy`: Controls the reconnection attempts.
+    pub async fn connect(params: ConnectionParams, policy: ReconnectPolicy) -> Result<Self> {
+        let ctx = SDM72::connect(&params).await?;
+        let min_request_interval = params.min_request_interval();
+        let slave = params.slave();
+        Ok(Self {
+            ctx: Arc::new(Mutex::new(ctx)),
+            decode_mode: proto::DecodeMode::default(),
+            reconnect: Some(Arc::new(Reconnect {
+                params: std::sync::Mutex::new(params),
+                policy,
+            })),
+            slave: Arc::new(std::sync::Mutex::new(Some(slave))),
+            scaling: ValueScaling::default(),
+            word_order: proto::WordOrder::default(),
+            input_via_holding: false,
+            reopen_after_comm_change: false,
+            password: None,
+            instrumentation: None,
+            write_verify: false,
+            batch_retry: BatchRetryPolicy::default(),
+            value_validation: ValueValidation::default(),
+            firmware: None,
+            identity: Arc::default(),
+            settings_cache: Arc::default(),
+            settings_cache_ttl: None,
+            watches: Arc::default(),
+            last_contact: Arc::default(),
+            link_stats: Arc::default(),
+            min_request_interval,
+            tcp_parallelism: 1,
+            #[cfg(feature = "tokio-tcp")]
+            extra_ctxs: Arc::default(),
+        })
+    }
+
     /// Creates a new `SafeClient` from an existing `Arc<Mutex<Context>>`.
     ///
     /// This allows multiple `SafeClient` instances to share the exact same
     /// underlying connection context.
     pub fn from_shared(ctx: Arc<Mutex<Context>>) -> Self {
-        Self { ctx }
+        Self {
+            ctx,
+            decode_mode: proto::DecodeMode::default(),
+            reconnect: None,
+            slave: Arc::default(),
+            scaling: ValueScaling::default(),
+            word_order: proto::WordOrder::default(),
+            input_via_holding: false,
+            reopen_after_comm_change: false,
+            password: None,
+            instrumentation: None,
+            write_verify: false,
+            batch_retry: BatchRetryPolicy::default(),
+            value_validation: ValueValidation::default(),
+            firmware: None,
+            identity: Arc::default(),
+            settings_cache: Arc::default(),
+            settings_cache_ttl: None,
+            watches: Arc::default(),
+            last_contact: Arc::default(),
+            link_stats: Arc::default(),
+            min_request_interval: Duration::ZERO,
+            tcp_parallelism: 1,
+            #[cfg(feature = "tokio-tcp")]
+            extra_ctxs: Arc::default(),
+        }
+    }
+
+    /// Sets how `read_all_settings` and the setting getters (e.g.
+    /// [`baud_rate`](Self::baud_rate)) decode a setting code unknown to this
+    /// library, see [`DecodeMode`](proto::DecodeMode). The default
+    /// [`Strict`](proto::DecodeMode::Strict) mode fails with
+    /// [`UnknownVariant`](proto::Error::UnknownVariant).
+    pub fn set_decode_mode(&mut self, mode: proto::DecodeMode) {
+        self.decode_mode = mode;
+    }
+
+    /// Returns the [`DecodeMode`](proto::DecodeMode) of the settings.
+    pub fn decode_mode(&self) -> proto::DecodeMode {
+        self.decode_mode
+    }
+
+    /// Sets the ratios of external current and voltage transformers.
+    ///
+    /// The profile is applied to all values returned by [`read_all`](Self::read_all).
+    pub fn set_scaling_profile(&mut self, profile: Option<ScalingProfile>) {
+        self.scaling.profile = profile;
+    }
+
+    /// Returns the scaling profile applied to the measurement values.
+    pub fn scaling_profile(&self) -> Option<ScalingProfile> {
+        self.scaling.profile
+    }
+
+    /// Sets the per-measurement calibrations, applied after the [scaling
+    /// profile](Self::set_scaling_profile) to all values returned by
+    /// [`read_all`](Self::read_all).
+    pub fn set_scaling_config(&mut self, config: Option<ScalingConfig>) {
+        self.scaling.calibration = config.filter(|config| !config.is_empty()).map(Arc::new);
+    }
+
+    /// Returns the per-measurement calibrations.
+    pub fn scaling_config(&self) -> Option<&ScalingConfig> {
+        self.scaling.calibration.as_deref()
+    }
+
+    /// Sets the decimals of the serialized and displayed values returned by
+    /// [`read_all`](Self::read_all), see [`AllValues::precision`].
+    pub fn set_precision(&mut self, precision: proto::Precision) {
+        self.scaling.precision = precision;
+    }
+
+    /// Returns the decimals of the serialized and displayed values.
+    pub fn precision(&self) -> proto::Precision {
+        self.scaling.precision
+    }
+
+    /// Sets the password used to acquire the Key Parameter Programming
+    /// Authorization (KPPA) automatically.
+    ///
+    /// Before every write the KPPA state is read and, if the client is not
+    /// authorized, the password is written to the KPPA register.
+    pub fn with_password(self, password: proto::Password) -> Self {
+        self.with_password_provider(Arc::new(password))
+    }
+
+    /// Sets the provider of the password used to acquire the KPPA
+    /// automatically, like [`with_password`](Self::with_password).
+    ///
+    /// The provider is only asked when the client is not authorized.
+    pub fn with_password_provider(mut self, provider: Arc<dyn PasswordProvider>) -> Self {
+        self.password = Some(provider);
+        self
+    }
+
+    /// Reports every request with its duration, retries and outcome to the
+    /// instrumentation, e.g. a
+    /// [`RegisterTimings`](crate::instrumentation::RegisterTimings).
+    pub fn set_instrumentation(&mut self, instrumentation: Option<Arc<dyn Instrumentation>>) {
+        self.instrumentation = instrumentation;
+    }
+
+    /// Acquires the KPPA if a password provider was given with
+    /// [`with_password_provider`](Self::with_password_provider).
+    async fn authorize(&mut self) -> Result<()> {
+        let Some(provider) = self.password.clone() else {
+            return Ok(());
+        };
+        if self.kppa().await? != proto::KPPA::Authorized {
+            if let Some(password) = provider.password()? {
+                self.set_kppa(password).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the order of the two words of 32-bit values.
+    ///
+    /// Use [`WordOrder::LittleEndianWords`](proto::WordOrder::LittleEndianWords)
+    /// for Modbus TCP gateways which swap the words of each value.
+    pub fn set_word_order(&mut self, word_order: proto::WordOrder) {
+        self.word_order = word_order;
+    }
+
+    /// Reads the measurement values with function code 0x03 (read holding
+    /// registers) at the addresses of the input registers, for RS485 to TCP
+    /// bridges which only forward function code 0x03.
+    ///
+    /// This applies to [`read_all`](Self::read_all), its variants and
+    /// [`read_raw_input`](Self::read_raw_input).
+    pub fn set_input_via_holding(&mut self, input_via_holding: bool) {
+        self.input_via_holding = input_via_holding;
+    }
+
+    /// Opens the connection again after the baud rate or the parity and stop
+    /// bit of a Modbus/RTU connection were written, and probes the meter after
+    /// these settings and the address were written.
+    ///
+    /// The meter applies these settings immediately, so without this option
+    /// the following requests fail until the connection is opened with the
+    /// new parameters. A meter which doesn't respond after the change fails
+    /// the write with [`Error::Unreachable`]. This requires a client created
+    /// with [`connect`](Self::connect).
+    pub fn set_reopen_after_comm_change(&mut self, reopen: bool) {
+        self.reopen_after_comm_change = reopen;
+    }
+
+    /// Returns `true` if the connection follows the written communication
+    /// settings.
+    pub fn reopen_after_comm_change(&self) -> bool {
+        self.reopen_after_comm_change
+    }
+
+    /// Updates the connection parameters after a setting which affects the
+    /// communication was written, see
+    /// [`set_reopen_after_comm_change`](Self::set_reopen_after_comm_change).
+    async fn follow_comm_change(&mut self, setting: Setting) -> Result<()> {
+        let Some(reconnect) = self.reconnect.clone().filter(|_| setting.affects_link()) else {
+            return Ok(());
+        };
+        let changed = reconnect.params.lock().unwrap().apply_setting(setting);
+        if !self.reopen_after_comm_change {
+            return Ok(());
+        }
+        let result = if changed && !matches!(setting, Setting::Address(_)) {
+            let min_request_interval = reconnect.params.lock().unwrap().min_request_interval();
+            self.min_request_interval = self.min_request_interval.max(min_request_interval);
+            self.reconnect().await.map(|_| ())
+        } else {
+            // The identity must not answer the probe
+            *self.identity.lock().unwrap() = None;
+            Ok(())
+        };
+        let result = match result {
+            Ok(()) => self.probe().await,
+            err => err,
+        };
+        result.map_err(|source| Error::Unreachable {
+            setting,
+            source: Box::new(source),
+        })
+    }
+
+    /// Returns `true` if the measurement values are read as holding registers.
+    pub fn input_via_holding(&self) -> bool {
+        self.input_via_holding
+    }
+
+    /// Enables reading back every written setting.
+    ///
+    /// If the value read back differs from the written one, the write fails
+    /// with [`Error::VerificationFailed`].
+    pub fn set_write_verify(&mut self, write_verify: bool) {
+        self.write_verify = write_verify;
+    }
+
+    /// Returns `true` if written settings are read back.
+    pub fn write_verify(&self) -> bool {
+        self.write_verify
+    }
+
+    /// Sets how `read_all` recovers from a failed batch, see [`BatchRetryPolicy`].
+    pub fn set_batch_retry_policy(&mut self, policy: BatchRetryPolicy) {
+        self.batch_retry = policy;
+    }
+
+    /// Returns the [`BatchRetryPolicy`] of `read_all`.
+    pub fn batch_retry_policy(&self) -> BatchRetryPolicy {
+        self.batch_retry
+    }
+
+    /// Sets which decoded values are rejected with
+    /// [`Error::ImplausibleValue`], see [`ValueValidation`].
+    ///
+    /// `read_all` fails with the first rejected value, `read_all_lenient`
+    /// leaves the rejected values out and reports their errors.
+    pub fn set_value_validation(&mut self, validation: ValueValidation) {
+        self.value_validation = validation;
+    }
+
+    /// Returns the [`ValueValidation`] of the measurement values.
+    pub fn value_validation(&self) -> ValueValidation {
+        self.value_validation
+    }
+
+    /// Enables the settings cache, the values read by the individual setting
+    /// getters (e.g. [`baud_rate`](Self::baud_rate)) are served from the cache
+    /// until they are older than `ttl`. `None` disables the cache.
+    ///
+    /// The cache is shared by all clones of the client and is invalidated
+    /// after a setting was written. The authorization ([`kppa`](Self::kppa))
+    /// is never cached.
+    pub fn set_settings_cache_ttl(&mut self, ttl: Option<Duration>) {
+        self.settings_cache_ttl = ttl;
+    }
+
+    /// Returns the time to live of the settings cache, `None` if it is disabled.
+    pub fn settings_cache_ttl(&self) -> Option<Duration> {
+        self.settings_cache_ttl
+    }
+
+    /// Clears the settings cache, e.g. after the settings were changed on the
+    /// meter's display.
+    pub fn invalidate_settings_cache(&self) {
+        self.settings_cache.lock().unwrap().clear();
+    }
+
+    fn cached_setting(&self, address: u16) -> Option<Vec<proto::Word>> {
+        let ttl = self.settings_cache_ttl?;
+        let cache = self.settings_cache.lock().unwrap();
+        let (words, read_at) = cache.get(&address)?;
+        (Instant::now().saturating_duration_since(*read_at) < ttl).then(|| words.clone())
+    }
+
+    fn cache_setting(&self, address: u16, words: Vec<proto::Word>) {
+        if self.settings_cache_ttl.is_some() {
+            let now = Instant::now();
+            self.settings_cache
+                .lock()
+                .unwrap()
+                .insert(address, (words, now));
+        }
+    }
+
+    /// Watches the `measurement` for a crossed `threshold`, the events are
+    /// sent to the returned channel.
+    ///
+    /// The watches are shared by all clones of the client and are checked
+    /// after every [`read_all`](Self::read_all), with the scaled values. A
+    /// watch is removed when its receiver is dropped.
+    pub fn watch(
+        &self,
+        measurement: proto::Measurement,
+        threshold: Threshold,
+    ) -> mpsc::UnboundedReceiver<WatchEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.watches
+            .lock()
+            .unwrap()
+            .push((Watch::new(measurement, threshold), sender));
+        receiver
+    }
+
+    /// Checks the watches with the values and sends the events, missing
+    /// values are skipped.
+    fn check_watches(&self, value: impl Fn(proto::Measurement) -> Option<f32>) {
+        let timestamp = SystemTime::now();
+        self.watches.lock().unwrap().retain_mut(|(watch, sender)| {
+            match value(watch.measurement()).and_then(|value| watch.check(value, timestamp)) {
+                Some(event) => sender.send(event).is_ok(),
+                None => true,
+            }
+        });
+    }
+
+    /// Sets the firmware of the meter, which is otherwise detected by
+    /// [`read_all_partial`](Self::read_all_partial).
+    pub fn set_firmware(&mut self, firmware: Option<Firmware>) {
+        self.firmware = firmware;
+    }
+
+    /// Returns the order of the two words of 32-bit values.
+    pub fn word_order(&self) -> proto::WordOrder {
+        self.word_order
+    }
+
+    /// Sets the minimum interval between the last response of the meter and
+    /// the next request.
+    ///
+    /// Clients created with [`connect`](Self::connect) for a Modbus/RTU
+    /// connection start with the silent interval of 3.5 characters at the
+    /// configured baud rate, see [`BaudRate::silent_interval`](proto::BaudRate::silent_interval).
+    /// Some devices and USB to RS485 adapters need longer pauses.
+    pub fn set_min_request_interval(&mut self, interval: Duration) {
+        self.min_request_interval = interval;
+    }
+
+    /// Returns the minimum interval between the last response and the next request.
+    pub fn min_request_interval(&self) -> Duration {
+        self.min_request_interval
+    }
+
+    /// Sets the number of connections used to read the batches of
+    /// [`read_all`](Self::read_all) concurrently, `1` reads them one after
+    /// the other.
+    ///
+    /// Applies only to clients created with [`connect`](Self::connect) for a
+    /// Modbus/TCP connection, the additional connections are opened with the
+    /// first read. The minimum request interval doesn't apply between the
+    /// concurrent requests. If a concurrent read fails, the additional
+    /// connections are closed and the batches are read one after the other
+    /// over the main connection, with its retries and reconnection.
+    #[cfg(feature = "tokio-tcp")]
+    pub fn set_tcp_parallelism(&mut self, parallelism: usize) {
+        self.tcp_parallelism = parallelism.max(1);
+    }
+
+    /// Returns the number of connections of the concurrent reads.
+    pub fn tcp_parallelism(&self) -> usize {
+        self.tcp_parallelism
+    }
+
+    /// Returns the time left until `interval` has passed since the last contact.
+    fn pacing_wait(&self, interval: Duration) -> Option<Duration> {
+        let last_contact = *self.last_contact.lock().unwrap();
+        last_contact.and_then(|last_contact| interval.checked_sub(last_contact.elapsed()))
+    }
+
+    /// Changes the slave id of the requests, e.g. to poll several meters on
+    /// one shared serial bus with a single client.
+    ///
+    /// The slave is changed for all clones of the client, which share the
+    /// context. A reconnection keeps the new slave, the cached identity and
+    /// settings of the previous meter are discarded.
+    pub async fn set_slave(&mut self, slave: impl Into<Slave>) {
+        let slave = slave.into();
+        let mut ctx = self.ctx.lock().await;
+        ctx.set_slave(slave.into());
+        if self.slave.lock().unwrap().replace(slave) == Some(slave) {
+            return;
+        }
+        if let Some(reconnect) = &self.reconnect {
+            reconnect.params.lock().unwrap().set_slave(slave);
+        }
+        *self.identity.lock().unwrap() = None;
+        self.invalidate_settings_cache();
+    }
+
+    /// Returns the slave id of the requests, if the client was created with
+    /// [`connect`](Self::connect) or it was set with
+    /// [`set_slave`](Self::set_slave).
+    pub fn slave(&self) -> Option<Slave> {
+        *self.slave.lock().unwrap()
+    }
+
+    /// Returns the time of the last response received from the meter.
+    pub fn last_contact(&self) -> Option<std::time::Instant> {
+        self.last_contact.lock().unwrap().map(Instant::into_std)
+    }
+
+    /// Returns the counters of the requests on the link to the meter, shared
+    /// by all clones of the client.
+    pub fn link_stats(&self) -> LinkStats {
+        *self.link_stats.lock().unwrap()
+    }
+
+    /// Checks that the connection is alive by reading the meter code.
+    ///
+    /// If the client was created with [`connect`](Self::connect), a dead
+    /// connection is re-established.
+    pub async fn probe(&mut self) -> Result<()> {
+        self.meter_code().await.map(|_| ())
+    }
+
+    /// Probes the connection if there was no contact with the meter within
+    /// `idle`, so long-lived but idle connections which were silently dropped
+    /// (e.g. by a Modbus TCP gateway) are detected before the next request.
+    pub async fn probe_if_idle(&mut self, idle: std::time::Duration) -> Result<()> {
+        match self.last_contact() {
+            Some(last_contact) if last_contact.elapsed() < idle => Ok(()),
+            _ => self.probe().await,
+        }
+    }
+
+    /// Closes the connection and opens a new one, e.g. after repeated
+    /// failures which did not surface as transport errors.
+    ///
+    /// Returns `false` without reconnecting if the client was not created
+    /// with [`connect`](Self::connect).
+    pub async fn reconnect(&mut self) -> Result<bool> {
+        let Some(reconnect) = &self.reconnect else {
+            return Ok(false);
+        };
+        let mut ctx = self.ctx.lock().await;
+        let params = reconnect.params.lock().unwrap().clone();
+        *ctx = SDM72::connect(&params).await?;
+        *self.identity.lock().unwrap() = None;
+        Ok(true)
     }
 
     /// Clones and returns the underlying `Arc<Mutex<Context>>`.
@@ -95,65 +920,627 @@ impl SafeClient {
         self.ctx.clone()
     }
 
-    read_holding!(system_type, SystemType);
+    read_holding!(system_type, SystemType, mode);
     write_holding!(system_type, SystemType);
     read_holding!(pulse_width, PulseWidth);
     write_holding!(pulse_width, PulseWidth);
-    read_holding!(kppa, KPPA);
+    read_holding!(kppa, KPPA, uncached);
 
     /// Sets the Key Parameter Programming Authorization (KPPA).
     ///
     /// This is required to change settings on the meter.
     pub async fn set_kppa(&mut self, password: proto::Password) -> Result<()> {
-        let mut ctx = self.ctx.lock().await;
-        SDM72::set_kppa(&mut ctx, password).await
+        with_ctx!(self, Write, Holding, "KPPA", Some(<proto::KPPA as proto::ModbusParam>::ADDRESS), ctx => SDM72::set_kppa_with_word_order(&mut ctx, password, self.word_order).await)
     }
 
-    read_holding!(parity_and_stop_bit, ParityAndStopBit);
+    read_holding!(parity_and_stop_bit, ParityAndStopBit, mode);
     write_holding!(parity_and_stop_bit, ParityAndStopBit);
     read_holding!(address, Address);
 
     pub async fn set_address(&mut self, value: proto::Address) -> Result<()> {
-        let mut ctx = self.ctx.lock().await;
-        SDM72::set_address(&mut ctx, value).await?;
-        ctx.set_slave(tokio_modbus::Slave(*value));
+        self.authorize().await?;
+        with_ctx!(self, Write, Holding, "Address", Some(<proto::Address as proto::ModbusParam>::ADDRESS), ctx => SDM72::set_address_with_word_order(&mut ctx, value, self.word_order).await.map(|()| {
+            ctx.set_slave(tokio_modbus::Slave(*value));
+        }))?;
+        *self.slave.lock().unwrap() = Some(value.into());
+        self.invalidate_settings_cache();
+        self.follow_comm_change(Setting::Address(value)).await?;
+        if self.write_verify {
+            let actual = self.address().await?;
+            verify(Setting::Address(value), Setting::Address(actual))?;
+        }
         Ok(())
     }
 
-    read_holding!(pulse_constant, PulseConstant);
+    read_holding!(pulse_constant, PulseConstant, mode);
     write_holding!(pulse_constant, PulseConstant);
     read_holding!(password, Password);
     write_holding!(password, Password);
-    read_holding!(baud_rate, BaudRate);
+    read_holding!(baud_rate, BaudRate, mode);
     write_holding!(baud_rate, BaudRate);
     read_holding!(auto_scroll_time, AutoScrollTime);
     write_holding!(auto_scroll_time, AutoScrollTime);
     read_holding!(backlight_time, BacklightTime);
     write_holding!(backlight_time, BacklightTime);
-    read_holding!(pulse_energy_type, PulseEnergyType);
+    read_holding!(pulse_energy_type, PulseEnergyType, mode);
     write_holding!(pulse_energy_type, PulseEnergyType);
 
     /// Resets the historical data on the meter.
     ///
     /// This requires KPPA authorization.
     pub async fn reset_historical_data(&mut self) -> Result<()> {
-        let mut ctx = self.ctx.lock().await;
-        SDM72::reset_historical_data(&mut ctx).await
+        self.authorize().await?;
+        with_ctx!(self, Write, Holding, "ResetHistoricalData", Some(<proto::ResetHistoricalData as proto::ModbusParam>::ADDRESS), ctx => SDM72::reset_historical_data(&mut ctx).await)
+    }
+
+    /// Resets the data of the target on the meter.
+    ///
+    /// This requires KPPA authorization.
+    pub async fn reset(&mut self, target: proto::ResetTarget) -> Result<()> {
+        self.authorize().await?;
+        with_ctx!(self, Write, Holding, target.register(), Some(<proto::ResetHistoricalData as proto::ModbusParam>::ADDRESS), ctx => SDM72::reset(&mut ctx, target).await)
+    }
+
+    /// Acquires the KPPA with the password, unless the client is already
+    /// authorized, and starts a [`SettingsSession`] to write several settings.
+    ///
+    /// Fails with [`Error::Unauthorized`] if the meter doesn't grant the
+    /// authorization.
+    pub async fn begin_settings(
+        &mut self,
+        password: proto::Password,
+    ) -> Result<SettingsSession<'_>> {
+        if self.kppa().await? != proto::KPPA::Authorized {
+            self.set_kppa(password).await?;
+            if self.kppa().await? != proto::KPPA::Authorized {
+                return Err(Error::Unauthorized);
+            }
+        }
+        Ok(SettingsSession {
+            client: self,
+            settings: Vec::new(),
+            delay: settings_diff::DEFAULT_WRITE_DELAY,
+        })
     }
 
-    read_holding!(serial_number, SerialNumber);
-    read_holding!(meter_code, MeterCode);
-    read_holding!(software_version, SoftwareVersion);
+    /// Writes a single [`Setting`], e.g. one of the changes returned by
+    /// [`settings_diff::diff`](crate::settings_diff::diff).
+    pub async fn write_setting(&mut self, setting: Setting) -> Result<()> {
+        match setting {
+            Setting::SystemType(val) => self.set_system_type(val).await,
+            Setting::PulseWidth(val) => self.set_pulse_width(val).await,
+            Setting::PulseConstant(val) => self.set_pulse_constant(val).await,
+            Setting::PulseEnergyType(val) => self.set_pulse_energy_type(val).await,
+            Setting::Password(val) => self.set_password(val).await,
+            Setting::AutoScrollTime(val) => self.set_auto_scroll_time(val).await,
+            Setting::BacklightTime(val) => self.set_backlight_time(val).await,
+            Setting::ParityAndStopBit(val) => self.set_parity_and_stop_bit(val).await,
+            Setting::BaudRate(val) => self.set_baud_rate(val).await,
+            Setting::Address(val) => self.set_address(val).await,
+        }
+    }
+
+    read_holding!(serial_number, SerialNumber, identity);
+    read_holding!(meter_code, MeterCode, identity);
+    read_holding!(software_version, SoftwareVersion, identity);
+
+    /// Reads raw words from Modbus input registers.
+    ///
+    /// This is an escape hatch for registers which are not covered by the
+    /// [`protocol`](crate::protocol) module, e.g. firmware specific ones.
+    pub async fn read_raw_input(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<proto::Word>> {
+        self.read_input("input registers", address, quantity).await
+    }
+
+    /// Reads input registers, as holding registers if
+    /// [`input_via_holding`](Self::set_input_via_holding) is set.
+    async fn read_input(
+        &mut self,
+        register: &'static str,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<proto::Word>> {
+        if self.input_via_holding {
+            with_ctx!(self, Read, Input, register, Some(address), ctx => SDM72::read_raw_holding(&mut ctx, address, quantity).await)
+        } else {
+            with_ctx!(self, Read, Input, register, Some(address), ctx => SDM72::read_raw_input(&mut ctx, address, quantity).await)
+        }
+    }
+
+    /// Reads raw words from Modbus holding registers.
+    ///
+    /// This is an escape hatch for registers which are not covered by the
+    /// [`protocol`](crate::protocol) module, e.g. firmware specific ones.
+    pub async fn read_raw_holding(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<proto::Word>> {
+        with_ctx!(self, Read, Holding, "holding registers", Some(address), ctx => SDM72::read_raw_holding(&mut ctx, address, quantity).await)
+    }
+
+    /// Writes raw words to Modbus holding registers.
+    ///
+    /// This is an escape hatch for registers which are not covered by the
+    /// [`protocol`](crate::protocol) module, e.g. firmware specific ones.
+    pub async fn write_raw_holding(&mut self, address: u16, words: &[proto::Word]) -> Result<()> {
+        with_ctx!(self, Write, Holding, "holding registers", Some(address), ctx => SDM72::write_raw_holding(&mut ctx, address, words).await)?;
+        self.invalidate_settings_cache();
+        Ok(())
+    }
+
+    /// Reads the identity of the meter with two requests, separated by the
+    /// [minimum request interval](Self::set_min_request_interval).
+    ///
+    /// The identity is read once per connection and served from memory until
+    /// the connection is re-established.
+    pub async fn identify(&mut self) -> Result<MeterIdentity> {
+        let delay = self.min_request_interval;
+        self.identify_paced(delay).await
+    }
+
+    async fn identify_paced(&mut self, delay: Duration) -> Result<MeterIdentity> {
+        if let Some(identity) = *self.identity.lock().unwrap() {
+            return Ok(identity);
+        }
+        let (offset, quantity) = tokio_common::IDENTITY_BATCH;
+        let rsp = with_ctx!(self, Read, Holding, "identity", Some(offset), ctx => SDM72::read_raw_holding(&mut ctx, offset, quantity).await)?;
+        self.pace(delay).await;
+        let software_version = self.software_version().await?;
+        let identity =
+            tokio_common::decode_identity(&self.word_order.apply(rsp), software_version)?;
+        *self.identity.lock().unwrap() = Some(identity);
+        Ok(identity)
+    }
+
+    /// Returns the firmware of the meter, it is detected with
+    /// [`identify`](Self::identify) on the first call.
+    pub async fn firmware(&mut self) -> Result<Firmware> {
+        if let Some(firmware) = self.firmware {
+            return Ok(firmware);
+        }
+        let firmware = Firmware::detect(&self.identify().await?);
+        self.firmware = Some(firmware);
+        Ok(firmware)
+    }
+
+    /// Reads the measurement values supported by the [firmware](Self::firmware)
+    /// of the meter, e.g. the first generation SDM72D-M which lacks some
+    /// registers. The values of unsupported registers are `None`.
+    pub async fn read_all_partial(&mut self) -> Result<AllValuesPartial> {
+        let firmware = self.firmware().await?;
+        Ok(self.read_batches(Some(firmware), false).await?.values)
+    }
+
+    /// Reads all measurement values like [`read_all`](Self::read_all), but a
+    /// failed batch doesn't abort the read. The values of the failed batches
+    /// are `None` and their errors are listed in the report.
+    ///
+    /// The batches unsupported by the [firmware](Self::set_firmware) are
+    /// skipped if it is known. Fails only if no batch could be read.
+    pub async fn read_all_lenient(&mut self) -> Result<ReadReport> {
+        let firmware = self.firmware;
+        let mut report = self.read_batches(firmware, true).await?;
+        if report.values == AllValuesPartial::default() {
+            if let Some(err) = report.errors.pop() {
+                return Err(err);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Reads the batches of the values supported by the firmware, all if it is
+    /// `None`. If `lenient` is set, the errors of failed batches are collected
+    /// in the report instead of returned.
+    async fn read_batches(
+        &mut self,
+        firmware: Option<Firmware>,
+        lenient: bool,
+    ) -> Result<ReadReport> {
+        let delay = self.min_request_interval;
+        let mut rsp: [Option<Vec<proto::Word>>; 4] = Default::default();
+        let mut errors = Vec::new();
+        let batches = tokio_common::VALUE_BATCHES.into_iter().enumerate();
+        for (n, (i, batch)) in batches
+            .filter(|(i, _)| firmware.is_none_or(|firmware| firmware.supports_batch(*i)))
+            .enumerate()
+        {
+            if n > 0 {
+                self.pace(delay).await;
+            }
+            match self.read_batch(batch, delay).await {
+                Ok(words) => rsp[i] = Some(self.word_order.apply(words)),
+                Err(err) if lenient => errors.push(err),
+                Err(err) => return Err(err),
+            }
+        }
+        let mut values = tokio_common::decode_values_partial(&rsp)?;
+        let mut rejected = self.value_validation.check_partial(&mut values);
+        if !lenient && !rejected.is_empty() {
+            return Err(rejected.remove(0));
+        }
+        errors.append(&mut rejected);
+        let values = self.scaling.apply_partial(values);
+        self.check_watches(|measurement| values.value(measurement));
+        Ok(ReadReport { values, errors })
+    }
 
     /// Reads all settings from the meter in a single batch operation.
-    pub async fn read_all_settings(&mut self, delay: &std::time::Duration) -> Result<AllSettings> {
-        let mut ctx = self.ctx.lock().await;
-        SDM72::read_all_settings(&mut ctx, delay).await
+    ///
+    /// The batches are separated by the [minimum request
+    /// interval](Self::set_min_request_interval).
+    pub async fn read_all_settings(&mut self) -> Result<AllSettings> {
+        let delay = self.min_request_interval;
+        self.read_all_settings_paced(delay).await
+    }
+
+    /// Reads all settings with an explicit delay between the batches.
+    #[deprecated(note = "use `set_min_request_interval` and `read_all_settings` instead")]
+    pub async fn read_all_settings_with_delay(
+        &mut self,
+        delay: &std::time::Duration,
+    ) -> Result<AllSettings> {
+        let delay = self.min_request_interval.max(*delay);
+        self.read_all_settings_paced(delay).await
+    }
+
+    /// Reads the batches of the settings, the client is locked for each batch
+    /// only, not while waiting `delay` between them. The
+    /// [identity](Self::identify) is only read with the first settings of a
+    /// connection.
+    async fn read_all_settings_paced(&mut self, delay: Duration) -> Result<AllSettings> {
+        let (offset, quantity) = tokio_common::SETTINGS_BATCH;
+        let rsp = with_ctx!(self, Read, Holding, "all settings", Some(offset), ctx => SDM72::read_raw_holding(&mut ctx, offset, quantity).await)?;
+        let cached = *self.identity.lock().unwrap();
+        let identity = match cached {
+            Some(identity) => identity,
+            None => {
+                self.pace(delay).await;
+                self.identify_paced(delay).await?
+            }
+        };
+        tokio_common::decode_settings(
+            &self.word_order.apply(rsp),
+            identity.serial_number,
+            identity.meter_code,
+            identity.software_version,
+            self.decode_mode,
+        )
+    }
+
+    /// Waits without holding the lock until `delay` has passed since the last contact.
+    async fn pace(&self, delay: Duration) {
+        if let Some(wait) = self.pacing_wait(delay) {
+            tokio::time::sleep(wait).await;
+        }
     }
 
     /// Reads all measurement values from the meter in a single batch operation.
-    pub async fn read_all(&mut self, delay: &std::time::Duration) -> Result<AllValues> {
+    ///
+    /// The batches are separated by the [minimum request
+    /// interval](Self::set_min_request_interval).
+    pub async fn read_all(&mut self) -> Result<AllValues> {
+        let delay = self.min_request_interval;
+        self.read_all_paced(delay).await
+    }
+
+    /// Reads all measurement values like [`read_all`](Self::read_all), with
+    /// the times the read started and completed.
+    pub async fn read_all_timestamped(&mut self) -> Result<TimestampedAllValues> {
+        let read_started = Timestamp::now();
+        let values = self.read_all().await?;
+        Ok(TimestampedAllValues {
+            read_started,
+            read_completed: Timestamp::now(),
+            values,
+        })
+    }
+
+    /// Returns a stream which polls the selected measurements every `interval`,
+    /// all measurements are read if the selection is empty.
+    ///
+    /// The stream shares the connection with this client and yields failed
+    /// reads as well. Dropping the stream stops the polling. If a read takes
+    /// longer than the interval, the missed polls are skipped.
+    pub fn subscribe(
+        &self,
+        interval: Duration,
+        selection: Vec<proto::Measurement>,
+    ) -> impl Stream<Item = Result<TimestampedValues>> {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        futures_util::stream::unfold(
+            (self.clone(), ticker, selection),
+            |(mut client, mut ticker, selection)| async move {
+                ticker.tick().await;
+                let result = client.read_all().await.map(|values| TimestampedValues {
+                    timestamp: SystemTime::now(),
+                    values: values.select(&selection),
+                });
+                Some((result, (client, ticker, selection)))
+            },
+        )
+    }
+
+    /// Reads all measurement values with an explicit delay between the batches.
+    #[deprecated(note = "use `set_min_request_interval` and `read_all` instead")]
+    pub async fn read_all_with_delay(&mut self, delay: &std::time::Duration) -> Result<AllValues> {
+        let delay = self.min_request_interval.max(*delay);
+        self.read_all_paced(delay).await
+    }
+
+    /// Reads a batch of input registers, a failed batch is retried or split
+    /// according to the [`BatchRetryPolicy`].
+    async fn read_batch(
+        &mut self,
+        (offset, quantity): (u16, u16),
+        delay: Duration,
+    ) -> Result<Vec<proto::Word>> {
+        let mut attempt = 0;
+        loop {
+            let result = self.read_input("all values", offset, quantity).await;
+            match result {
+                // An exception response would be the same again, only a failure
+                // of the link, e.g. a timeout, is worth a retry
+                Err(err) if err.link_error().is_some() && attempt < self.batch_retry.retries => {
+                    attempt += 1;
+                    self.pace(delay).await;
+                }
+                Err(err) if self.batch_retry.split => {
+                    let Some(halves) = tokio_common::split_batch((offset, quantity)) else {
+                        return Err(err);
+                    };
+                    let mut words = Vec::with_capacity(quantity as usize);
+                    for (offset, quantity) in halves {
+                        self.pace(delay).await;
+                        words.extend(self.read_input("all values", offset, quantity).await?);
+                    }
+                    return Ok(words);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Reads the batches of the values over the main and the additional
+    /// connections at once, `None` if the client doesn't read concurrently.
+    #[cfg(feature = "tokio-tcp")]
+    async fn read_all_concurrent(&self) -> Option<Result<[Vec<proto::Word>; 4]>> {
+        if self.tcp_parallelism < 2 {
+            return None;
+        }
+        let params = self.reconnect.as_ref()?.params.lock().unwrap().clone();
+        if !params.is_tcp() {
+            return None;
+        }
+        let mut extra = self.extra_ctxs.lock().await;
+        extra.truncate(self.tcp_parallelism - 1);
+        while extra.len() < self.tcp_parallelism - 1 {
+            match SDM72::connect(&params).await {
+                Ok(ctx) => extra.push(ctx),
+                Err(err) => return Some(Err(err)),
+            }
+        }
         let mut ctx = self.ctx.lock().await;
-        SDM72::read_all(&mut ctx, delay).await
+        let result = SDM72::read_value_batches_concurrent(
+            std::iter::once(&mut *ctx).chain(extra.iter_mut()),
+            self.input_via_holding,
+            |address, duration, result| {
+                self.link_stats.lock().unwrap().record(result);
+                if let Some(instrumentation) = &self.instrumentation {
+                    instrumentation.record(&RequestRecord {
+                        operation: Operation::Read,
+                        register: "all values",
+                        address: Some(address),
+                        duration,
+                        retries: 0,
+                        outcome: RequestOutcome::of(result),
+                    });
+                }
+            },
+        )
+        .await;
+        match &result {
+            Ok(_) => *self.last_contact.lock().unwrap() = Some(Instant::now()),
+            Err(_) => extra.clear(),
+        }
+        Some(result)
+    }
+
+    /// Reads the batches of the values, the client is locked for each batch
+    /// only, not while waiting `delay` between them.
+    async fn read_all_paced(&mut self, delay: Duration) -> Result<AllValues> {
+        #[cfg(feature = "tokio-tcp")]
+        let concurrent = self.read_all_concurrent().await;
+        #[cfg(not(feature = "tokio-tcp"))]
+        let concurrent: Option<Result<[Vec<proto::Word>; 4]>> = None;
+        let rsp = match concurrent {
+            Some(Ok(rsp)) => rsp,
+            _ => {
+                let mut rsp: [Vec<proto::Word>; 4] = Default::default();
+                for (i, batch) in tokio_common::VALUE_BATCHES.into_iter().enumerate() {
+                    if i > 0 {
+                        self.pace(delay).await;
+                    }
+                    rsp[i] = self.read_batch(batch, delay).await?;
+                }
+                rsp
+            }
+        };
+        let rsp = rsp.map(|words| self.word_order.apply(words));
+        let values = tokio_common::decode_values(&rsp)?;
+        self.value_validation.check_values(&values)?;
+        let values = self.scaling.apply(values);
+        self.check_watches(|measurement| Some(values.value(measurement)));
+        Ok(values)
+    }
+}
+
+#[cfg(all(test, feature = "tokio-tcp"))]
+mod tests {
+    use super::*;
+    use crate::{recording::Recording, tokio_common::TcpOptions};
+
+    #[tokio::test]
+    async fn setting_getters_decode_mode() {
+        // The meter answers both reads of the system type with the unknown code 4
+        let recording = Recording::parse(
+            "1760600000.000000 > 00 00 00 00 00 06 01 03 00 0a 00 02\n\
+             1760600000.010000 < 00 00 00 00 00 07 01 03 04 40 80 00 00\n\
+             1760600000.020000 > 00 01 00 00 00 06 01 03 00 0a 00 02\n\
+             1760600000.030000 < 00 01 00 00 00 07 01 03 04 40 80 00 00",
+        )
+        .unwrap();
+        let params = ConnectionParams::Tcp {
+            socket_addr: ([127, 0, 0, 1], 502).into(),
+            slave: Slave(1),
+            options: TcpOptions::default(),
+        };
+        let mut client = SafeClient::new(SDM72::replay(&params, recording).unwrap());
+        assert!(matches!(
+            client.system_type().await,
+            Err(Error::Protocol(proto::Error::UnknownVariant(4.0)))
+        ));
+        client.set_decode_mode(proto::DecodeMode::Lenient);
+        assert_eq!(
+            client.system_type().await.unwrap(),
+            proto::SystemType::Unknown(4.0)
+        );
+    }
+
+    #[tokio::test]
+    async fn split_batch_after_exception() {
+        // The gateway rejects the batch, but answers its halves
+        let recording = Recording::parse(
+            "1760600000.000000 > 00 00 00 00 00 06 01 04 00 00 00 04\n\
+             1760600000.010000 < 00 00 00 00 00 03 01 84 02\n\
+             1760600000.020000 > 00 01 00 00 00 06 01 04 00 00 00 02\n\
+             1760600000.030000 < 00 01 00 00 00 07 01 04 04 43 66 00 00\n\
+             1760600000.040000 > 00 02 00 00 00 06 01 04 00 02 00 02\n\
+             1760600000.050000 < 00 02 00 00 00 07 01 04 04 43 67 00 00",
+        )
+        .unwrap();
+        let params = ConnectionParams::Tcp {
+            socket_addr: ([127, 0, 0, 1], 502).into(),
+            slave: Slave(1),
+            options: TcpOptions::default(),
+        };
+        let mut client = SafeClient::new(SDM72::replay(&params, recording).unwrap());
+        client.set_batch_retry_policy(BatchRetryPolicy {
+            retries: 2,
+            split: true,
+        });
+        // A retry of the exception would not match the recorded requests
+        assert_eq!(
+            client
+                .read_batch((0x0000, 0x0004), Duration::ZERO)
+                .await
+                .unwrap(),
+            vec![0x4366, 0x0000, 0x4367, 0x0000]
+        );
+    }
+
+    #[tokio::test]
+    async fn reset_register_of_target() {
+        // The meter rejects the reset of the maximum demand with an illegal data address
+        let recording = Recording::parse(
+            "1760600000.000000 > 00 00 00 00 00 09 01 10 f0 10 00 01 02 00 00\n\
+             1760600000.010000 < 00 00 00 00 00 03 01 90 02",
+        )
+        .unwrap();
+        let params = ConnectionParams::Tcp {
+            socket_addr: ([127, 0, 0, 1], 502).into(),
+            slave: Slave(1),
+            options: TcpOptions::default(),
+        };
+        let mut client = SafeClient::new(SDM72::replay(&params, recording).unwrap());
+        let err = client
+            .reset(proto::ResetTarget::MaxDemand)
+            .await
+            .unwrap_err();
+        assert_eq!(err.context().unwrap().register, "ResetMaxDemand");
+    }
+
+    /// Starts a Modbus/TCP server which drops the first connection after
+    /// receiving a request and answers the requests on the next connection
+    /// with `response`. Returns the address and the number of requests.
+    async fn flaky_server(
+        response: &'static [u8],
+    ) -> (std::net::SocketAddr, Arc<std::sync::atomic::AtomicUsize>) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let socket_addr = listener.local_addr().unwrap();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 260];
+            let (mut stream, _) = listener.accept().await.unwrap();
+            if stream.read(&mut buf).await.unwrap() > 0 {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+            drop(stream);
+            let (mut stream, _) = listener.accept().await.unwrap();
+            while stream.read(&mut buf).await.unwrap_or(0) > 0 {
+                counter.fetch_add(1, Ordering::SeqCst);
+                // Answer with the transaction id of the request
+                let mut frame = buf[..2].to_vec();
+                frame.extend_from_slice(&response[2..]);
+                stream.write_all(&frame).await.unwrap();
+            }
+        });
+        (socket_addr, requests)
+    }
+
+    fn fast_reconnect() -> ReconnectPolicy {
+        ReconnectPolicy {
+            max_attempts: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn replay_read_after_reconnect() {
+        let (socket_addr, requests) =
+            flaky_server(&[0, 0, 0, 0, 0, 7, 1, 3, 4, 0x40, 0x40, 0, 0]).await;
+        let params = ConnectionParams::Tcp {
+            socket_addr,
+            slave: Slave(1),
+            options: TcpOptions::default(),
+        };
+        let mut client = SafeClient::connect(params, fast_reconnect()).await.unwrap();
+        assert_eq!(
+            client.system_type().await.unwrap(),
+            proto::SystemType::Type3P4W
+        );
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn no_replay_of_write_after_reconnect() {
+        let (socket_addr, requests) =
+            flaky_server(&[0, 0, 0, 0, 0, 6, 1, 0x10, 0xf0, 0x10, 0, 1]).await;
+        let params = ConnectionParams::Tcp {
+            socket_addr,
+            slave: Slave(1),
+            options: TcpOptions::default(),
+        };
+        let mut client = SafeClient::connect(params, fast_reconnect()).await.unwrap();
+        // The meter may have reset the maximum demand before the connection broke
+        let err = client
+            .reset(proto::ResetTarget::MaxDemand)
+            .await
+            .unwrap_err();
+        assert!(err.is_transport());
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 1);
+        // The connection was re-established for the next operation
+        client.reset(proto::ResetTarget::MaxDemand).await.unwrap();
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 2);
     }
 }
@@ -5,7 +5,12 @@
 //! errors, and the `AllSettings` and `AllValues` structs, which are used to
 //! return all the settings and values from the device in one go.
 
-use crate::protocol as proto;
+use crate::{
+    labels::{Labeled, Labels},
+    modbus::{ExceptionCode, Slave, SlaveId},
+    protocol::{self as proto, ModbusParam},
+    settings_diff::Setting,
+};
 
 /// Represents all possible errors that can occur during Modbus communication.
 #[derive(Debug, thiserror::Error)]
@@ -16,16 +21,669 @@ pub enum Error {
 
     /// A Modbus exception response from the device (e.g., "Illegal Function").
     #[error(transparent)]
-    ModbusException(#[from] tokio_modbus::ExceptionCode),
+    ModbusException(#[from] ExceptionCode),
 
     /// A transport or communication error from the underlying `tokio-modbus` client.
     #[error(transparent)]
     Modbus(#[from] tokio_modbus::Error),
+
+    /// An error of a register access, with the details of the access.
+    #[error("{context}: {source}")]
+    Register {
+        context: RegisterContext,
+        source: Box<Error>,
+    },
+
+    /// The value read back after a write differs from the written one.
+    #[error("Verification of {} failed, wrote {expected} but read back {actual}", expected.name())]
+    VerificationFailed {
+        expected: crate::settings_diff::Setting,
+        actual: crate::settings_diff::Setting,
+    },
+
+    /// The password of a [`PasswordProvider`](crate::password::PasswordProvider)
+    /// cannot be read or is invalid.
+    #[error("Cannot get the password from {0}")]
+    Password(String),
+
+    /// The meter didn't grant the authorization (KPPA) after the password was
+    /// written, usually because the password is wrong.
+    #[error("The meter did not grant the authorization, the password may be wrong")]
+    Unauthorized,
+
+    /// The meter doesn't respond after a setting which affects the
+    /// communication was written, e.g. because the serial port of a gateway
+    /// still uses the old baud rate.
+    #[error("The meter does not respond after changing the {} to {setting}: {source}", setting.name().to_lowercase())]
+    Unreachable {
+        setting: Setting,
+        source: Box<Error>,
+    },
+
+    /// The setting must not be written to the broadcast address, e.g. the
+    /// address, which must differ between the meters of a bus.
+    #[error("The {} cannot be written to the broadcast address", .0.name().to_lowercase())]
+    NotBroadcastable(Setting),
+
+    /// A decoded value was rejected by the [`ValueValidation`], e.g. a NaN
+    /// returned while the meter updates its registers. `raw` are the bits of
+    /// the 32-bit float as read.
+    #[error("Implausible value {} of {register} (raw {raw:#010X})", f32::from_bits(*.raw))]
+    ImplausibleValue { register: &'static str, raw: u32 },
+}
+
+impl From<tokio_modbus::ExceptionCode> for Error {
+    fn from(code: tokio_modbus::ExceptionCode) -> Self {
+        Error::ModbusException(code.into())
+    }
+}
+
+impl Error {
+    /// Returns `true` if the error was caused by the transport layer (e.g. a
+    /// broken TCP connection or a serial port failure), in which case the
+    /// connection may be re-established.
+    pub fn is_transport(&self) -> bool {
+        match self {
+            Error::Modbus(tokio_modbus::Error::Transport(_)) => true,
+            Error::Register { source, .. } => source.is_transport(),
+            _ => false,
+        }
+    }
+
+    /// Classifies the failure of a request on the link to the meter, `None`
+    /// if the meter answered with a valid frame.
+    pub fn link_error(&self) -> Option<LinkError> {
+        match self {
+            Error::Modbus(tokio_modbus::Error::Transport(err)) => Some(match err.kind() {
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock => LinkError::Timeout,
+                std::io::ErrorKind::InvalidData
+                    if err.to_string().to_ascii_uppercase().contains("CRC") =>
+                {
+                    LinkError::Crc
+                }
+                std::io::ErrorKind::InvalidData => LinkError::MalformedFrame,
+                _ => LinkError::Transport,
+            }),
+            Error::Modbus(tokio_modbus::Error::Protocol(_)) => Some(LinkError::MalformedFrame),
+            Error::Register { source, .. } => source.link_error(),
+            _ => None,
+        }
+    }
+
+    /// Returns the details of the failed register access, if known.
+    pub fn context(&self) -> Option<&RegisterContext> {
+        match self {
+            Error::Register { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Explains an exception response of the meter with the register of the
+    /// access, if known, and what to do about it, e.g. "The meter does not
+    /// support register 0x00E0 NeutralCurrent, ...". `None` for other errors
+    /// and the exception codes outside the Modbus specification.
+    pub fn hint(&self) -> Option<String> {
+        let (context, code) = match self {
+            Error::ModbusException(code) => (None, *code),
+            Error::Register { context, source } => match source.as_ref() {
+                Error::ModbusException(code) => (Some(context), *code),
+                source => return source.hint(),
+            },
+            _ => return None,
+        };
+        let target = match context {
+            Some(RegisterContext {
+                register,
+                address: Some(address),
+                ..
+            }) => format!("register {address:#06X} {register}"),
+            Some(context) => context.register.to_string(),
+            None => "the register".to_string(),
+        };
+        let operation = match context.map(|context| context.operation) {
+            Some(Operation::Write) => "writing",
+            _ => "reading",
+        };
+        Some(match code {
+            ExceptionCode::IllegalFunction => format!(
+                "The meter or gateway does not support the function code of {operation} \
+                 {target}, some RS485 to TCP gateways only forward function code 03 (read \
+                 holding registers)"
+            ),
+            ExceptionCode::IllegalDataAddress
+                if context
+                    .filter(|context| context.kind == proto::RegisterKind::Input)
+                    .and_then(|context| context.address)
+                    .is_some_and(|address| !Firmware::V1.supports_address(address)) =>
+            {
+                format!(
+                    "The meter does not support {target}, the first generation SDM72D-M lacks \
+                     it; only the SDM72D-M-2 (meter code 0089) has it"
+                )
+            }
+            ExceptionCode::IllegalDataAddress if context.is_some_and(|c| c.address.is_none()) => {
+                format!(
+                    "The meter does not support a register of {target}, e.g. one added by a \
+                     later firmware; read the registers individually to find it"
+                )
+            }
+            ExceptionCode::IllegalDataAddress => format!(
+                "The meter does not support {target}, e.g. because it was added by a later \
+                 firmware; compare the software version of the meter"
+            ),
+            ExceptionCode::IllegalDataValue if operation == "writing" => format!(
+                "The meter rejected the value of {target}: it is outside the range of this \
+                 model, or the setting requires the authorization (KPPA) with the password"
+            ),
+            ExceptionCode::IllegalDataValue => format!(
+                "The meter rejected the quantity of words of {target}, the register may have \
+                 another size on this model"
+            ),
+            ExceptionCode::ServerDeviceFailure | ExceptionCode::MemoryParityError => format!(
+                "The meter reported an internal failure while {operation} {target}; power-cycle \
+                 it and replace it if the failure persists"
+            ),
+            ExceptionCode::Acknowledge | ExceptionCode::ServerDeviceBusy => format!(
+                "The meter is busy, e.g. while it stores a setting; retry {operation} {target} \
+                 later or increase the delay between requests"
+            ),
+            ExceptionCode::GatewayPathUnavailable => {
+                "The gateway has no path to the serial bus of the meter, check the configuration \
+                 of the gateway"
+                    .to_string()
+            }
+            ExceptionCode::GatewayTargetDevice => format!(
+                "The gateway got no answer from {}, check the slave address, baud rate, parity \
+                 and wiring of the meter",
+                match context.and_then(|context| context.slave) {
+                    Some(slave) => format!("slave {slave}"),
+                    None => "the meter".to_string(),
+                }
+            ),
+            ExceptionCode::Custom(_) => return None,
+        })
+    }
+}
+
+/// The kind of a failed request on the link to the meter, see
+/// [`Error::link_error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkError {
+    /// The meter didn't answer in time.
+    Timeout,
+    /// A frame with an invalid CRC was received, usually a wiring problem
+    /// like a missing termination.
+    Crc,
+    /// A frame which is truncated or doesn't match the request was received.
+    MalformedFrame,
+    /// Any other transport error, e.g. a refused connection.
+    Transport,
+}
+
+/// The kind of a register access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Operation {
+    Read,
+    Write,
+}
+
+/// Describes a failed register access, e.g. "reading SystemType at 0x000A on slave 1".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterContext {
+    pub operation: Operation,
+    /// The register table of the address, input registers read as holding
+    /// registers are still input registers.
+    pub kind: proto::RegisterKind,
+    /// The name of the register (e.g. `SystemType`) or of a block of registers.
+    pub register: &'static str,
+    /// The Modbus address of the first register, if the access is to a single address.
+    pub address: Option<u16>,
+    /// The slave id, if the client knows the connection parameters.
+    pub slave: Option<SlaveId>,
+}
+impl std::fmt::Display for RegisterContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.operation {
+            Operation::Read => write!(f, "reading {}", self.register)?,
+            Operation::Write => write!(f, "writing {}", self.register)?,
+        }
+        if let Some(address) = self.address {
+            write!(f, " at {address:#06x}")?;
+        }
+        if let Some(slave) = self.slave {
+            write!(f, " on slave {slave}")?;
+        }
+        Ok(())
+    }
 }
 
 /// The result type for tokio operations.
 pub(crate) type Result<T> = std::result::Result<T, Error>;
 
+/// The parameters required to establish (and re-establish) a connection to the meter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionParams {
+    /// A Modbus/TCP connection.
+    Tcp {
+        /// The socket address of the meter or gateway (e.g. `192.168.0.222:502`).
+        socket_addr: std::net::SocketAddr,
+        /// The Modbus unit identifier.
+        slave: Slave,
+        /// The socket options of the connection.
+        options: TcpOptions,
+    },
+    /// A Modbus/RTU connection over a serial port.
+    Rtu {
+        /// The path to the serial port device (e.g. `/dev/ttyUSB0`).
+        device: String,
+        /// The baud rate for the serial communication.
+        baud_rate: proto::BaudRate,
+        /// The parity and stop bit settings.
+        parity_and_stop_bit: proto::ParityAndStopBit,
+        /// The RS485 address of the meter.
+        slave: Slave,
+    },
+    /// Modbus/RTU frames tunneled through a TCP connection, e.g. to a serial
+    /// device server which does not translate to Modbus/TCP.
+    RtuOverTcp {
+        /// The socket address of the serial device server.
+        socket_addr: std::net::SocketAddr,
+        /// The RS485 address of the meter.
+        slave: Slave,
+        /// The socket options of the connection.
+        options: TcpOptions,
+    },
+    /// A Modbus/TCP connection secured with TLS.
+    #[cfg(feature = "tokio-tcp-tls")]
+    TcpTls {
+        /// The socket address of the meter or gateway (e.g. `192.168.0.222:802`).
+        socket_addr: std::net::SocketAddr,
+        /// The name the server certificate is verified against.
+        server_name: String,
+        /// The TLS client configuration.
+        tls: TlsConfig,
+        /// The Modbus unit identifier.
+        slave: Slave,
+        /// The socket options of the connection.
+        options: TcpOptions,
+    },
+}
+impl ConnectionParams {
+    /// Creates the parameters for Modbus/RTU frames over a TCP connection.
+    pub fn rtu_over_tcp(socket_addr: std::net::SocketAddr, slave: Slave) -> Self {
+        ConnectionParams::RtuOverTcp {
+            socket_addr,
+            slave,
+            options: TcpOptions::default(),
+        }
+    }
+
+    /// Creates the parameters for a Modbus/TCP connection secured with TLS.
+    ///
+    /// The certificate of the server is verified against `server_name`
+    /// according to the `tls` configuration, which also holds the client
+    /// certificate if the server requires one.
+    #[cfg(feature = "tokio-tcp-tls")]
+    pub fn tcp_tls(
+        socket_addr: std::net::SocketAddr,
+        server_name: impl Into<String>,
+        tls: std::sync::Arc<tokio_rustls::rustls::ClientConfig>,
+    ) -> Self {
+        ConnectionParams::TcpTls {
+            socket_addr,
+            server_name: server_name.into(),
+            tls: TlsConfig(tls),
+            slave: Slave::tcp_device(),
+            options: TcpOptions::default(),
+        }
+    }
+
+    /// Returns the minimum interval between two requests, which is the silent
+    /// interval between two frames for Modbus/RTU.
+    pub fn min_request_interval(&self) -> std::time::Duration {
+        match self {
+            ConnectionParams::Rtu { baud_rate, .. } => baud_rate.silent_interval(),
+            _ => std::time::Duration::ZERO,
+        }
+    }
+
+    /// Returns `true` for Modbus/TCP connections, with or without TLS, which
+    /// can be opened more than once to read the batches concurrently.
+    ///
+    /// Modbus/RTU frames over TCP share the serial bus behind the device
+    /// server and are not concurrent.
+    pub fn is_tcp(&self) -> bool {
+        match self {
+            ConnectionParams::Tcp { .. } => true,
+            #[cfg(feature = "tokio-tcp-tls")]
+            ConnectionParams::TcpTls { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the safe clients re-establish the connection after
+    /// the error of a request, e.g. a broken TCP connection. A timeout of a
+    /// serial port only means that the meter didn't answer, a new port
+    /// wouldn't change that.
+    #[cfg(any(feature = "safe-client-sync", feature = "safe-client-async"))]
+    pub(crate) fn reconnects_after(&self, err: &Error) -> bool {
+        err.is_transport()
+            && !(matches!(self, ConnectionParams::Rtu { .. })
+                && err.link_error() == Some(LinkError::Timeout))
+    }
+
+    /// Returns the slave of the connection.
+    pub fn slave(&self) -> Slave {
+        match self {
+            ConnectionParams::Tcp { slave, .. }
+            | ConnectionParams::Rtu { slave, .. }
+            | ConnectionParams::RtuOverTcp { slave, .. } => *slave,
+            #[cfg(feature = "tokio-tcp-tls")]
+            ConnectionParams::TcpTls { slave, .. } => *slave,
+        }
+    }
+
+    /// Changes the slave used when the connection is re-established.
+    pub fn set_slave(&mut self, new_slave: Slave) {
+        match self {
+            ConnectionParams::Tcp { slave, .. }
+            | ConnectionParams::Rtu { slave, .. }
+            | ConnectionParams::RtuOverTcp { slave, .. } => *slave = new_slave,
+            #[cfg(feature = "tokio-tcp-tls")]
+            ConnectionParams::TcpTls { slave, .. } => *slave = new_slave,
+        }
+    }
+
+    /// Applies a written setting which affects the communication, i.e. the
+    /// address and the serial parameters of a Modbus/RTU connection.
+    ///
+    /// Returns `true` if the parameters changed.
+    pub fn apply_setting(&mut self, setting: Setting) -> bool {
+        let before = self.clone();
+        match (setting, &mut *self) {
+            (Setting::Address(address), _) => self.set_slave(Slave::from(address)),
+            (Setting::BaudRate(value), ConnectionParams::Rtu { baud_rate, .. }) => {
+                *baud_rate = value;
+            }
+            (
+                Setting::ParityAndStopBit(value),
+                ConnectionParams::Rtu {
+                    parity_and_stop_bit,
+                    ..
+                },
+            ) => *parity_and_stop_bit = value,
+            _ => {}
+        }
+        *self != before
+    }
+}
+impl std::fmt::Display for ConnectionParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionParams::Tcp { socket_addr, .. } => write!(f, "TCP {socket_addr}"),
+            ConnectionParams::Rtu {
+                device, baud_rate, ..
+            } => write!(f, "RTU {device} baud rate {baud_rate}"),
+            ConnectionParams::RtuOverTcp { socket_addr, .. } => {
+                write!(f, "RTU over TCP {socket_addr}")
+            }
+            #[cfg(feature = "tokio-tcp-tls")]
+            ConnectionParams::TcpTls {
+                socket_addr,
+                server_name,
+                ..
+            } => write!(f, "TCP TLS {socket_addr} ({server_name})"),
+        }
+    }
+}
+
+/// A shared TLS client configuration.
+///
+/// Two configurations are equal if they are the same instance.
+#[cfg(feature = "tokio-tcp-tls")]
+#[derive(Debug, Clone)]
+pub struct TlsConfig(pub std::sync::Arc<tokio_rustls::rustls::ClientConfig>);
+#[cfg(feature = "tokio-tcp-tls")]
+impl PartialEq for TlsConfig {
+    fn eq(&self, other: &Self) -> bool {
+        std::sync::Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// The socket options of a Modbus/TCP connection.
+///
+/// Non-default options are only supported by the asynchronous client
+/// (`tokio-tcp` feature), the synchronous `tokio-modbus` client opens the
+/// socket itself.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TcpOptions {
+    /// The local IP address to bind the connection to, e.g. to select the
+    /// interface on a multi-homed host.
+    pub bind_addr: Option<std::net::IpAddr>,
+    /// Enables TCP keepalive, as some gateways silently drop idle connections.
+    pub keepalive: Option<TcpKeepalive>,
+}
+
+/// The TCP keepalive parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcpKeepalive {
+    /// The idle time before the first keepalive probe is sent.
+    pub time: std::time::Duration,
+    /// The interval between unanswered keepalive probes, ignored on platforms
+    /// without support for it.
+    pub interval: std::time::Duration,
+}
+impl Default for TcpKeepalive {
+    fn default() -> Self {
+        Self {
+            time: std::time::Duration::from_secs(60),
+            interval: std::time::Duration::from_secs(10),
+        }
+    }
+}
+
+/// The default Modbus/TCP port, used if an address has no port.
+pub const DEFAULT_TCP_PORT: u16 = 502;
+
+/// Resolves a Modbus/TCP address into the socket addresses to try in order.
+///
+/// The address may be an IPv4 or IPv6 address, a bracketed IPv6 address with
+/// port (`[fd00::12]:502`) or a host name (`meter1.local:502`). Without a port
+/// [`DEFAULT_TCP_PORT`] is used. The name resolution is aborted after `timeout`.
+///
+/// Like in the happy eyeballs algorithm (RFC 8305) the resolved IPv6 and IPv4
+/// addresses are interleaved, so a connection can fall back to the next
+/// address family if the first address is unreachable.
+pub fn resolve_tcp_address(
+    address: &str,
+    timeout: std::time::Duration,
+) -> std::io::Result<Vec<std::net::SocketAddr>> {
+    use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+
+    if let Ok(socket_addr) = address.parse::<SocketAddr>() {
+        return Ok(vec![socket_addr]);
+    }
+    if let Ok(ip) = address.trim_matches(['[', ']']).parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(ip, DEFAULT_TCP_PORT)]);
+    }
+    let host_and_port = if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{address}:{DEFAULT_TCP_PORT}")
+    };
+
+    // The standard library offers no timeout for name resolution, so resolve
+    // in a separate thread and stop waiting for it after the timeout.
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(
+            host_and_port
+                .to_socket_addrs()
+                .map(|addrs| addrs.collect::<Vec<_>>()),
+        );
+    });
+    let resolved = rx.recv_timeout(timeout).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            format!("Resolving {address} timed out after {timeout:?}"),
+        )
+    })??;
+
+    let (mut v6, mut v4): (Vec<_>, Vec<_>) = resolved.into_iter().partition(SocketAddr::is_ipv6);
+    let mut ordered = Vec::with_capacity(v6.len() + v4.len());
+    v6.reverse();
+    v4.reverse();
+    while !v6.is_empty() || !v4.is_empty() {
+        ordered.extend(v6.pop());
+        ordered.extend(v4.pop());
+    }
+    if ordered.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("No address found for {address}"),
+        ));
+    }
+    Ok(ordered)
+}
+
+/// Controls how often and how fast a lost connection is re-established.
+///
+/// The delay before each reconnection attempt doubles, starting with
+/// `initial_backoff` and capped at `max_backoff`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconnectPolicy {
+    /// The maximum number of reconnection attempts for a single operation.
+    pub max_attempts: u32,
+    /// The delay before the first reconnection attempt.
+    pub initial_backoff: std::time::Duration,
+    /// The upper limit of the delay between reconnection attempts.
+    pub max_backoff: std::time::Duration,
+}
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: std::time::Duration::from_millis(500),
+            max_backoff: std::time::Duration::from_secs(30),
+        }
+    }
+}
+impl ReconnectPolicy {
+    /// Returns the delay before the given (zero based) reconnection attempt.
+    pub fn backoff(&self, attempt: u32) -> std::time::Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
+
+/// Controls how `read_all` of the safe clients recovers from a failed batch,
+/// e.g. if a gateway consistently times out on a specific block of registers.
+///
+/// Instead of failing the whole read, the failed batch is retried alone and,
+/// if that fails too, read with two requests of half the size. Only failures
+/// of the link, e.g. timeouts, are retried, a batch with an exception
+/// response is split right away. The default neither retries nor splits.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BatchRetryPolicy {
+    /// The number of times a batch is retried alone after a failure of the link.
+    pub retries: u32,
+    /// Read a batch which failed all retries in two halves.
+    pub split: bool,
+}
+
+/// Controls which decoded measurement values the safe clients reject with
+/// [`Error::ImplausibleValue`], so bad samples don't end up in a database.
+///
+/// Meters occasionally return `0xFFFF_FFFF` (a NaN) or garbage while they
+/// update their registers. The values are checked before any scaling.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ValueValidation {
+    /// The values are returned as decoded.
+    #[default]
+    Off,
+    /// NaN and infinite values are rejected.
+    Finite,
+    /// Like [`Finite`](Self::Finite), and values outside the
+    /// [plausible range](proto::Measurement::plausible_range) of their
+    /// register type are rejected as well.
+    Plausible,
+}
+impl ValueValidation {
+    /// Checks a decoded value of a measurement.
+    pub fn check(self, measurement: proto::Measurement, value: f32) -> Result<()> {
+        let valid = match self {
+            Self::Off => true,
+            Self::Finite => value.is_finite(),
+            Self::Plausible => measurement.plausible_range().contains(&value),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::ImplausibleValue {
+                register: measurement.name(),
+                raw: value.to_bits(),
+            })
+        }
+    }
+
+    /// Checks all values, fails with the first rejected one.
+    pub fn check_values(self, values: &AllValues) -> Result<()> {
+        if self == Self::Off {
+            return Ok(());
+        }
+        proto::Measurement::ALL
+            .iter()
+            .try_for_each(|measurement| self.check(*measurement, values.value(*measurement)))
+    }
+
+    /// Removes the rejected values of partial values, returns the errors of
+    /// the removed ones.
+    pub fn check_partial(self, values: &mut AllValuesPartial) -> Vec<Error> {
+        let mut errors = Vec::new();
+        if self == Self::Off {
+            return errors;
+        }
+        for measurement in proto::Measurement::ALL {
+            if let Some(value) = values.value(*measurement) {
+                if let Err(err) = self.check(*measurement, value) {
+                    values.remove(*measurement);
+                    errors.push(err);
+                }
+            }
+        }
+        errors
+    }
+}
+
+/// A source of time for the delays, backoff and scheduling of the synchronous
+/// safe client.
+///
+/// The default [`SystemClock`] can be replaced, e.g. by
+/// `testing::MockClock`, to test retry and scheduling logic deterministically.
+/// The asynchronous client uses the `tokio` timer, which can be paused in
+/// tests with `tokio::time::pause`.
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    /// Returns the current instant.
+    fn now(&self) -> std::time::Instant;
+    /// Blocks the current thread for the given duration.
+    fn sleep(&self, duration: std::time::Duration);
+}
+
+/// The [`Clock`] of the operating system.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+impl Clock for SystemClock {
+    fn now(&self) -> std::time::Instant {
+        std::time::Instant::now()
+    }
+    fn sleep(&self, duration: std::time::Duration) {
+        std::thread::sleep(duration)
+    }
+}
+
 /// The number of data bits used for serial communication.
 pub const DATA_BITS: &tokio_serial::DataBits = &tokio_serial::DataBits::Eight;
 
@@ -37,7 +695,8 @@ pub const DATA_BITS: &tokio_serial::DataBits = &tokio_serial::DataBits::Eight;
 /// Note that this function only creates and configures the builder. It does not
 /// open the serial port, and therefore does not perform any I/O and cannot fail.
 /// The actual connection is established when this builder is used by a `tokio-modbus`
-/// client constructor.
+/// client constructor. An unknown baud rate or parity code is not checked here, the
+/// clients of this crate reject it before they open the port.
 ///
 /// # Arguments
 ///
@@ -51,7 +710,7 @@ pub fn serial_port_builder(
     parity_and_stop_bits: &proto::ParityAndStopBit,
 ) -> tokio_serial::SerialPortBuilder {
     let (parity, stop_bits) = match parity_and_stop_bits {
-        proto::ParityAndStopBit::NoParityOneStopBit => {
+        proto::ParityAndStopBit::NoParityOneStopBit | proto::ParityAndStopBit::Unknown(_) => {
             (tokio_serial::Parity::None, tokio_serial::StopBits::One)
         }
         proto::ParityAndStopBit::EvenParityOneStopBit => {
@@ -72,6 +731,12 @@ pub fn serial_port_builder(
         .flow_control(tokio_serial::FlowControl::None)
 }
 
+/// Version of the serialized layout of [`AllSettings`] and [`AllValues`].
+///
+/// MQTT and HTTP consumers depend on the field names and value formats, so any
+/// change which breaks them must increase this version.
+pub const SCHEMA_VERSION: u32 = 1;
+
 /// A struct containing all the settings of the SDM72 meter.
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -91,29 +756,260 @@ pub struct AllSettings {
     pub meter_code: proto::MeterCode,
     pub software_version: proto::SoftwareVersion,
 }
+impl AllSettings {
+    /// Displays the settings with the given labels, e.g. in another language.
+    pub fn labeled<'a>(&'a self, labels: &'a Labels) -> Labeled<'a, Self> {
+        Labeled::new(self, labels)
+    }
+}
 impl std::fmt::Display for AllSettings {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        writeln!(fmt, "System type: {}", self.system_type)?;
-        writeln!(fmt, "Pulse width: {}", self.pulse_width)?;
-        writeln!(fmt, "KPPA: {}", self.kppa)?;
-        writeln!(fmt, "Parity and stop bit: {}", self.parity_and_stop_bit)?;
-        writeln!(fmt, "Address: {}", self.address)?;
-        writeln!(fmt, "Pulse constant: {}", self.pulse_constant)?;
-        writeln!(fmt, "Password: {}", self.password)?;
-        writeln!(fmt, "Baud rate: {}", self.baud_rate)?;
-        writeln!(fmt, "Auto scroll time: {}", self.auto_scroll_time)?;
-        writeln!(fmt, "Backlight time: {}", self.backlight_time)?;
-        writeln!(fmt, "Pulse energy type: {}", self.pulse_energy_type)?;
+        std::fmt::Display::fmt(&self.labeled(&Labels::English), fmt)
+    }
+}
+
+/// The identity of a meter, e.g. to fingerprint the meters of an installation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MeterIdentity {
+    pub serial_number: proto::SerialNumber,
+    pub meter_code: proto::MeterCode,
+    pub software_version: proto::SoftwareVersion,
+    /// The model derived from the meter code, `None` if it is unknown.
+    pub model: Option<proto::MeterModel>,
+}
+impl std::fmt::Display for MeterIdentity {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
         writeln!(fmt, "Serial number: {}", self.serial_number)?;
         writeln!(fmt, "Meter code: {}", self.meter_code)?;
-        write!(fmt, "Software version: {}", self.software_version)?;
-        Ok(())
+        writeln!(fmt, "Software version: {}", self.software_version)?;
+        match self.model {
+            Some(model) => write!(fmt, "Model: {model}"),
+            None => write!(fmt, "Model: unknown"),
+        }
     }
 }
 
-/// A struct containing all the measurement values of the SDM72 meter.
+/// The ratios of external current (CT) and voltage (VT) transformers.
+///
+/// Currents are multiplied by `ct_ratio`, voltages by `vt_ratio` and powers
+/// and energies by the product of both. Power factors and the frequency are
+/// not affected.
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScalingProfile {
+    pub ct_ratio: f32,
+    pub vt_ratio: f32,
+}
+impl Default for ScalingProfile {
+    fn default() -> Self {
+        Self {
+            ct_ratio: 1.0,
+            vt_ratio: 1.0,
+        }
+    }
+}
+impl std::fmt::Display for ScalingProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CT ratio {}, VT ratio {}", self.ct_ratio, self.vt_ratio)
+    }
+}
+
+/// A linear correction of a measurement, `value * multiplier + offset`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct Calibration {
+    pub multiplier: f32,
+    pub offset: f32,
+}
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            multiplier: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+impl std::fmt::Display for Calibration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "x{} {:+}", self.multiplier, self.offset)
+    }
+}
+
+/// Per-measurement calibrations, e.g. for current transformers whose ratio
+/// differs per phase or a meter with a known offset.
+///
+/// The calibrations are applied after the [`ScalingProfile`], measurements
+/// without a calibration are left unchanged.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScalingConfig {
+    pub calibrations: std::collections::HashMap<proto::Measurement, Calibration>,
+}
+impl ScalingConfig {
+    /// Adds or replaces the calibration of a measurement.
+    pub fn with(mut self, measurement: proto::Measurement, calibration: Calibration) -> Self {
+        self.calibrations.insert(measurement, calibration);
+        self
+    }
+
+    /// Returns `true` if no measurement is calibrated.
+    pub fn is_empty(&self) -> bool {
+        self.calibrations.is_empty()
+    }
+
+    /// Returns the calibration of a measurement.
+    pub fn calibration(&self, measurement: proto::Measurement) -> Option<Calibration> {
+        self.calibrations.get(&measurement).copied()
+    }
+}
+impl FromIterator<(proto::Measurement, Calibration)> for ScalingConfig {
+    fn from_iter<T: IntoIterator<Item = (proto::Measurement, Calibration)>>(iter: T) -> Self {
+        Self {
+            calibrations: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// The scaling, calibrations and precision of the measurement values, which
+/// every client applies the same way after decoding.
+#[cfg(any(
+    feature = "safe-client-sync",
+    feature = "safe-client-async",
+    feature = "testing"
+))]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ValueScaling {
+    pub(crate) profile: Option<ScalingProfile>,
+    pub(crate) calibration: Option<std::sync::Arc<ScalingConfig>>,
+    pub(crate) precision: proto::Precision,
+}
+#[cfg(any(
+    feature = "safe-client-sync",
+    feature = "safe-client-async",
+    feature = "testing"
+))]
+impl ValueScaling {
+    /// Returns the decoded values scaled and calibrated, with the precision.
+    pub(crate) fn apply(&self, values: AllValues) -> AllValues {
+        let values = match &self.profile {
+            Some(profile) => values.scaled(profile),
+            None => values,
+        };
+        let mut values = match &self.calibration {
+            Some(config) => values.calibrated(config),
+            None => values,
+        };
+        values.precision = self.precision;
+        values
+    }
+
+    /// Like [`apply`](Self::apply) for the values which were read.
+    #[cfg(any(feature = "safe-client-sync", feature = "safe-client-async"))]
+    pub(crate) fn apply_partial(&self, values: AllValuesPartial) -> AllValuesPartial {
+        let values = match &self.profile {
+            Some(profile) => values.scaled(profile),
+            None => values,
+        };
+        let mut values = match &self.calibration {
+            Some(config) => values.calibrated(config),
+            None => values,
+        };
+        values.precision = self.precision;
+        values
+    }
+}
+
+/// Measurement values tagged with the time they were read.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimestampedValues {
+    /// The time the values were read.
+    pub timestamp: std::time::SystemTime,
+    pub values: Vec<proto::MeasurementValue>,
+}
+
+/// A point in time as monotonic and as wall-clock time.
+///
+/// The monotonic time measures durations, e.g. the sampling jitter of a
+/// poll loop, the wall-clock time aligns the values in a time-series
+/// database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    pub instant: std::time::Instant,
+    pub system_time: std::time::SystemTime,
+}
+impl Timestamp {
+    pub fn now() -> Self {
+        Self {
+            instant: std::time::Instant::now(),
+            system_time: std::time::SystemTime::now(),
+        }
+    }
+}
+
+/// All measurement values with the times their read started and completed,
+/// see `read_all_timestamped` of the safe clients.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimestampedAllValues {
+    /// The time before the first request.
+    pub read_started: Timestamp,
+    /// The time after the last response.
+    pub read_completed: Timestamp,
+    pub values: AllValues,
+}
+impl TimestampedAllValues {
+    /// The duration of the read.
+    pub fn duration(&self) -> std::time::Duration {
+        self.read_completed
+            .instant
+            .saturating_duration_since(self.read_started.instant)
+    }
+
+    /// The wall-clock time in the middle of the read, the best estimate of
+    /// the time the values were sampled.
+    pub fn sampled_at(&self) -> std::time::SystemTime {
+        self.read_started.system_time + self.duration() / 2
+    }
+}
+
+/// A measurement value with its canonical metric, see [`AllValues::to_metrics`].
+///
+/// The display is a sample in the Prometheus text exposition format, e.g.
+/// `sdm72_voltage_volts{phase="l1"} 230.1`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Metric {
+    pub descriptor: proto::MetricDescriptor,
+    pub measurement: proto::Measurement,
+    pub value: f32,
+}
+impl std::fmt::Display for Metric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.descriptor.name)?;
+        for (index, (name, value)) in self.descriptor.labels.iter().enumerate() {
+            let separator = if index == 0 { '{' } else { ',' };
+            write!(f, "{separator}{name}=\"{value}\"")?;
+        }
+        if !self.descriptor.labels.is_empty() {
+            write!(f, "}}")?;
+        }
+        write!(f, " {}", self.value)
+    }
+}
+
+/// A struct containing all the measurement values of the SDM72 meter.
+///
+/// The values are serialized and displayed rounded with their
+/// [`precision`](Self::precision). Outside of this crate the values are
+/// built with [`AllValuesPartial::complete`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[non_exhaustive]
 pub struct AllValues {
     // L1
     pub l1_voltage: proto::L1Voltage,
@@ -168,79 +1064,1224 @@ pub struct AllValues {
 
     pub import_total_energy_active: proto::ImportTotalPowerActive,
     pub export_total_energy_active: proto::ExportTotalPowerActive,
+
+    /// The scaling profile which was applied to the values, if any.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub scaling: Option<ScalingProfile>,
+
+    /// The decimals of the serialized and displayed values, the decoded
+    /// values are never rounded.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub precision: proto::Precision,
+}
+impl AllValues {
+    /// Returns the value of the given measurement.
+    pub fn value(&self, measurement: proto::Measurement) -> f32 {
+        match measurement {
+            proto::Measurement::L1Voltage => *self.l1_voltage,
+            proto::Measurement::L2Voltage => *self.l2_voltage,
+            proto::Measurement::L3Voltage => *self.l3_voltage,
+            proto::Measurement::L1Current => *self.l1_current,
+            proto::Measurement::L2Current => *self.l2_current,
+            proto::Measurement::L3Current => *self.l3_current,
+            proto::Measurement::L1PowerActive => *self.l1_power_active,
+            proto::Measurement::L2PowerActive => *self.l2_power_active,
+            proto::Measurement::L3PowerActive => *self.l3_power_active,
+            proto::Measurement::L1PowerApparent => *self.l1_power_apparent,
+            proto::Measurement::L2PowerApparent => *self.l2_power_apparent,
+            proto::Measurement::L3PowerApparent => *self.l3_power_apparent,
+            proto::Measurement::L1PowerReactive => *self.l1_power_reactive,
+            proto::Measurement::L2PowerReactive => *self.l2_power_reactive,
+            proto::Measurement::L3PowerReactive => *self.l3_power_reactive,
+            proto::Measurement::L1PowerFactor => *self.l1_power_factor,
+            proto::Measurement::L2PowerFactor => *self.l2_power_factor,
+            proto::Measurement::L3PowerFactor => *self.l3_power_factor,
+            proto::Measurement::LtoNAverageVoltage => *self.ln_average_voltage,
+            proto::Measurement::LtoNAverageCurrent => *self.ln_average_current,
+            proto::Measurement::TotalLineCurrent => *self.total_line_current,
+            proto::Measurement::TotalPower => *self.total_power,
+            proto::Measurement::TotalPowerApparent => *self.total_power_apparent,
+            proto::Measurement::TotalPowerReactive => *self.total_power_reactive,
+            proto::Measurement::TotalPowerFactor => *self.total_power_factor,
+            proto::Measurement::Frequency => *self.frequency,
+            proto::Measurement::ImportEnergyActive => *self.import_energy_active,
+            proto::Measurement::ExportEnergyActive => *self.export_energy_active,
+            proto::Measurement::L1ToL2Voltage => *self.l1l2_voltage,
+            proto::Measurement::L2ToL3Voltage => *self.l2l3_voltage,
+            proto::Measurement::L3ToL1Voltage => *self.l3l1_voltage,
+            proto::Measurement::LtoLAverageVoltage => *self.ll_average_voltage,
+            proto::Measurement::NeutralCurrent => *self.neutral_current,
+            proto::Measurement::TotalEnergyActive => *self.total_energy_active,
+            proto::Measurement::TotalEnergyReactive => *self.total_energy_reactive,
+            proto::Measurement::ResettableTotalEnergyActive => *self.resettable_total_energy_active,
+            proto::Measurement::ResettableTotalEnergyReactive => {
+                *self.resettable_total_energy_reactive
+            }
+            proto::Measurement::ResettableImportEnergyActive => {
+                *self.resettable_import_energy_active
+            }
+            proto::Measurement::ResettableExportEnergyActive => {
+                *self.resettable_export_energy_active
+            }
+            proto::Measurement::NetKwh => *self.net_kwh,
+            proto::Measurement::ImportTotalPowerActive => *self.import_total_energy_active,
+            proto::Measurement::ExportTotalPowerActive => *self.export_total_energy_active,
+        }
+    }
+
+    /// Returns the values of the selected measurements, all values if the
+    /// selection is empty.
+    pub fn select(&self, selection: &[proto::Measurement]) -> Vec<proto::MeasurementValue> {
+        let selection = if selection.is_empty() {
+            proto::Measurement::ALL
+        } else {
+            selection
+        };
+        selection
+            .iter()
+            .map(|measurement| proto::MeasurementValue {
+                measurement: *measurement,
+                value: self.value(*measurement),
+            })
+            .collect()
+    }
+
+    /// Returns the values as metrics with their canonical names, labels and
+    /// units, see [`proto::MetricDescriptor`].
+    ///
+    /// The metrics are in the order of [`proto::Measurement::ALL`], the
+    /// labels of a name are adjacent.
+    pub fn to_metrics(&self) -> Vec<Metric> {
+        proto::Measurement::ALL
+            .iter()
+            .map(|measurement| Metric {
+                descriptor: measurement.metric(),
+                measurement: *measurement,
+                value: self.value(*measurement),
+            })
+            .collect()
+    }
+
+    /// Returns the values scaled by the ratios of external current and voltage transformers.
+    ///
+    /// The profile is recorded in the `scaling` field of the returned values.
+    pub fn scaled(&self, profile: &ScalingProfile) -> Self {
+        let ct = profile.ct_ratio;
+        let vt = profile.vt_ratio;
+        let power = ct * vt;
+        Self {
+            l1_voltage: self.l1_voltage.scaled(vt),
+            l2_voltage: self.l2_voltage.scaled(vt),
+            l3_voltage: self.l3_voltage.scaled(vt),
+            l1_current: self.l1_current.scaled(ct),
+            l2_current: self.l2_current.scaled(ct),
+            l3_current: self.l3_current.scaled(ct),
+            l1_power_active: self.l1_power_active.scaled(power),
+            l2_power_active: self.l2_power_active.scaled(power),
+            l3_power_active: self.l3_power_active.scaled(power),
+            l1_power_apparent: self.l1_power_apparent.scaled(power),
+            l2_power_apparent: self.l2_power_apparent.scaled(power),
+            l3_power_apparent: self.l3_power_apparent.scaled(power),
+            l1_power_reactive: self.l1_power_reactive.scaled(power),
+            l2_power_reactive: self.l2_power_reactive.scaled(power),
+            l3_power_reactive: self.l3_power_reactive.scaled(power),
+            l1_power_factor: self.l1_power_factor,
+            l2_power_factor: self.l2_power_factor,
+            l3_power_factor: self.l3_power_factor,
+            ln_average_voltage: self.ln_average_voltage.scaled(vt),
+            ln_average_current: self.ln_average_current.scaled(ct),
+            total_line_current: self.total_line_current.scaled(ct),
+            total_power: self.total_power.scaled(power),
+            total_power_apparent: self.total_power_apparent.scaled(power),
+            total_power_reactive: self.total_power_reactive.scaled(power),
+            total_power_factor: self.total_power_factor,
+            frequency: self.frequency,
+            import_energy_active: self.import_energy_active.scaled(power),
+            export_energy_active: self.export_energy_active.scaled(power),
+
+            l1l2_voltage: self.l1l2_voltage.scaled(vt),
+            l2l3_voltage: self.l2l3_voltage.scaled(vt),
+            l3l1_voltage: self.l3l1_voltage.scaled(vt),
+            ll_average_voltage: self.ll_average_voltage.scaled(vt),
+            neutral_current: self.neutral_current.scaled(ct),
+
+            total_energy_active: self.total_energy_active.scaled(power),
+            total_energy_reactive: self.total_energy_reactive.scaled(power),
+            resettable_total_energy_active: self.resettable_total_energy_active.scaled(power),
+            resettable_total_energy_reactive: self.resettable_total_energy_reactive.scaled(power),
+            resettable_import_energy_active: self.resettable_import_energy_active.scaled(power),
+            resettable_export_energy_active: self.resettable_export_energy_active.scaled(power),
+            net_kwh: self.net_kwh.scaled(power),
+
+            import_total_energy_active: self.import_total_energy_active.scaled(power),
+            export_total_energy_active: self.export_total_energy_active.scaled(power),
+
+            scaling: Some(*profile),
+            precision: self.precision,
+        }
+    }
+}
+impl AllValues {
+    /// Displays the values with the given labels, e.g. in another language.
+    pub fn labeled<'a>(&'a self, labels: &'a Labels) -> Labeled<'a, Self> {
+        Labeled::new(self, labels)
+    }
 }
 impl std::fmt::Display for AllValues {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        writeln!(fmt, "L1 Voltage: {}", self.l1_voltage)?;
-        writeln!(fmt, "L2 Voltage: {}", self.l2_voltage)?;
-        writeln!(fmt, "L3 Voltage: {}", self.l3_voltage)?;
-        writeln!(fmt, "L1 Current: {}", self.l1_current)?;
-        writeln!(fmt, "L2 Current: {}", self.l2_current)?;
-        writeln!(fmt, "L3 Current: {}", self.l3_current)?;
-        writeln!(fmt, "L1 Power Active: {}", self.l1_power_active)?;
-        writeln!(fmt, "L2 Power Active: {}", self.l2_power_active)?;
-        writeln!(fmt, "L3 Power Active: {}", self.l3_power_active)?;
-        writeln!(fmt, "L1 Power Apparent: {}", self.l1_power_apparent)?;
-        writeln!(fmt, "L2 Power Apparent: {}", self.l2_power_apparent)?;
-        writeln!(fmt, "L3 Power Apparent: {}", self.l3_power_apparent)?;
-        writeln!(fmt, "L1 Power Reactive: {}", self.l1_power_reactive)?;
-        writeln!(fmt, "L2 Power Reactive: {}", self.l2_power_reactive)?;
-        writeln!(fmt, "L3 Power Reactive: {}", self.l3_power_reactive)?;
-        writeln!(fmt, "L1 Power Factor: {}", self.l1_power_factor)?;
-        writeln!(fmt, "L2 Power Factor: {}", self.l2_power_factor)?;
-        writeln!(fmt, "L3 Power Factor: {}", self.l3_power_factor)?;
-        writeln!(fmt, "L-N average Voltage: {}", self.ln_average_voltage)?;
-        writeln!(fmt, "L-N average Current: {}", self.ln_average_current)?;
-        writeln!(fmt, "Total Line Current: {}", self.total_line_current)?;
-        writeln!(fmt, "Total Power: {}", self.total_power)?;
-        writeln!(fmt, "Total Power Apparent: {}", self.total_power_apparent)?;
-        writeln!(fmt, "Total Power Reactive: {}", self.total_power_reactive)?;
-        writeln!(fmt, "Total Power Factor: {}", self.total_power_factor)?;
-        writeln!(fmt, "Frequency: {}", self.frequency)?;
-        writeln!(fmt, "Import Energy Active: {}", self.import_energy_active)?;
-        writeln!(fmt, "Export Energy Active: {}", self.export_energy_active)?;
-
-        writeln!(fmt, "L1-L2 Voltage: {}", self.l1l2_voltage)?;
-        writeln!(fmt, "L2-L3 Voltage: {}", self.l2l3_voltage)?;
-        writeln!(fmt, "L3-L1 Voltage: {}", self.l3l1_voltage)?;
-        writeln!(fmt, "L-L average Voltage: {}", self.ll_average_voltage)?;
-        writeln!(fmt, "Neutral Current: {}", self.neutral_current)?;
-
-        writeln!(fmt, "Total Energy Active: {}", self.total_energy_active)?;
-        writeln!(fmt, "Total Energy Reactive: {}", self.total_energy_reactive)?;
-        writeln!(
-            fmt,
-            "Resettable Total Energy Active: {}",
-            self.resettable_total_energy_active
-        )?;
-        writeln!(
-            fmt,
-            "Resettable Total Energy Reactive: {}",
-            self.resettable_total_energy_reactive
-        )?;
-        writeln!(
-            fmt,
-            "Resettable Import Energy Active: {}",
-            self.resettable_import_energy_active
-        )?;
-        writeln!(
-            fmt,
-            "Resettable Export Energy Active: {}",
-            self.resettable_export_energy_active
-        )?;
-        writeln!(fmt, "Net kWh (Import - Export): {}", self.net_kwh)?;
-
-        writeln!(
-            fmt,
-            "Import Total Energy Active: {}",
-            self.import_total_energy_active
-        )?;
-        write!(
-            fmt,
-            "Export Total Energy Active: {}",
-            self.export_total_energy_active
-        )?;
+        std::fmt::Display::fmt(&self.labeled(&Labels::English), fmt)
+    }
+}
 
-        Ok(())
+/// The holding registers read in a single request by `read_all_settings`, as
+/// `(address, quantity)`.
+///
+/// The serial number, meter code and software version are read separately.
+pub(crate) const SETTINGS_BATCH: (u16, u16) = (
+    proto::SystemType::ADDRESS,
+    proto::PulseEnergyType::ADDRESS - proto::SystemType::ADDRESS + proto::PulseEnergyType::QUANTITY,
+);
+
+/// The holding registers of the serial number and the meter code, read in a
+/// single request by `identify`, as `(address, quantity)`.
+///
+/// The software version is too far away and is read separately.
+pub(crate) const IDENTITY_BATCH: (u16, u16) = (
+    proto::SerialNumber::ADDRESS,
+    proto::MeterCode::ADDRESS - proto::SerialNumber::ADDRESS + proto::MeterCode::QUANTITY,
+);
+
+/// The input registers read by `read_all`, as `(address, quantity)` of each
+/// request.
+pub(crate) const VALUE_BATCHES: [(u16, u16); 4] = [
+    (
+        proto::L1Voltage::ADDRESS,
+        proto::ExportEnergyActive::ADDRESS - proto::L1Voltage::ADDRESS
+            + proto::ExportEnergyActive::QUANTITY,
+    ),
+    (
+        proto::L1ToL2Voltage::ADDRESS,
+        proto::NeutralCurrent::ADDRESS - proto::L1ToL2Voltage::ADDRESS
+            + proto::NeutralCurrent::QUANTITY,
+    ),
+    (
+        proto::TotalEnergyActive::ADDRESS,
+        proto::NetKwh::ADDRESS - proto::TotalEnergyActive::ADDRESS + proto::NetKwh::QUANTITY,
+    ),
+    (
+        proto::ImportTotalPowerActive::ADDRESS,
+        proto::ExportTotalPowerActive::ADDRESS - proto::ImportTotalPowerActive::ADDRESS
+            + proto::ExportTotalPowerActive::QUANTITY,
+    ),
+];
+
+/// The time the meters get to execute a broadcast request before the next
+/// request is sent, there is no response to wait for.
+pub const BROADCAST_TURNAROUND: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// The result of a request to the broadcast address. No meter responds, so a
+/// timeout is the expected outcome.
+pub(crate) fn broadcast_result(result: Result<()>) -> Result<()> {
+    match result {
+        Err(err) if err.link_error() == Some(LinkError::Timeout) => Ok(()),
+        result => result,
+    }
+}
+
+/// Splits a batch of 32-bit registers into two halves, returns `None` if the
+/// batch is a single register.
+#[cfg(any(feature = "safe-client-sync", feature = "safe-client-async"))]
+pub(crate) fn split_batch((address, quantity): (u16, u16)) -> Option<[(u16, u16); 2]> {
+    let first = (quantity / 2) & !1;
+    (first > 0).then_some([(address, first), (address + first, quantity - first)])
+}
+
+/// Decodes the response to the [`SETTINGS_BATCH`] request, with the word order
+/// already applied. `mode` decides how unknown codes of the enum settings are
+/// decoded.
+pub(crate) fn decode_settings(
+    rsp: &[proto::Word],
+    serial_number: proto::SerialNumber,
+    meter_code: proto::MeterCode,
+    software_version: proto::SoftwareVersion,
+    mode: proto::DecodeMode,
+) -> Result<AllSettings> {
+    let offset = SETTINGS_BATCH.0;
+    Ok(AllSettings {
+        system_type: crate::decode_subset_item_from_holding_register!(
+            offset,
+            proto::SystemType,
+            rsp,
+            mode
+        )?,
+        pulse_width: crate::decode_subset_item_from_holding_register!(
+            offset,
+            proto::PulseWidth,
+            rsp
+        )?,
+        kppa: crate::decode_subset_item_from_holding_register!(offset, proto::KPPA, rsp, mode)?,
+        parity_and_stop_bit: crate::decode_subset_item_from_holding_register!(
+            offset,
+            proto::ParityAndStopBit,
+            rsp,
+            mode
+        )?,
+        address: crate::decode_subset_item_from_holding_register!(offset, proto::Address, rsp)?,
+        pulse_constant: crate::decode_subset_item_from_holding_register!(
+            offset,
+            proto::PulseConstant,
+            rsp,
+            mode
+        )?,
+        password: crate::decode_subset_item_from_holding_register!(offset, proto::Password, rsp)?,
+        baud_rate: crate::decode_subset_item_from_holding_register!(
+            offset,
+            proto::BaudRate,
+            rsp,
+            mode
+        )?,
+        auto_scroll_time: crate::decode_subset_item_from_holding_register!(
+            offset,
+            proto::AutoScrollTime,
+            rsp
+        )?,
+        backlight_time: crate::decode_subset_item_from_holding_register!(
+            offset,
+            proto::BacklightTime,
+            rsp
+        )?,
+        pulse_energy_type: crate::decode_subset_item_from_holding_register!(
+            offset,
+            proto::PulseEnergyType,
+            rsp,
+            mode
+        )?,
+        serial_number,
+        meter_code,
+        software_version,
+    })
+}
+
+/// Decodes the response to the [`IDENTITY_BATCH`] request, with the word order
+/// already applied.
+pub(crate) fn decode_identity(
+    rsp: &[proto::Word],
+    software_version: proto::SoftwareVersion,
+) -> Result<MeterIdentity> {
+    let offset = IDENTITY_BATCH.0;
+    let meter_code =
+        crate::decode_subset_item_from_holding_register!(offset, proto::MeterCode, rsp)?;
+    Ok(MeterIdentity {
+        serial_number: crate::decode_subset_item_from_holding_register!(
+            offset,
+            proto::SerialNumber,
+            rsp
+        )?,
+        meter_code,
+        software_version,
+        model: meter_code.model(),
+    })
+}
+
+/// Decodes the responses to the [`VALUE_BATCHES`] requests, with the word
+/// order already applied.
+pub(crate) fn decode_values(rsp: &[Vec<proto::Word>; 4]) -> Result<AllValues> {
+    let [rsp1, rsp2, rsp3, rsp4] = rsp;
+    let [offset1, offset2, offset3, offset4] = VALUE_BATCHES.map(|(offset, _)| offset);
+    Ok(AllValues {
+        l1_voltage: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L1Voltage,
+            rsp1
+        )?,
+        l2_voltage: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L2Voltage,
+            rsp1
+        )?,
+        l3_voltage: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L3Voltage,
+            rsp1
+        )?,
+        l1_current: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L1Current,
+            rsp1
+        )?,
+        l2_current: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L2Current,
+            rsp1
+        )?,
+        l3_current: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L3Current,
+            rsp1
+        )?,
+        l1_power_active: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L1PowerActive,
+            rsp1
+        )?,
+        l2_power_active: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L2PowerActive,
+            rsp1
+        )?,
+        l3_power_active: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L3PowerActive,
+            rsp1
+        )?,
+        l1_power_apparent: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L1PowerApparent,
+            rsp1
+        )?,
+        l2_power_apparent: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L2PowerApparent,
+            rsp1
+        )?,
+        l3_power_apparent: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L3PowerApparent,
+            rsp1
+        )?,
+        l1_power_reactive: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L1PowerReactive,
+            rsp1
+        )?,
+        l2_power_reactive: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L2PowerReactive,
+            rsp1
+        )?,
+        l3_power_reactive: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L3PowerReactive,
+            rsp1
+        )?,
+        l1_power_factor: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L1PowerFactor,
+            rsp1
+        )?,
+        l2_power_factor: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L2PowerFactor,
+            rsp1
+        )?,
+        l3_power_factor: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::L3PowerFactor,
+            rsp1
+        )?,
+        ln_average_voltage: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::LtoNAverageVoltage,
+            rsp1
+        )?,
+        ln_average_current: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::LtoNAverageCurrent,
+            rsp1
+        )?,
+        total_line_current: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::TotalLineCurrent,
+            rsp1
+        )?,
+        total_power: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::TotalPower,
+            rsp1
+        )?,
+        total_power_apparent: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::TotalPowerApparent,
+            rsp1
+        )?,
+        total_power_reactive: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::TotalPowerReactive,
+            rsp1
+        )?,
+        total_power_factor: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::TotalPowerFactor,
+            rsp1
+        )?,
+        frequency: crate::decode_subset_item_from_input_register!(offset1, proto::Frequency, rsp1)?,
+        import_energy_active: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::ImportEnergyActive,
+            rsp1
+        )?,
+        export_energy_active: crate::decode_subset_item_from_input_register!(
+            offset1,
+            proto::ExportEnergyActive,
+            rsp1
+        )?,
+
+        l1l2_voltage: crate::decode_subset_item_from_input_register!(
+            offset2,
+            proto::L1ToL2Voltage,
+            rsp2
+        )?,
+        l2l3_voltage: crate::decode_subset_item_from_input_register!(
+            offset2,
+            proto::L2ToL3Voltage,
+            rsp2
+        )?,
+        l3l1_voltage: crate::decode_subset_item_from_input_register!(
+            offset2,
+            proto::L3ToL1Voltage,
+            rsp2
+        )?,
+        ll_average_voltage: crate::decode_subset_item_from_input_register!(
+            offset2,
+            proto::LtoLAverageVoltage,
+            rsp2
+        )?,
+        neutral_current: crate::decode_subset_item_from_input_register!(
+            offset2,
+            proto::NeutralCurrent,
+            rsp2
+        )?,
+
+        total_energy_active: crate::decode_subset_item_from_input_register!(
+            offset3,
+            proto::TotalEnergyActive,
+            rsp3
+        )?,
+        total_energy_reactive: crate::decode_subset_item_from_input_register!(
+            offset3,
+            proto::TotalEnergyReactive,
+            rsp3
+        )?,
+        resettable_total_energy_active: crate::decode_subset_item_from_input_register!(
+            offset3,
+            proto::ResettableTotalEnergyActive,
+            rsp3
+        )?,
+        resettable_total_energy_reactive: crate::decode_subset_item_from_input_register!(
+            offset3,
+            proto::ResettableTotalEnergyReactive,
+            rsp3
+        )?,
+        resettable_import_energy_active: crate::decode_subset_item_from_input_register!(
+            offset3,
+            proto::ResettableImportEnergyActive,
+            rsp3
+        )?,
+        resettable_export_energy_active: crate::decode_subset_item_from_input_register!(
+            offset3,
+            proto::ResettableExportEnergyActive,
+            rsp3
+        )?,
+        net_kwh: crate::decode_subset_item_from_input_register!(offset3, proto::NetKwh, rsp3)?,
+
+        import_total_energy_active: crate::decode_subset_item_from_input_register!(
+            offset4,
+            proto::ImportTotalPowerActive,
+            rsp4
+        )?,
+        export_total_energy_active: crate::decode_subset_item_from_input_register!(
+            offset4,
+            proto::ExportTotalPowerActive,
+            rsp4
+        )?,
+
+        scaling: None,
+        precision: proto::Precision::default(),
+    })
+}
+
+/// A macro to select the factor of a [`ScalingProfile`] by name.
+macro_rules! scaling_factor {
+    ($profile:ident, ct) => {
+        $profile.ct_ratio
+    };
+    ($profile:ident, vt) => {
+        $profile.vt_ratio
+    };
+    ($profile:ident, power) => {
+        $profile.ct_ratio * $profile.vt_ratio
+    };
+    ($profile:ident, none) => {
+        1.0
+    };
+}
+
+/// A macro to define [`AllValuesPartial`] with the fields of [`AllValues`],
+/// grouped by the index of the [`VALUE_BATCHES`] request they are read with.
+///
+/// Each field names its register type, which is also the name of its
+/// [`Measurement`](proto::Measurement), and the factor of the
+/// [`ScalingProfile`] it is scaled with.
+macro_rules! all_values_partial {
+    ($($batch:literal => {
+        $($field:ident: $ty:ident $(as $rename:literal)?, scaled by $factor:ident;)*
+    })*) => {
+        /// Like [`AllValues`], but values which were not read are `None`, e.g.
+        /// because the [`Firmware`] of the meter lacks their registers.
+        #[derive(Debug, Clone, Copy, PartialEq, Default)]
+        #[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+        #[non_exhaustive]
+        pub struct AllValuesPartial {
+            $($(
+                $(#[cfg_attr(feature = "serde", serde(rename = $rename))])?
+                pub $field: Option<proto::$ty>,
+            )*)*
+
+            /// The scaling profile which was applied to the values, if any.
+            #[cfg_attr(
+                feature = "serde",
+                serde(default, skip_serializing_if = "Option::is_none")
+            )]
+            pub scaling: Option<ScalingProfile>,
+
+            /// The decimals of the serialized values, see [`AllValues::precision`].
+            #[cfg_attr(feature = "serde", serde(skip))]
+            pub precision: proto::Precision,
+        }
+        impl AllValuesPartial {
+            /// Returns the value of the given measurement, `None` if it was not read.
+            pub fn value(&self, measurement: proto::Measurement) -> Option<f32> {
+                match measurement {
+                    $($(proto::Measurement::$ty => self.$field.map(|value| *value),)*)*
+                }
+            }
+
+            /// Removes the value of the given measurement, e.g. because it
+            /// is implausible.
+            pub fn remove(&mut self, measurement: proto::Measurement) {
+                match measurement {
+                    $($(proto::Measurement::$ty => self.$field = None,)*)*
+                }
+            }
+
+            /// Returns all values if none is missing.
+            pub fn complete(&self) -> Option<AllValues> {
+                Some(AllValues {
+                    $($($field: self.$field?,)*)*
+                    scaling: self.scaling,
+                    precision: self.precision,
+                })
+            }
+
+            /// Returns the values scaled by the ratios of external current and
+            /// voltage transformers, see [`AllValues::scaled`].
+            pub fn scaled(&self, profile: &ScalingProfile) -> Self {
+                Self {
+                    $($($field: self
+                        .$field
+                        .map(|value| value.scaled(scaling_factor!(profile, $factor))),)*)*
+                    scaling: Some(*profile),
+                    precision: self.precision,
+                }
+            }
+
+            /// Returns the values with the calibrations of the scaling
+            /// configuration applied, see [`AllValues::calibrated`].
+            pub fn calibrated(&self, config: &ScalingConfig) -> Self {
+                Self {
+                    $($($field: self.$field.map(|value| {
+                        match config.calibration(proto::Measurement::$ty) {
+                            Some(calibration) => {
+                                value.calibrated(calibration.multiplier, calibration.offset)
+                            }
+                            None => value,
+                        }
+                    }),)*)*
+                    scaling: self.scaling,
+                    precision: self.precision,
+                }
+            }
+        }
+        impl AllValues {
+            /// Returns the values with the calibrations of the scaling
+            /// configuration applied.
+            pub fn calibrated(&self, config: &ScalingConfig) -> Self {
+                Self {
+                    $($($field: match config.calibration(proto::Measurement::$ty) {
+                        Some(calibration) => self
+                            .$field
+                            .calibrated(calibration.multiplier, calibration.offset),
+                        None => self.$field,
+                    },)*)*
+                    scaling: self.scaling,
+                    precision: self.precision,
+                }
+            }
+        }
+        impl From<AllValues> for AllValuesPartial {
+            fn from(values: AllValues) -> Self {
+                Self {
+                    $($($field: Some(values.$field),)*)*
+                    scaling: values.scaling,
+                    precision: values.precision,
+                }
+            }
+        }
+
+        /// Decodes the responses to the [`VALUE_BATCHES`] requests, with the
+        /// word order already applied. The values of missing responses are `None`.
+        pub(crate) fn decode_values_partial(
+            rsp: &[Option<Vec<proto::Word>>; 4],
+        ) -> Result<AllValuesPartial> {
+            Ok(AllValuesPartial {
+                $($($field: rsp[$batch]
+                    .as_ref()
+                    .map(|rsp| {
+                        crate::decode_subset_item_from_input_register!(
+                            VALUE_BATCHES[$batch].0,
+                            proto::$ty,
+                            rsp
+                        )
+                    })
+                    .transpose()?,)*)*
+                scaling: None,
+                precision: proto::Precision::default(),
+            })
+        }
+
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for AllValues {
+            fn serialize<S: serde::Serializer>(
+                &self,
+                se: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                use serde::ser::SerializeStruct;
+
+                let len = proto::Measurement::ALL.len() + usize::from(self.scaling.is_some());
+                let mut values = se.serialize_struct("AllValues", len)?;
+                $($(values.serialize_field(
+                    proto::Measurement::$ty.name(),
+                    &self.precision.round(proto::Measurement::$ty, *self.$field),
+                )?;)*)*
+                if let Some(scaling) = &self.scaling {
+                    values.serialize_field("scaling", scaling)?;
+                }
+                values.end()
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for AllValuesPartial {
+            fn serialize<S: serde::Serializer>(
+                &self,
+                se: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                use serde::ser::SerializeStruct;
+
+                let len = proto::Measurement::ALL.len() + usize::from(self.scaling.is_some());
+                let mut values = se.serialize_struct("AllValuesPartial", len)?;
+                $($(values.serialize_field(
+                    proto::Measurement::$ty.name(),
+                    &self
+                        .$field
+                        .map(|value| self.precision.round(proto::Measurement::$ty, *value)),
+                )?;)*)*
+                if let Some(scaling) = &self.scaling {
+                    values.serialize_field("scaling", scaling)?;
+                }
+                values.end()
+            }
+        }
+    };
+}
+
+all_values_partial! {
+    0 => {
+        l1_voltage: L1Voltage, scaled by vt;
+        l2_voltage: L2Voltage, scaled by vt;
+        l3_voltage: L3Voltage, scaled by vt;
+        l1_current: L1Current, scaled by ct;
+        l2_current: L2Current, scaled by ct;
+        l3_current: L3Current, scaled by ct;
+        l1_power_active: L1PowerActive, scaled by power;
+        l2_power_active: L2PowerActive, scaled by power;
+        l3_power_active: L3PowerActive, scaled by power;
+        l1_power_apparent: L1PowerApparent, scaled by power;
+        l2_power_apparent: L2PowerApparent, scaled by power;
+        l3_power_apparent: L3PowerApparent, scaled by power;
+        l1_power_reactive: L1PowerReactive, scaled by power;
+        l2_power_reactive: L2PowerReactive, scaled by power;
+        l3_power_reactive: L3PowerReactive, scaled by power;
+        l1_power_factor: L1PowerFactor, scaled by none;
+        l2_power_factor: L2PowerFactor, scaled by none;
+        l3_power_factor: L3PowerFactor, scaled by none;
+        ln_average_voltage: LtoNAverageVoltage as "l-n_average_voltage", scaled by vt;
+        ln_average_current: LtoNAverageCurrent as "l-n_average_current", scaled by ct;
+        total_line_current: TotalLineCurrent, scaled by ct;
+        total_power: TotalPower, scaled by power;
+        total_power_apparent: TotalPowerApparent, scaled by power;
+        total_power_reactive: TotalPowerReactive, scaled by power;
+        total_power_factor: TotalPowerFactor, scaled by none;
+        frequency: Frequency, scaled by none;
+        import_energy_active: ImportEnergyActive, scaled by power;
+        export_energy_active: ExportEnergyActive, scaled by power;
+    }
+    1 => {
+        l1l2_voltage: L1ToL2Voltage as "l1-l2_voltage", scaled by vt;
+        l2l3_voltage: L2ToL3Voltage as "l2-l3_voltage", scaled by vt;
+        l3l1_voltage: L3ToL1Voltage as "l3-l1_voltage", scaled by vt;
+        ll_average_voltage: LtoLAverageVoltage as "l-l_average_voltage", scaled by vt;
+        neutral_current: NeutralCurrent, scaled by ct;
+    }
+    2 => {
+        total_energy_active: TotalEnergyActive, scaled by power;
+        total_energy_reactive: TotalEnergyReactive, scaled by power;
+        resettable_total_energy_active: ResettableTotalEnergyActive, scaled by power;
+        resettable_total_energy_reactive: ResettableTotalEnergyReactive, scaled by power;
+        resettable_import_energy_active: ResettableImportEnergyActive, scaled by power;
+        resettable_export_energy_active: ResettableExportEnergyActive, scaled by power;
+        net_kwh: NetKwh as "net_kwh_import_-_export", scaled by power;
+    }
+    3 => {
+        import_total_energy_active: ImportTotalPowerActive, scaled by power;
+        export_total_energy_active: ExportTotalPowerActive, scaled by power;
+    }
+}
+
+/// The firmware generation of the meter, which determines the available input
+/// registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Firmware {
+    /// The first generation SDM72D-M lacks the line to line voltages, the
+    /// neutral current and the import/export total power block, it answers
+    /// with exceptions for them.
+    V1,
+    /// The SDM72D-M-2 supports all registers.
+    V2,
+}
+impl Firmware {
+    /// Detects the firmware by the meter code, the SDM72D-M-2 reports
+    /// `0089`. All other meters are treated as the first generation.
+    pub fn detect(identity: &MeterIdentity) -> Self {
+        match identity.model {
+            Some(proto::MeterModel::SDM72DM2) => Self::V2,
+            None => Self::V1,
+        }
+    }
+
+    /// Returns `true` if the firmware supports the request of [`VALUE_BATCHES`]
+    /// with the given index.
+    pub(crate) fn supports_batch(&self, index: usize) -> bool {
+        match self {
+            Self::V1 => matches!(index, 0 | 2),
+            Self::V2 => true,
+        }
+    }
+
+    /// Returns `true` if the firmware supports the measurement.
+    pub fn supports(&self, measurement: proto::Measurement) -> bool {
+        self.supports_address(measurement.address())
+    }
+
+    /// Returns `true` if the firmware supports the input register at the
+    /// address.
+    pub fn supports_address(&self, address: u16) -> bool {
+        VALUE_BATCHES
+            .iter()
+            .position(|(first, quantity)| (*first..first + quantity).contains(&address))
+            .is_some_and(|index| self.supports_batch(index))
+    }
+}
+
+/// The values read by `read_all_lenient`, with the errors of the failed
+/// batches.
+#[derive(Debug)]
+pub struct ReadReport {
+    /// The values, those of failed batches are `None`.
+    pub values: AllValuesPartial,
+    /// The errors of the failed batches, their addresses are available with
+    /// [`Error::context`].
+    pub errors: Vec<Error>,
+}
+impl ReadReport {
+    /// Returns `true` if no batch failed.
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn resolve_tcp_address_literals() {
+        let timeout = Duration::from_secs(1);
+        assert_eq!(
+            resolve_tcp_address("192.168.0.222", timeout).unwrap(),
+            vec!["192.168.0.222:502".parse().unwrap()]
+        );
+        assert_eq!(
+            resolve_tcp_address("[fd00::12]:1502", timeout).unwrap(),
+            vec!["[fd00::12]:1502".parse().unwrap()]
+        );
+        assert_eq!(
+            resolve_tcp_address("fd00::12", timeout).unwrap(),
+            vec!["[fd00::12]:502".parse().unwrap()]
+        );
+        assert_eq!(
+            resolve_tcp_address("[fd00::12]", timeout).unwrap(),
+            vec!["[fd00::12]:502".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn exception_hints() {
+        let register_error = |operation, kind, register, address, code| Error::Register {
+            context: RegisterContext {
+                operation,
+                kind,
+                register,
+                address,
+                slave: Some(1),
+            },
+            source: Box::new(Error::ModbusException(code)),
+        };
+        let hint = register_error(
+            Operation::Read,
+            proto::RegisterKind::Input,
+            "NeutralCurrent",
+            Some(proto::NeutralCurrent::ADDRESS),
+            ExceptionCode::IllegalDataAddress,
+        )
+        .hint()
+        .unwrap();
+        assert!(hint.starts_with("The meter does not support register 0x00E0 NeutralCurrent"));
+        assert!(hint.contains("SDM72D-M-2"));
+        let hint = register_error(
+            Operation::Read,
+            proto::RegisterKind::Input,
+            "L1Voltage",
+            Some(proto::L1Voltage::ADDRESS),
+            ExceptionCode::IllegalDataAddress,
+        )
+        .hint()
+        .unwrap();
+        assert!(hint.contains("later firmware"));
+        // The input register map of the first generation doesn't apply to holding registers
+        for address in [0x0056, 0xF010, 0xFC00] {
+            let hint = register_error(
+                Operation::Read,
+                proto::RegisterKind::Holding,
+                "holding registers",
+                Some(address),
+                ExceptionCode::IllegalDataAddress,
+            )
+            .hint()
+            .unwrap();
+            assert!(!hint.contains("SDM72D-M-2"), "{hint}");
+            assert!(hint.contains("later firmware"), "{hint}");
+        }
+        assert!(register_error(
+            Operation::Write,
+            proto::RegisterKind::Holding,
+            "SystemType",
+            Some(proto::SystemType::ADDRESS),
+            ExceptionCode::IllegalDataValue,
+        )
+        .hint()
+        .unwrap()
+        .contains("KPPA"));
+        assert!(Error::ModbusException(ExceptionCode::GatewayTargetDevice)
+            .hint()
+            .unwrap()
+            .contains("the meter"));
+        assert_eq!(
+            Error::ModbusException(ExceptionCode::Custom(0x20)).hint(),
+            None
+        );
+        assert_eq!(Error::Unauthorized.hint(), None);
+    }
+
+    #[test]
+    fn broadcast_results() {
+        let timeout =
+            Error::Modbus(std::io::Error::new(std::io::ErrorKind::TimedOut, "Timeout").into());
+        assert!(broadcast_result(Err(timeout)).is_ok());
+        assert!(broadcast_result(Ok(())).is_ok());
+        assert!(matches!(
+            broadcast_result(Err(Error::ModbusException(ExceptionCode::IllegalDataValue))),
+            Err(Error::ModbusException(_))
+        ));
+    }
+
+    #[test]
+    fn decode_partial_values() {
+        let rsp = [
+            Some([0x4366, 0x0000].repeat(VALUE_BATCHES[0].1 as usize / 2)),
+            None,
+            Some(vec![0; VALUE_BATCHES[2].1 as usize]),
+            None,
+        ];
+        let values = decode_values_partial(&rsp).unwrap();
+        assert_eq!(values.value(proto::Measurement::L1Voltage), Some(230.0));
+        assert_eq!(values.value(proto::Measurement::NeutralCurrent), None);
+        assert_eq!(values.value(proto::Measurement::NetKwh), Some(0.0));
+        assert!(values.complete().is_none());
+
+        assert!(Firmware::V1.supports(proto::Measurement::TotalEnergyActive));
+        assert!(!Firmware::V1.supports(proto::Measurement::NeutralCurrent));
+        assert!(!Firmware::V1.supports(proto::Measurement::ExportTotalPowerActive));
+        assert!(Firmware::V2.supports(proto::Measurement::ExportTotalPowerActive));
+    }
+
+    #[test]
+    fn calibrate_values() {
+        let rsp = VALUE_BATCHES.map(|(_, quantity)| [0x4366, 0x0000].repeat(quantity as usize / 2));
+        let config = ScalingConfig::default()
+            .with(
+                proto::Measurement::L1Current,
+                Calibration {
+                    multiplier: 2.0,
+                    offset: -0.5,
+                },
+            )
+            .with(
+                proto::Measurement::L1Voltage,
+                Calibration {
+                    offset: 1.0,
+                    ..Default::default()
+                },
+            );
+        let values = decode_values(&rsp)
+            .unwrap()
+            .scaled(&ScalingProfile {
+                ct_ratio: 10.0,
+                vt_ratio: 1.0,
+            })
+            .calibrated(&config);
+        assert_eq!(*values.l1_current, 4599.5);
+        assert_eq!(*values.l1_voltage, 231.0);
+        assert_eq!(*values.l2_current, 2300.0);
+        assert!(values.scaling.is_some());
+
+        let partial = AllValuesPartial {
+            l1_current: Some(
+                proto::L1Current::decode_from_input_register(&[0x4366, 0x0000]).unwrap(),
+            ),
+            ..Default::default()
+        }
+        .calibrated(&config);
+        assert_eq!(partial.value(proto::Measurement::L1Current), Some(459.5));
+        assert_eq!(partial.value(proto::Measurement::L1Voltage), None);
+    }
+
+    #[test]
+    #[cfg(any(feature = "safe-client-sync", feature = "safe-client-async"))]
+    fn apply_value_scaling() {
+        let rsp = VALUE_BATCHES.map(|(_, quantity)| [0x4366, 0x0000].repeat(quantity as usize / 2));
+        let config = ScalingConfig::default().with(
+            proto::Measurement::L1Current,
+            Calibration {
+                multiplier: 2.0,
+                offset: -0.5,
+            },
+        );
+        let profile = ScalingProfile {
+            ct_ratio: 10.0,
+            vt_ratio: 1.0,
+        };
+        let values = decode_values(&rsp)
+            .unwrap()
+            .scaled(&profile)
+            .calibrated(&config);
+        let mut precision = proto::Precision::default();
+        precision.set(proto::MeasurementClass::Current, 1).unwrap();
+        let scaling = ValueScaling {
+            profile: Some(profile),
+            calibration: Some(std::sync::Arc::new(config)),
+            precision,
+        };
+        let shared = scaling.apply(decode_values(&rsp).unwrap());
+        assert_eq!(shared.precision, precision);
+        assert_eq!(
+            AllValues {
+                precision: proto::Precision::default(),
+                ..shared
+            },
+            values
+        );
+    }
+
+    #[test]
+    fn validate_values() {
+        let mut rsp =
+            VALUE_BATCHES.map(|(_, quantity)| [0x4366, 0x0000].repeat(quantity as usize / 2));
+        let values = decode_values(&rsp).unwrap();
+        assert!(ValueValidation::Finite.check_values(&values).is_ok());
+        assert!(matches!(
+            ValueValidation::Plausible.check_values(&values),
+            Err(Error::ImplausibleValue {
+                register: "l1_power_factor",
+                raw: 0x4366_0000
+            })
+        ));
+
+        rsp[0][..2].copy_from_slice(&[0xFFFF, 0xFFFF]);
+        let values = decode_values(&rsp).unwrap();
+        assert!(ValueValidation::Off.check_values(&values).is_ok());
+        let err = ValueValidation::Finite.check_values(&values).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Implausible value NaN of l1_voltage (raw 0xFFFFFFFF)"
+        );
+
+        let mut partial = AllValuesPartial::from(values);
+        let errors = ValueValidation::Finite.check_partial(&mut partial);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(partial.value(proto::Measurement::L1Voltage), None);
+        assert_eq!(partial.value(proto::Measurement::L2Voltage), Some(230.0));
+    }
+
+    #[test]
+    fn metric_names() {
+        let rsp = VALUE_BATCHES.map(|(_, quantity)| [0x4366, 0x0000].repeat(quantity as usize / 2));
+        let metrics = decode_values(&rsp).unwrap().to_metrics();
+        assert_eq!(metrics.len(), proto::Measurement::ALL.len());
+        assert_eq!(
+            metrics[0].to_string(),
+            r#"sdm72_voltage_volts{phase="l1"} 230"#
+        );
+        let frequency = metrics
+            .iter()
+            .find(|metric| metric.measurement == proto::Measurement::Frequency)
+            .unwrap();
+        assert_eq!(frequency.to_string(), "sdm72_frequency_hertz 230");
+
+        // A name and its labels identify a measurement
+        for (index, metric) in metrics.iter().enumerate() {
+            assert!(!metrics[..index].iter().any(|other| {
+                other.descriptor.name == metric.descriptor.name
+                    && other.descriptor.labels == metric.descriptor.labels
+            }));
+            assert_eq!(
+                metric.descriptor.name.ends_with("_total"),
+                metric.descriptor.kind == proto::MetricKind::Counter
+            );
+        }
+    }
+
+    #[test]
+    fn timestamped_values() {
+        let rsp = VALUE_BATCHES.map(|(_, quantity)| vec![0; quantity as usize]);
+        let read_started = Timestamp::now();
+        let read_completed = Timestamp {
+            instant: read_started.instant + Duration::from_millis(120),
+            system_time: read_started.system_time + Duration::from_millis(121),
+        };
+        let timestamped = TimestampedAllValues {
+            read_started,
+            read_completed,
+            values: decode_values(&rsp).unwrap(),
+        };
+        assert_eq!(timestamped.duration(), Duration::from_millis(120));
+        assert_eq!(
+            timestamped.sampled_at(),
+            read_started.system_time + Duration::from_millis(60)
+        );
+    }
+
+    #[test]
+    fn decode_identity_batch() {
+        assert_eq!(IDENTITY_BATCH, (0xFC00, 3));
+        let software_version =
+            proto::SoftwareVersion::decode_from_holding_registers(&[0x0102]).unwrap();
+        let identity = decode_identity(&[0x0001, 0xE240, 0x0089], software_version).unwrap();
+        assert_eq!(*identity.serial_number, 123456);
+        assert_eq!(*identity.meter_code, 0x0089);
+        assert_eq!(identity.model, Some(proto::MeterModel::SDM72DM2));
+    }
+
+    #[test]
+    #[cfg(any(feature = "safe-client-sync", feature = "safe-client-async"))]
+    fn reconnect_after_transport_errors() {
+        let tcp = ConnectionParams::Tcp {
+            socket_addr: ([127, 0, 0, 1], 502).into(),
+            slave: Slave(1),
+            options: TcpOptions::default(),
+        };
+        let rtu = ConnectionParams::Rtu {
+            device: "/dev/ttyUSB0".to_string(),
+            baud_rate: proto::BaudRate::B9600,
+            parity_and_stop_bit: proto::ParityAndStopBit::default(),
+            slave: Slave(1),
+        };
+        let transport =
+            |kind| Error::Modbus(tokio_modbus::Error::Transport(std::io::Error::from(kind)));
+        let timeout = transport(std::io::ErrorKind::TimedOut);
+        assert!(tcp.reconnects_after(&timeout));
+        assert!(!rtu.reconnects_after(&timeout));
+        let broken = transport(std::io::ErrorKind::BrokenPipe);
+        assert!(tcp.reconnects_after(&broken));
+        assert!(rtu.reconnects_after(&broken));
+        let exception = Error::ModbusException(ExceptionCode::IllegalDataAddress);
+        assert!(!tcp.reconnects_after(&exception));
+    }
+
+    #[test]
+    #[cfg(any(feature = "safe-client-sync", feature = "safe-client-async"))]
+    fn split_batches() {
+        assert_eq!(
+            split_batch((0x0000, 0x0050)),
+            Some([(0x0000, 0x0028), (0x0028, 0x0028)])
+        );
+        assert_eq!(
+            split_batch((0x0156, 0x0006)),
+            Some([(0x0156, 0x0002), (0x0158, 0x0004)])
+        );
+        assert_eq!(split_batch((0x0500, 0x0002)), None);
+    }
+
+    #[test]
+    fn apply_settings_to_params() {
+        let mut params = ConnectionParams::Rtu {
+            device: "/dev/ttyUSB0".to_string(),
+            baud_rate: proto::BaudRate::B9600,
+            parity_and_stop_bit: proto::ParityAndStopBit::default(),
+            slave: Slave(1),
+        };
+        assert!(params.apply_setting(Setting::BaudRate(proto::BaudRate::B19200)));
+        assert!(!params.apply_setting(Setting::BaudRate(proto::BaudRate::B19200)));
+        assert!(params.apply_setting(Setting::Address(proto::Address::try_from(5).unwrap())));
+        assert_eq!(params.slave(), Slave(5));
+        assert!(!params.apply_setting(Setting::Password(proto::Password::default())));
+
+        let mut params = ConnectionParams::rtu_over_tcp("127.0.0.1:502".parse().unwrap(), Slave(1));
+        assert!(!params.apply_setting(Setting::BaudRate(proto::BaudRate::B19200)));
+    }
+
+    #[test]
+    fn register_error_display() {
+        let err = Error::Register {
+            context: RegisterContext {
+                operation: Operation::Read,
+                kind: proto::RegisterKind::Input,
+                register: "TotalPower",
+                address: Some(0x0034),
+                slave: Some(3),
+            },
+            source: Box::new(Error::Modbus(tokio_modbus::Error::Transport(
+                std::io::ErrorKind::TimedOut.into(),
+            ))),
+        };
+        assert!(err
+            .to_string()
+            .starts_with("reading TotalPower at 0x0034 on slave 3: "));
+        assert!(err.is_transport());
     }
 }
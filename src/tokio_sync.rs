@@ -33,10 +33,15 @@
 //! ```
 
 use crate::{
+    modbus::Slave,
     protocol::{self as proto, ModbusParam},
-    tokio_common::{AllSettings, AllValues, Result},
+    settings_diff::Setting,
+    tokio_common::{
+        self, AllSettings, AllValues, AllValuesPartial, ConnectionParams, Error, Firmware,
+        MeterIdentity, Result,
+    },
 };
-use tokio_modbus::prelude::{SyncReader, SyncWriter};
+use tokio_modbus::prelude::{SlaveContext, SyncReader, SyncWriter};
 
 /// A synchronous client for the SDM72 energy meter.
 ///
@@ -51,9 +56,14 @@ macro_rules! read_holding {
         paste::item! {
             #[doc = "Reads the [`proto::" $ty "`] value from the Modbus holding register."]
             pub fn $func_name(ctx: &mut tokio_modbus::client::sync::Context) -> Result<proto::$ty> {
+                Self::[< $func_name _with_word_order >](ctx, proto::WordOrder::BigEndian)
+            }
+
+            #[doc = "Reads the [`proto::" $ty "`] value from the Modbus holding register with the given [`WordOrder`](proto::WordOrder)."]
+            pub fn [< $func_name _with_word_order >](ctx: &mut tokio_modbus::client::sync::Context, word_order: proto::WordOrder) -> Result<proto::$ty> {
                 let rsp = ctx
                     .read_holding_registers(<proto::$ty>::ADDRESS, <proto::$ty>::QUANTITY)??;
-                Ok(<proto::$ty>::decode_from_holding_registers(&rsp)?)
+                Ok(<proto::$ty>::decode_from_holding_registers(&word_order.apply(rsp))?)
             }
         }
     };
@@ -65,9 +75,14 @@ macro_rules! write_holding {
         paste::item! {
             #[doc = "Writes the [`proto::" $ty "`] value to the Modbus holding register."]
             pub fn [< set_ $func_name >](ctx: &mut tokio_modbus::client::sync::Context, value: proto::$ty) -> Result<()> {
+                Self::[< set_ $func_name _with_word_order >](ctx, value, proto::WordOrder::BigEndian)
+            }
+
+            #[doc = "Writes the [`proto::" $ty "`] value to the Modbus holding register with the given [`WordOrder`](proto::WordOrder)."]
+            pub fn [< set_ $func_name _with_word_order >](ctx: &mut tokio_modbus::client::sync::Context, value: proto::$ty, word_order: proto::WordOrder) -> Result<()> {
                 Ok(ctx.write_multiple_registers(
                     <proto::$ty>::ADDRESS,
-                    &value.encode_for_write_registers(),
+                    &word_order.apply(value.encode_for_write_registers()),
                 )??)
             }
         }
@@ -75,6 +90,92 @@ macro_rules! write_holding {
 }
 
 impl SDM72 {
+    /// Opens a new synchronous Modbus context for the given connection parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - The connection parameters.
+    /// * `timeout` - The timeout for establishing the connection and for all
+    ///   subsequent Modbus Input/Output operations.
+    pub fn connect(
+        params: &ConnectionParams,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<tokio_modbus::client::sync::Context> {
+        let ctx = match params {
+            #[cfg(feature = "tokio-tcp-sync")]
+            ConnectionParams::Tcp {
+                socket_addr,
+                slave,
+                options,
+            } => {
+                if *options == crate::tokio_common::TcpOptions::default() {
+                    tokio_modbus::client::sync::tcp::connect_slave_with_timeout(
+                        *socket_addr,
+                        (*slave).into(),
+                        timeout,
+                    )
+                } else {
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::Unsupported,
+                        "TCP socket options are only supported by the asynchronous client",
+                    ))
+                }
+            }
+            #[cfg(feature = "tokio-rtu-sync")]
+            ConnectionParams::Rtu {
+                device,
+                baud_rate,
+                parity_and_stop_bit,
+                slave,
+            } => {
+                proto::check_serial_settings(baud_rate, parity_and_stop_bit)?;
+                tokio_modbus::client::sync::rtu::connect_slave_with_timeout(
+                    &crate::tokio_common::serial_port_builder(
+                        device,
+                        baud_rate,
+                        parity_and_stop_bit,
+                    ),
+                    (*slave).into(),
+                    timeout,
+                )
+            }
+            ConnectionParams::RtuOverTcp { .. } => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "RTU over TCP is only supported by the asynchronous client",
+            )),
+            #[cfg(feature = "tokio-tcp-tls")]
+            ConnectionParams::TcpTls { .. } => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "TLS is only supported by the asynchronous client",
+            )),
+            #[allow(unreachable_patterns)]
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("Connection type is not enabled: {params}"),
+            )),
+        };
+        Ok(ctx.map_err(tokio_modbus::Error::Transport)?)
+    }
+
+    /// Opens a new synchronous Modbus context like [`connect`](Self::connect),
+    /// with a separate timeout for establishing the connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - The connection parameters.
+    /// * `connect_timeout` - The timeout for establishing the connection, i.e.
+    ///   the TCP connect, which may need a longer budget than a request.
+    /// * `request_timeout` - The timeout for all Modbus Input/Output operations.
+    pub fn connect_with_timeouts(
+        params: &ConnectionParams,
+        connect_timeout: Option<std::time::Duration>,
+        request_timeout: Option<std::time::Duration>,
+    ) -> Result<tokio_modbus::client::sync::Context> {
+        let mut ctx = Self::connect(params, connect_timeout)?;
+        ctx.set_timeout(request_timeout);
+        Ok(ctx)
+    }
+
     read_holding!(system_type, SystemType);
     write_holding!(system_type, SystemType);
     read_holding!(pulse_width, PulseWidth);
@@ -86,10 +187,20 @@ impl SDM72 {
     pub fn set_kppa(
         ctx: &mut tokio_modbus::client::sync::Context,
         password: proto::Password,
+    ) -> Result<()> {
+        Self::set_kppa_with_word_order(ctx, password, proto::WordOrder::BigEndian)
+    }
+
+    /// Like [`set_kppa`](Self::set_kppa), with the given
+    /// [`WordOrder`](proto::WordOrder) of the 32-bit values.
+    pub fn set_kppa_with_word_order(
+        ctx: &mut tokio_modbus::client::sync::Context,
+        password: proto::Password,
+        word_order: proto::WordOrder,
     ) -> Result<()> {
         Ok(ctx.write_multiple_registers(
             proto::KPPA::ADDRESS,
-            &proto::KPPA::encode_for_write_registers(password),
+            &word_order.apply(proto::KPPA::encode_for_write_registers(password)),
         )??)
     }
     read_holding!(parity_and_stop_bit, ParityAndStopBit);
@@ -117,10 +228,160 @@ impl SDM72 {
             &proto::ResetHistoricalData::encode_for_write_registers(),
         )??)
     }
+
+    /// Resets the data of the target on the meter.
+    ///
+    /// This requires KPPA authorization.
+    pub fn reset(
+        ctx: &mut tokio_modbus::client::sync::Context,
+        target: proto::ResetTarget,
+    ) -> Result<()> {
+        Ok(ctx.write_multiple_registers(
+            proto::ResetHistoricalData::ADDRESS,
+            &target.encode_for_write_registers(),
+        )??)
+    }
+
+    /// Writes a setting to the Modbus broadcast address, which all meters on
+    /// the bus execute without a response, e.g. to set the baud rate of many
+    /// meters at once during commissioning.
+    ///
+    /// The KPPA is written with `password` first, then the setting. Each
+    /// request waits `turnaround` for the meters, see
+    /// [`BROADCAST_TURNAROUND`](tokio_common::BROADCAST_TURNAROUND). The
+    /// slave of the context is set to `slave` afterwards.
+    ///
+    /// # Warning
+    ///
+    /// Nothing confirms that a meter executed the write: a meter with another
+    /// password, parity or baud rate silently ignores it, so read the setting
+    /// back from every meter afterwards. After a change of the parity or the
+    /// baud rate the meters only answer with the new setting, the context must
+    /// be reopened before the next broadcast. The address would be the same on
+    /// all meters, it fails with [`Error::NotBroadcastable`].
+    pub fn broadcast_setting(
+        ctx: &mut tokio_modbus::client::sync::Context,
+        password: proto::Password,
+        setting: Setting,
+        turnaround: std::time::Duration,
+        slave: Slave,
+    ) -> Result<()> {
+        Self::broadcast_setting_with_word_order(
+            ctx,
+            password,
+            setting,
+            proto::WordOrder::BigEndian,
+            turnaround,
+            slave,
+        )
+    }
+
+    /// Like [`broadcast_setting`](Self::broadcast_setting), with the given
+    /// [`WordOrder`](proto::WordOrder) of the 32-bit values.
+    pub fn broadcast_setting_with_word_order(
+        ctx: &mut tokio_modbus::client::sync::Context,
+        password: proto::Password,
+        setting: Setting,
+        word_order: proto::WordOrder,
+        turnaround: std::time::Duration,
+        slave: Slave,
+    ) -> Result<()> {
+        if matches!(setting, Setting::Address(_)) {
+            return Err(Error::NotBroadcastable(setting));
+        }
+        let (address, words) = setting.encode_for_write_registers();
+        let timeout = ctx.timeout();
+        ctx.set_slave(tokio_modbus::Slave::broadcast());
+        ctx.set_timeout(turnaround);
+        let mut broadcast = |address, words: Vec<proto::Word>| {
+            let started = std::time::Instant::now();
+            let result = tokio_common::broadcast_result(
+                ctx.write_multiple_registers(address, &word_order.apply(words))
+                    .map_err(Error::from)
+                    .and_then(|rsp| Ok(rsp?)),
+            );
+            std::thread::sleep(turnaround.saturating_sub(started.elapsed()));
+            result
+        };
+        let result = broadcast(
+            proto::KPPA::ADDRESS,
+            proto::KPPA::encode_for_write_registers(password),
+        )
+        .and_then(|()| broadcast(address, words));
+        ctx.set_timeout(timeout);
+        ctx.set_slave(slave.into());
+        result
+    }
+
     read_holding!(serial_number, SerialNumber);
     read_holding!(meter_code, MeterCode);
     read_holding!(software_version, SoftwareVersion);
 
+    /// Reads raw words from Modbus input registers.
+    ///
+    /// This is an escape hatch for registers which are not covered by the
+    /// [`protocol`](crate::protocol) module, e.g. firmware specific ones.
+    pub fn read_raw_input(
+        ctx: &mut tokio_modbus::client::sync::Context,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<proto::Word>> {
+        Ok(ctx.read_input_registers(address, quantity)??)
+    }
+
+    /// Reads raw words from Modbus holding registers.
+    ///
+    /// This is an escape hatch for registers which are not covered by the
+    /// [`protocol`](crate::protocol) module, e.g. firmware specific ones.
+    pub fn read_raw_holding(
+        ctx: &mut tokio_modbus::client::sync::Context,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<proto::Word>> {
+        Ok(ctx.read_holding_registers(address, quantity)??)
+    }
+
+    /// Writes raw words to Modbus holding registers.
+    ///
+    /// This is an escape hatch for registers which are not covered by the
+    /// [`protocol`](crate::protocol) module, e.g. firmware specific ones.
+    pub fn write_raw_holding(
+        ctx: &mut tokio_modbus::client::sync::Context,
+        address: u16,
+        words: &[proto::Word],
+    ) -> Result<()> {
+        Ok(ctx.write_multiple_registers(address, words)??)
+    }
+
+    /// Reads the identity of the meter, the serial number and the meter code
+    /// are read in a single request, the software version in a second one.
+    ///
+    /// # Arguments
+    ///
+    /// * `delay` - The delay to be inserted between the two Modbus requests.
+    pub fn identify(
+        ctx: &mut tokio_modbus::client::sync::Context,
+        delay: &std::time::Duration,
+    ) -> Result<MeterIdentity> {
+        Self::identify_with_word_order(ctx, delay, proto::WordOrder::BigEndian)
+    }
+
+    /// Like [`identify`](Self::identify), with the given
+    /// [`WordOrder`](proto::WordOrder) of the 32-bit values.
+    pub fn identify_with_word_order(
+        ctx: &mut tokio_modbus::client::sync::Context,
+        delay: &std::time::Duration,
+        word_order: proto::WordOrder,
+    ) -> Result<MeterIdentity> {
+        let (offset, quantity) = tokio_common::IDENTITY_BATCH;
+        let rsp = word_order.apply(ctx.read_holding_registers(offset, quantity)??);
+
+        std::thread::sleep(*delay);
+        let software_version = Self::software_version_with_word_order(ctx, word_order)?;
+
+        tokio_common::decode_identity(&rsp, software_version)
+    }
+
     /// Reads all settings from the meter in a single batch operation.
     ///
     /// This method is more efficient than reading each setting individually because
@@ -130,6 +391,8 @@ impl SDM72 {
     ///
     /// # Arguments
     ///
+    /// * `mode` - How unknown codes of the enum settings are decoded, see
+    ///   [`DecodeMode`](proto::DecodeMode).
     /// * `delay` - The delay to be inserted between Modbus requests. This is
     ///   necessary for some Modbus devices, which may need a short pause to
     ///   process a request before they are ready to accept the next one. A
@@ -137,76 +400,31 @@ impl SDM72 {
     ///   device and network conditions.
     pub fn read_all_settings(
         ctx: &mut tokio_modbus::client::sync::Context,
+        mode: proto::DecodeMode,
         delay: &std::time::Duration,
     ) -> Result<AllSettings> {
-        let offset1 = proto::SystemType::ADDRESS;
-        let quantity =
-            { proto::PulseEnergyType::ADDRESS - offset1 + proto::PulseEnergyType::QUANTITY };
-        let rsp1 = ctx.read_holding_registers(offset1, quantity)??;
+        Self::read_all_settings_with_word_order(ctx, mode, delay, proto::WordOrder::BigEndian)
+    }
+
+    /// Like [`read_all_settings`](Self::read_all_settings), with the given
+    /// [`WordOrder`](proto::WordOrder) of the 32-bit values.
+    pub fn read_all_settings_with_word_order(
+        ctx: &mut tokio_modbus::client::sync::Context,
+        mode: proto::DecodeMode,
+        delay: &std::time::Duration,
+        word_order: proto::WordOrder,
+    ) -> Result<AllSettings> {
+        let (offset, quantity) = tokio_common::SETTINGS_BATCH;
+        let rsp = word_order.apply(ctx.read_holding_registers(offset, quantity)??);
 
         std::thread::sleep(*delay);
-        let serial_number = Self::serial_number(ctx)?;
+        let serial_number = Self::serial_number_with_word_order(ctx, word_order)?;
         std::thread::sleep(*delay);
-        let meter_code = Self::meter_code(ctx)?;
+        let meter_code = Self::meter_code_with_word_order(ctx, word_order)?;
         std::thread::sleep(*delay);
-        let software_version = Self::software_version(ctx)?;
+        let software_version = Self::software_version_with_word_order(ctx, word_order)?;
 
-        Ok(AllSettings {
-            system_type: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::SystemType,
-                &rsp1
-            )?,
-            pulse_width: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::PulseWidth,
-                &rsp1
-            )?,
-            kppa: crate::decode_subset_item_from_holding_register!(offset1, proto::KPPA, &rsp1)?,
-            parity_and_stop_bit: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::ParityAndStopBit,
-                &rsp1
-            )?,
-            address: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::Address,
-                &rsp1
-            )?,
-            pulse_constant: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::PulseConstant,
-                &rsp1
-            )?,
-            password: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::Password,
-                &rsp1
-            )?,
-            baud_rate: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::BaudRate,
-                &rsp1
-            )?,
-            auto_scroll_time: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::AutoScrollTime,
-                &rsp1
-            )?,
-            backlight_time: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::BacklightTime,
-                &rsp1
-            )?,
-            pulse_energy_type: crate::decode_subset_item_from_holding_register!(
-                offset1,
-                proto::PulseEnergyType,
-                &rsp1
-            )?,
-            serial_number,
-            meter_code,
-            software_version,
-        })
+        tokio_common::decode_settings(&rsp, serial_number, meter_code, software_version, mode)
     }
 
     /// Reads all measurement values from the meter in a single batch operation.
@@ -216,6 +434,9 @@ impl SDM72 {
     /// meter has a limit of 30 parameters per request, so this function splits
     /// the reads into multiple batches.
     ///
+    /// The values are returned as decoded, the scaling, calibrations and
+    /// precision of the safe clients are not applied.
+    ///
     /// # Arguments
     ///
     /// * `delay` - The delay to be inserted between Modbus requests. This is
@@ -227,243 +448,61 @@ impl SDM72 {
         ctx: &mut tokio_modbus::client::sync::Context,
         delay: &std::time::Duration,
     ) -> Result<AllValues> {
-        let offset1 = proto::L1Voltage::ADDRESS;
-        let quantity =
-            { proto::ExportEnergyActive::ADDRESS - offset1 + proto::ExportEnergyActive::QUANTITY };
-        let rsp1 = ctx.read_input_registers(offset1, quantity)??;
-
-        std::thread::sleep(*delay);
-
-        let offset2 = proto::L1ToL2Voltage::ADDRESS;
-        let quantity =
-            { proto::NeutralCurrent::ADDRESS - offset2 + proto::NeutralCurrent::QUANTITY };
-        let rsp2 = ctx.read_input_registers(offset2, quantity)??;
-
-        std::thread::sleep(*delay);
-
-        let offset3 = proto::TotalEnergyActive::ADDRESS;
-        let quantity = { proto::NetKwh::ADDRESS - offset3 + proto::NetKwh::QUANTITY };
-        let rsp3 = ctx.read_input_registers(offset3, quantity)??;
-
-        std::thread::sleep(*delay);
-
-        let offset4 = proto::ImportTotalPowerActive::ADDRESS;
-        let quantity = {
-            proto::ExportTotalPowerActive::ADDRESS - offset4
-                + proto::ExportTotalPowerActive::QUANTITY
-        };
-        let rsp4 = ctx.read_input_registers(offset4, quantity)??;
-
-        Ok(AllValues {
-            l1_voltage: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L1Voltage,
-                &rsp1
-            )?,
-            l2_voltage: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L2Voltage,
-                &rsp1
-            )?,
-            l3_voltage: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L3Voltage,
-                &rsp1
-            )?,
-            l1_current: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L1Current,
-                &rsp1
-            )?,
-            l2_current: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L2Current,
-                &rsp1
-            )?,
-            l3_current: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L3Current,
-                &rsp1
-            )?,
-            l1_power_active: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L1PowerActive,
-                &rsp1
-            )?,
-            l2_power_active: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L2PowerActive,
-                &rsp1
-            )?,
-            l3_power_active: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L3PowerActive,
-                &rsp1
-            )?,
-            l1_power_apparent: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L1PowerApparent,
-                &rsp1
-            )?,
-            l2_power_apparent: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L2PowerApparent,
-                &rsp1
-            )?,
-            l3_power_apparent: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L3PowerApparent,
-                &rsp1
-            )?,
-            l1_power_reactive: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L1PowerReactive,
-                &rsp1
-            )?,
-            l2_power_reactive: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L2PowerReactive,
-                &rsp1
-            )?,
-            l3_power_reactive: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L3PowerReactive,
-                &rsp1
-            )?,
-            l1_power_factor: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L1PowerFactor,
-                &rsp1
-            )?,
-            l2_power_factor: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L2PowerFactor,
-                &rsp1
-            )?,
-            l3_power_factor: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::L3PowerFactor,
-                &rsp1
-            )?,
-            ln_average_voltage: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::LtoNAverageVoltage,
-                &rsp1
-            )?,
-            ln_average_current: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::LtoNAverageCurrent,
-                &rsp1
-            )?,
-            total_line_current: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::TotalLineCurrent,
-                &rsp1
-            )?,
-            total_power: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::TotalPower,
-                &rsp1
-            )?,
-            total_power_apparent: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::TotalPowerApparent,
-                &rsp1
-            )?,
-            total_power_reactive: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::TotalPowerReactive,
-                &rsp1
-            )?,
-            total_power_factor: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::TotalPowerFactor,
-                &rsp1
-            )?,
-            frequency: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::Frequency,
-                &rsp1
-            )?,
-            import_energy_active: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::ImportEnergyActive,
-                &rsp1
-            )?,
-            export_energy_active: crate::decode_subset_item_from_input_register!(
-                offset1,
-                proto::ExportEnergyActive,
-                &rsp1
-            )?,
+        Self::read_all_with_word_order(ctx, delay, proto::WordOrder::BigEndian)
+    }
 
-            l1l2_voltage: crate::decode_subset_item_from_input_register!(
-                offset2,
-                proto::L1ToL2Voltage,
-                &rsp2
-            )?,
-            l2l3_voltage: crate::decode_subset_item_from_input_register!(
-                offset2,
-                proto::L2ToL3Voltage,
-                &rsp2
-            )?,
-            l3l1_voltage: crate::decode_subset_item_from_input_register!(
-                offset2,
-                proto::L3ToL1Voltage,
-                &rsp2
-            )?,
-            ll_average_voltage: crate::decode_subset_item_from_input_register!(
-                offset2,
-                proto::LtoLAverageVoltage,
-                &rsp2
-            )?,
-            neutral_current: crate::decode_subset_item_from_input_register!(
-                offset2,
-                proto::NeutralCurrent,
-                &rsp2
-            )?,
+    /// Like [`read_all`](Self::read_all), with the given
+    /// [`WordOrder`](proto::WordOrder) of the 32-bit values.
+    pub fn read_all_with_word_order(
+        ctx: &mut tokio_modbus::client::sync::Context,
+        delay: &std::time::Duration,
+        word_order: proto::WordOrder,
+    ) -> Result<AllValues> {
+        let mut rsp: [Vec<proto::Word>; 4] = Default::default();
+        for (i, (offset, quantity)) in tokio_common::VALUE_BATCHES.into_iter().enumerate() {
+            if i > 0 {
+                std::thread::sleep(*delay);
+            }
+            rsp[i] = word_order.apply(ctx.read_input_registers(offset, quantity)??);
+        }
+        tokio_common::decode_values(&rsp)
+    }
 
-            total_energy_active: crate::decode_subset_item_from_input_register!(
-                offset3,
-                proto::TotalEnergyActive,
-                &rsp3
-            )?,
-            total_energy_reactive: crate::decode_subset_item_from_input_register!(
-                offset3,
-                proto::TotalEnergyReactive,
-                &rsp3
-            )?,
-            resettable_total_energy_active: crate::decode_subset_item_from_input_register!(
-                offset3,
-                proto::ResettableTotalEnergyActive,
-                &rsp3
-            )?,
-            resettable_total_energy_reactive: crate::decode_subset_item_from_input_register!(
-                offset3,
-                proto::ResettableTotalEnergyReactive,
-                &rsp3
-            )?,
-            resettable_import_energy_active: crate::decode_subset_item_from_input_register!(
-                offset3,
-                proto::ResettableImportEnergyActive,
-                &rsp3
-            )?,
-            resettable_export_energy_active: crate::decode_subset_item_from_input_register!(
-                offset3,
-                proto::ResettableExportEnergyActive,
-                &rsp3
-            )?,
-            net_kwh: crate::decode_subset_item_from_input_register!(offset3, proto::NetKwh, &rsp3)?,
+    /// Reads the measurement values supported by the firmware, the batches of
+    /// unsupported registers are skipped and their values are `None`.
+    ///
+    /// # Arguments
+    ///
+    /// * `delay` - The delay to be inserted between Modbus requests, see
+    ///   [`read_all`](Self::read_all).
+    /// * `firmware` - The firmware of the meter, see [`Firmware::detect`].
+    pub fn read_all_partial(
+        ctx: &mut tokio_modbus::client::sync::Context,
+        delay: &std::time::Duration,
+        firmware: Firmware,
+    ) -> Result<AllValuesPartial> {
+        Self::read_all_partial_with_word_order(ctx, delay, proto::WordOrder::BigEndian, firmware)
+    }
 
-            import_total_energy_active: crate::decode_subset_item_from_input_register!(
-                offset4,
-                proto::ImportTotalPowerActive,
-                &rsp4
-            )?,
-            export_total_energy_active: crate::decode_subset_item_from_input_register!(
-                offset4,
-                proto::ExportTotalPowerActive,
-                &rsp4
-            )?,
-        })
+    /// Like [`read_all_partial`](Self::read_all_partial), with the given
+    /// [`WordOrder`](proto::WordOrder) of the 32-bit values.
+    pub fn read_all_partial_with_word_order(
+        ctx: &mut tokio_modbus::client::sync::Context,
+        delay: &std::time::Duration,
+        word_order: proto::WordOrder,
+        firmware: Firmware,
+    ) -> Result<AllValuesPartial> {
+        let mut rsp: [Option<Vec<proto::Word>>; 4] = Default::default();
+        let batches = tokio_common::VALUE_BATCHES.into_iter().enumerate();
+        for (n, (i, (offset, quantity))) in batches
+            .filter(|(i, _)| firmware.supports_batch(*i))
+            .enumerate()
+        {
+            if n > 0 {
+                std::thread::sleep(*delay);
+            }
+            rsp[i] = Some(word_order.apply(ctx.read_input_registers(offset, quantity)??));
+        }
+        tokio_common::decode_values_partial(&rsp)
     }
 }
@@ -2,6 +2,8 @@
 //!
 //! The [`SafeClient`] struct wraps the core synchronous API and manages the Modbus
 //! context within an `Arc<Mutex>`, allowing it to be shared across threads safely.
+//! The [`BusManager`] hands out the clients of several meters sharing one
+//! Modbus context, e.g. of an RS485 bus.
 //!
 //! # Example
 //!
@@ -12,14 +14,13 @@
 //! };
 //! use tokio_modbus::client::sync::tcp;
 //! use tokio_modbus::Slave;
-//! use std::time::Duration;
 //!
 //! fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!     let socket_addr = "192.168.1.100:502".parse()?;
 //!     let ctx = tcp::connect_slave(socket_addr, Slave(*Address::default()))?;
 //!     let mut client = SafeClient::new(ctx);
 //!
-//!     let values = client.read_all(&Duration::from_millis(100))?;
+//!     let values = client.read_all()?;
 //!
 //!     println!("Successfully read values: {:#?}", values);
 //!
@@ -28,26 +29,345 @@
 //! ```
 
 use crate::{
+    instrumentation::{Instrumentation, RequestOutcome, RequestRecord},
+    modbus::Slave,
+    password::PasswordProvider,
     protocol as proto,
-    tokio_common::{AllSettings, AllValues, Result},
+    settings_diff::{self, Setting, SettingsReport, WriteOutcome},
+    stats::LinkStats,
+    tokio_common::{
+        self, AllSettings, AllValues, AllValuesPartial, BatchRetryPolicy, Clock, ConnectionParams,
+        Error, Firmware, MeterIdentity, Operation, ReadReport, ReconnectPolicy, RegisterContext,
+        Result, ScalingConfig, ScalingProfile, SystemClock, Timestamp, TimestampedAllValues,
+        TimestampedValues, ValueScaling, ValueValidation,
+    },
     tokio_sync::SDM72,
+    watch::{Threshold, Watch, WatchEvent},
+};
+use std::{
+    collections::HashMap,
+    ops::ControlFlow,
+    sync::{mpsc, Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
 };
-use std::sync::{Arc, Mutex};
 use tokio_modbus::{client::sync::Context, prelude::SlaveContext};
 
+/// The state required to re-establish a lost connection.
+struct Reconnect {
+    params: Mutex<ConnectionParams>,
+    policy: ReconnectPolicy,
+    connect_timeout: Option<Duration>,
+}
+
+/// The raw words of the settings by register address, with the time they were read.
+type SettingsCache = HashMap<u16, (Vec<proto::Word>, Instant)>;
+
+/// The registered watches with the channels of their events.
+type Watches = Vec<(Watch, mpsc::Sender<WatchEvent>)>;
+
 /// A thread-safe synchronous client for the SDM72 energy meter.
 #[derive(Clone)]
 pub struct SafeClient {
     ctx: Arc<Mutex<Context>>,
+    decode_mode: proto::DecodeMode,
+    reconnect: Option<Arc<Reconnect>>,
+    /// The slave id of the requests, shared by all clones of the client.
+    slave: Arc<Mutex<Option<Slave>>>,
+    scaling: ValueScaling,
+    word_order: proto::WordOrder,
+    input_via_holding: bool,
+    reopen_after_comm_change: bool,
+    password: Option<Arc<dyn PasswordProvider>>,
+    instrumentation: Option<Arc<dyn Instrumentation>>,
+    write_verify: bool,
+    batch_retry: BatchRetryPolicy,
+    value_validation: ValueValidation,
+    firmware: Option<Firmware>,
+    /// The identity of the meter, read once per connection.
+    identity: Arc<Mutex<Option<MeterIdentity>>>,
+    settings_cache: Arc<Mutex<SettingsCache>>,
+    settings_cache_ttl: Option<Duration>,
+    watches: Arc<Mutex<Watches>>,
+    last_contact: Arc<Mutex<Option<Instant>>>,
+    link_stats: Arc<Mutex<LinkStats>>,
+    clock: Arc<dyn Clock>,
+    min_request_interval: Duration,
+}
+
+/// Shares one Modbus context, e.g. of an RS485 bus, between the clients of
+/// several meters.
+///
+/// Every [`client`](Self::client) addresses its own meter. The requests of all
+/// clients are serialized by the lock of the context and separated by the
+/// minimum request interval since the last response on the bus, so the
+/// clients can poll their meters concurrently from several threads.
+pub struct BusManager {
+    ctx: Arc<Mutex<Context>>,
+    last_contact: Arc<Mutex<Option<Instant>>>,
+    min_request_interval: Duration,
+}
+
+impl BusManager {
+    /// Creates a bus manager for a connected context.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx`: The Modbus context of the bus.
+    /// * `min_request_interval`: The interval between the last response and
+    ///   the next request, the [silent interval](proto::BaudRate::silent_interval)
+    ///   of the baud rate for Modbus/RTU.
+    pub fn new(ctx: Context, min_request_interval: Duration) -> Self {
+        Self {
+            ctx: Arc::new(Mutex::new(ctx)),
+            last_contact: Arc::default(),
+            min_request_interval,
+        }
+    }
+
+    /// Connects to the bus, the minimum request interval is the one of the
+    /// connection parameters, whose slave is ignored.
+    pub fn connect(params: &ConnectionParams, timeout: Option<Duration>) -> Result<Self> {
+        Ok(Self::new(
+            SDM72::connect(params, timeout)?,
+            params.min_request_interval(),
+        ))
+    }
+
+    /// Returns a client of the meter with the address, which shares the
+    /// context with all other clients of the bus.
+    ///
+    /// The identity, the settings cache and the link statistics are per
+    /// client.
+    pub fn client(&self, address: proto::Address) -> SafeClient {
+        let mut client = SafeClient::from_shared(self.ctx.clone());
+        client.slave = Arc::new(Mutex::new(Some(address.into())));
+        client.last_contact = self.last_contact.clone();
+        client.min_request_interval = self.min_request_interval;
+        client
+    }
+
+    /// The minimum interval between the last response and the next request.
+    pub fn min_request_interval(&self) -> Duration {
+        self.min_request_interval
+    }
+}
+
+/// Writes several settings with the authorization acquired once, see
+/// [`SafeClient::begin_settings`].
+pub struct SettingsSession<'a> {
+    client: &'a mut SafeClient,
+    settings: Vec<Setting>,
+    delay: Duration,
+}
+
+impl SettingsSession<'_> {
+    /// Queues a setting, which is written by [`apply`](Self::apply).
+    pub fn queue(&mut self, setting: Setting) -> &mut Self {
+        self.settings.push(setting);
+        self
+    }
+
+    /// Sets the delay between two writes,
+    /// [`DEFAULT_WRITE_DELAY`](settings_diff::DEFAULT_WRITE_DELAY) by default.
+    pub fn set_delay(&mut self, delay: Duration) -> &mut Self {
+        self.delay = delay;
+        self
+    }
+
+    /// The queued settings.
+    pub fn queued(&self) -> &[Setting] {
+        &self.settings
+    }
+
+    /// Writes the queued settings in the order of
+    /// [`diff`](settings_diff::diff), so the settings which affect the
+    /// communication come last.
+    ///
+    /// After a failed write the remaining settings are skipped.
+    pub fn apply(mut self) -> SettingsReport {
+        settings_diff::sort_for_write(&mut self.settings);
+        let mut report = SettingsReport::default();
+        let mut failed = false;
+        for (index, setting) in self.settings.into_iter().enumerate() {
+            let outcome = if failed {
+                WriteOutcome::Skipped
+            } else {
+                if index > 0 {
+                    self.client.pace(self.delay);
+                }
+                match self.client.write_setting(setting) {
+                    Ok(()) => WriteOutcome::Written,
+                    Err(err) => {
+                        failed = true;
+                        WriteOutcome::Failed(err)
+                    }
+                }
+            };
+            report.outcomes.push((setting, outcome));
+        }
+        report
+    }
+}
+
+/// A macro to run an operation on the locked context.
+///
+/// Errors are wrapped in [`Error::Register`] with the given operation,
+/// [register kind](proto::RegisterKind), register name and address.
+///
+/// The operation is delayed until the minimum request interval has passed
+/// since the last response. Every response of the meter, including exception
+/// responses, updates the time of the last contact.
+///
+/// If the client was created with [`SafeClient::connect`] and the operation
+/// fails with a transport error, the connection is re-established according
+/// to the [`ReconnectPolicy`]. Only reads are replayed, a failed write is
+/// returned after the first reconnection. A timeout of a serial port fails the
+/// operation without a reconnection. Every attempt is counted in the
+/// [`LinkStats`], the request with all its attempts is reported to the
+/// [`Instrumentation`].
+macro_rules! with_ctx {
+    ($self:ident, $operation:ident, $kind:ident, $register:expr, $address:expr, $ctx:ident => $op:expr) => {{
+        let mut $ctx = $self.ctx.lock().unwrap();
+        $self.pace($self.min_request_interval);
+        if let Some(slave) = $self.slave() {
+            $ctx.set_slave(slave.into());
+        }
+        let started = $self.clock.now();
+        #[cfg(feature = "tracing")]
+        let span = crate::instrumentation::request_span(
+            Operation::$operation,
+            $register,
+            $address,
+            $self.slave().map(|slave| slave.0),
+        );
+        let mut attempt = 0;
+        let result = loop {
+            let outcome = $op;
+            $self.link_stats.lock().unwrap().record(&outcome);
+            match outcome {
+                Err(err) => match &$self.reconnect {
+                    Some(reconnect)
+                        if attempt < reconnect.policy.max_attempts
+                            && reconnect.params.lock().unwrap().reconnects_after(&err) =>
+                    {
+                        $self.clock.sleep(reconnect.policy.backoff(attempt));
+                        attempt += 1;
+                        let params = reconnect.params.lock().unwrap().clone();
+                        if let Ok(new_ctx) = SDM72::connect_with_timeouts(
+                            &params,
+                            reconnect.connect_timeout,
+                            $ctx.timeout(),
+                        ) {
+                            *$ctx = new_ctx;
+                            *$self.identity.lock().unwrap() = None;
+                        }
+                        // The meter may have executed a write before the connection broke
+                        if !matches!(Operation::$operation, Operation::Read) {
+                            break Err(err);
+                        }
+                    }
+                    _ => break Err(err),
+                },
+                ok => break ok,
+            }
+        };
+        let record = RequestRecord {
+            operation: Operation::$operation,
+            register: $register,
+            address: $address,
+            duration: $self.clock.now().saturating_duration_since(started),
+            retries: attempt,
+            outcome: RequestOutcome::of(&result),
+        };
+        if let Some(instrumentation) = &$self.instrumentation {
+            instrumentation.record(&record);
+        }
+        #[cfg(feature = "tracing")]
+        record.trace(&span);
+        if !matches!(&result, Err(err) if err.is_transport()) {
+            *$self.last_contact.lock().unwrap() = Some($self.clock.now());
+        }
+        result.map_err(|source| Error::Register {
+            context: RegisterContext {
+                operation: Operation::$operation,
+                kind: proto::RegisterKind::$kind,
+                register: $register,
+                address: $address,
+                slave: $self.slave().map(|slave| slave.0),
+            },
+            source: Box::new(source),
+        })
+    }};
+}
+
+/// Checks the value read back after a write.
+fn verify(expected: Setting, actual: Setting) -> Result<()> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(Error::VerificationFailed { expected, actual })
+    }
 }
 
 macro_rules! read_holding {
     ($func_name:ident, $ty:ident) => {
+        read_holding!($func_name, $ty, decode |words, _| proto::$ty::decode_from_holding_registers(words));
+    };
+    ($func_name:ident, $ty:ident, mode) => {
+        read_holding!($func_name, $ty, decode proto::$ty::decode_with_mode);
+    };
+    ($func_name:ident, $ty:ident, decode $decode:expr) => {
+        paste::item! {
+            #[doc = "Reads the [`proto::" $ty "`] value from the Modbus holding register."]
+            ///
+            /// The value is served from the [settings
+            /// cache](Self::set_settings_cache_ttl) if it is enabled.
+            pub fn $func_name(&mut self) -> Result<proto::$ty> {
+                const ADDRESS: u16 = <proto::$ty as proto::ModbusParam>::ADDRESS;
+                const QUANTITY: u16 = <proto::$ty as proto::ModbusParam>::QUANTITY;
+                if let Some(words) = self.cached_setting(ADDRESS) {
+                    return Ok(($decode)(&words, self.decode_mode)?);
+                }
+                let words = with_ctx!(self, Read, Holding, stringify!($ty), Some(ADDRESS), ctx => SDM72::read_raw_holding(&mut ctx, ADDRESS, QUANTITY))?;
+                let words = self.word_order.apply(words);
+                let value = ($decode)(&words, self.decode_mode)?;
+                self.cache_setting(ADDRESS, words);
+                Ok(value)
+            }
+        }
+    };
+    ($func_name:ident, $ty:ident, identity) => {
         paste::item! {
             #[doc = "Reads the [`proto::" $ty "`] value from the Modbus holding register."]
+            ///
+            /// The value is served from the [identity](Self::identify) if it
+            /// was read on this connection, otherwise from the [settings
+            /// cache](Self::set_settings_cache_ttl) if it is enabled.
             pub fn $func_name(&mut self) -> Result<proto::$ty> {
-                let mut ctx = self.ctx.lock().unwrap();
-                SDM72::$func_name(&mut ctx)
+                const ADDRESS: u16 = <proto::$ty as proto::ModbusParam>::ADDRESS;
+                const QUANTITY: u16 = <proto::$ty as proto::ModbusParam>::QUANTITY;
+                if let Some(identity) = *self.identity.lock().unwrap() {
+                    return Ok(identity.$func_name);
+                }
+                if let Some(words) = self.cached_setting(ADDRESS) {
+                    return Ok(proto::$ty::decode_from_holding_registers(&words)?);
+                }
+                let words = with_ctx!(self, Read, Holding, stringify!($ty), Some(ADDRESS), ctx => SDM72::read_raw_holding(&mut ctx, ADDRESS, QUANTITY))?;
+                let words = self.word_order.apply(words);
+                let value = proto::$ty::decode_from_holding_registers(&words)?;
+                self.cache_setting(ADDRESS, words);
+                Ok(value)
+            }
+        }
+    };
+    ($func_name:ident, $ty:ident, uncached) => {
+        paste::item! {
+            #[doc = "Reads the [`proto::" $ty "`] value from the Modbus holding register."]
+            pub fn $func_name(&mut self) -> Result<proto::$ty> {
+                const ADDRESS: u16 = <proto::$ty as proto::ModbusParam>::ADDRESS;
+                const QUANTITY: u16 = <proto::$ty as proto::ModbusParam>::QUANTITY;
+                let words = with_ctx!(self, Read, Holding, stringify!($ty), Some(ADDRESS), ctx => SDM72::read_raw_holding(&mut ctx, ADDRESS, QUANTITY))?;
+                Ok(proto::$ty::decode_with_mode(&self.word_order.apply(words), self.decode_mode)?)
             }
         }
     };
@@ -58,8 +378,15 @@ macro_rules! write_holding {
         paste::item! {
             #[doc = "Writes the [`proto::" $ty "`] value to the Modbus holding register."]
             pub fn [< set_ $func_name >](&mut self, value: proto::$ty) -> Result<()> {
-                let mut ctx = self.ctx.lock().unwrap();
-                SDM72::[< set_ $func_name >](&mut ctx, value)
+                self.authorize()?;
+                with_ctx!(self, Write, Holding, stringify!($ty), Some(<proto::$ty as proto::ModbusParam>::ADDRESS), ctx => SDM72::[< set_ $func_name _with_word_order >](&mut ctx, value, self.word_order))?;
+                self.invalidate_settings_cache();
+                self.follow_comm_change(Setting::$ty(value))?;
+                if self.write_verify {
+                    let actual = self.$func_name()?;
+                    verify(Setting::$ty(value), Setting::$ty(actual))?;
+                }
+                Ok(())
             }
         }
     };
@@ -74,15 +401,507 @@ impl SafeClient {
     pub fn new(ctx: Context) -> Self {
         Self {
             ctx: Arc::new(Mutex::new(ctx)),
+            decode_mode: proto::DecodeMode::default(),
+            reconnect: None,
+            slave: Arc::default(),
+            scaling: ValueScaling::default(),
+            word_order: proto::WordOrder::default(),
+            input_via_holding: false,
+            reopen_after_comm_change: false,
+            password: None,
+            instrumentation: None,
+            write_verify: false,
+            batch_retry: BatchRetryPolicy::default(),
+            value_validation: ValueValidation::default(),
+            firmware: None,
+            identity: Arc::default(),
+            settings_cache: Arc::default(),
+            settings_cache_ttl: None,
+            watches: Arc::default(),
+            last_contact: Arc::default(),
+            link_stats: Arc::default(),
+            clock: Arc::new(SystemClock),
+            min_request_interval: Duration::ZERO,
         }
     }
 
+    /// Connects to the meter and creates a new `SafeClient` instance that
+    /// automatically reconnects.
+    ///
+    /// If an operation fails with a transport error (e.g. because a Modbus TCP
+    /// gateway was rebooted), the connection is re-established according to
+    /// the `policy` and a failed read is replayed. A failed write is not
+    /// replayed, as the meter may have executed it, and a timeout of a serial
+    /// port is returned without reconnecting.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: The parameters used to (re-)establish the connection.
+    /// * `timeout`: The timeout for establishing the connection and for all
+    ///   Modbus Input/Output operations.
+    /// * `policy`: Controls the reconnection attempts.
+    pub fn connect(
+        params: ConnectionParams,
+        timeout: Option<std::time::Duration>,
+        policy: ReconnectPolicy,
+    ) -> Result<Self> {
+        Self::connect_with_timeouts(params, timeout, timeout, policy)
+    }
+
+    /// Connects to the meter like [`connect`](Self::connect), with a separate
+    /// timeout for establishing the connection, which is also used by the
+    /// reconnections.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: The parameters used to (re-)establish the connection.
+    /// * `connect_timeout`: The timeout for establishing the connection.
+    /// * `request_timeout`: The timeout for all Modbus Input/Output operations.
+    /// * `policy`: Controls the reconnection attempts.
+    pub fn connect_with_timeouts(
+        params: ConnectionParams,
+        connect_timeout: Option<Duration>,
+        request_timeout: Option<Duration>,
+        policy: ReconnectPolicy,
+    ) -> Result<Self> {
+        let ctx = SDM72::connect_with_timeouts(&params, connect_timeout, request_timeout)?;
+        let min_request_interval = params.min_request_interval();
+        let slave = params.slave();
+        Ok(Self {
+            ctx: Arc::new(Mutex::new(ctx)),
+            decode_mode: proto::DecodeMode::default(),
+            reconnect: Some(Arc::new(Reconnect {
+                params: Mutex::new(params),
+                policy,
+                connect_timeout,
+            })),
+            slave: Arc::new(Mutex::new(Some(slave))),
+            scaling: ValueScaling::default(),
+            word_order: proto::WordOrder::default(),
+            input_via_holding: false,
+            reopen_after_comm_change: false,
+            password: None,
+            instrumentation: None,
+            write_verify: false,
+            batch_retry: BatchRetryPolicy::default(),
+            value_validation: ValueValidation::default(),
+            firmware: None,
+            identity: Arc::default(),
+            settings_cache: Arc::default(),
+            settings_cache_ttl: None,
+            watches: Arc::default(),
+            last_contact: Arc::default(),
+            link_stats: Arc::default(),
+            clock: Arc::new(SystemClock),
+            min_request_interval,
+        })
+    }
+
     /// Creates a new `SafeClient` from an existing `Arc<Mutex<Context>>`.
     ///
     /// This allows multiple `SafeClient` instances to share the exact same
     /// underlying connection context.
     pub fn from_shared(ctx: Arc<Mutex<Context>>) -> Self {
-        Self { ctx }
+        Self {
+            ctx,
+            decode_mode: proto::DecodeMode::default(),
+            reconnect: None,
+            slave: Arc::default(),
+            scaling: ValueScaling::default(),
+            word_order: proto::WordOrder::default(),
+            input_via_holding: false,
+            reopen_after_comm_change: false,
+            password: None,
+            instrumentation: None,
+            write_verify: false,
+            batch_retry: BatchRetryPolicy::default(),
+            value_validation: ValueValidation::default(),
+            firmware: None,
+            identity: Arc::default(),
+            settings_cache: Arc::default(),
+            settings_cache_ttl: None,
+            watches: Arc::default(),
+            last_contact: Arc::default(),
+            link_stats: Arc::default(),
+            clock: Arc::new(SystemClock),
+            min_request_interval: Duration::ZERO,
+        }
+    }
+
+    /// Sets how `read_all_settings` and the setting getters (e.g.
+    /// [`baud_rate`](Self::baud_rate)) decode a setting code unknown to this
+    /// library, see [`DecodeMode`](proto::DecodeMode). The default
+    /// [`Strict`](proto::DecodeMode::Strict) mode fails with
+    /// [`UnknownVariant`](proto::Error::UnknownVariant).
+    pub fn set_decode_mode(&mut self, mode: proto::DecodeMode) {
+        self.decode_mode = mode;
+    }
+
+    /// Returns the [`DecodeMode`](proto::DecodeMode) of the settings.
+    pub fn decode_mode(&self) -> proto::DecodeMode {
+        self.decode_mode
+    }
+
+    /// Sets the ratios of external current and voltage transformers.
+    ///
+    /// The profile is applied to all values returned by [`read_all`](Self::read_all).
+    pub fn set_scaling_profile(&mut self, profile: Option<ScalingProfile>) {
+        self.scaling.profile = profile;
+    }
+
+    /// Returns the scaling profile applied to the measurement values.
+    pub fn scaling_profile(&self) -> Option<ScalingProfile> {
+        self.scaling.profile
+    }
+
+    /// Sets the per-measurement calibrations, applied after the [scaling
+    /// profile](Self::set_scaling_profile) to all values returned by
+    /// [`read_all`](Self::read_all).
+    pub fn set_scaling_config(&mut self, config: Option<ScalingConfig>) {
+        self.scaling.calibration = config.filter(|config| !config.is_empty()).map(Arc::new);
+    }
+
+    /// Returns the per-measurement calibrations.
+    pub fn scaling_config(&self) -> Option<&ScalingConfig> {
+        self.scaling.calibration.as_deref()
+    }
+
+    /// Sets the decimals of the serialized and displayed values returned by
+    /// [`read_all`](Self::read_all), see [`AllValues::precision`].
+    pub fn set_precision(&mut self, precision: proto::Precision) {
+        self.scaling.precision = precision;
+    }
+
+    /// Returns the decimals of the serialized and displayed values.
+    pub fn precision(&self) -> proto::Precision {
+        self.scaling.precision
+    }
+
+    /// Sets the password used to acquire the Key Parameter Programming
+    /// Authorization (KPPA) automatically.
+    ///
+    /// Before every write the KPPA state is read and, if the client is not
+    /// authorized, the password is written to the KPPA register.
+    pub fn with_password(self, password: proto::Password) -> Self {
+        self.with_password_provider(Arc::new(password))
+    }
+
+    /// Sets the provider of the password used to acquire the KPPA
+    /// automatically, like [`with_password`](Self::with_password).
+    ///
+    /// The provider is only asked when the client is not authorized.
+    pub fn with_password_provider(mut self, provider: Arc<dyn PasswordProvider>) -> Self {
+        self.password = Some(provider);
+        self
+    }
+
+    /// Reports every request with its duration, retries and outcome to the
+    /// instrumentation, e.g. a
+    /// [`RegisterTimings`](crate::instrumentation::RegisterTimings).
+    pub fn set_instrumentation(&mut self, instrumentation: Option<Arc<dyn Instrumentation>>) {
+        self.instrumentation = instrumentation;
+    }
+
+    /// Acquires the KPPA if a password provider was given with
+    /// [`with_password_provider`](Self::with_password_provider).
+    fn authorize(&mut self) -> Result<()> {
+        let Some(provider) = self.password.clone() else {
+            return Ok(());
+        };
+        if self.kppa()? != proto::KPPA::Authorized {
+            if let Some(password) = provider.password()? {
+                self.set_kppa(password)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the order of the two words of 32-bit values.
+    ///
+    /// Use [`WordOrder::LittleEndianWords`](proto::WordOrder::LittleEndianWords)
+    /// for Modbus TCP gateways which swap the words of each value.
+    pub fn set_word_order(&mut self, word_order: proto::WordOrder) {
+        self.word_order = word_order;
+    }
+
+    /// Reads the measurement values with function code 0x03 (read holding
+    /// registers) at the addresses of the input registers, for RS485 to TCP
+    /// bridges which only forward function code 0x03.
+    ///
+    /// This applies to [`read_all`](Self::read_all), its variants and
+    /// [`read_raw_input`](Self::read_raw_input).
+    pub fn set_input_via_holding(&mut self, input_via_holding: bool) {
+        self.input_via_holding = input_via_holding;
+    }
+
+    /// Opens the connection again after the baud rate or the parity and stop
+    /// bit of a Modbus/RTU connection were written, and probes the meter after
+    /// these settings and the address were written.
+    ///
+    /// The meter applies these settings immediately, so without this option
+    /// the following requests fail until the connection is opened with the
+    /// new parameters. A meter which doesn't respond after the change fails
+    /// the write with [`Error::Unreachable`]. This requires a client created
+    /// with [`connect`](Self::connect).
+    pub fn set_reopen_after_comm_change(&mut self, reopen: bool) {
+        self.reopen_after_comm_change = reopen;
+    }
+
+    /// Returns `true` if the connection follows the written communication
+    /// settings.
+    pub fn reopen_after_comm_change(&self) -> bool {
+        self.reopen_after_comm_change
+    }
+
+    /// Updates the connection parameters after a setting which affects the
+    /// communication was written, see
+    /// [`set_reopen_after_comm_change`](Self::set_reopen_after_comm_change).
+    fn follow_comm_change(&mut self, setting: Setting) -> Result<()> {
+        let Some(reconnect) = self.reconnect.clone().filter(|_| setting.affects_link()) else {
+            return Ok(());
+        };
+        let changed = reconnect.params.lock().unwrap().apply_setting(setting);
+        if !self.reopen_after_comm_change {
+            return Ok(());
+        }
+        let result = if changed && !matches!(setting, Setting::Address(_)) {
+            let min_request_interval = reconnect.params.lock().unwrap().min_request_interval();
+            self.min_request_interval = self.min_request_interval.max(min_request_interval);
+            self.reconnect().map(|_| ())
+        } else {
+            // The identity must not answer the probe
+            *self.identity.lock().unwrap() = None;
+            Ok(())
+        };
+        let result = match result {
+            Ok(()) => self.probe(),
+            err => err,
+        };
+        result.map_err(|source| Error::Unreachable {
+            setting,
+            source: Box::new(source),
+        })
+    }
+
+    /// Returns `true` if the measurement values are read as holding registers.
+    pub fn input_via_holding(&self) -> bool {
+        self.input_via_holding
+    }
+
+    /// Enables reading back every written setting.
+    ///
+    /// If the value read back differs from the written one, the write fails
+    /// with [`Error::VerificationFailed`].
+    pub fn set_write_verify(&mut self, write_verify: bool) {
+        self.write_verify = write_verify;
+    }
+
+    /// Returns `true` if written settings are read back.
+    pub fn write_verify(&self) -> bool {
+        self.write_verify
+    }
+
+    /// Sets how `read_all` recovers from a failed batch, see [`BatchRetryPolicy`].
+    pub fn set_batch_retry_policy(&mut self, policy: BatchRetryPolicy) {
+        self.batch_retry = policy;
+    }
+
+    /// Returns the [`BatchRetryPolicy`] of `read_all`.
+    pub fn batch_retry_policy(&self) -> BatchRetryPolicy {
+        self.batch_retry
+    }
+
+    /// Sets which decoded values are rejected with
+    /// [`Error::ImplausibleValue`], see [`ValueValidation`].
+    ///
+    /// `read_all` fails with the first rejected value, `read_all_lenient`
+    /// leaves the rejected values out and reports their errors.
+    pub fn set_value_validation(&mut self, validation: ValueValidation) {
+        self.value_validation = validation;
+    }
+
+    /// Returns the [`ValueValidation`] of the measurement values.
+    pub fn value_validation(&self) -> ValueValidation {
+        self.value_validation
+    }
+
+    /// Enables the settings cache, the values read by the individual setting
+    /// getters (e.g. [`baud_rate`](Self::baud_rate)) are served from the cache
+    /// until they are older than `ttl`. `None` disables the cache.
+    ///
+    /// The cache is shared by all clones of the client and is invalidated
+    /// after a setting was written. The authorization ([`kppa`](Self::kppa))
+    /// is never cached.
+    pub fn set_settings_cache_ttl(&mut self, ttl: Option<Duration>) {
+        self.settings_cache_ttl = ttl;
+    }
+
+    /// Returns the time to live of the settings cache, `None` if it is disabled.
+    pub fn settings_cache_ttl(&self) -> Option<Duration> {
+        self.settings_cache_ttl
+    }
+
+    /// Clears the settings cache, e.g. after the settings were changed on the
+    /// meter's display.
+    pub fn invalidate_settings_cache(&self) {
+        self.settings_cache.lock().unwrap().clear();
+    }
+
+    fn cached_setting(&self, address: u16) -> Option<Vec<proto::Word>> {
+        let ttl = self.settings_cache_ttl?;
+        let cache = self.settings_cache.lock().unwrap();
+        let (words, read_at) = cache.get(&address)?;
+        (self.clock.now().saturating_duration_since(*read_at) < ttl).then(|| words.clone())
+    }
+
+    fn cache_setting(&self, address: u16, words: Vec<proto::Word>) {
+        if self.settings_cache_ttl.is_some() {
+            let now = self.clock.now();
+            self.settings_cache
+                .lock()
+                .unwrap()
+                .insert(address, (words, now));
+        }
+    }
+
+    /// Watches the `measurement` for a crossed `threshold`, the events are
+    /// sent to the returned channel.
+    ///
+    /// The watches are shared by all clones of the client and are checked
+    /// after every [`read_all`](Self::read_all), with the scaled values. A
+    /// watch is removed when its receiver is dropped.
+    pub fn watch(
+        &self,
+        measurement: proto::Measurement,
+        threshold: Threshold,
+    ) -> mpsc::Receiver<WatchEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.watches
+            .lock()
+            .unwrap()
+            .push((Watch::new(measurement, threshold), sender));
+        receiver
+    }
+
+    /// Checks the watches with the values and sends the events, missing
+    /// values are skipped.
+    fn check_watches(&self, value: impl Fn(proto::Measurement) -> Option<f32>) {
+        let timestamp = SystemTime::now();
+        self.watches.lock().unwrap().retain_mut(|(watch, sender)| {
+            match value(watch.measurement()).and_then(|value| watch.check(value, timestamp)) {
+                Some(event) => sender.send(event).is_ok(),
+                None => true,
+            }
+        });
+    }
+
+    /// Sets the firmware of the meter, which is otherwise detected by
+    /// [`read_all_partial`](Self::read_all_partial).
+    pub fn set_firmware(&mut self, firmware: Option<Firmware>) {
+        self.firmware = firmware;
+    }
+
+    /// Returns the order of the two words of 32-bit values.
+    pub fn word_order(&self) -> proto::WordOrder {
+        self.word_order
+    }
+
+    /// Sets the minimum interval between the last response of the meter and
+    /// the next request.
+    ///
+    /// Clients created with [`connect`](Self::connect) for a Modbus/RTU
+    /// connection start with the silent interval of 3.5 characters at the
+    /// configured baud rate, see [`BaudRate::silent_interval`](proto::BaudRate::silent_interval).
+    /// Some devices and USB to RS485 adapters need longer pauses.
+    pub fn set_min_request_interval(&mut self, interval: Duration) {
+        self.min_request_interval = interval;
+    }
+
+    /// Returns the minimum interval between the last response and the next request.
+    pub fn min_request_interval(&self) -> Duration {
+        self.min_request_interval
+    }
+
+    /// Replaces the [`Clock`] used for the request pacing, the reconnection
+    /// backoff and [`subscribe`](Self::subscribe).
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Changes the slave id of the requests, e.g. to poll several meters on
+    /// one shared serial bus with a single client.
+    ///
+    /// The slave is changed for all clones of the client, which share the
+    /// context. A reconnection keeps the new slave, the cached identity and
+    /// settings of the previous meter are discarded.
+    pub fn set_slave(&mut self, slave: impl Into<Slave>) {
+        let slave = slave.into();
+        let mut ctx = self.ctx.lock().unwrap();
+        ctx.set_slave(slave.into());
+        if self.slave.lock().unwrap().replace(slave) == Some(slave) {
+            return;
+        }
+        if let Some(reconnect) = &self.reconnect {
+            reconnect.params.lock().unwrap().set_slave(slave);
+        }
+        *self.identity.lock().unwrap() = None;
+        self.invalidate_settings_cache();
+    }
+
+    /// Returns the slave id of the requests, if the client was created with
+    /// [`connect`](Self::connect) or it was set with
+    /// [`set_slave`](Self::set_slave).
+    pub fn slave(&self) -> Option<Slave> {
+        *self.slave.lock().unwrap()
+    }
+
+    /// Returns the time of the last response received from the meter.
+    pub fn last_contact(&self) -> Option<Instant> {
+        *self.last_contact.lock().unwrap()
+    }
+
+    /// Returns the counters of the requests on the link to the meter, shared
+    /// by all clones of the client.
+    pub fn link_stats(&self) -> LinkStats {
+        *self.link_stats.lock().unwrap()
+    }
+
+    /// Checks that the connection is alive by reading the meter code.
+    ///
+    /// If the client was created with [`connect`](Self::connect), a dead
+    /// connection is re-established.
+    pub fn probe(&mut self) -> Result<()> {
+        self.meter_code().map(|_| ())
+    }
+
+    /// Probes the connection if there was no contact with the meter within
+    /// `idle`, so long-lived but idle connections which were silently dropped
+    /// (e.g. by a Modbus TCP gateway) are detected before the next request.
+    pub fn probe_if_idle(&mut self, idle: std::time::Duration) -> Result<()> {
+        match self.last_contact() {
+            Some(last_contact)
+                if self.clock.now().saturating_duration_since(last_contact) < idle =>
+            {
+                Ok(())
+            }
+            _ => self.probe(),
+        }
+    }
+
+    /// Closes the connection and opens a new one, e.g. after repeated
+    /// failures which did not surface as transport errors.
+    ///
+    /// Returns `false` without reconnecting if the client was not created
+    /// with [`connect`](Self::connect).
+    pub fn reconnect(&mut self) -> Result<bool> {
+        let Some(reconnect) = &self.reconnect else {
+            return Ok(false);
+        };
+        let mut ctx = self.ctx.lock().unwrap();
+        let params = reconnect.params.lock().unwrap().clone();
+        *ctx = SDM72::connect_with_timeouts(&params, reconnect.connect_timeout, ctx.timeout())?;
+        *self.identity.lock().unwrap() = None;
+        Ok(true)
     }
 
     /// Clones and returns the underlying `Arc<Mutex<Context>>`.
@@ -93,65 +912,401 @@ impl SafeClient {
         self.ctx.clone()
     }
 
-    read_holding!(system_type, SystemType);
+    read_holding!(system_type, SystemType, mode);
     write_holding!(system_type, SystemType);
     read_holding!(pulse_width, PulseWidth);
     write_holding!(pulse_width, PulseWidth);
-    read_holding!(kppa, KPPA);
+    read_holding!(kppa, KPPA, uncached);
 
     /// Sets the Key Parameter Programming Authorization (KPPA).
     ///
     /// This is required to change settings on the meter.
     pub fn set_kppa(&mut self, password: proto::Password) -> Result<()> {
-        let mut ctx = self.ctx.lock().unwrap();
-        SDM72::set_kppa(&mut ctx, password)
+        with_ctx!(self, Write, Holding, "KPPA", Some(<proto::KPPA as proto::ModbusParam>::ADDRESS), ctx => SDM72::set_kppa_with_word_order(&mut ctx, password, self.word_order))
     }
 
-    read_holding!(parity_and_stop_bit, ParityAndStopBit);
+    read_holding!(parity_and_stop_bit, ParityAndStopBit, mode);
     write_holding!(parity_and_stop_bit, ParityAndStopBit);
     read_holding!(address, Address);
 
     pub fn set_address(&mut self, value: proto::Address) -> Result<()> {
-        let mut ctx = self.ctx.lock().unwrap();
-        SDM72::set_address(&mut ctx, value)?;
-        ctx.set_slave(tokio_modbus::Slave(*value));
+        self.authorize()?;
+        with_ctx!(self, Write, Holding, "Address", Some(<proto::Address as proto::ModbusParam>::ADDRESS), ctx => SDM72::set_address_with_word_order(&mut ctx, value, self.word_order).map(|()| {
+            ctx.set_slave(tokio_modbus::Slave(*value));
+        }))?;
+        *self.slave.lock().unwrap() = Some(value.into());
+        self.invalidate_settings_cache();
+        self.follow_comm_change(Setting::Address(value))?;
+        if self.write_verify {
+            let actual = self.address()?;
+            verify(Setting::Address(value), Setting::Address(actual))?;
+        }
         Ok(())
     }
 
-    read_holding!(pulse_constant, PulseConstant);
+    read_holding!(pulse_constant, PulseConstant, mode);
     write_holding!(pulse_constant, PulseConstant);
     read_holding!(password, Password);
     write_holding!(password, Password);
-    read_holding!(baud_rate, BaudRate);
+    read_holding!(baud_rate, BaudRate, mode);
     write_holding!(baud_rate, BaudRate);
     read_holding!(auto_scroll_time, AutoScrollTime);
     write_holding!(auto_scroll_time, AutoScrollTime);
     read_holding!(backlight_time, BacklightTime);
     write_holding!(backlight_time, BacklightTime);
-    read_holding!(pulse_energy_type, PulseEnergyType);
+    read_holding!(pulse_energy_type, PulseEnergyType, mode);
     write_holding!(pulse_energy_type, PulseEnergyType);
 
     /// Resets the historical data on the meter.
     ///
     /// This requires KPPA authorization.
     pub fn reset_historical_data(&mut self) -> Result<()> {
-        let mut ctx = self.ctx.lock().unwrap();
-        SDM72::reset_historical_data(&mut ctx)
+        self.authorize()?;
+        with_ctx!(self, Write, Holding, "ResetHistoricalData", Some(<proto::ResetHistoricalData as proto::ModbusParam>::ADDRESS), ctx => SDM72::reset_historical_data(&mut ctx))
     }
 
-    read_holding!(serial_number, SerialNumber);
-    read_holding!(meter_code, MeterCode);
-    read_holding!(software_version, SoftwareVersion);
+    /// Resets the data of the target on the meter.
+    ///
+    /// This requires KPPA authorization.
+    pub fn reset(&mut self, target: proto::ResetTarget) -> Result<()> {
+        self.authorize()?;
+        with_ctx!(self, Write, Holding, target.register(), Some(<proto::ResetHistoricalData as proto::ModbusParam>::ADDRESS), ctx => SDM72::reset(&mut ctx, target))
+    }
+
+    /// Acquires the KPPA with the password, unless the client is already
+    /// authorized, and starts a [`SettingsSession`] to write several settings.
+    ///
+    /// Fails with [`Error::Unauthorized`] if the meter doesn't grant the
+    /// authorization.
+    pub fn begin_settings(&mut self, password: proto::Password) -> Result<SettingsSession<'_>> {
+        if self.kppa()? != proto::KPPA::Authorized {
+            self.set_kppa(password)?;
+            if self.kppa()? != proto::KPPA::Authorized {
+                return Err(Error::Unauthorized);
+            }
+        }
+        Ok(SettingsSession {
+            client: self,
+            settings: Vec::new(),
+            delay: settings_diff::DEFAULT_WRITE_DELAY,
+        })
+    }
+
+    /// Writes a single [`Setting`], e.g. one of the changes returned by
+    /// [`settings_diff::diff`](crate::settings_diff::diff).
+    pub fn write_setting(&mut self, setting: Setting) -> Result<()> {
+        match setting {
+            Setting::SystemType(val) => self.set_system_type(val),
+            Setting::PulseWidth(val) => self.set_pulse_width(val),
+            Setting::PulseConstant(val) => self.set_pulse_constant(val),
+            Setting::PulseEnergyType(val) => self.set_pulse_energy_type(val),
+            Setting::Password(val) => self.set_password(val),
+            Setting::AutoScrollTime(val) => self.set_auto_scroll_time(val),
+            Setting::BacklightTime(val) => self.set_backlight_time(val),
+            Setting::ParityAndStopBit(val) => self.set_parity_and_stop_bit(val),
+            Setting::BaudRate(val) => self.set_baud_rate(val),
+            Setting::Address(val) => self.set_address(val),
+        }
+    }
+
+    read_holding!(serial_number, SerialNumber, identity);
+    read_holding!(meter_code, MeterCode, identity);
+    read_holding!(software_version, SoftwareVersion, identity);
+
+    /// Reads raw words from Modbus input registers.
+    ///
+    /// This is an escape hatch for registers which are not covered by the
+    /// [`protocol`](crate::protocol) module, e.g. firmware specific ones.
+    pub fn read_raw_input(&mut self, address: u16, quantity: u16) -> Result<Vec<proto::Word>> {
+        self.read_input("input registers", address, quantity)
+    }
+
+    /// Reads input registers, as holding registers if
+    /// [`input_via_holding`](Self::set_input_via_holding) is set.
+    fn read_input(
+        &mut self,
+        register: &'static str,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<proto::Word>> {
+        if self.input_via_holding {
+            with_ctx!(self, Read, Input, register, Some(address), ctx => SDM72::read_raw_holding(&mut ctx, address, quantity))
+        } else {
+            with_ctx!(self, Read, Input, register, Some(address), ctx => SDM72::read_raw_input(&mut ctx, address, quantity))
+        }
+    }
+
+    /// Reads raw words from Modbus holding registers.
+    ///
+    /// This is an escape hatch for registers which are not covered by the
+    /// [`protocol`](crate::protocol) module, e.g. firmware specific ones.
+    pub fn read_raw_holding(&mut self, address: u16, quantity: u16) -> Result<Vec<proto::Word>> {
+        with_ctx!(self, Read, Holding, "holding registers", Some(address), ctx => SDM72::read_raw_holding(&mut ctx, address, quantity))
+    }
+
+    /// Writes raw words to Modbus holding registers.
+    ///
+    /// This is an escape hatch for registers which are not covered by the
+    /// [`protocol`](crate::protocol) module, e.g. firmware specific ones.
+    pub fn write_raw_holding(&mut self, address: u16, words: &[proto::Word]) -> Result<()> {
+        with_ctx!(self, Write, Holding, "holding registers", Some(address), ctx => SDM72::write_raw_holding(&mut ctx, address, words))?;
+        self.invalidate_settings_cache();
+        Ok(())
+    }
+
+    /// Reads the identity of the meter with two requests, separated by the
+    /// [minimum request interval](Self::set_min_request_interval).
+    ///
+    /// The identity is read once per connection and served from memory until
+    /// the connection is re-established.
+    pub fn identify(&mut self) -> Result<MeterIdentity> {
+        let delay = self.min_request_interval;
+        self.identify_paced(delay)
+    }
+
+    fn identify_paced(&mut self, delay: Duration) -> Result<MeterIdentity> {
+        if let Some(identity) = *self.identity.lock().unwrap() {
+            return Ok(identity);
+        }
+        let identity = with_ctx!(self, Read, Holding, "identity", None, ctx => SDM72::identify_with_word_order(&mut ctx, &delay, self.word_order))?;
+        *self.identity.lock().unwrap() = Some(identity);
+        Ok(identity)
+    }
+
+    /// Returns the firmware of the meter, it is detected with
+    /// [`identify`](Self::identify) on the first call.
+    pub fn firmware(&mut self) -> Result<Firmware> {
+        if let Some(firmware) = self.firmware {
+            return Ok(firmware);
+        }
+        let firmware = Firmware::detect(&self.identify()?);
+        self.firmware = Some(firmware);
+        Ok(firmware)
+    }
+
+    /// Reads the measurement values supported by the [firmware](Self::firmware)
+    /// of the meter, e.g. the first generation SDM72D-M which lacks some
+    /// registers. The values of unsupported registers are `None`.
+    pub fn read_all_partial(&mut self) -> Result<AllValuesPartial> {
+        let firmware = self.firmware()?;
+        Ok(self.read_batches(Some(firmware), false)?.values)
+    }
+
+    /// Reads all measurement values like [`read_all`](Self::read_all), but a
+    /// failed batch doesn't abort the read. The values of the failed batches
+    /// are `None` and their errors are listed in the report.
+    ///
+    /// The batches unsupported by the [firmware](Self::set_firmware) are
+    /// skipped if it is known. Fails only if no batch could be read.
+    pub fn read_all_lenient(&mut self) -> Result<ReadReport> {
+        let firmware = self.firmware;
+        let mut report = self.read_batches(firmware, true)?;
+        if report.values == AllValuesPartial::default() {
+            if let Some(err) = report.errors.pop() {
+                return Err(err);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Reads the batches of the values supported by the firmware, all if it is
+    /// `None`. If `lenient` is set, the errors of failed batches are collected
+    /// in the report instead of returned.
+    fn read_batches(&mut self, firmware: Option<Firmware>, lenient: bool) -> Result<ReadReport> {
+        let delay = self.min_request_interval;
+        let mut rsp: [Option<Vec<proto::Word>>; 4] = Default::default();
+        let mut errors = Vec::new();
+        let batches = tokio_common::VALUE_BATCHES.into_iter().enumerate();
+        for (n, (i, batch)) in batches
+            .filter(|(i, _)| firmware.is_none_or(|firmware| firmware.supports_batch(*i)))
+            .enumerate()
+        {
+            if n > 0 {
+                self.pace(delay);
+            }
+            match self.read_batch(batch, delay) {
+                Ok(words) => rsp[i] = Some(self.word_order.apply(words)),
+                Err(err) if lenient => errors.push(err),
+                Err(err) => return Err(err),
+            }
+        }
+        let mut values = tokio_common::decode_values_partial(&rsp)?;
+        let mut rejected = self.value_validation.check_partial(&mut values);
+        if !lenient && !rejected.is_empty() {
+            return Err(rejected.remove(0));
+        }
+        errors.append(&mut rejected);
+        let values = self.scaling.apply_partial(values);
+        self.check_watches(|measurement| values.value(measurement));
+        Ok(ReadReport { values, errors })
+    }
 
     /// Reads all settings from the meter in a single batch operation.
-    pub fn read_all_settings(&mut self, delay: &std::time::Duration) -> Result<AllSettings> {
-        let mut ctx = self.ctx.lock().unwrap();
-        SDM72::read_all_settings(&mut ctx, delay)
+    ///
+    /// The batches are separated by the [minimum request
+    /// interval](Self::set_min_request_interval).
+    pub fn read_all_settings(&mut self) -> Result<AllSettings> {
+        let delay = self.min_request_interval;
+        self.read_all_settings_paced(delay)
+    }
+
+    /// Reads all settings with an explicit delay between the batches.
+    #[deprecated(note = "use `set_min_request_interval` and `read_all_settings` instead")]
+    pub fn read_all_settings_with_delay(
+        &mut self,
+        delay: &std::time::Duration,
+    ) -> Result<AllSettings> {
+        let delay = self.min_request_interval.max(*delay);
+        self.read_all_settings_paced(delay)
+    }
+
+    /// Reads the batch of the settings, the [identity](Self::identify) is
+    /// only read with the first settings of a connection.
+    fn read_all_settings_paced(&mut self, delay: Duration) -> Result<AllSettings> {
+        let (offset, quantity) = tokio_common::SETTINGS_BATCH;
+        let rsp = with_ctx!(self, Read, Holding, "all settings", Some(offset), ctx => SDM72::read_raw_holding(&mut ctx, offset, quantity))?;
+        let cached = *self.identity.lock().unwrap();
+        let identity = match cached {
+            Some(identity) => identity,
+            None => {
+                self.pace(delay);
+                self.identify_paced(delay)?
+            }
+        };
+        tokio_common::decode_settings(
+            &self.word_order.apply(rsp),
+            identity.serial_number,
+            identity.meter_code,
+            identity.software_version,
+            self.decode_mode,
+        )
     }
 
     /// Reads all measurement values from the meter in a single batch operation.
-    pub fn read_all(&mut self, delay: &std::time::Duration) -> Result<AllValues> {
-        let mut ctx = self.ctx.lock().unwrap();
-        SDM72::read_all(&mut ctx, delay)
+    ///
+    /// The batches are separated by the [minimum request
+    /// interval](Self::set_min_request_interval).
+    pub fn read_all(&mut self) -> Result<AllValues> {
+        let delay = self.min_request_interval;
+        self.read_all_paced(delay)
+    }
+
+    /// Reads all measurement values like [`read_all`](Self::read_all), with
+    /// the times the read started and completed.
+    pub fn read_all_timestamped(&mut self) -> Result<TimestampedAllValues> {
+        let read_started = self.timestamp();
+        let values = self.read_all()?;
+        Ok(TimestampedAllValues {
+            read_started,
+            read_completed: self.timestamp(),
+            values,
+        })
+    }
+
+    /// The current time, with the monotonic time of the [clock](Self::set_clock).
+    fn timestamp(&self) -> Timestamp {
+        Timestamp {
+            instant: self.clock.now(),
+            system_time: SystemTime::now(),
+        }
+    }
+
+    /// Polls the selected measurements every `interval` and passes them to the
+    /// `callback`, all measurements are read if the selection is empty.
+    ///
+    /// Failed reads are passed to the callback as well. The polling stops when
+    /// the callback returns [`ControlFlow::Break`]. If a read takes longer
+    /// than the interval, the missed polls are skipped.
+    pub fn subscribe<F>(
+        &mut self,
+        interval: Duration,
+        selection: &[proto::Measurement],
+        mut callback: F,
+    ) where
+        F: FnMut(Result<TimestampedValues>) -> ControlFlow<()>,
+    {
+        let mut next = self.clock.now();
+        loop {
+            let result = self.read_all().map(|values| TimestampedValues {
+                timestamp: SystemTime::now(),
+                values: values.select(selection),
+            });
+            if callback(result).is_break() {
+                return;
+            }
+            next += interval;
+            let now = self.clock.now();
+            match next.checked_duration_since(now) {
+                Some(wait) => self.clock.sleep(wait),
+                None => next = now,
+            }
+        }
+    }
+
+    /// Reads all measurement values with an explicit delay between the batches.
+    #[deprecated(note = "use `set_min_request_interval` and `read_all` instead")]
+    pub fn read_all_with_delay(&mut self, delay: &std::time::Duration) -> Result<AllValues> {
+        let delay = self.min_request_interval.max(*delay);
+        self.read_all_paced(delay)
+    }
+
+    /// Waits until `delay` has passed since the last contact.
+    fn pace(&self, delay: Duration) {
+        let last_contact = *self.last_contact.lock().unwrap();
+        if let Some(wait) = last_contact.and_then(|last_contact| {
+            delay.checked_sub(self.clock.now().saturating_duration_since(last_contact))
+        }) {
+            self.clock.sleep(wait);
+        }
+    }
+
+    /// Reads a batch of input registers, a failed batch is retried or split
+    /// according to the [`BatchRetryPolicy`].
+    fn read_batch(
+        &mut self,
+        (offset, quantity): (u16, u16),
+        delay: Duration,
+    ) -> Result<Vec<proto::Word>> {
+        let mut attempt = 0;
+        loop {
+            let result = self.read_input("all values", offset, quantity);
+            match result {
+                // An exception response would be the same again, only a failure
+                // of the link, e.g. a timeout, is worth a retry
+                Err(err) if err.link_error().is_some() && attempt < self.batch_retry.retries => {
+                    attempt += 1;
+                    self.pace(delay);
+                }
+                Err(err) if self.batch_retry.split => {
+                    let Some(halves) = tokio_common::split_batch((offset, quantity)) else {
+                        return Err(err);
+                    };
+                    let mut words = Vec::with_capacity(quantity as usize);
+                    for (offset, quantity) in halves {
+                        self.pace(delay);
+                        words.extend(self.read_input("all values", offset, quantity)?);
+                    }
+                    return Ok(words);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Reads the batches of the values, separated by `delay`.
+    fn read_all_paced(&mut self, delay: Duration) -> Result<AllValues> {
+        let mut rsp: [Vec<proto::Word>; 4] = Default::default();
+        for (i, batch) in tokio_common::VALUE_BATCHES.into_iter().enumerate() {
+            if i > 0 {
+                self.pace(delay);
+            }
+            let words = self.read_batch(batch, delay)?;
+            rsp[i] = self.word_order.apply(words);
+        }
+        let values = tokio_common::decode_values(&rsp)?;
+        self.value_validation.check_values(&values)?;
+        let values = self.scaling.apply(values);
+        self.check_watches(|measurement| Some(values.value(measurement)));
+        Ok(values)
     }
 }
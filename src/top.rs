@@ -0,0 +1,167 @@
+//! A live view of the meters of the daemon configuration file, one row per
+//! meter refreshed in place, like `top`.
+//!
+//! Every meter is polled by its own thread, a meter which is unreachable is
+//! retried with every poll and counted as an error.
+
+use crate::commandline::Args;
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use sdm72_lib::{
+    schedule::Ticker,
+    tokio_common::{AllValues, ReconnectPolicy, SystemClock},
+    tokio_sync_safe_client::SafeClient,
+};
+use std::{
+    fmt::Write,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Clears the terminal and moves the cursor to the top left corner.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// The state of a meter in the view.
+#[derive(Debug, Default)]
+struct Row {
+    total_power: Option<f32>,
+    /// The imported energy at the first read of the day.
+    day_start: Option<(NaiveDate, f32)>,
+    import_today: Option<f32>,
+    last_update: Option<Instant>,
+    errors: u64,
+}
+
+impl Row {
+    fn update(&mut self, values: &AllValues, today: NaiveDate, now: Instant) {
+        let import = *values.import_energy_active;
+        let start = match self.day_start {
+            Some((day, start)) if day == today && start <= import => start,
+            // A new day, the first read or a reset of the meter
+            _ => {
+                self.day_start = Some((today, import));
+                import
+            }
+        };
+        self.total_power = Some(*values.total_power);
+        self.import_today = Some(import - start);
+        self.last_update = Some(now);
+    }
+}
+
+/// Polls a meter until the process exits.
+fn poll(args: &Args, poll_interval: Duration, row: &Mutex<Row>) {
+    let mut ticker = Ticker::new(poll_interval, Arc::new(SystemClock));
+    let mut client: Option<SafeClient> = None;
+    loop {
+        let result = match &mut client {
+            Some(client) => client.read_all().with_context(|| "Cannot read all values"),
+            None => crate::resolve(args)
+                .and_then(|(candidates, delay, _)| {
+                    crate::open_client(args, &candidates, delay, ReconnectPolicy::default())
+                })
+                .and_then(|mut opened| {
+                    let values = opened.read_all().with_context(|| "Cannot read all values");
+                    client = Some(opened);
+                    values
+                }),
+        };
+        match result {
+            Ok(values) => row.lock().unwrap().update(
+                &values,
+                chrono::Local::now().date_naive(),
+                Instant::now(),
+            ),
+            Err(err) => {
+                log::debug!("{err:#}");
+                row.lock().unwrap().errors += 1;
+            }
+        }
+        ticker.wait();
+    }
+}
+
+fn format_value(value: Option<f32>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| format!("{value:.2}"))
+}
+
+/// Formats the view of the meters.
+fn render(rows: &[(String, Arc<Mutex<Row>>)], now: Instant) -> String {
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0)
+        .max("METER".len());
+    let mut text = format!(
+        "{:<width$}  {:>12}  {:>18}  {:>8}  {:>6}\n",
+        "METER", "POWER (W)", "IMPORT TODAY (kWh)", "AGE", "ERRORS"
+    );
+    for (name, row) in rows {
+        let row = row.lock().unwrap();
+        let age = row.last_update.map_or_else(
+            || "-".to_string(),
+            |last_update| format!("{}s", now.saturating_duration_since(last_update).as_secs()),
+        );
+        let _ = writeln!(
+            text,
+            "{name:<width$}  {:>12}  {:>18}  {age:>8}  {:>6}",
+            format_value(row.total_power),
+            format_value(row.import_today),
+            row.errors,
+        );
+    }
+    text
+}
+
+/// Polls the meters and refreshes the view until the process is interrupted.
+///
+/// The import today starts with the first read, if the view is started during
+/// the day.
+pub fn run(meters: Vec<(String, Args)>, poll_interval: Duration, refresh: Duration) -> Result<()> {
+    let mut rows = Vec::with_capacity(meters.len());
+    for (name, args) in meters {
+        let row = Arc::new(Mutex::new(Row::default()));
+        std::thread::Builder::new()
+            .name(format!("top {name}"))
+            .spawn({
+                let row = row.clone();
+                move || poll(&args, poll_interval, &row)
+            })
+            .with_context(|| format!("Cannot start the poll of meter {name:?}"))?;
+        rows.push((name, row));
+    }
+    let mut ticker = Ticker::new(refresh, Arc::new(SystemClock));
+    loop {
+        print!("{CLEAR_SCREEN}{}", render(&rows, Instant::now()));
+        std::io::Write::flush(&mut std::io::stdout())?;
+        ticker.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_rows() {
+        let now = Instant::now();
+        let row = Row {
+            total_power: Some(1234.5),
+            import_today: Some(3.214),
+            last_update: Some(now - Duration::from_secs(2)),
+            errors: 1,
+            ..Row::default()
+        };
+        let rows = vec![
+            ("house".to_string(), Arc::new(Mutex::new(row))),
+            ("garage".to_string(), Arc::new(Mutex::new(Row::default()))),
+        ];
+        assert_eq!(
+            render(&rows, now),
+            "METER      POWER (W)  IMPORT TODAY (kWh)       AGE  ERRORS\n\
+             house        1234.50                3.21        2s       1\n\
+             garage             -                   -         -       0\n"
+        );
+    }
+}
@@ -0,0 +1,152 @@
+//! This module detects significant changes of single measurement values, e.g.
+//! to switch a relay when the exported power exceeds a limit.
+//!
+//! A [`Watch`] compares every new value of its [`Measurement`](proto::Measurement)
+//! with a [`Threshold`] and returns a [`WatchEvent`] if the threshold was
+//! crossed. The safe clients register watches with `watch` and check them
+//! after every `read_all`, so control loops don't need to compare the values
+//! themselves.
+//!
+//! # Example
+//!
+//! ```
+//! use sdm72_lib::{
+//!     protocol::Measurement,
+//!     watch::{Threshold, Watch},
+//! };
+//! use std::time::SystemTime;
+//!
+//! let mut watch = Watch::new(Measurement::TotalPower, Threshold::Delta(100.0));
+//! assert!(watch.check(1000.0, SystemTime::now()).is_none());
+//! assert!(watch.check(1050.0, SystemTime::now()).is_none());
+//! let event = watch.check(1120.0, SystemTime::now()).unwrap();
+//! assert_eq!(event.previous, Some(1000.0));
+//! ```
+
+use crate::protocol as proto;
+use std::time::SystemTime;
+
+/// The condition which triggers a [`WatchEvent`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Threshold {
+    /// The value changed by at least this amount since the last event.
+    ///
+    /// The first value is the reference and doesn't trigger an event, a
+    /// delta of `0` triggers an event for every further value.
+    Delta(f32),
+    /// The value rose above the limit, the first value triggers an event if
+    /// it is already above.
+    Above(f32),
+    /// The value fell below the limit, the first value triggers an event if
+    /// it is already below.
+    Below(f32),
+}
+
+/// A crossed [`Threshold`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WatchEvent {
+    pub measurement: proto::Measurement,
+    pub threshold: Threshold,
+    /// The reference value for [`Threshold::Delta`], otherwise the previous
+    /// value. `None` for the first value.
+    pub previous: Option<f32>,
+    pub value: f32,
+    /// The time the value was read.
+    pub timestamp: SystemTime,
+}
+
+/// Watches a single measurement for a crossed [`Threshold`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watch {
+    measurement: proto::Measurement,
+    threshold: Threshold,
+    /// The last value, or the value of the last event for [`Threshold::Delta`].
+    reference: Option<f32>,
+}
+
+impl Watch {
+    pub fn new(measurement: proto::Measurement, threshold: Threshold) -> Self {
+        Self {
+            measurement,
+            threshold,
+            reference: None,
+        }
+    }
+
+    /// The watched measurement.
+    pub fn measurement(&self) -> proto::Measurement {
+        self.measurement
+    }
+
+    /// The threshold of the watch.
+    pub fn threshold(&self) -> Threshold {
+        self.threshold
+    }
+
+    /// Checks the next value of the measurement, `NaN` values are ignored.
+    pub fn check(&mut self, value: f32, timestamp: SystemTime) -> Option<WatchEvent> {
+        if value.is_nan() {
+            return None;
+        }
+        let previous = self.reference;
+        let triggered = match (self.threshold, previous) {
+            (Threshold::Delta(_), None) => false,
+            (Threshold::Delta(delta), Some(reference)) => (value - reference).abs() >= delta,
+            (Threshold::Above(limit), previous) => {
+                value > limit && previous.is_none_or(|previous| previous <= limit)
+            }
+            (Threshold::Below(limit), previous) => {
+                value < limit && previous.is_none_or(|previous| previous >= limit)
+            }
+        };
+        let update_reference = match self.threshold {
+            Threshold::Delta(_) => triggered || previous.is_none(),
+            Threshold::Above(_) | Threshold::Below(_) => true,
+        };
+        if update_reference {
+            self.reference = Some(value);
+        }
+        triggered.then_some(WatchEvent {
+            measurement: self.measurement,
+            threshold: self.threshold,
+            previous,
+            value,
+            timestamp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(threshold: Threshold, values: &[f32]) -> Vec<f32> {
+        let mut watch = Watch::new(proto::Measurement::TotalPower, threshold);
+        values
+            .iter()
+            .filter_map(|&value| watch.check(value, SystemTime::UNIX_EPOCH))
+            .map(|event| event.value)
+            .collect()
+    }
+
+    #[test]
+    fn thresholds() {
+        assert_eq!(
+            values(Threshold::Delta(10.0), &[0.0, 5.0, 9.0, 10.0, 1.0, 0.0]),
+            [10.0, 0.0]
+        );
+        assert_eq!(
+            values(Threshold::Above(0.0), &[1.0, 2.0, -1.0, f32::NAN, 0.0, 3.0]),
+            [1.0, 3.0]
+        );
+        assert_eq!(
+            values(
+                Threshold::Below(-100.0),
+                &[0.0, -150.0, -200.0, -50.0, -101.0]
+            ),
+            [-150.0, -101.0]
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! The webhook daemon, which POSTs the values as JSON to an HTTP endpoint,
+//! e.g. of Node-RED or a serverless function, without an MQTT broker.
+//!
+//! The body is the JSON object of the values with a `timestamp`, the `meter`
+//! of the daemon configuration file if there is one and the `alarms` which
+//! were raised or cleared in the poll, if any. A failed request is retried
+//! with an exponential backoff, except for client errors other than
+//! `429 Too Many Requests`. If all attempts fail, the values of the poll are
+//! dropped and the daemon carries on.
+
+use crate::{commandline, events, run_limit::RunLimit};
+use anyhow::{Context, Result};
+use sdm72_lib::{
+    alarms::{AlarmEngine, AlarmEvent, AlarmRule, AlarmState},
+    delta::{Deadband, DeadbandConfig, ValuesDelta},
+    schedule::Ticker,
+    tokio_common::{AllValues, SystemClock},
+    tokio_sync_safe_client::SafeClient,
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// The timeout of a single request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where and how the values are posted.
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// The additional headers as name and value.
+    pub headers: Vec<(String, String)>,
+    pub on: commandline::WebhookTrigger,
+    pub deadband: f32,
+    pub alarms: Vec<AlarmRule>,
+    pub retries: u8,
+    pub retry_delay: Duration,
+}
+
+/// An alarm transition in the body.
+#[derive(serde::Serialize)]
+struct Alarm<'a> {
+    alarm: &'a str,
+    measurement: &'static str,
+    state: AlarmState,
+    value: f32,
+}
+
+#[derive(serde::Serialize)]
+struct Body<'a> {
+    timestamp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meter: Option<&'a str>,
+    #[serde(flatten)]
+    values: &'a AllValues,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    alarms: Vec<Alarm<'a>>,
+}
+
+/// Decides which polls are posted, according to the [`WebhookTrigger`](commandline::WebhookTrigger).
+struct Trigger {
+    on: commandline::WebhookTrigger,
+    deadband: DeadbandConfig,
+    /// The values of the last post.
+    posted: Option<AllValues>,
+    alarms: AlarmEngine,
+}
+
+impl Trigger {
+    fn new(config: &WebhookConfig) -> Self {
+        Self {
+            on: config.on,
+            deadband: DeadbandConfig::new(Deadband::absolute(config.deadband)),
+            posted: None,
+            alarms: AlarmEngine::new(config.alarms.iter().cloned()),
+        }
+    }
+
+    /// Evaluates the alarms with the values, returns the transitions if the
+    /// values must be posted.
+    fn check(
+        &mut self,
+        values: &AllValues,
+        now: Instant,
+        timestamp: SystemTime,
+    ) -> Option<Vec<AlarmEvent>> {
+        let alarms = self.alarms.evaluate(values, now, timestamp);
+        let post = match self.on {
+            commandline::WebhookTrigger::Always => true,
+            commandline::WebhookTrigger::Change => self.posted.as_ref().is_none_or(|posted| {
+                !ValuesDelta::compare(posted, values, &self.deadband).is_empty()
+            }),
+            commandline::WebhookTrigger::Alarm => !alarms.is_empty(),
+        };
+        if post {
+            self.posted = Some(*values);
+        }
+        post.then_some(alarms)
+    }
+}
+
+/// The delay before the retry after `attempt` failed attempts.
+fn backoff(retry_delay: Duration, attempt: u8) -> Duration {
+    retry_delay.saturating_mul(1 << attempt.min(16))
+}
+
+/// Returns `true` if a failed request may succeed when it is retried.
+fn retryable(err: &ureq::Error) -> bool {
+    match err {
+        ureq::Error::Status(status, _) => *status == 429 || *status >= 500,
+        ureq::Error::Transport(_) => true,
+    }
+}
+
+fn post(config: &WebhookConfig, body: &str) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        let mut request = ureq::post(&config.url)
+            .timeout(REQUEST_TIMEOUT)
+            .set("Content-Type", "application/json");
+        for (name, value) in &config.headers {
+            request = request.set(name, value);
+        }
+        match request.send_string(body) {
+            Ok(_) => return Ok(()),
+            Err(err) if attempt < config.retries && retryable(&err) => {
+                let delay = backoff(config.retry_delay, attempt);
+                log::debug!(
+                    "Cannot call webhook {}: {err}, retry in {delay:?}",
+                    config.url
+                );
+                std::thread::sleep(delay);
+                attempt += 1;
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("Cannot call webhook {}", config.url))
+            }
+        }
+    }
+}
+
+pub fn run_webhook_daemon(
+    client: &mut SafeClient,
+    poll_interval: Duration,
+    config: &WebhookConfig,
+    limit: &mut RunLimit,
+) -> Result<()> {
+    let meter = events::meter();
+    let mut trigger = Trigger::new(config);
+    let mut ticker = Ticker::new(poll_interval, Arc::new(SystemClock));
+    loop {
+        let values = events::read_all(client)?;
+        let timestamp = SystemTime::now();
+        if let Some(alarms) = trigger.check(&values, Instant::now(), timestamp) {
+            let body = Body {
+                timestamp: humantime::format_rfc3339_millis(timestamp).to_string(),
+                meter: meter.as_deref(),
+                values: &values,
+                alarms: alarms
+                    .iter()
+                    .map(|event| Alarm {
+                        alarm: &event.rule,
+                        measurement: event.measurement.name(),
+                        state: event.state,
+                        value: event.value,
+                    })
+                    .collect(),
+            };
+            if let Err(err) = post(config, &serde_json::to_string(&body)?) {
+                log::warn!("{err:#}, the values are dropped");
+            }
+        }
+        if !limit.next_poll(true, &mut ticker) {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_policy() {
+        let delay = Duration::from_millis(500);
+        assert_eq!(backoff(delay, 0), Duration::from_millis(500));
+        assert_eq!(backoff(delay, 3), Duration::from_secs(4));
+        assert_eq!(
+            commandline::parse_header("Authorization: Bearer abc"),
+            Ok(("Authorization".to_string(), "Bearer abc".to_string()))
+        );
+        assert!(commandline::parse_header("Bad Name: x").is_err());
+        assert!(commandline::parse_header("no header").is_err());
+    }
+}
@@ -0,0 +1,211 @@
+//! Golden-file tests for the serialized layout of `AllSettings` and `AllValues`.
+//!
+//! The files in `tests/golden/v<SCHEMA_VERSION>/` must only change together with
+//! a bump of `tokio_common::SCHEMA_VERSION`. Run the tests with `UPDATE_GOLDEN=1`
+//! to write the files for a new schema version.
+#![cfg(all(
+    feature = "serde",
+    any(
+        feature = "tokio-rtu-sync",
+        feature = "tokio-tcp-sync",
+        feature = "tokio-rtu",
+        feature = "tokio-tcp"
+    )
+))]
+
+use sdm72_lib::{
+    protocol::{self as proto, Word},
+    settings_diff::{self, DesiredSettings, Setting},
+    tokio_common::{AllSettings, AllValues, AllValuesPartial, ScalingProfile, SCHEMA_VERSION},
+};
+use std::path::PathBuf;
+
+fn words(bytes: &[u8]) -> Vec<Word> {
+    bytes
+        .chunks(2)
+        .map(|chunk| Word::from_be_bytes([chunk[0], chunk[1]]))
+        .collect()
+}
+
+fn settings() -> AllSettings {
+    AllSettings {
+        system_type: proto::SystemType::Type3P4W,
+        pulse_width: proto::PulseWidth::try_from(100).unwrap(),
+        kppa: proto::KPPA::NotAuthorized,
+        parity_and_stop_bit: proto::ParityAndStopBit::NoParityOneStopBit,
+        address: proto::Address::try_from(1).unwrap(),
+        pulse_constant: proto::PulseConstant::PC1000,
+        password: proto::Password::try_from(1000).unwrap(),
+        baud_rate: proto::BaudRate::B9600,
+        auto_scroll_time: proto::AutoScrollTime::try_from(5).unwrap(),
+        backlight_time: proto::BacklightTime::Delayed(60),
+        pulse_energy_type: proto::PulseEnergyType::TotalActiveEnergy,
+        serial_number: proto::SerialNumber::decode_from_holding_registers(&words(
+            &21_034_567_u32.to_be_bytes(),
+        ))
+        .unwrap(),
+        meter_code: proto::MeterCode::decode_from_holding_registers(&words(
+            &0x0089_u16.to_be_bytes(),
+        ))
+        .unwrap(),
+        software_version: proto::SoftwareVersion::decode_from_holding_registers(&words(
+            &0x0102_u16.to_be_bytes(),
+        ))
+        .unwrap(),
+    }
+}
+
+macro_rules! input {
+    ($ty:ident, $val:expr) => {
+        proto::$ty::decode_from_input_register(&words(&f32::to_be_bytes($val))).unwrap()
+    };
+}
+
+fn values() -> AllValues {
+    let mut values = AllValuesPartial::default();
+    values.l1_voltage = Some(input!(L1Voltage, 231.456));
+    values.l2_voltage = Some(input!(L2Voltage, 229.9));
+    values.l3_voltage = Some(input!(L3Voltage, 230.12));
+    values.l1_current = Some(input!(L1Current, 1.234));
+    values.l2_current = Some(input!(L2Current, 0.5));
+    values.l3_current = Some(input!(L3Current, 2.75));
+    values.l1_power_active = Some(input!(L1PowerActive, 280.1));
+    values.l2_power_active = Some(input!(L2PowerActive, 110.0));
+    values.l3_power_active = Some(input!(L3PowerActive, 610.55));
+    values.l1_power_apparent = Some(input!(L1PowerApparent, 285.6));
+    values.l2_power_apparent = Some(input!(L2PowerApparent, 114.95));
+    values.l3_power_apparent = Some(input!(L3PowerApparent, 632.83));
+    values.l1_power_reactive = Some(input!(L1PowerReactive, -55.2));
+    values.l2_power_reactive = Some(input!(L2PowerReactive, 33.3));
+    values.l3_power_reactive = Some(input!(L3PowerReactive, 166.0));
+    values.l1_power_factor = Some(input!(L1PowerFactor, 0.981));
+    values.l2_power_factor = Some(input!(L2PowerFactor, 0.957));
+    values.l3_power_factor = Some(input!(L3PowerFactor, 0.965));
+    values.ln_average_voltage = Some(input!(LtoNAverageVoltage, 230.49));
+    values.ln_average_current = Some(input!(LtoNAverageCurrent, 1.49));
+    values.total_line_current = Some(input!(TotalLineCurrent, 4.484));
+    values.total_power = Some(input!(TotalPower, 1000.65));
+    values.total_power_apparent = Some(input!(TotalPowerApparent, 1033.38));
+    values.total_power_reactive = Some(input!(TotalPowerReactive, 144.1));
+    values.total_power_factor = Some(input!(TotalPowerFactor, 0.968));
+    values.frequency = Some(input!(Frequency, 50.01));
+    values.import_energy_active = Some(input!(ImportEnergyActive, 12345.67));
+    values.export_energy_active = Some(input!(ExportEnergyActive, 89.1));
+    values.l1l2_voltage = Some(input!(L1ToL2Voltage, 399.2));
+    values.l2l3_voltage = Some(input!(L2ToL3Voltage, 398.7));
+    values.l3l1_voltage = Some(input!(L3ToL1Voltage, 400.4));
+    values.ll_average_voltage = Some(input!(LtoLAverageVoltage, 399.43));
+    values.neutral_current = Some(input!(NeutralCurrent, 0.12));
+    values.total_energy_active = Some(input!(TotalEnergyActive, 12434.77));
+    values.total_energy_reactive = Some(input!(TotalEnergyReactive, 1500.25));
+    values.resettable_total_energy_active = Some(input!(ResettableTotalEnergyActive, 234.5));
+    values.resettable_total_energy_reactive = Some(input!(ResettableTotalEnergyReactive, 12.0));
+    values.resettable_import_energy_active = Some(input!(ResettableImportEnergyActive, 230.0));
+    values.resettable_export_energy_active = Some(input!(ResettableExportEnergyActive, 4.5));
+    values.net_kwh = Some(input!(NetKwh, 12256.57));
+    values.import_total_energy_active = Some(input!(ImportTotalPowerActive, 1000.65));
+    values.export_total_energy_active = Some(input!(ExportTotalPowerActive, 0.0));
+    values.complete().unwrap()
+}
+
+fn check_golden(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("v{SCHEMA_VERSION}"))
+        .join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Cannot read golden file {}: {e}", path.display()));
+    assert_eq!(
+        expected, actual,
+        "The serialized layout of {name} changed. If this is intended, bump \
+         SCHEMA_VERSION and run the tests with UPDATE_GOLDEN=1."
+    );
+}
+
+#[test]
+fn all_settings_json() {
+    check_golden(
+        "all_settings.json",
+        &serde_json::to_string_pretty(&settings()).unwrap(),
+    );
+}
+
+#[test]
+fn all_settings_yaml() {
+    check_golden(
+        "all_settings.yaml",
+        &serde_yaml::to_string(&settings()).unwrap(),
+    );
+}
+
+#[test]
+fn all_values_json() {
+    check_golden(
+        "all_values.json",
+        &serde_json::to_string_pretty(&values()).unwrap(),
+    );
+}
+
+#[test]
+fn all_values_yaml() {
+    check_golden(
+        "all_values.yaml",
+        &serde_yaml::to_string(&values()).unwrap(),
+    );
+}
+
+#[test]
+fn scaled_values_json() {
+    let profile = ScalingProfile {
+        ct_ratio: 40.0,
+        vt_ratio: 1.0,
+    };
+    check_golden(
+        "scaled_values.json",
+        &serde_json::to_string_pretty(&values().scaled(&profile)).unwrap(),
+    );
+}
+
+#[test]
+fn values_with_precision() {
+    let mut precision = proto::Precision::default();
+    precision.set(proto::MeasurementClass::Voltage, 1).unwrap();
+    precision.set(proto::MeasurementClass::Current, 3).unwrap();
+    let mut values = values();
+    values.precision = precision;
+    let json = serde_json::to_string(&values).unwrap();
+    assert!(json.starts_with(r#"{"l1_voltage":231.5,"l2_voltage":229.9,"#));
+    assert!(json.contains(r#""l1_current":1.234,"#));
+    assert!(json.contains(r#""l1_power_factor":0.98,"#));
+    let display = values.to_string();
+    let mut lines = display.lines();
+    assert!(lines.next().unwrap().ends_with(": 231.5"));
+    assert!(lines.nth(2).unwrap().ends_with(": 1.234"));
+
+    // The precision is not part of the layout
+    let parsed: AllValues = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.precision, proto::Precision::default());
+}
+
+#[test]
+fn settings_example_file() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("settings-example.yaml");
+    let desired: DesiredSettings =
+        serde_yaml::from_reader(std::fs::File::open(path).unwrap()).unwrap();
+    desired.validate().unwrap();
+    let changes = settings_diff::diff(&settings(), &desired);
+    assert_eq!(
+        changes.iter().map(|change| change.to).collect::<Vec<_>>(),
+        vec![
+            Setting::PulseConstant(proto::PulseConstant::PC100),
+            Setting::PulseEnergyType(proto::PulseEnergyType::ImportActiveEnergy),
+            Setting::AutoScrollTime(proto::AutoScrollTime::try_from(10).unwrap()),
+            Setting::BacklightTime(proto::BacklightTime::Delayed(30)),
+        ]
+    );
+}